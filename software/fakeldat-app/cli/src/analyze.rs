@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+use fakeldat_lib::analysis::{
+    auto_threshold, read_raw_csv, validate, DetectorKind, LatencyDetector, Simulation,
+};
+use fakeldat_lib::{Error, Stats};
+
+use crate::tail::print_stats;
+use crate::Detector;
+
+#[derive(clap::Args)]
+pub struct Analyze {
+    /// Raw recording made by the GUI or the CLI
+    #[arg(required_unless_present = "validate")]
+    path: Option<PathBuf>,
+    /// Algorithm deciding when the brightness changed enough
+    #[arg(long, value_enum, default_value_t = Detector::Fixed)]
+    detector: Detector,
+    /// Brightness change, negative for light to dark, auto derives it from the recording
+    #[arg(long, default_value = "150", allow_hyphen_values = true, value_parser = parse_threshold)]
+    threshold: Threshold,
+    /// Measure the fixed detector on simulated data with known latencies instead of a recording,
+    /// prints how far off it is on average and how much it scatters
+    #[arg(long, conflicts_with_all = ["path", "detector"])]
+    validate: bool,
+    /// Simulated triggers to validate with
+    #[arg(long, default_value_t = 1000, requires = "validate")]
+    trials: usize,
+}
+
+#[derive(Clone, Copy)]
+enum Threshold {
+    Auto,
+    Value(i16),
+}
+
+fn parse_threshold(value: &str) -> Result<Threshold, String> {
+    if value == "auto" {
+        return Ok(Threshold::Auto);
+    }
+    value
+        .parse()
+        .map(Threshold::Value)
+        .map_err(|_| format!("{value} is neither auto nor a number"))
+}
+
+pub fn run(args: &Analyze) -> Result<(), Error> {
+    let Some(ref path) = args.path else {
+        return run_validation(args);
+    };
+    let reports = read_raw_csv(path)?;
+    let threshold = match args.threshold {
+        Threshold::Value(threshold) => threshold,
+        Threshold::Auto => {
+            let Some(threshold) = auto_threshold(&reports) else {
+                eprintln!("No triggers followed by a brightness change, pass a threshold instead");
+                std::process::exit(1);
+            };
+            eprintln!("Threshold: {threshold}");
+            threshold
+        }
+    };
+    let mut detector =
+        LatencyDetector::with_detector(DetectorKind::from(args.detector.clone()).create(threshold));
+    let mut stats = Stats::default();
+    for summary in detector.detect_all(&reports) {
+        println!("{}, {}", summary.delay, summary.threshold);
+        stats.push(summary.delay);
+    }
+    print_stats(reports.len() as u64, &stats);
+    Ok(())
+}
+
+// Error bar of the detector, the simulation has no recording to derive a threshold from
+fn run_validation(args: &Analyze) -> Result<(), Error> {
+    let Threshold::Value(threshold) = args.threshold else {
+        eprintln!("Validation needs a threshold, auto only works with a recording");
+        std::process::exit(1);
+    };
+    let report = validate(&Simulation::default(), threshold, args.trials);
+    println!("trials: {}", report.trials);
+    println!("detected: {}", report.detected);
+    println!("bias_us: {:.1}", report.bias_us);
+    println!("std_dev_us: {:.1}", report.std_dev_us());
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use fakeldat_lib::{Error, FakeLDAT, ReportMode, Stats};
+
+use crate::report::{csv_histogram, text_histogram};
+use crate::stream::parse_duration;
+use crate::tail::print_stats;
+
+#[derive(clap::Args)]
+pub struct Bench {
+    /// Number of triggers fired
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+    shots: u32,
+    /// Pause between triggers, a random one within the range when given as 500ms..1500ms
+    #[arg(long, default_value = "500ms..1500ms", value_parser = parse_interval)]
+    interval: (Duration, Duration),
+    /// Threshold set before the first trigger, the device keeps its own otherwise
+    #[arg(long, allow_hyphen_values = true)]
+    threshold: Option<i16>,
+    /// Histogram bars
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u16).range(1..))]
+    bins: u16,
+    /// Write the histogram as CSV, the device label is added to the name with several devices
+    #[arg(long)]
+    histogram: Option<PathBuf>,
+    /// Leave the histogram out of the printed results
+    #[arg(long)]
+    no_chart: bool,
+}
+
+fn parse_interval(value: &str) -> Result<(Duration, Duration), String> {
+    let Some((min, max)) = value.split_once("..") else {
+        let interval = parse_duration(value)?;
+        return Ok((interval, interval));
+    };
+    let (min, max) = (parse_duration(min)?, parse_duration(max)?);
+    if min > max {
+        return Err(format!("{value} ends before it starts"));
+    }
+    Ok((min, max))
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn print_percentiles(stats: &Stats) {
+    let ms = |delay: Option<u64>| delay.map_or(0.0, |delay| delay as f64 / 1000.0);
+    println!(
+        "Median: {:.2} ms, p95: {:.2} ms, p99: {:.2} ms",
+        ms(stats.median()),
+        ms(stats.percentile(95.0)),
+        ms(stats.percentile(99.0))
+    );
+}
+
+// Summaries only, the device is switched to summary mode and stays in it
+fn measure(fakeldat: &mut FakeLDAT, args: &Bench, progress: bool) -> Result<(Stats, usize), Error> {
+    fakeldat.set_report_mode(ReportMode::Summary)?;
+    if let Some(threshold) = args.threshold {
+        fakeldat.set_threshold(threshold)?;
+    }
+    // every trigger needs its own summary
+    match fakeldat.set_summary_aggregation(0) {
+        Ok(()) | Err(Error::Unsupported(_)) => {}
+        Err(why) => return Err(why),
+    }
+    let (min, max) = args.interval;
+    let mut stats = Stats::default();
+    let mut missed = 0;
+    for shot in 1..=args.shots {
+        let result = fakeldat.run_trigger_sequence(1, min, max.saturating_sub(min))?;
+        missed += result.missed;
+        for summary_report in result.reports {
+            stats.push(summary_report.delay);
+        }
+        if progress {
+            eprint!("\r{shot}/{} triggers, {missed} missed", args.shots);
+        }
+    }
+    if progress {
+        eprintln!();
+    }
+    Ok((stats, missed))
+}
+
+// out.csv becomes out-ttyACM0.csv
+fn labeled_path(path: &Path, label: &str) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push("-");
+    name.push(label);
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+// The label is only given with several devices
+fn print_results(
+    stats: &Stats,
+    missed: usize,
+    label: Option<&str>,
+    args: &Bench,
+) -> Result<(), Error> {
+    if let Some(label) = label {
+        println!("{label}:");
+    }
+    print_stats(u64::from(args.shots), stats);
+    print_percentiles(stats);
+    if !args.no_chart {
+        print!("{}", text_histogram(stats, args.bins));
+    }
+    if missed > 0 {
+        eprintln!("{missed} triggers didn't result in a measurement, check the threshold");
+    }
+    if let Some(ref path) = args.histogram {
+        let path = label.map_or_else(|| path.clone(), |label| labeled_path(path, label));
+        fs::write(path, csv_histogram(stats, args.bins))?;
+    }
+    Ok(())
+}
+
+// Several devices are triggered at the same time, each on its own thread,
+// their results are printed one after another once all are done
+pub fn run(devices: &mut [(String, &mut FakeLDAT)], args: &Bench) -> Result<(), Error> {
+    if let [(_, fakeldat)] = devices {
+        let (stats, missed) = measure(fakeldat, args, true)?;
+        return print_results(&stats, missed, None, args);
+    }
+    eprintln!(
+        "Firing {} triggers on {} devices",
+        args.shots,
+        devices.len()
+    );
+    let results: Vec<Result<(Stats, usize), Error>> = thread::scope(|scope| {
+        let handles: Vec<_> = devices
+            .iter_mut()
+            .map(|(_, fakeldat)| scope.spawn(move || measure(fakeldat, args, false)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("bench thread panicked"))
+            .collect()
+    });
+    for ((label, _), result) in devices.iter().zip(results) {
+        let (stats, missed) = result?;
+        print_results(&stats, missed, Some(label), args)?;
+    }
+    Ok(())
+}
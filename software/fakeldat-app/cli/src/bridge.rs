@@ -0,0 +1,155 @@
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+use fakeldat_lib::{Error, FakeLDAT, Profile, Report};
+use tungstenite::Message;
+
+#[derive(clap::Args)]
+pub struct Bridge {
+    /// Address the WebSocket server listens on, only this machine by default
+    #[arg(long, default_value = "127.0.0.1:9001")]
+    listen: String,
+}
+
+// Sent by clients, one JSON object per text message:
+// {"set": {"Threshold": 150}}, {"get": "Threshold"}, "manual_trigger", "telemetry" or {"profile": {...}}
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Request {
+    // setting reports as they're streamed, the echo is sent to every client
+    Set(Report),
+    // name of a setting report
+    Get(String),
+    ManualTrigger,
+    Telemetry,
+    Profile(Profile),
+}
+
+// Sent to clients, every report goes to all of them and errors only to the one asking
+#[derive(serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Reply<'a> {
+    Report(&'a Report),
+    Error(String),
+}
+
+struct Client {
+    id: u64,
+    replies: Sender<String>,
+}
+
+fn handle(fakeldat: &mut FakeLDAT, request: &str) -> Result<(), String> {
+    let request: Request =
+        serde_json::from_str(request).map_err(|why| format!("Invalid request: {why}"))?;
+    match request {
+        Request::Set(report) => match fakeldat.apply_setting(&report) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("Not a setting which can be changed over the bridge".to_string()),
+            Err(why) => Err(format!("{why:?}")),
+        },
+        Request::Get(setting) => match fakeldat.request_setting(&setting) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("Unknown setting: {setting}")),
+            Err(why) => Err(format!("{why:?}")),
+        },
+        Request::ManualTrigger => fakeldat.manual_trigger().map_err(|why| format!("{why:?}")),
+        Request::Telemetry => fakeldat
+            .request_telemetry()
+            .map_err(|why| format!("{why:?}")),
+        Request::Profile(profile) => fakeldat
+            .apply_profile(&profile)
+            .map_err(|why| format!("{why:?}")),
+    }
+}
+
+fn to_json(reply: &Reply) -> String {
+    serde_json::to_string(reply).unwrap_or_default()
+}
+
+// Reads time out quickly once connected so replies don't wait for the client to say something
+fn serve(
+    stream: TcpStream,
+    id: u64,
+    clients: &Sender<Client>,
+    requests: &Sender<(u64, String)>,
+) -> Result<(), Box<tungstenite::Error>> {
+    // blocking until upgraded, a remote client can take longer than the timeout
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(why) => {
+            eprintln!("Client {id} failed the handshake: {why}");
+            return Ok(());
+        }
+    };
+    socket
+        .get_ref()
+        .set_read_timeout(Some(Duration::from_millis(10)))
+        .map_err(tungstenite::Error::Io)?;
+    let (replies, outgoing): (Sender<String>, Receiver<String>) = channel();
+    if clients.send(Client { id, replies }).is_err() {
+        return Ok(());
+    }
+    loop {
+        match socket.read() {
+            Ok(message) if message.is_text() => {
+                if requests.send((id, message.to_text()?.to_string())).is_err() {
+                    return Ok(());
+                }
+            }
+            Ok(message) if message.is_close() => return Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(why))
+                if matches!(
+                    why.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) => {}
+            Err(why) => return Err(why.into()),
+        }
+        for reply in outgoing.try_iter() {
+            socket.send(Message::text(reply))?;
+        }
+    }
+}
+
+// Owns the device, every client sees the same reports and can change the settings
+pub fn run(mut fakeldat: FakeLDAT, args: &Bridge) -> Result<(), Error> {
+    let listener = TcpListener::bind(&args.listen)?;
+    eprintln!("Listening on ws://{}", listener.local_addr()?);
+    let (new_clients, accepted) = channel();
+    let (requests_sender, requests) = channel();
+    spawn(move || {
+        for (id, stream) in (0..).zip(listener.incoming()) {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let new_clients = new_clients.clone();
+            let requests = requests_sender.clone();
+            spawn(move || serve(stream, id, &new_clients, &requests));
+        }
+    });
+
+    let mut clients: Vec<Client> = Vec::new();
+    loop {
+        clients.extend(accepted.try_iter());
+        for (id, request) in requests.try_iter() {
+            if let Err(why) = handle(&mut fakeldat, &request) {
+                let reply = to_json(&Reply::Error(why));
+                if let Some(client) = clients.iter().find(|client| client.id == id) {
+                    _ = client.replies.send(reply);
+                }
+            }
+        }
+        fakeldat.poll_bulk_data()?;
+        let Some(reports) = fakeldat.take_report_buffer() else {
+            sleep(Duration::from_millis(1));
+            continue;
+        };
+        for report in &reports {
+            let reply = to_json(&Reply::Report(report));
+            // disconnected clients drop their end
+            clients.retain(|client| client.replies.send(reply.clone()).is_ok());
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+
+use fakeldat_lib::analysis::{DetectorKind, LatencyDetector};
+use fakeldat_lib::{Error, Stats};
+
+use crate::report::read_delays;
+use crate::Detector;
+
+// Two-sided p-value below which the difference is called significant
+const SIGNIFICANCE: f64 = 0.05;
+
+#[derive(clap::Args)]
+pub struct Compare {
+    /// Summary or raw recording of the first run
+    a: PathBuf,
+    /// Summary or raw recording of the second run
+    b: PathBuf,
+    /// Algorithm used to derive delays from raw recordings
+    #[arg(long, value_enum, default_value_t = Detector::Fixed)]
+    detector: Detector,
+    /// Threshold used to derive delays from raw recordings
+    #[arg(long, default_value_t = 150, allow_hyphen_values = true)]
+    threshold: i16,
+}
+
+// Abramowitz and Stegun 7.1.26, within 1.5e-7
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let polynomial = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    (1.0 - polynomial * (-x * x).exp()).copysign(x)
+}
+
+fn normal_cdf(z: f64) -> f64 {
+    0.5 * (1.0 + erf(z / std::f64::consts::SQRT_2))
+}
+
+// Mann-Whitney U of a against b and its two-sided p-value, normal approximation with
+// the tie correction, None when either side is empty or all delays are the same
+#[allow(clippy::cast_precision_loss)]
+fn mann_whitney(a: &[u64], b: &[u64]) -> Option<(f64, f64)> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let mut delays: Vec<(u64, bool)> = a
+        .iter()
+        .map(|&delay| (delay, true))
+        .chain(b.iter().map(|&delay| (delay, false)))
+        .collect();
+    delays.sort_unstable();
+    let total = delays.len() as f64;
+    let mut rank_sum = 0.0;
+    let mut ties = 0.0;
+    let mut start = 0;
+    while start < delays.len() {
+        let end = start + delays[start..].partition_point(|&(delay, _)| delay == delays[start].0);
+        // tied delays share the average of their ranks, counted from 1
+        let rank = (start + end + 1) as f64 / 2.0;
+        let group = (end - start) as f64;
+        rank_sum += rank * delays[start..end].iter().filter(|(_, in_a)| *in_a).count() as f64;
+        ties += group.powi(3) - group;
+        start = end;
+    }
+    let statistic = rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((total + 1.0) - ties / (total * (total - 1.0)));
+    if variance <= 0.0 {
+        return None;
+    }
+    // continuity correction towards the mean
+    let z_score = ((statistic - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+    Some((statistic, 2.0 * (1.0 - normal_cdf(z_score))))
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn print_row(name: &str, a: Option<f64>, b: Option<f64>) {
+    let ms = |delay: Option<f64>| {
+        delay.map_or_else(
+            || "-".to_string(),
+            |delay| format!("{:.2} ms", delay / 1000.0),
+        )
+    };
+    let difference = a.zip(b).map(|(a, b)| b - a);
+    println!(
+        "{name:<8} {:>12} {:>12} {:>12}",
+        ms(a),
+        ms(b),
+        ms(difference)
+    );
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub fn run(args: &Compare) -> Result<(), Error> {
+    let read = |path| {
+        let mut detector = LatencyDetector::with_detector(
+            DetectorKind::from(args.detector.clone()).create(args.threshold),
+        );
+        read_delays(path, &mut detector)
+    };
+    let a: Stats = read(&args.a)?.into_iter().collect();
+    let b: Stats = read(&args.b)?.into_iter().collect();
+    let percentile =
+        |stats: &Stats, percentile| stats.percentile(percentile).map(|delay: u64| delay as f64);
+
+    println!("A: {}", args.a.display());
+    println!("B: {}", args.b.display());
+    println!("{:<8} {:>12} {:>12} {:>12}", "", "A", "B", "B - A");
+    println!("{:<8} {:>12} {:>12}", "Delays", a.count(), b.count());
+    print_row("Mean", a.mean(), b.mean());
+    print_row("Std dev", a.std_dev(), b.std_dev());
+    print_row("Median", percentile(&a, 50.0), percentile(&b, 50.0));
+    print_row("p95", percentile(&a, 95.0), percentile(&b, 95.0));
+    print_row("p99", percentile(&a, 99.0), percentile(&b, 99.0));
+
+    let Some((u, p)) = mann_whitney(a.delays(), b.delays()) else {
+        println!("Not enough delays to tell if the runs differ");
+        return Ok(());
+    };
+    let verdict = if p >= SIGNIFICANCE {
+        "no significant difference".to_string()
+    } else {
+        // U counts the pairs with A slower, more than half means B is faster
+        let faster = if u > a.count() as f64 * b.count() as f64 / 2.0 {
+            "B"
+        } else {
+            "A"
+        };
+        format!(
+            "{faster} is faster, significant at {}%",
+            SIGNIFICANCE * 100.0
+        )
+    };
+    println!("Mann-Whitney U: {u}, p = {p:.4}, {verdict}");
+    Ok(())
+}
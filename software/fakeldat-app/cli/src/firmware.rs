@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use fakeldat_lib::{firmware, Error, FakeLDAT};
+
+// Between the attempts to reach the device once it rebooted
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(clap::Subcommand)]
+pub enum Firmware {
+    /// Print the protocol version and the features of the running firmware
+    Info,
+    /// Reboot into the UF2 bootloader, its drive shows up a few seconds later
+    EnterBootloader,
+    /// Copy a UF2 image to the device and check that it answers afterwards
+    Flash(Flash),
+}
+
+#[derive(clap::Args)]
+pub struct Flash {
+    image: PathBuf,
+    /// How long the bootloader drive and the updated device each get to show up
+    #[arg(long, value_parser = crate::stream::parse_duration, default_value = "30s")]
+    timeout: Duration,
+}
+
+fn print_info(fakeldat: &FakeLDAT) {
+    let (Some(version), Some(capabilities)) =
+        (fakeldat.protocol_version(), fakeldat.capabilities())
+    else {
+        println!("Firmware from before the hello, it doesn't tell its features");
+        return;
+    };
+    // field names of the capabilities, the ones set
+    let features: Vec<String> = match serde_json::to_value(capabilities) {
+        Ok(serde_json::Value::Object(fields)) => fields
+            .into_iter()
+            .filter(|(_, value)| value.as_bool() == Some(true))
+            .map(|(name, _)| name)
+            .collect(),
+        _ => Vec::new(),
+    };
+    println!("Protocol version: {version}");
+    println!("Features: {}", features.join(", "));
+}
+
+pub fn run(fakeldat: FakeLDAT, port: &str, args: &Firmware) -> Result<(), Error> {
+    match args {
+        Firmware::Info => print_info(&fakeldat),
+        Firmware::EnterBootloader => {
+            fakeldat.enter_bootloader()?;
+            println!("Rebooting into the bootloader");
+        }
+        Firmware::Flash(flash) => {
+            firmware::update(fakeldat, &flash.image, flash.timeout, |written, total| {
+                eprint!("\rFlashing: {}%", written * 100 / total.max(1));
+            })?;
+            eprintln!();
+            // the new firmware has to answer the hello to count as updated
+            let retries = u32::try_from(flash.timeout.as_millis() / RECONNECT_DELAY.as_millis())
+                .unwrap_or(u32::MAX);
+            let fakeldat = FakeLDAT::builder()
+                .path(port)
+                .handshake(Some(Duration::from_secs(1)))
+                .retries(retries, RECONNECT_DELAY)
+                .open()?;
+            println!("Device answered after the update");
+            print_info(&fakeldat);
+        }
+    }
+    Ok(())
+}
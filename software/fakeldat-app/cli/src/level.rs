@@ -0,0 +1,90 @@
+use std::collections::VecDeque;
+use std::io::{stdout, Write};
+use std::thread::sleep;
+use std::time::Duration;
+
+use fakeldat_lib::{Baseline, Error, FakeLDAT, Report, ReportMode};
+
+// Same as HISTORY_SIZE in the firmware, the relative threshold is added to their average
+const HISTORY_SIZE: usize = 150;
+
+#[derive(clap::Args)]
+pub struct Level {
+    /// Characters the bar takes at its fullest
+    #[arg(long, default_value_t = 50)]
+    width: usize,
+}
+
+// Brightness as a bar with the threshold marked in it, scaled to the highest value seen so far
+fn bar(brightness: u16, threshold: Option<u16>, scale: u16, width: usize) -> String {
+    let position = |value: u16| usize::from(value) * width / usize::from(scale.max(1));
+    let filled = position(brightness);
+    let marker = threshold.map(position);
+    (0..=width)
+        .map(|column| {
+            if marker == Some(column) {
+                '|'
+            } else if column < filled {
+                '#'
+            } else {
+                ' '
+            }
+        })
+        .collect()
+}
+
+// Raw samples are needed, the device is switched to combined mode and stays in it
+pub fn run(fakeldat: &mut FakeLDAT, args: &Level) -> Result<(), Error> {
+    fakeldat.set_report_mode(ReportMode::Combined)?;
+    fakeldat.get_threshold()?;
+    // older firmware only knows the relative threshold
+    match fakeldat.get_detection_mode() {
+        Ok(()) | Err(Error::Unsupported(_)) => {}
+        Err(why) => return Err(why),
+    }
+    let mut threshold = None;
+    let mut baseline = Baseline::Relative;
+    let mut history = VecDeque::with_capacity(HISTORY_SIZE);
+    let mut scale = 0;
+    loop {
+        fakeldat.poll_bulk_data()?;
+        for report in fakeldat.take_report_buffer().unwrap_or_default() {
+            match report {
+                Report::Raw(raw_report) if raw_report.channel == 0 => {
+                    if history.len() == HISTORY_SIZE {
+                        history.pop_front();
+                    }
+                    history.push_back(raw_report.brightness);
+                }
+                Report::Threshold(value) => threshold = Some(value),
+                Report::DetectionMode(detection_mode) => baseline = detection_mode.baseline,
+                _ => {}
+            }
+        }
+        let Some(&brightness) = history.back() else {
+            sleep(Duration::from_millis(100));
+            continue;
+        };
+        let absolute_threshold = threshold.map(|threshold| {
+            let base = match baseline {
+                Baseline::Relative => {
+                    let sum: i64 = history.iter().map(|&value| i64::from(value)).sum();
+                    sum / i64::try_from(history.len()).unwrap_or(1)
+                }
+                Baseline::Absolute => 0,
+            };
+            u16::try_from((base + i64::from(threshold)).max(0)).unwrap_or(u16::MAX)
+        });
+        scale = scale
+            .max(brightness)
+            .max(absolute_threshold.unwrap_or_default());
+        let threshold_text =
+            absolute_threshold.map_or_else(|| "?".to_string(), |threshold| threshold.to_string());
+        print!(
+            "\rBrightness: {brightness:>5} [{}] threshold: {threshold_text:>5}",
+            bar(brightness, absolute_threshold, scale, args.width)
+        );
+        stdout().flush()?;
+        sleep(Duration::from_millis(100));
+    }
+}
@@ -1,13 +1,106 @@
-use std::{thread::sleep, time::Duration};
+use std::{
+    fs::File,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread::sleep,
+    time::{Duration, Instant},
+};
 
-use clap::Parser;
-use fakeldat_lib::{self, serialport, Error, FakeLDAT, Report};
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use fakeldat_lib::{
+    self, AckPolicy, Error, FakeLDAT, GamepadButton, MouseButton, Profile, ReplaySpeed, Report,
+    Stats,
+};
+use tracing_subscriber::EnvFilter;
 
+mod analyze;
+mod bench;
+mod bridge;
+mod compare;
+mod firmware;
+mod level;
+mod multi;
+mod plan;
+mod ports;
+mod report;
+mod set_all;
+mod sniff;
+mod status;
+mod stream;
+mod summary;
+mod tail;
+mod tui;
+
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Parser)]
 struct Args {
-    /// Name of the port, i.e. /dev/ttyACM0 on Linux or COM1 on Windows
+    /// Name of the port, i.e. /dev/ttyACM0 on Linux or COM1 on Windows, repeat to stream from several devices
+    #[arg(short, long)]
+    port: Vec<String>,
+    /// Stream from every device that answers
+    #[arg(long, conflicts_with = "port")]
+    all: bool,
+    /// Play a raw or summary recording, CSV or NDJSON, back instead of using a device
+    #[arg(long, conflicts_with_all = ["port", "all"])]
+    replay: Option<PathBuf>,
+    /// Hand out the recording as fast as it's read instead of at its original pace
+    #[arg(long, requires = "replay")]
+    replay_fast: bool,
+    /// Print statistics every second instead of the data when streaming from several devices
+    #[arg(long, value_enum)]
+    stats: Option<multi::StatsMode>,
+    /// Frame checksum to request from the device, older firmware stays on sum
+    #[arg(long, value_enum, default_value_t = Integrity::Sum)]
+    integrity: Integrity,
+    /// Bytes per frame, older firmware stays on 16
+    #[arg(long, value_enum, default_value_t = FrameSize::Standard)]
+    frame_size: FrameSize,
+    /// Ask the device for sequence numbers to report lost frames
+    #[arg(long)]
+    sequence_numbers: bool,
+    /// Report raw samples further apart than the poll rate allows
+    #[arg(long)]
+    detect_gaps: bool,
+    /// Switch to this baud rate after connecting, stays on 115200 if that fails
+    #[arg(long)]
+    baud_rate: Option<u32>,
+    /// Publish streamed raw reports into a shared memory ring buffer with this name
+    #[arg(long)]
+    shared_ring: Option<String>,
+    /// Number of raw reports the shared memory ring buffer holds
+    #[arg(long, default_value_t = 65536)]
+    shared_ring_capacity: u32,
+    /// How long get and set commands wait for the answer, like 500ms or 2s
+    #[arg(long, value_parser = stream::parse_duration, default_value = "1s")]
+    timeout: Duration,
+    /// How many times get and set commands are sent again without an answer
+    #[arg(long, default_value_t = 2)]
+    retries: u32,
+    /// Print a man page and exit
+    #[arg(long, hide = true)]
+    man: bool,
+    /// Log more of what happens, repeat for more detail
+    #[arg(short, long, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    /// Only log errors
     #[arg(short, long)]
-    port: String,
+    quiet: bool,
+    /// Log every frame sent and received in hex
+    #[arg(long)]
+    log_frames: bool,
+    /// Write the log to this file instead of the standard error, replaced when it exists
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+    /// Stop streaming after this long, like 90s, 10m or 1h
+    #[arg(long, value_parser = stream::parse_duration)]
+    duration: Option<Duration>,
+    /// Stop streaming after this many measurements and trigger events
+    #[arg(long)]
+    count: Option<u64>,
     /// Set device poll rate
     #[command(subcommand)]
     command: Option<Command>,
@@ -21,8 +114,121 @@ enum Command {
     /// Set a setting
     #[command(subcommand)]
     Set(SettingSet),
-    /// Set a setting
-    ManualTrigger
+    /// Press the action once, with --wait print the delay it measured
+    ManualTrigger(ManualTriggerS),
+    /// Measure the base and flash brightness to recommend a threshold
+    Calibrate(Calibrate),
+    /// Print the device temperature and supply voltage
+    Telemetry,
+    /// Print every setting with the firmware and link details, with --json as one document
+    Status(status::Status),
+    /// Let the device measure a synthetic brightness step, exits with 1 when it's off
+    SelfTest(SelfTestS),
+    /// Fire triggers at random intervals and print delay statistics with a histogram
+    Bench(bench::Bench),
+    /// Show the current brightness against the threshold to position the sensor
+    Level(level::Level),
+    /// Print every frame read in hex with what it decodes to and if its checksum matches
+    Sniff(sniff::Sniff),
+    /// Dashboard in the terminal with the brightness, triggers, settings and delays
+    Tui,
+    /// Print summary delays, or with --live a table of their statistics
+    Summary(summary::Summary),
+    /// Write reports as CSV, JSON or NDJSON, to a file or the standard output
+    Stream(stream::Stream),
+    /// List serial ports and which of them a FakeLDAT answers on
+    Ports(ports::Ports),
+    /// Follow a recording in progress without using the device
+    Tail(tail::Tail),
+    /// Derive delays from a raw recording
+    Analyze(analyze::Analyze),
+    /// Compare the delays of two recordings and tell if the difference is significant
+    Compare(compare::Compare),
+    /// Set or print all device settings at once as TOML
+    #[command(subcommand)]
+    Profile(ProfileCommand),
+    /// Show the firmware version, reboot into the bootloader or update the firmware
+    #[command(subcommand)]
+    Firmware(firmware::Firmware),
+    /// Print shell completions, i.e. fakeldat-cli completions bash > /etc/bash_completion.d/fakeldat-cli
+    Completions(CompletionsArgs),
+    /// Serial protocol description
+    #[command(subcommand)]
+    Protocol(ProtocolCommand),
+    /// Print the JSON Schema of a machine-readable format
+    Schema(SchemaArgs),
+    /// Print a documented measurement plan to start from
+    InitPlan(plan::InitPlan),
+    /// Summarize stored sessions as Markdown or HTML
+    Report(report::SessionReport),
+    /// Share the device with WebSocket clients, reports and requests as JSON
+    Bridge(bridge::Bridge),
+}
+
+#[derive(clap::Args)]
+struct Calibrate {
+    /// Milliseconds spent on each of the base and flash levels
+    #[arg(long, default_value_t = 2000)]
+    duration: u64,
+    /// Write the recommended threshold to the device
+    #[arg(long)]
+    apply: bool,
+}
+
+#[derive(clap::Args)]
+struct ManualTriggerS {
+    /// Wait for the summary report of the press, needs summary or combined report mode
+    #[arg(long)]
+    wait: bool,
+    /// How long to wait for the summary report, like 500ms or 2s
+    #[arg(long, value_parser = stream::parse_duration, default_value = "2s", requires = "wait")]
+    timeout: Duration,
+}
+
+#[derive(clap::Args)]
+struct SelfTestS {
+    /// Milliseconds between the simulated trigger and the step
+    #[arg(long, default_value_t = 20)]
+    step: u16,
+}
+
+#[derive(clap::Args)]
+struct CompletionsArgs {
+    shell: clap_complete::Shell,
+}
+
+#[derive(clap::Args)]
+struct SchemaArgs {
+    document: SchemaDocument,
+}
+
+#[derive(Clone, clap::ValueEnum)]
+enum SchemaDocument {
+    /// Single report of an NDJSON report stream
+    Reports,
+    /// Delay statistics
+    Stats,
+    /// Metadata stored with a session
+    Session,
+}
+
+#[derive(clap::Subcommand)]
+enum ProfileCommand {
+    /// Set every setting stored in the file
+    Apply(ProfileFile),
+    /// Print the current settings, ready to be applied later
+    Dump,
+}
+
+#[derive(clap::Args)]
+struct ProfileFile {
+    path: PathBuf,
+}
+
+#[derive(clap::Subcommand)]
+enum ProtocolCommand {
+    /// Print commands, frame layouts and enums as JSON
+    Dump,
 }
 
 #[derive(clap::Subcommand)]
@@ -35,9 +241,27 @@ enum SettingSet {
     Threshold(Threshold),
     /// Set Action key
     Action(ActionModeS),
+    /// Set how long the manual trigger holds the action
+    ActionTiming(ActionTimingS),
+    /// Pack raw samples into fewer frames, without audio
+    RawBatching(RawBatching),
+    /// Fire the action on its own at random intervals, count of 0 stops it
+    AutoTrigger(AutoTriggerS),
+    /// Set the brightness change which ends a measurement
+    DetectionMode(DetectionModeS),
+    /// Set how many light sensor readings are summed per sample
+    SensorGain(SensorGainS),
+    /// Set which light sensors are streamed, bit n enables channel n
+    Channels(Channels),
+    /// Set seconds between telemetry reports, 0 turns them off
+    TelemetryInterval(TelemetryInterval),
+    /// Send one min/mean/max report per so many measurements, 0 reports each one
+    SummaryAggregation(SummaryAggregation),
+    /// Set several settings at once and check that the device kept all of them
+    All(set_all::AllSettings),
 }
 
-#[derive(clap::Subcommand)]
+#[derive(Clone, clap::Subcommand)]
 enum SettingGet {
     /// Get Poll rate
     PollRate,
@@ -47,6 +271,28 @@ enum SettingGet {
     Threshold,
     // Get Action key
     Action,
+    /// Get how long the manual trigger holds the action
+    ActionTiming,
+    /// Get frame checksum in use
+    Integrity,
+    /// Get whether raw samples are batched
+    RawBatching,
+    /// Get serial link speed
+    BaudRate,
+    /// Get bytes per frame
+    FrameSize,
+    /// Get automatic trigger intervals and presses left
+    AutoTrigger,
+    /// Get the brightness change which ends a measurement
+    DetectionMode,
+    /// Get how many light sensor readings are summed per sample
+    SensorGain,
+    /// Get which light sensors are streamed
+    Channels,
+    /// Get seconds between telemetry reports
+    TelemetryInterval,
+    /// Get measurements per summary aggregate
+    SummaryAggregation,
 }
 
 #[derive(clap::Args)]
@@ -59,6 +305,140 @@ struct Threshold {
     value: i16,
 }
 
+#[derive(clap::Args)]
+struct ActionTimingS {
+    /// Press duration in milliseconds
+    #[arg(value_parser = clap::value_parser!(u16).range(1..=32767))]
+    press_duration: u16,
+    /// Press twice, with a pause as long as the press
+    #[arg(long)]
+    double_press: bool,
+}
+
+impl From<ActionTimingS> for fakeldat_lib::ActionTiming {
+    fn from(value: ActionTimingS) -> Self {
+        Self {
+            press_duration: value.press_duration,
+            double_press: value.double_press,
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct AutoTriggerS {
+    /// Shortest pause between presses in milliseconds
+    min_interval: u16,
+    /// Longest pause between presses in milliseconds
+    max_interval: u16,
+    count: u16,
+}
+
+#[derive(clap::Args)]
+struct DetectionModeS {
+    edge: Edge,
+    #[arg(default_value = "relative")]
+    baseline: Baseline,
+}
+
+impl From<DetectionModeS> for fakeldat_lib::DetectionMode {
+    fn from(value: DetectionModeS) -> Self {
+        Self {
+            edge: value.edge.into(),
+            baseline: value.baseline.into(),
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct TelemetryInterval {
+    value: u16,
+}
+
+#[derive(clap::Args)]
+struct SummaryAggregation {
+    value: u8,
+}
+
+#[derive(clap::Args)]
+struct Channels {
+    #[arg(value_parser = clap::value_parser!(u8).range(1..))]
+    value: u8,
+}
+
+#[derive(clap::Args)]
+struct SensorGainS {
+    value: SensorGain,
+}
+
+#[derive(Clone, clap::ValueEnum)]
+enum SensorGain {
+    #[value(name = "1")]
+    X1,
+    #[value(name = "2")]
+    X2,
+    #[value(name = "4")]
+    X4,
+    #[value(name = "8")]
+    X8,
+    #[value(name = "16")]
+    X16,
+}
+
+impl From<SensorGain> for fakeldat_lib::SensorGain {
+    fn from(value: SensorGain) -> Self {
+        match value {
+            SensorGain::X1 => Self::X1,
+            SensorGain::X2 => Self::X2,
+            SensorGain::X4 => Self::X4,
+            SensorGain::X8 => Self::X8,
+            SensorGain::X16 => Self::X16,
+        }
+    }
+}
+
+#[derive(Clone, clap::ValueEnum)]
+enum Edge {
+    /// Follow the sign of the threshold
+    Auto,
+    /// Dark to light
+    Rising,
+    /// Light to dark
+    Falling,
+}
+
+impl From<Edge> for fakeldat_lib::Edge {
+    fn from(value: Edge) -> Self {
+        match value {
+            Edge::Auto => Self::Auto,
+            Edge::Rising => Self::Rising,
+            Edge::Falling => Self::Falling,
+        }
+    }
+}
+
+#[derive(Clone, clap::ValueEnum)]
+enum Baseline {
+    /// Threshold added to the recent average brightness
+    Relative,
+    /// Threshold is the brightness level itself
+    Absolute,
+}
+
+impl From<Baseline> for fakeldat_lib::Baseline {
+    fn from(value: Baseline) -> Self {
+        match value {
+            Baseline::Relative => Self::Relative,
+            Baseline::Absolute => Self::Absolute,
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct RawBatching {
+    #[arg(action = clap::ArgAction::Set)]
+    value: bool,
+}
+
 #[derive(clap::Args)]
 struct ReportModeS {
     value: ReportMode,
@@ -69,6 +449,10 @@ enum ReportMode {
     Raw,
     Summary,
     Combined,
+    /// Audio sensor samples only
+    RawAudio,
+    /// Delays of both the screen and the audio click
+    AudioVideo,
 }
 
 impl From<ReportMode> for fakeldat_lib::ReportMode {
@@ -77,6 +461,59 @@ impl From<ReportMode> for fakeldat_lib::ReportMode {
             ReportMode::Raw => Self::Raw,
             ReportMode::Summary => Self::Summary,
             ReportMode::Combined => Self::Combined,
+            ReportMode::RawAudio => Self::RawAudio,
+            ReportMode::AudioVideo => Self::AudioVideo,
+        }
+    }
+}
+
+#[derive(Clone, clap::ValueEnum)]
+enum Integrity {
+    Sum,
+    Crc8,
+    Crc16,
+}
+
+impl From<Integrity> for fakeldat_lib::Integrity {
+    fn from(value: Integrity) -> Self {
+        match value {
+            Integrity::Sum => Self::Sum,
+            Integrity::Crc8 => Self::Crc8,
+            Integrity::Crc16 => Self::Crc16,
+        }
+    }
+}
+
+#[derive(Clone, clap::ValueEnum)]
+enum FrameSize {
+    #[value(name = "16")]
+    Standard,
+    #[value(name = "32")]
+    Extended,
+}
+
+impl From<FrameSize> for fakeldat_lib::FrameSize {
+    fn from(value: FrameSize) -> Self {
+        match value {
+            FrameSize::Standard => Self::Standard,
+            FrameSize::Extended => Self::Extended,
+        }
+    }
+}
+
+#[derive(Clone, clap::ValueEnum)]
+enum Detector {
+    Fixed,
+    Adaptive,
+    Derivative,
+}
+
+impl From<Detector> for fakeldat_lib::analysis::DetectorKind {
+    fn from(value: Detector) -> Self {
+        match value {
+            Detector::Fixed => Self::Fixed,
+            Detector::Adaptive => Self::Adaptive,
+            Detector::Derivative => Self::Derivative,
         }
     }
 }
@@ -90,12 +527,13 @@ struct ActionModeS {
 impl From<ActionModeS> for fakeldat_lib::ActionMode {
     fn from(value: ActionModeS) -> Self {
         match value.action_mode {
-            ActionMode::Mouse => {
-                Self::Mouse((value.key as u8).try_into().expect("Invalid mouse button"))
-            }
+            ActionMode::Mouse => Self::Mouse(value.key.try_into().expect("Invalid mouse button")),
             ActionMode::Keyboard => {
                 Self::Keyboard((value.key as u8).try_into().expect("Invalid keyboard key"))
             }
+            ActionMode::Gamepad => {
+                Self::Gamepad(value.key.try_into().expect("Invalid gamepad button"))
+            }
         }
     }
 }
@@ -106,6 +544,8 @@ enum Key {
     Left = 1,
     Right = 2,
     Middle = 4,
+    Back = 8,
+    Forward = 16,
     A = b'a',
     B = b'b',
     C = b'c',
@@ -132,16 +572,131 @@ enum Key {
     X = b'x',
     Y = b'y',
     Z = b'z',
+    #[value(name = "0")]
+    Num0 = b'0',
+    #[value(name = "1")]
+    Num1 = b'1',
+    #[value(name = "2")]
+    Num2 = b'2',
+    #[value(name = "3")]
+    Num3 = b'3',
+    #[value(name = "4")]
+    Num4 = b'4',
+    #[value(name = "5")]
+    Num5 = b'5',
+    #[value(name = "6")]
+    Num6 = b'6',
+    #[value(name = "7")]
+    Num7 = b'7',
+    #[value(name = "8")]
+    Num8 = b'8',
+    #[value(name = "9")]
+    Num9 = b'9',
+    Space = b' ',
+    Enter = 0xB0,
+    ArrowRight = 0xD7,
+    ArrowLeft = 0xD8,
+    ArrowDown = 0xD9,
+    ArrowUp = 0xDA,
+    F1 = 0xC2,
+    F2 = 0xC3,
+    F3 = 0xC4,
+    F4 = 0xC5,
+    F5 = 0xC6,
+    F6 = 0xC7,
+    F7 = 0xC8,
+    F8 = 0xC9,
+    F9 = 0xCA,
+    F10 = 0xCB,
+    F11 = 0xCC,
+    F12 = 0xCD,
+    LeftCtrl = 0x80,
+    LeftShift = 0x81,
+    LeftAlt = 0x82,
+    LeftGui = 0x83,
+    RightCtrl = 0x84,
+    RightShift = 0x85,
+    RightAlt = 0x86,
+    RightGui = 0x87,
+    // only for the gamepad, letters cover the face buttons
+    LeftBumper = 0xF0,
+    RightBumper = 0xF1,
+    LeftTrigger = 0xF2,
+    RightTrigger = 0xF3,
+    Start = 0xF5,
+    // only for the mouse, device values overlap with keys
+    ScrollUp = 0xF6,
+    ScrollDown = 0xF7,
+}
+
+impl TryFrom<Key> for MouseButton {
+    type Error = Error;
+
+    fn try_from(value: Key) -> Result<Self, Self::Error> {
+        match value {
+            Key::Left => Ok(Self::Left),
+            Key::Right => Ok(Self::Right),
+            Key::Middle => Ok(Self::Middle),
+            Key::Back => Ok(Self::Back),
+            Key::Forward => Ok(Self::Forward),
+            Key::ScrollUp => Ok(Self::ScrollUp),
+            Key::ScrollDown => Ok(Self::ScrollDown),
+            _ => Err(Error::InvalidEnumConverion),
+        }
+    }
+}
+
+impl TryFrom<Key> for GamepadButton {
+    type Error = Error;
+
+    fn try_from(value: Key) -> Result<Self, Self::Error> {
+        match value {
+            Key::A => Ok(Self::A),
+            Key::B => Ok(Self::B),
+            Key::X => Ok(Self::X),
+            Key::Y => Ok(Self::Y),
+            Key::LeftBumper => Ok(Self::LeftBumper),
+            Key::RightBumper => Ok(Self::RightBumper),
+            Key::LeftTrigger => Ok(Self::LeftTrigger),
+            Key::RightTrigger => Ok(Self::RightTrigger),
+            Key::Back => Ok(Self::Back),
+            Key::Start => Ok(Self::Start),
+            _ => Err(Error::InvalidEnumConverion),
+        }
+    }
 }
 
 #[derive(Clone, clap::ValueEnum)]
 enum ActionMode {
     Mouse,
     Keyboard,
+    Gamepad,
+}
+
+// Set by Ctrl-C once handle_interrupts was called
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Only for loops checking interrupted, Ctrl-C doesn't end the process anymore afterwards
+pub fn handle_interrupts() {
+    // fails only when a handler is already set
+    _ = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::Relaxed));
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
 }
 
+// Lets scripts tell an unresponsive device apart from other failures
+const EXIT_FAILURE: i32 = 1;
+const EXIT_TIMEOUT: i32 = 2;
+
 fn main() {
     if let Some(err) = handle_fakeldat().err() {
+        let code = if matches!(err, Error::CommandTimeout(_)) {
+            EXIT_TIMEOUT
+        } else {
+            EXIT_FAILURE
+        };
         match err {
             Error::WrongChecksum(_, _, _) | Error::ReadTooLittleData => unreachable!(), // Those should be internal
             Error::InvalidSetting(command, buf) => {
@@ -151,30 +706,187 @@ fn main() {
             Error::SendCommandFail => eprintln!("Issue with sending a command"),
             Error::IOError(io_error) => eprintln!("Issue with saving a file: {io_error}"),
             Error::InvalidEnumConverion => eprintln!("TryFrom enum conversion error"),
+            Error::CommandTimeout(command) => eprintln!("No response for: {command}"),
+            Error::ReportBufferFull => eprintln!("Reports are coming in faster than handled"),
+            Error::Unsupported(command) => {
+                eprintln!("The firmware doesn't support: {command}, updating it might help");
+            }
             Error::PortFail(serialport_error) => {
                 eprintln!("Port fail: {}", serialport_error.description);
             }
         }
+        std::process::exit(code);
+    }
+}
+
+fn get(fakeldat: &mut FakeLDAT, setting: &SettingGet) -> Result<(), Error> {
+    match setting {
+        SettingGet::PollRate => fakeldat.get_poll_rate(),
+        SettingGet::ReportMode => fakeldat.get_report_mode(),
+        SettingGet::Threshold => fakeldat.get_threshold(),
+        SettingGet::Action => fakeldat.get_action(),
+        SettingGet::Integrity => fakeldat.get_integrity(),
+        SettingGet::ActionTiming => fakeldat.get_action_timing(),
+        SettingGet::RawBatching => fakeldat.get_raw_batching(),
+        SettingGet::BaudRate => fakeldat.get_baud_rate(),
+        SettingGet::FrameSize => fakeldat.get_frame_size(),
+        SettingGet::AutoTrigger => fakeldat.get_auto_trigger(),
+        SettingGet::DetectionMode => fakeldat.get_detection_mode(),
+        SettingGet::SensorGain => fakeldat.get_sensor_gain(),
+        SettingGet::Channels => fakeldat.get_channels(),
+        SettingGet::TelemetryInterval => fakeldat.get_telemetry_interval(),
+        SettingGet::SummaryAggregation => fakeldat.get_summary_aggregation(),
+    }
+}
+
+// Warnings only by default, the standard output stays free for the data
+fn init_logging(args: &Args) -> Result<(), Error> {
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => "error",
+        (false, 0) => "warn",
+        (false, 1) => "info",
+        (false, 2) => "debug",
+        _ => "trace",
+    };
+    let filter = if args.log_frames {
+        EnvFilter::new(format!("{level},fakeldat_lib::frames=trace"))
+    } else {
+        EnvFilter::new(level)
+    };
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match args.log_file {
+        Some(ref path) => subscriber
+            .with_writer(Mutex::new(File::create(path)?))
+            .with_ansi(false)
+            .init(),
+        None => subscriber.with_writer(std::io::stderr).init(),
     }
+    Ok(())
 }
 
+#[allow(clippy::too_many_lines)]
 fn handle_fakeldat() -> Result<(), Error> {
     let args = Args::parse();
+    init_logging(&args)?;
+
+    if args.man {
+        clap_mangen::Man::new(Args::command()).render(&mut std::io::stdout())?;
+        return Ok(());
+    }
 
-    let port = serialport::new(args.port, 115_200)
-        .timeout(Duration::from_secs(100_000))
-        .open()?;
+    match &args.command {
+        Some(Command::Ports(ports)) => return ports::run(ports),
+        Some(Command::Completions(completions)) => {
+            clap_complete::generate(
+                completions.shell,
+                &mut Args::command(),
+                "fakeldat-cli",
+                &mut std::io::stdout(),
+            );
+            return Ok(());
+        }
+        Some(Command::Tail(tail)) => return tail::run(tail),
+        Some(Command::Analyze(analyze)) => return analyze::run(analyze),
+        Some(Command::Compare(compare)) => return compare::run(compare),
+        Some(Command::Protocol(ProtocolCommand::Dump)) => {
+            println!("{}", fakeldat_lib::protocol::describe().to_json()?);
+            return Ok(());
+        }
+        Some(Command::Schema(schema)) => {
+            let schema = match schema.document {
+                SchemaDocument::Reports => fakeldat_lib::schema::reports(),
+                SchemaDocument::Stats => fakeldat_lib::schema::stats(),
+                SchemaDocument::Session => fakeldat_lib::schema::session(),
+            };
+            println!("{}", schema.to_json()?);
+            return Ok(());
+        }
+        Some(Command::Report(report)) => return report::run(report),
+        Some(Command::InitPlan(init_plan)) => {
+            plan::run(init_plan);
+            return Ok(());
+        }
+        _ => {}
+    }
 
-    let mut fakeldat = FakeLDAT::create(port)?;
+    // None for replays
+    let (mut fakeldat, port) = if let Some(ref recording) = args.replay {
+        let speed = if args.replay_fast {
+            ReplaySpeed::Fastest
+        } else {
+            ReplaySpeed::Original
+        };
+        (FakeLDAT::replay(recording, speed)?, None)
+    } else {
+        let ports = if args.all {
+            multi::all_ports()?
+        } else {
+            args.port.clone()
+        };
+        let Some(port) = ports.first().cloned() else {
+            Args::command()
+                .error(ErrorKind::MissingRequiredArgument, "--port is required")
+                .exit()
+        };
+        let mut builder = FakeLDAT::builder()
+            .timeout(Duration::from_secs(100_000))
+            .integrity(args.integrity.into())
+            .frame_size(args.frame_size.into())
+            .sequence_numbers(args.sequence_numbers);
+        if let Some(baud_rate) = args.baud_rate {
+            builder = builder.switch_baud_rate(baud_rate);
+        }
+        if args.all || ports.len() > 1 {
+            if !matches!(
+                args.command,
+                None | Some(Command::Stream(_) | Command::Bench(_))
+            ) {
+                Args::command()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        "Only streaming, stream and bench work with several devices",
+                    )
+                    .exit()
+            }
+            if args.command.is_none() {
+                return multi::run(
+                    &builder,
+                    &ports,
+                    args.all,
+                    args.stats,
+                    args.duration,
+                    args.count,
+                );
+            }
+            let mut devices = multi::open_all(&builder, &ports, args.all)?;
+            let mut devices: Vec<(String, &mut FakeLDAT)> = devices
+                .iter_mut()
+                .map(|(label, fakeldat)| (label.clone(), fakeldat))
+                .collect();
+            return match args.command {
+                Some(Command::Stream(ref stream)) => stream::run(&mut devices, stream),
+                Some(Command::Bench(ref bench)) => bench::run(&mut devices, bench),
+                _ => unreachable!(),
+            };
+        }
+        (builder.path(port.clone()).open()?, Some(port))
+    };
+    fakeldat.set_gap_detection(args.detect_gaps);
 
     if let Some(command) = args.command {
+        // sets retry on their own until echoed, gets and telemetry are sent again here
+        fakeldat.set_acknowledge(Some(AckPolicy {
+            timeout: args.timeout,
+            retries: args.retries,
+        }));
+        // the inner None asks for telemetry again
+        let resend = match command {
+            Command::Get(ref setting) => Some(Some(setting.clone())),
+            Command::Telemetry => Some(None),
+            _ => None,
+        };
         match command {
-            Command::Get(setting) => match setting {
-                SettingGet::PollRate => fakeldat.get_poll_rate(),
-                SettingGet::ReportMode => fakeldat.get_report_mode(),
-                SettingGet::Threshold => fakeldat.get_threshold(),
-                SettingGet::Action => fakeldat.get_action(),
-            },
+            Command::Get(setting) => get(&mut fakeldat, &setting),
             Command::Set(setting) => match setting {
                 SettingSet::PollRate(poll_rate) => fakeldat.set_poll_rate(poll_rate.value),
                 SettingSet::ReportMode(report_mode) => {
@@ -182,11 +894,139 @@ fn handle_fakeldat() -> Result<(), Error> {
                 }
                 SettingSet::Threshold(threshold) => fakeldat.set_threshold(threshold.value),
                 SettingSet::Action(action) => fakeldat.set_action(action.into()),
+                SettingSet::ActionTiming(action_timing) => {
+                    fakeldat.set_action_timing(action_timing.into())
+                }
+                SettingSet::RawBatching(raw_batching) => {
+                    fakeldat.set_raw_batching(raw_batching.value)
+                }
+                SettingSet::AutoTrigger(auto_trigger) if auto_trigger.count == 0 => {
+                    fakeldat.stop_auto_trigger()
+                }
+                SettingSet::AutoTrigger(auto_trigger) => fakeldat.start_auto_trigger(
+                    auto_trigger.min_interval,
+                    auto_trigger.max_interval,
+                    auto_trigger.count,
+                ),
+                SettingSet::DetectionMode(detection_mode) => {
+                    fakeldat.set_detection_mode(detection_mode.into())
+                }
+                SettingSet::SensorGain(sensor_gain) => {
+                    fakeldat.set_sensor_gain(sensor_gain.value.into())
+                }
+                SettingSet::Channels(channels) => fakeldat.set_channels(channels.value),
+                SettingSet::TelemetryInterval(interval) => {
+                    fakeldat.set_telemetry_interval(interval.value)
+                }
+                SettingSet::SummaryAggregation(aggregation) => {
+                    fakeldat.set_summary_aggregation(aggregation.value)
+                }
+                SettingSet::All(settings) => {
+                    return set_all::run(&mut fakeldat, &settings, args.timeout)
+                }
             },
-            Command::ManualTrigger => {
+            Command::ManualTrigger(trigger) if trigger.wait => {
+                #[allow(clippy::cast_precision_loss)]
+                return match fakeldat.manual_trigger_and_wait(trigger.timeout) {
+                    Ok(summary) => {
+                        println!("Delay: {:.2} ms", summary.delay as f64 / 1000.0);
+                        Ok(())
+                    }
+                    Err(Error::CommandTimeout(_)) => {
+                        eprintln!("Nothing measured, check the threshold and the report mode");
+                        std::process::exit(EXIT_TIMEOUT);
+                    }
+                    Err(why) => Err(why),
+                };
+            }
+            Command::ManualTrigger(_) => {
                 return fakeldat.manual_trigger();
             }
+            Command::Telemetry => fakeldat.request_telemetry(),
+            Command::SelfTest(self_test) => {
+                let result = fakeldat.self_test(Duration::from_millis(self_test.step.into()))?;
+                let detected = result
+                    .detected_us
+                    .map_or_else(|| "nothing".to_string(), |delay| format!("{delay} µs"));
+                println!(
+                    "Step at {} µs, detected {detected}, sampled every {} µs",
+                    result.step_us, result.sample_interval_us
+                );
+                if !result.passed() {
+                    println!("Self-test failed, check the threshold and detection mode");
+                    std::process::exit(1);
+                }
+                println!("Self-test passed");
+                return Ok(());
+            }
+            Command::Profile(ProfileCommand::Apply(file)) => {
+                fakeldat.apply_profile(&Profile::load(&file.path)?)?;
+                return Ok(());
+            }
+            Command::Profile(ProfileCommand::Dump) => {
+                print!("{}", fakeldat.read_profile(args.timeout)?.to_toml()?);
+                return Ok(());
+            }
+            Command::Bridge(bridge) => return bridge::run(fakeldat, &bridge),
+            Command::Firmware(firmware) => {
+                let Some(port) = port else {
+                    Args::command()
+                        .error(
+                            ErrorKind::ArgumentConflict,
+                            "Firmware commands need a device, not a recording",
+                        )
+                        .exit()
+                };
+                return firmware::run(fakeldat, &port, &firmware);
+            }
+            Command::Stream(stream) => {
+                return stream::run(&mut [(port.unwrap_or_default(), &mut fakeldat)], &stream)
+            }
+            Command::Status(status) => {
+                return status::run(
+                    &mut fakeldat,
+                    &port.unwrap_or_default(),
+                    &status,
+                    args.timeout,
+                    args.retries,
+                )
+            }
+            Command::Summary(summary) => return summary::run(&mut fakeldat, &summary),
+            Command::Tui => return tui::run(&mut fakeldat),
+            Command::Sniff(sniff) => return sniff::run(&mut fakeldat, &sniff),
+            Command::Level(level) => return level::run(&mut fakeldat, &level),
+            Command::Bench(bench) => {
+                return bench::run(&mut [(port.unwrap_or_default(), &mut fakeldat)], &bench)
+            }
+            Command::Calibrate(calibrate) => {
+                println!("Point the sensor at the base state and press Enter");
+                _ = std::io::stdin().read_line(&mut String::new());
+                let calibration = fakeldat.calibrate_threshold(
+                    Duration::from_millis(calibrate.duration),
+                    calibrate.apply,
+                    || {
+                        println!("Switch to the flash state and press Enter");
+                        _ = std::io::stdin().read_line(&mut String::new());
+                    },
+                )?;
+                println!(
+                    "Base: {}, flash: {}, noise: {}, recommended threshold: {}",
+                    calibration.base, calibration.flash, calibration.noise, calibration.threshold
+                );
+                return Ok(());
+            }
+            Command::Ports(_)
+            | Command::Completions(_)
+            | Command::Tail(_)
+            | Command::Analyze(_)
+            | Command::Compare(_)
+            | Command::Protocol(_)
+            | Command::Schema(_)
+            | Command::InitPlan(_)
+            | Command::Report(_) => unreachable!(),
         }?;
+        let mut attempts = 1;
+        let mut deadline = Instant::now() + args.timeout;
         loop {
             fakeldat.poll_bulk_data()?;
             if let Some(reports) = fakeldat.take_report_buffer() {
@@ -212,35 +1052,166 @@ fn handle_fakeldat() -> Result<(), Error> {
                                 fakeldat_lib::ActionMode::Keyboard(key) => {
                                     println!("Action: Keyboard, {key}");
                                 }
+                                fakeldat_lib::ActionMode::Gamepad(button) => {
+                                    println!("Action: Gamepad, {button}");
+                                }
                             };
                             return Ok(());
                         }
+                        Report::ActionTiming(action_timing) => {
+                            println!(
+                                "Action timing: {} ms{}",
+                                action_timing.press_duration,
+                                if action_timing.double_press {
+                                    ", double press"
+                                } else {
+                                    ""
+                                }
+                            );
+                            return Ok(());
+                        }
+                        Report::Integrity(integrity) => {
+                            println!("Integrity: {integrity}");
+                            return Ok(());
+                        }
+                        Report::RawBatching(enabled) => {
+                            println!("Raw batching: {enabled}");
+                            return Ok(());
+                        }
+                        Report::BaudRate(baud_rate) => {
+                            println!("Baud rate: {baud_rate}");
+                            return Ok(());
+                        }
+                        Report::FrameSize(frame_size) => {
+                            println!("Frame size: {frame_size}");
+                            return Ok(());
+                        }
+                        Report::AutoTriggerSettings(auto_trigger) => {
+                            println!(
+                                "Auto trigger: {}-{} ms, {} left",
+                                auto_trigger.min_interval,
+                                auto_trigger.max_interval,
+                                auto_trigger.remaining
+                            );
+                            return Ok(());
+                        }
+                        Report::SensorGain(sensor_gain) => {
+                            println!("Sensor gain: {sensor_gain}");
+                            return Ok(());
+                        }
+                        Report::Telemetry(telemetry) => {
+                            println!("Telemetry: {telemetry}");
+                            return Ok(());
+                        }
+                        Report::TelemetryInterval(interval) => {
+                            println!("Telemetry interval: {interval} s");
+                            return Ok(());
+                        }
+                        Report::SummaryAggregation(measurements) => {
+                            println!("Summary aggregation: {measurements}");
+                            return Ok(());
+                        }
+                        Report::Channels(channels) => {
+                            let enabled: Vec<String> = (0..8)
+                                .filter(|channel| channels >> channel & 1 == 1)
+                                .map(|channel: u8| channel.to_string())
+                                .collect();
+                            println!("Channels: {}", enabled.join(", "));
+                            return Ok(());
+                        }
+                        Report::DetectionMode(detection_mode) => {
+                            println!(
+                                "Detection mode: {}, {}",
+                                detection_mode.edge, detection_mode.baseline
+                            );
+                            return Ok(());
+                        }
                         _ => {}
                     }
                 }
             }
+            if Instant::now() >= deadline {
+                match resend {
+                    Some(ref setting) if attempts <= args.retries => {
+                        match setting {
+                            Some(setting) => get(&mut fakeldat, setting)?,
+                            None => fakeldat.request_telemetry()?,
+                        }
+                        attempts += 1;
+                        deadline = Instant::now() + args.timeout;
+                    }
+                    _ => {
+                        eprintln!("No answer after {attempts} attempts, check if the device is responsive");
+                        std::process::exit(EXIT_TIMEOUT);
+                    }
+                }
+            }
             sleep(Duration::from_millis(50));
         }
     } else {
-        loop {
+        if let Some(name) = args.shared_ring {
+            let path = fakeldat.publish_shared_ring(&name, args.shared_ring_capacity)?;
+            eprintln!("Publishing raw reports to {}", path.display());
+        }
+        // stops early on Ctrl-C, with the output flushed and the port closed
+        handle_interrupts();
+        let start = Instant::now();
+        let mut measurements = 0;
+        let mut stats = Stats::default();
+        let mut saturated = false;
+        'stream: while !interrupted()
+            && args
+                .duration
+                .is_none_or(|duration| start.elapsed() < duration)
+        {
             fakeldat.poll_bulk_data()?;
+            for warning in fakeldat.take_warnings() {
+                eprintln!("{warning}");
+            }
+            if fakeldat.link_stats().saturated != saturated {
+                saturated = !saturated;
+                if saturated {
+                    eprintln!("Sensor saturated, check if it's placed on the screen");
+                } else {
+                    eprintln!("Sensor no longer saturated");
+                }
+            }
             if let Some(reports) = fakeldat.take_report_buffer() {
                 for report in reports {
+                    if report.is_measurement() {
+                        measurements += 1;
+                    }
                     match report {
-                        Report::Raw(raw_report) => {
-                            println!(
-                                "{}, {}, {}",
-                                raw_report.timestamp, raw_report.brightness, raw_report.trigger
-                            );
-                        }
+                        // the extra column of other channels keeps them out of analyze
+                        Report::Raw(raw_report) => println!("{}", raw_report.to_csv_row()),
                         Report::Summary(summary_report) => {
-                            println!("{}, {}", summary_report.delay, summary_report.threshold);
+                            stats.push(summary_report.delay);
+                            println!("{}", summary_report.to_csv_row());
                         }
+                        Report::SummaryAggregate(aggregate) => {
+                            println!("{}", aggregate.to_csv_row());
+                        }
+                        Report::RawAudio(raw_audio) => println!("{}", raw_audio.to_csv_row()),
+                        Report::AudioVideo(audio_video) => {
+                            println!("{}", audio_video.to_csv_row());
+                        }
+                        Report::GapDetected {
+                            expected_us,
+                            actual_us,
+                        } => eprintln!(
+                            "Samples missing: {actual_us} µs apart, expected {expected_us} µs"
+                        ),
                         _ => {}
                     }
+                    if args.count.is_some_and(|count| measurements >= count) {
+                        break 'stream;
+                    }
                 }
             }
             sleep(Duration::from_millis(50));
         }
+        std::io::stdout().flush()?;
+        eprintln!("{}", tail::stats_line(measurements, &stats));
+        fakeldat.close()
     }
 }
@@ -0,0 +1,169 @@
+use std::io::Write;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use fakeldat_lib::{serialport, Error, FakeLDAT, FakeLDATBuilder, Report, Stats};
+
+use crate::tail::{print_stats, stats_line};
+use crate::{handle_interrupts, interrupted};
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum StatsMode {
+    /// One line for all devices together
+    Combined,
+    /// One line for every device
+    PerDevice,
+}
+
+struct Device {
+    label: String,
+    fakeldat: FakeLDAT,
+    samples: u64,
+    stats: Stats,
+    saturated: bool,
+}
+
+// Last part of the path, ttyACM0 or COM3
+fn label(port: &str) -> String {
+    Path::new(port).file_name().map_or_else(
+        || port.to_string(),
+        |name| name.to_string_lossy().to_string(),
+    )
+}
+
+// Ports which don't answer the handshake are skipped when looking through all of them
+pub fn all_ports() -> Result<Vec<String>, Error> {
+    Ok(serialport::available_ports()?
+        .into_iter()
+        .map(|port| port.port_name)
+        .collect())
+}
+
+// Devices with their labels, at least one
+pub fn open_all(
+    builder: &FakeLDATBuilder,
+    ports: &[String],
+    skip_failed: bool,
+) -> Result<Vec<(String, FakeLDAT)>, Error> {
+    let mut devices = Vec::with_capacity(ports.len());
+    for port in ports {
+        match builder.clone().path(port.clone()).open() {
+            Ok(fakeldat) => devices.push((label(port), fakeldat)),
+            Err(_) if skip_failed => eprintln!("Skipping {port}, no device answered"),
+            Err(why) => return Err(why),
+        }
+    }
+    if devices.is_empty() {
+        return Err(
+            serialport::Error::new(serialport::ErrorKind::NoDevice, "No device answered").into(),
+        );
+    }
+    Ok(devices)
+}
+
+// Every row starts with the device label, statistics replace the rows when enabled.
+// Duration and count are for all devices together, like streaming from a single one
+pub fn run(
+    builder: &FakeLDATBuilder,
+    ports: &[String],
+    skip_failed: bool,
+    stats_mode: Option<StatsMode>,
+    duration: Option<Duration>,
+    count: Option<u64>,
+) -> Result<(), Error> {
+    let mut devices: Vec<Device> = open_all(builder, ports, skip_failed)?
+        .into_iter()
+        .map(|(label, fakeldat)| Device {
+            label,
+            fakeldat,
+            samples: 0,
+            stats: Stats::default(),
+            saturated: false,
+        })
+        .collect();
+    // stops early on Ctrl-C, with the output flushed and the ports closed
+    handle_interrupts();
+    let start = Instant::now();
+    let mut measurements = 0;
+    let mut last_print = Instant::now();
+    'stream: while !interrupted() && duration.is_none_or(|duration| start.elapsed() < duration) {
+        for device in &mut devices {
+            device.fakeldat.poll_bulk_data()?;
+            for warning in device.fakeldat.take_warnings() {
+                eprintln!("{}: {warning}", device.label);
+            }
+            if device.fakeldat.link_stats().saturated != device.saturated {
+                device.saturated = !device.saturated;
+                if device.saturated {
+                    eprintln!(
+                        "{}: sensor saturated, check if it's placed on the screen",
+                        device.label
+                    );
+                } else {
+                    eprintln!("{}: sensor no longer saturated", device.label);
+                }
+            }
+            let Some(reports) = device.fakeldat.take_report_buffer() else {
+                continue;
+            };
+            for report in reports {
+                if report.is_measurement() {
+                    measurements += 1;
+                }
+                match report {
+                    // devices are compared by their first sensor
+                    Report::Raw(raw_report) if raw_report.channel == 0 => {
+                        device.samples += 1;
+                        if stats_mode.is_none() {
+                            println!("{}, {}", device.label, raw_report.to_csv_row());
+                        }
+                    }
+                    Report::Summary(summary_report) => {
+                        device.samples += 1;
+                        device.stats.push(summary_report.delay);
+                        if stats_mode.is_none() {
+                            println!("{}, {}", device.label, summary_report.to_csv_row());
+                        }
+                    }
+                    _ => {}
+                }
+                if count.is_some_and(|count| measurements >= count) {
+                    break 'stream;
+                }
+            }
+        }
+        if last_print.elapsed() >= Duration::from_secs(1) {
+            last_print = Instant::now();
+            match stats_mode {
+                Some(StatsMode::Combined) => {
+                    let stats = devices
+                        .iter()
+                        .flat_map(|device| device.stats.delays().iter().copied())
+                        .collect();
+                    print_stats(devices.iter().map(|device| device.samples).sum(), &stats);
+                }
+                Some(StatsMode::PerDevice) => {
+                    for device in &devices {
+                        print!("{}: ", device.label);
+                        print_stats(device.samples, &device.stats);
+                    }
+                }
+                None => {}
+            }
+        }
+        sleep(Duration::from_millis(50));
+    }
+    std::io::stdout().flush()?;
+    // every port gets closed, the first failure is returned
+    let mut closed = Ok(());
+    for device in devices {
+        eprintln!(
+            "{}: {}",
+            device.label,
+            stats_line(device.samples, &device.stats)
+        );
+        closed = closed.and(device.fakeldat.close());
+    }
+    closed
+}
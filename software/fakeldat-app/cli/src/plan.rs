@@ -0,0 +1,157 @@
+use std::fmt::Write;
+
+#[derive(clap::Args)]
+pub struct InitPlan {
+    /// Kind of test the plan is made for
+    template: PlanTemplate,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum PlanTemplate {
+    /// Click to photon in a game, comparing in-game settings
+    CompetitiveFps,
+    /// Click to photon on the desktop, comparing displays or compositors
+    Desktop,
+    /// Delay between picture and sound
+    AudioVideo,
+}
+
+struct Template {
+    name: &'static str,
+    description: &'static str,
+    report_mode: &'static str,
+    action: &'static str,
+    threshold: i16,
+    trials: u16,
+    warm_up: u16,
+    min_interval: u16,
+    max_interval: u16,
+    conditions: &'static [&'static str],
+    prompts: &'static [&'static str],
+}
+
+impl PlanTemplate {
+    const fn template(self) -> Template {
+        match self {
+            Self::CompetitiveFps => Template {
+                name: "competitive-fps",
+                description: "Click to photon latency in a game",
+                report_mode: "summary",
+                action: "mouse left",
+                threshold: 150,
+                trials: 200,
+                warm_up: 10,
+                min_interval: 300,
+                max_interval: 700,
+                conditions: &["baseline", "frame cap", "low latency mode"],
+                prompts: &[
+                    "Game and version",
+                    "Map or scene, with the spot the sensor looks at",
+                    "Resolution, refresh rate and VRR",
+                    "Frame rate cap and V-Sync",
+                    "GPU, driver version and low latency mode",
+                    "Mouse and its polling rate",
+                ],
+            },
+            Self::Desktop => Template {
+                name: "desktop",
+                description: "Click to photon latency on the desktop",
+                report_mode: "summary",
+                action: "mouse left",
+                threshold: 150,
+                trials: 100,
+                warm_up: 5,
+                min_interval: 500,
+                max_interval: 1000,
+                conditions: &["baseline"],
+                prompts: &[
+                    "Display model and connection",
+                    "Resolution, refresh rate and VRR",
+                    "Operating system, compositor and its settings",
+                    "Application the sensor looks at",
+                ],
+            },
+            Self::AudioVideo => Template {
+                name: "audio-video",
+                description: "Delay of the picture and the sound after the same input",
+                report_mode: "audio-video",
+                action: "mouse left",
+                threshold: 150,
+                trials: 100,
+                warm_up: 5,
+                min_interval: 800,
+                max_interval: 1500,
+                conditions: &["baseline"],
+                prompts: &[
+                    "Display model and picture mode",
+                    "Audio output, connection and any receiver",
+                    "Application or content played",
+                ],
+            },
+        }
+    }
+}
+
+impl Template {
+    // Written by hand so every key keeps its explanation
+    fn to_yaml(&self) -> String {
+        let mut conditions = String::new();
+        for condition in self.conditions {
+            _ = write!(conditions, "\n  - name: {condition}\n    notes: \"\"");
+        }
+        let mut prompts = String::new();
+        for prompt in self.prompts {
+            _ = write!(prompts, "\n  - prompt: {prompt}\n    answer: \"\"");
+        }
+        format!(
+            "\
+# Measurement plan generated by fakeldat-cli init-plan
+# {description}
+name: {name}
+
+# Applied to the device before the first trial
+device:
+  poll_rate: 1000
+  report_mode: {report_mode}
+  # brightness change ending a measurement, negative for light to dark
+  threshold: {threshold}
+  action: {action}
+
+# Presses discarded at the start of every condition, while caches,
+# clocks and the frame pacing settle
+warm_up: {warm_up}
+
+# Measured presses per condition, with a spread of a few milliseconds
+# 100 gives the mean within about half a millisecond, 200 helps the tails
+trials: {trials}
+
+# Random pause between presses in milliseconds, keeps the presses
+# from lining up with the refresh rate and lets the screen settle
+interval:
+  min: {min_interval}
+  max: {max_interval}
+
+# Measured one after another, repeat the list in a different order
+# to spot drift like thermal throttling
+conditions:{conditions}
+
+# Asked before the run and stored with the results, answers can be
+# filled in here to skip the questions
+metadata:{prompts}
+",
+            description = self.description,
+            name = self.name,
+            report_mode = self.report_mode,
+            threshold = self.threshold,
+            action = self.action,
+            warm_up = self.warm_up,
+            trials = self.trials,
+            min_interval = self.min_interval,
+            max_interval = self.max_interval,
+        )
+    }
+}
+
+pub fn run(args: &InitPlan) {
+    print!("{}", args.template.template().to_yaml());
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use fakeldat_lib::serialport::{self, SerialPortType};
+use fakeldat_lib::{Error, FakeLDAT};
+
+#[derive(clap::Args)]
+pub struct Ports {
+    /// Also list ports without a device answering
+    #[arg(long)]
+    all: bool,
+    /// Only list the ports, without sending anything to them
+    #[arg(long)]
+    no_probe: bool,
+    /// Milliseconds each port gets to answer the handshake
+    #[arg(long, default_value_t = 500)]
+    timeout: u64,
+}
+
+// USB ids and names when the OS knows them, empty otherwise
+fn describe(port_type: &SerialPortType) -> String {
+    match port_type {
+        SerialPortType::UsbPort(usb) => {
+            let mut description = format!("USB {:04x}:{:04x}", usb.vid, usb.pid);
+            for detail in [&usb.manufacturer, &usb.product, &usb.serial_number]
+                .into_iter()
+                .flatten()
+            {
+                description.push_str(", ");
+                description.push_str(detail);
+            }
+            description
+        }
+        SerialPortType::PciPort => "PCI".to_string(),
+        SerialPortType::BluetoothPort => "Bluetooth".to_string(),
+        SerialPortType::Unknown => String::new(),
+    }
+}
+
+// Firmware without a hello shows up as protocol 0
+fn probe(port: &str, timeout: Duration) -> Option<u16> {
+    let fakeldat = FakeLDAT::builder()
+        .path(port)
+        .timeout(timeout)
+        .handshake(Some(timeout))
+        .open()
+        .ok()?;
+    Some(fakeldat.protocol_version().unwrap_or_default())
+}
+
+pub fn run(args: &Ports) -> Result<(), Error> {
+    let timeout = Duration::from_millis(args.timeout);
+    let mut found = false;
+    for port in serialport::available_ports()? {
+        let description = describe(&port.port_type);
+        let status = if args.no_probe {
+            String::new()
+        } else if let Some(version) = probe(&port.port_name, timeout) {
+            found = true;
+            format!("FakeLDAT, protocol {version}")
+        } else if args.all {
+            "no answer".to_string()
+        } else {
+            continue;
+        };
+        let details: Vec<&str> = [status.as_str(), description.as_str()]
+            .into_iter()
+            .filter(|detail| !detail.is_empty())
+            .collect();
+        if details.is_empty() {
+            println!("{}", port.port_name);
+        } else {
+            println!("{}: {}", port.port_name, details.join(", "));
+        }
+    }
+    if !found && !args.no_probe {
+        eprintln!("No FakeLDAT answered, check the cable or if another program has the port open");
+    }
+    Ok(())
+}
@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use fakeldat_lib::analysis::{parse_raw_row, parse_summary_row, DetectorKind, LatencyDetector};
+use fakeldat_lib::{Error, SessionMetadata, Stats};
+
+use crate::Detector;
+
+const BAR_WIDTH: usize = 40;
+
+#[derive(clap::Args)]
+pub struct SessionReport {
+    /// Folder with the recordings, subfolders are searched too
+    #[arg(default_value = ".")]
+    path: PathBuf,
+    /// Only sessions started within this long, like 12h, 30d or 8w
+    #[arg(long, value_parser = parse_age)]
+    since: Option<Duration>,
+    /// Session property the sessions are grouped by: port, day, report-mode, poll-rate,
+    /// threshold or meta.<name>
+    #[arg(long, value_parser = parse_group)]
+    group_by: Option<String>,
+    #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+    format: ReportFormat,
+    /// Histogram bars per group
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u16).range(1..))]
+    bins: u16,
+    /// Algorithm used to derive delays from raw recordings
+    #[arg(long, value_enum, default_value_t = Detector::Fixed)]
+    detector: Detector,
+    /// Threshold for raw recordings without stored settings
+    #[arg(long, default_value_t = 150, allow_hyphen_values = true)]
+    threshold: i16,
+}
+
+#[derive(Clone, clap::ValueEnum)]
+enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+// Recording with the metadata stored next to it, if there is any
+struct Session {
+    // seconds since the Unix epoch
+    started: u64,
+    metadata: Option<SessionMetadata>,
+    delays: Vec<u64>,
+}
+
+#[derive(Default)]
+struct Group {
+    sessions: usize,
+    stats: Stats,
+}
+
+fn parse_age(value: &str) -> Result<Duration, String> {
+    let unit = value.len() - value.trim_end_matches(char::is_alphabetic).len();
+    let (count, unit) = value.split_at(value.len() - unit);
+    let count: u64 = count
+        .parse()
+        .map_err(|_| format!("{value} isn't a number followed by s, m, h, d or w"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("{unit} isn't one of s, m, h, d or w")),
+    };
+    Ok(Duration::from_secs(count * seconds))
+}
+
+fn parse_group(value: &str) -> Result<String, String> {
+    match value {
+        "port" | "day" | "report-mode" | "poll-rate" | "threshold" => Ok(value.to_string()),
+        _ if value
+            .strip_prefix("meta.")
+            .is_some_and(|name| !name.is_empty()) =>
+        {
+            Ok(value.to_string())
+        }
+        _ => Err(format!(
+            "{value} isn't one of port, day, report-mode, poll-rate, threshold or meta.<name>"
+        )),
+    }
+}
+
+fn find_recordings(dir: &Path, recordings: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_recordings(&path, recordings)?;
+        } else if path.extension().is_some_and(|extension| extension == "csv") {
+            recordings.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_session(path: &Path, args: &SessionReport) -> Result<Session, Error> {
+    let metadata = SessionMetadata::load(SessionMetadata::sidecar_path(path)).ok();
+    let started = match metadata {
+        Some(ref metadata) => metadata.started,
+        // recordings from before the metadata was stored
+        None => fs::metadata(path)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs()),
+    };
+    let threshold = metadata
+        .as_ref()
+        .and_then(|metadata| metadata.profile)
+        .map_or(args.threshold, |profile| profile.threshold);
+    let mut detector =
+        LatencyDetector::with_detector(DetectorKind::from(args.detector.clone()).create(threshold));
+    Ok(Session {
+        started,
+        metadata,
+        delays: read_delays(path, &mut detector)?,
+    })
+}
+
+// Summary rows as they are, raw rows go through the detector
+pub fn read_delays(path: &Path, detector: &mut LatencyDetector) -> Result<Vec<u64>, Error> {
+    let mut delays = Vec::new();
+    for line in fs::read_to_string(path)?.lines() {
+        if let Some(summary) = parse_summary_row(line) {
+            delays.push(summary.delay);
+        } else if let Some(summary) = parse_raw_row(line).and_then(|raw| detector.process(&raw)) {
+            delays.push(summary.delay);
+        }
+    }
+    Ok(delays)
+}
+
+// UTC date, from the days since the epoch to the civil calendar
+#[allow(clippy::cast_possible_wrap)]
+fn day(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+fn group_name(session: &Session, group_by: &str) -> String {
+    let metadata = session.metadata.as_ref();
+    let profile = metadata.and_then(|metadata| metadata.profile);
+    let name = match group_by {
+        "port" => metadata.and_then(|metadata| metadata.port.clone()),
+        "day" => Some(day(session.started)),
+        "report-mode" => profile.map(|profile| profile.report_mode.to_string()),
+        "poll-rate" => profile.map(|profile| profile.poll_rate.to_string()),
+        "threshold" => profile.map(|profile| profile.threshold.to_string()),
+        _ => group_by
+            .strip_prefix("meta.")
+            .and_then(|name| metadata?.meta.get(name).cloned()),
+    };
+    name.unwrap_or_else(|| "unknown".to_string())
+}
+
+// Bars of equal width from the shortest to the longest delay, with the start of each
+#[allow(clippy::cast_possible_truncation)]
+fn histogram(stats: &Stats, bins: u16) -> Vec<(u64, usize)> {
+    let (Some(min), Some(max)) = (stats.min(), stats.max()) else {
+        return Vec::new();
+    };
+    let width = (max - min + 1).div_ceil(u64::from(bins));
+    let mut counts = vec![0; usize::from(bins)];
+    for &delay in stats.delays() {
+        counts[((delay - min) / width) as usize] += 1;
+    }
+    (0..)
+        .zip(counts)
+        .map(|(bin, count)| (min + bin * width, count))
+        .collect()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn ms(delay: u64) -> String {
+    format!("{:.2} ms", delay as f64 / 1000.0)
+}
+
+// One line per bar, scaled to the fullest one, empty without delays
+pub fn text_histogram(stats: &Stats, bins: u16) -> String {
+    let histogram = histogram(stats, bins);
+    let highest = histogram.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let mut text = String::new();
+    for (start, count) in histogram {
+        let bar = "#".repeat(count * BAR_WIDTH / highest);
+        _ = writeln!(text, "{:>10} |{bar:<BAR_WIDTH$} {count}", ms(start));
+    }
+    text
+}
+
+// Bin edges in microseconds and the delays in each, for plotting tools
+pub fn csv_histogram(stats: &Stats, bins: u16) -> String {
+    let histogram = histogram(stats, bins);
+    let width = match histogram.as_slice() {
+        [(first, _), (second, _), ..] => second - first,
+        // a single bin covers all delays
+        _ => stats.max().unwrap_or(0) - stats.min().unwrap_or(0) + 1,
+    };
+    let mut csv = "start_us,end_us,count\n".to_string();
+    for (start, count) in histogram {
+        _ = writeln!(csv, "{start},{},{count}", start + width);
+    }
+    csv
+}
+
+// Columns of the summary table
+fn columns(name: &str, group: &Group) -> [String; 9] {
+    let stats = &group.stats;
+    let delay = |delay: Option<u64>| delay.map_or_else(|| "-".to_string(), ms);
+    let mean = |value: Option<f64>| {
+        value.map_or_else(
+            || "-".to_string(),
+            |value| format!("{:.2} ms", value / 1000.0),
+        )
+    };
+    [
+        name.to_string(),
+        group.sessions.to_string(),
+        stats.count().to_string(),
+        mean(stats.mean()),
+        delay(stats.median()),
+        delay(stats.percentile(99.0)),
+        mean(stats.std_dev()),
+        delay(stats.min()),
+        delay(stats.max()),
+    ]
+}
+
+const HEADER: [&str; 9] = [
+    "Group", "Sessions", "Delays", "Mean", "Median", "P99", "Std dev", "Min", "Max",
+];
+
+fn markdown(title: &str, groups: &BTreeMap<String, Group>, bins: u16) -> String {
+    let mut report = format!("# Latency report\n\n{title}\n\n");
+    _ = writeln!(report, "| {} |", HEADER.join(" | "));
+    _ = writeln!(report, "|{}", "---|".repeat(HEADER.len()));
+    for (name, group) in groups {
+        // a pipe would end the cell early
+        let name = name.replace('|', "\\|");
+        _ = writeln!(report, "| {} |", columns(&name, group).join(" | "));
+    }
+    for (name, group) in groups {
+        let histogram = text_histogram(&group.stats, bins);
+        if histogram.is_empty() {
+            continue;
+        }
+        _ = write!(report, "\n## {name}\n\n```text\n{histogram}```\n");
+    }
+    report
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html(title: &str, groups: &BTreeMap<String, Group>, bins: u16) -> String {
+    let mut report = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Latency report</title>\n\
+         <style>table { border-collapse: collapse; } td, th { padding: 2px 8px; text-align: right; } \
+         .bar { background: #4a90d9; height: 1em; }</style>\n</head>\n<body>\n<h1>Latency report</h1>\n",
+    );
+    _ = writeln!(report, "<p>{}</p>\n<table>", escape(title));
+    _ = writeln!(report, "<tr><th>{}</th></tr>", HEADER.join("</th><th>"));
+    for (name, group) in groups {
+        let columns = columns(name, group).map(|column| escape(&column));
+        _ = writeln!(report, "<tr><td>{}</td></tr>", columns.join("</td><td>"));
+    }
+    _ = writeln!(report, "</table>");
+    for (name, group) in groups {
+        let histogram = histogram(&group.stats, bins);
+        let Some(highest) = histogram.iter().map(|(_, count)| *count).max() else {
+            continue;
+        };
+        _ = writeln!(report, "<h2>{}</h2>\n<table>", escape(name));
+        for (start, count) in histogram {
+            _ = writeln!(
+                report,
+                "<tr><td>{}</td><td style=\"width: 400px; text-align: left\"><div class=\"bar\" style=\"width: {}%\"></div></td><td>{count}</td></tr>",
+                ms(start),
+                count * 100 / highest
+            );
+        }
+        _ = writeln!(report, "</table>");
+    }
+    report.push_str("</body>\n</html>\n");
+    report
+}
+
+pub fn run(args: &SessionReport) -> Result<(), Error> {
+    let mut recordings = Vec::new();
+    find_recordings(&args.path, &mut recordings)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let oldest = args
+        .since
+        .map_or(0, |since| now.saturating_sub(since.as_secs()));
+
+    let mut groups: BTreeMap<String, Group> = BTreeMap::new();
+    for path in recordings {
+        let session = read_session(&path, args)?;
+        if session.started < oldest {
+            continue;
+        }
+        let name = args.group_by.as_ref().map_or_else(
+            || "all".to_string(),
+            |group_by| group_name(&session, group_by),
+        );
+        let group = groups.entry(name).or_default();
+        group.sessions += 1;
+        for delay in session.delays {
+            group.stats.push(delay);
+        }
+    }
+
+    let mut title = format!("Sessions in {}", args.path.display());
+    if args.since.is_some() {
+        _ = write!(title, " started since {}", day(oldest));
+    }
+    if let Some(ref group_by) = args.group_by {
+        _ = write!(title, ", grouped by {group_by}");
+    }
+    print!(
+        "{}",
+        match args.format {
+            ReportFormat::Markdown => markdown(&title, &groups, args.bins),
+            ReportFormat::Html => html(&title, &groups, args.bins),
+        }
+    );
+    Ok(())
+}
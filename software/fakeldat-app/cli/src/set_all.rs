@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+use fakeldat_lib::{Error, FakeLDAT};
+
+use crate::{ActionMode, Key, ReportMode};
+
+#[derive(clap::Args)]
+#[group(required = true, multiple = true)]
+pub struct AllSettings {
+    #[arg(long)]
+    poll_rate: Option<u16>,
+    #[arg(long, value_enum)]
+    report_mode: Option<ReportMode>,
+    #[arg(long, allow_hyphen_values = true)]
+    threshold: Option<i16>,
+    /// Action mode and key, like mouse:left or keyboard:a
+    #[arg(long, value_parser = crate::set_all::parse_action)]
+    action: Option<fakeldat_lib::ActionMode>,
+}
+
+pub fn parse_action(value: &str) -> Result<fakeldat_lib::ActionMode, String> {
+    let (mode, key) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected mode:key, got {value}"))?;
+    let key = Key::from_str(key, true)?;
+    let action = match ActionMode::from_str(mode, true)? {
+        ActionMode::Mouse => key.try_into().map(fakeldat_lib::ActionMode::Mouse),
+        ActionMode::Keyboard => (key as u8)
+            .try_into()
+            .map(fakeldat_lib::ActionMode::Keyboard),
+        ActionMode::Gamepad => key.try_into().map(fakeldat_lib::ActionMode::Gamepad),
+    };
+    action.map_err(|_| format!("{value} isn't a key of that action mode"))
+}
+
+fn describe_action(action: fakeldat_lib::ActionMode) -> String {
+    match action {
+        fakeldat_lib::ActionMode::Mouse(button) => format!("Mouse, {button}"),
+        fakeldat_lib::ActionMode::Keyboard(key) => format!("Keyboard, {key}"),
+        fakeldat_lib::ActionMode::Gamepad(button) => format!("Gamepad, {button}"),
+    }
+}
+
+// Names the setting before main prints the error itself
+fn failed(setting: &'static str) -> impl FnOnce(Error) -> Error {
+    move |why| {
+        eprintln!("Setting the {setting} failed");
+        why
+    }
+}
+
+// Each set waits for its echo, the settings are then read back to catch ones the
+// device echoed but didn't keep
+pub fn run(fakeldat: &mut FakeLDAT, args: &AllSettings, timeout: Duration) -> Result<(), Error> {
+    if let Some(poll_rate) = args.poll_rate {
+        fakeldat
+            .set_poll_rate(poll_rate)
+            .map_err(failed("poll rate"))?;
+    }
+    if let Some(report_mode) = &args.report_mode {
+        fakeldat
+            .set_report_mode(report_mode.clone().into())
+            .map_err(failed("report mode"))?;
+    }
+    if let Some(threshold) = args.threshold {
+        fakeldat
+            .set_threshold(threshold)
+            .map_err(failed("threshold"))?;
+    }
+    if let Some(action) = args.action {
+        fakeldat.set_action(action).map_err(failed("action"))?;
+    }
+
+    let profile = fakeldat.read_profile(timeout)?;
+    let mut mismatches = Vec::new();
+    if let Some(poll_rate) = args.poll_rate.filter(|&value| value != profile.poll_rate) {
+        mismatches.push(format!(
+            "poll rate is {} instead of {poll_rate}",
+            profile.poll_rate
+        ));
+    }
+    if let Some(report_mode) = args.report_mode.clone().map(fakeldat_lib::ReportMode::from) {
+        if report_mode != profile.report_mode {
+            mismatches.push(format!(
+                "report mode is {} instead of {report_mode}",
+                profile.report_mode
+            ));
+        }
+    }
+    if let Some(threshold) = args.threshold.filter(|&value| value != profile.threshold) {
+        mismatches.push(format!(
+            "threshold is {} instead of {threshold}",
+            profile.threshold
+        ));
+    }
+    if let Some(action) = args.action.filter(|&value| value != profile.action) {
+        mismatches.push(format!(
+            "action is {} instead of {}",
+            describe_action(profile.action),
+            describe_action(action)
+        ));
+    }
+    if !mismatches.is_empty() {
+        for mismatch in mismatches {
+            eprintln!("Device didn't keep the setting: {mismatch}");
+        }
+        std::process::exit(crate::EXIT_FAILURE);
+    }
+    println!("All settings applied");
+    Ok(())
+}
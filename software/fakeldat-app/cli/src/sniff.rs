@@ -0,0 +1,90 @@
+use std::fmt::Write;
+use std::sync::mpsc;
+use std::thread::sleep;
+use std::time::Duration;
+
+use fakeldat_lib::protocol::{self, Protocol};
+use fakeldat_lib::{Error, FakeLDAT};
+
+#[derive(clap::Args)]
+pub struct Sniff {
+    /// Leave out raw sample frames, they make up most of the traffic in raw mode
+    #[arg(long)]
+    no_raw: bool,
+}
+
+// Little endian value of a field, named when it's one of an enum
+fn field_value(protocol: &Protocol, field: &protocol::Field, bytes: &[u8]) -> Option<String> {
+    let raw = bytes
+        .get(field.offset..field.offset + field.size)?
+        .iter()
+        .rev()
+        .fold(0u64, |value, &byte| value << 8 | u64::from(byte));
+    #[allow(clippy::cast_possible_truncation)]
+    let value = match field.kind {
+        "bool" => (raw != 0).to_string(),
+        "i16" => (raw as u16).cast_signed().to_string(),
+        kind => protocol
+            .enums
+            .iter()
+            .find(|info| info.name == kind)
+            .and_then(|info| {
+                info.values
+                    .iter()
+                    .find(|value| u64::from(value.value) == raw)
+            })
+            .map_or_else(|| raw.to_string(), |value| value.name.clone()),
+    };
+    Some(value)
+}
+
+// Command name and fields, from what the protocol description says about the frame
+fn decode(protocol: &Protocol, bytes: &[u8]) -> String {
+    let Some(command) = protocol
+        .commands
+        .iter()
+        .find(|command| command.id == bytes[0])
+    else {
+        return format!("unknown command 0x{:02x}", bytes[0]);
+    };
+    // the device starts the exchange of reports, everything else it sends is an answer
+    let fields = if command.sender == "device" {
+        &command.request
+    } else {
+        &command.response
+    };
+    let mut text = command.name.clone();
+    for field in fields {
+        if let Some(value) = field_value(protocol, field, bytes) {
+            _ = write!(text, " {}={value}", field.name);
+        }
+    }
+    text
+}
+
+// Reports are parsed as usual but thrown away, only the frames are printed
+pub fn run(fakeldat: &mut FakeLDAT, args: &Sniff) -> Result<(), Error> {
+    let protocol = protocol::describe();
+    let raw_id = fakeldat_lib::Command::ReportRaw as u8;
+    let (sender, receiver) = mpsc::channel();
+    fakeldat.on_frame(move |frame| {
+        _ = sender.send((frame.bytes.to_vec(), frame.checksum_valid));
+    });
+    loop {
+        fakeldat.poll_bulk_data()?;
+        _ = fakeldat.take_report_buffer();
+        for (bytes, checksum_valid) in receiver.try_iter() {
+            if args.no_raw && bytes[0] == raw_id {
+                continue;
+            }
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+            println!(
+                "{}  {}  {}",
+                hex.join(" "),
+                if checksum_valid { "ok " } else { "BAD" },
+                decode(&protocol, &bytes)
+            );
+        }
+        sleep(Duration::from_millis(50));
+    }
+}
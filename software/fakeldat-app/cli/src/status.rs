@@ -0,0 +1,144 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use fakeldat_lib::{Capabilities, Error, FakeLDAT, LinkInfo, Report};
+use serde_json::{Map, Value};
+
+use crate::SettingGet;
+
+#[derive(clap::Args)]
+pub struct Status {
+    /// Print a single JSON document instead of one line per field
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(serde::Serialize)]
+struct Snapshot {
+    port: String,
+    // None for firmware from before the hello
+    protocol_version: Option<u16>,
+    capabilities: Option<Capabilities>,
+    link: LinkInfo,
+    // null for settings the firmware doesn't know
+    settings: Map<String, Value>,
+}
+
+// Every setting with a get, under the name its answer is stored as
+const SETTINGS: [(&str, SettingGet); 15] = [
+    ("poll_rate", SettingGet::PollRate),
+    ("report_mode", SettingGet::ReportMode),
+    ("threshold", SettingGet::Threshold),
+    ("action", SettingGet::Action),
+    ("action_timing", SettingGet::ActionTiming),
+    ("integrity", SettingGet::Integrity),
+    ("raw_batching", SettingGet::RawBatching),
+    ("baud_rate", SettingGet::BaudRate),
+    ("frame_size", SettingGet::FrameSize),
+    ("auto_trigger", SettingGet::AutoTrigger),
+    ("detection_mode", SettingGet::DetectionMode),
+    ("sensor_gain", SettingGet::SensorGain),
+    ("channels", SettingGet::Channels),
+    ("telemetry_interval", SettingGet::TelemetryInterval),
+    ("summary_aggregation", SettingGet::SummaryAggregation),
+];
+
+fn setting(report: &Report) -> Option<(&'static str, Result<Value, serde_json::Error>)> {
+    let setting = match report {
+        Report::PollRate(value) => ("poll_rate", serde_json::to_value(value)),
+        Report::ReportMode(value) => ("report_mode", serde_json::to_value(value)),
+        Report::Threshold(value) => ("threshold", serde_json::to_value(value)),
+        Report::Action(value) => ("action", serde_json::to_value(value)),
+        Report::ActionTiming(value) => ("action_timing", serde_json::to_value(value)),
+        Report::Integrity(value) => ("integrity", serde_json::to_value(value)),
+        Report::RawBatching(value) => ("raw_batching", serde_json::to_value(value)),
+        Report::BaudRate(value) => ("baud_rate", serde_json::to_value(value)),
+        Report::FrameSize(value) => ("frame_size", serde_json::to_value(value)),
+        Report::AutoTriggerSettings(value) => ("auto_trigger", serde_json::to_value(value)),
+        Report::DetectionMode(value) => ("detection_mode", serde_json::to_value(value)),
+        Report::SensorGain(value) => ("sensor_gain", serde_json::to_value(value)),
+        Report::Channels(value) => ("channels", serde_json::to_value(value)),
+        Report::TelemetryInterval(value) => ("telemetry_interval", serde_json::to_value(value)),
+        Report::SummaryAggregation(value) => ("summary_aggregation", serde_json::to_value(value)),
+        _ => return None,
+    };
+    Some(setting)
+}
+
+fn to_io_error(why: serde_json::Error) -> Error {
+    Error::IOError(std::io::Error::new(std::io::ErrorKind::InvalidData, why))
+}
+
+// All gets go out at once, the ones still unanswered are sent again like a single get
+pub fn run(
+    fakeldat: &mut FakeLDAT,
+    port: &str,
+    args: &Status,
+    timeout: Duration,
+    retries: u32,
+) -> Result<(), Error> {
+    let mut settings = Map::new();
+    let mut pending = Vec::new();
+    for (name, get) in &SETTINGS {
+        match crate::get(fakeldat, get) {
+            Ok(()) => pending.push((*name, get)),
+            Err(Error::Unsupported(_)) => {
+                settings.insert((*name).to_string(), Value::Null);
+            }
+            Err(why) => return Err(why),
+        }
+    }
+    let mut attempts = 1;
+    let mut deadline = Instant::now() + timeout;
+    while !pending.is_empty() {
+        fakeldat.poll_bulk_data()?;
+        for report in fakeldat.take_report_buffer().unwrap_or_default() {
+            let Some((name, value)) = setting(&report) else {
+                continue;
+            };
+            pending.retain(|(pending_name, _)| *pending_name != name);
+            settings.insert(name.to_string(), value.map_err(to_io_error)?);
+        }
+        if !pending.is_empty() && Instant::now() >= deadline {
+            if attempts > retries {
+                let names: Vec<&str> = pending.iter().map(|(name, _)| *name).collect();
+                eprintln!(
+                    "No answer for {} after {attempts} attempts, check if the device is responsive",
+                    names.join(", ")
+                );
+                std::process::exit(crate::EXIT_TIMEOUT);
+            }
+            for (_, get) in &pending {
+                crate::get(fakeldat, get)?;
+            }
+            attempts += 1;
+            deadline = Instant::now() + timeout;
+        }
+        sleep(Duration::from_millis(50));
+    }
+
+    let snapshot = Snapshot {
+        port: port.to_string(),
+        protocol_version: fakeldat.protocol_version(),
+        capabilities: fakeldat.capabilities(),
+        link: fakeldat.link_info(),
+        settings,
+    };
+    if args.json {
+        println!("{}", serde_json::to_string(&snapshot).map_err(to_io_error)?);
+        return Ok(());
+    }
+    println!("Port: {}", snapshot.port);
+    match snapshot.protocol_version {
+        Some(version) => println!("Protocol version: {version}"),
+        None => println!("Protocol version: from before the hello"),
+    }
+    println!(
+        "Link: {}, {} baud, {} per frame",
+        snapshot.link.integrity, snapshot.link.baud_rate, snapshot.link.frame_size
+    );
+    for (name, value) in &snapshot.settings {
+        println!("{name}: {value}");
+    }
+    Ok(())
+}
@@ -0,0 +1,243 @@
+use std::fs::File;
+use std::io::{stdout, BufWriter, Write};
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use fakeldat_lib::{Error, FakeLDAT, Report};
+
+#[derive(clap::Args)]
+pub struct Stream {
+    #[arg(long, value_enum, default_value_t = StreamFormat::Csv)]
+    format: StreamFormat,
+    /// File written instead of the standard output, replaced when it exists
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Stop after this long, like 90s, 10m or 1h
+    #[arg(long, value_parser = parse_duration)]
+    duration: Option<Duration>,
+    /// Stop after this many measurements and trigger events
+    #[arg(long)]
+    count: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum StreamFormat {
+    /// Measurements of the first sensor, a header line before the first row of every kind
+    Csv,
+    /// Every report in a single array, only complete once the stream stops
+    Json,
+    /// Every report, one JSON object per line
+    Ndjson,
+}
+
+// Header line of every CSV row kind, the same as recordings so analyze reads them
+#[derive(Clone, Copy)]
+enum RowKind {
+    Raw,
+    Summary,
+    SummaryAggregate,
+    RawAudio,
+    AudioVideo,
+}
+
+impl RowKind {
+    const fn header(self) -> &'static str {
+        match self {
+            Self::Raw => "timestamp,brightness,audio,trigger",
+            Self::Summary => "delay,threshold",
+            Self::SummaryAggregate => "count,min,mean,max",
+            Self::RawAudio => "timestamp,amplitudes",
+            Self::AudioVideo => "video_delay,audio_delay,threshold",
+        }
+    }
+}
+
+// Seconds without a unit
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let unit = value.len() - value.trim_end_matches(char::is_alphabetic).len();
+    let (count, unit) = value.split_at(value.len() - unit);
+    let count: u64 = count
+        .parse()
+        .map_err(|_| format!("{value} isn't a number followed by ms, s, m or h"))?;
+    let milliseconds = match unit {
+        "ms" => 1,
+        "" | "s" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        _ => return Err(format!("{unit} isn't one of ms, s, m or h")),
+    };
+    Ok(Duration::from_millis(count * milliseconds))
+}
+
+fn csv_row(report: &Report) -> Option<(RowKind, String)> {
+    // empty when not detected
+    let delay = |delay: Option<u64>| delay.map_or_else(String::new, |delay| delay.to_string());
+    Some(match report {
+        Report::Raw(raw_report) if raw_report.channel == 0 => (
+            RowKind::Raw,
+            format!(
+                "{},{},{},{}",
+                raw_report.timestamp,
+                raw_report.brightness,
+                raw_report.audio,
+                u8::from(raw_report.trigger)
+            ),
+        ),
+        Report::Summary(summary_report) => (
+            RowKind::Summary,
+            format!("{},{}", summary_report.delay, summary_report.threshold),
+        ),
+        Report::SummaryAggregate(aggregate) => (
+            RowKind::SummaryAggregate,
+            format!(
+                "{},{},{},{}",
+                aggregate.count, aggregate.min, aggregate.mean, aggregate.max
+            ),
+        ),
+        Report::RawAudio(raw_audio) => {
+            let mut row = raw_audio.timestamp.to_string();
+            for amplitude in &raw_audio.amplitudes {
+                row.push(',');
+                row.push_str(&amplitude.to_string());
+            }
+            (RowKind::RawAudio, row)
+        }
+        Report::AudioVideo(audio_video) => (
+            RowKind::AudioVideo,
+            format!(
+                "{},{},{}",
+                delay(audio_video.video_delay),
+                delay(audio_video.audio_delay),
+                audio_video.threshold
+            ),
+        ),
+        _ => return None,
+    })
+}
+
+// Report with the device it came from, when streaming from several
+#[derive(serde::Serialize)]
+struct DeviceReport<'a> {
+    device: &'a str,
+    #[serde(flatten)]
+    report: &'a Report,
+}
+
+struct Writer {
+    output: Box<dyn Write>,
+    format: StreamFormat,
+    // rows and reports start with the device label
+    labeled: bool,
+    headers: [bool; 5],
+    // JSON array elements after the first need a comma
+    written: bool,
+}
+
+impl Writer {
+    fn write(&mut self, device: &str, report: &Report) -> Result<(), Error> {
+        match self.format {
+            StreamFormat::Csv => {
+                let Some((kind, row)) = csv_row(report) else {
+                    return Ok(());
+                };
+                if !self.headers[kind as usize] {
+                    if self.labeled {
+                        write!(self.output, "device,")?;
+                    }
+                    writeln!(self.output, "{}", kind.header())?;
+                    self.headers[kind as usize] = true;
+                }
+                if self.labeled {
+                    write!(self.output, "{device},")?;
+                }
+                writeln!(self.output, "{row}")?;
+            }
+            StreamFormat::Json | StreamFormat::Ndjson => {
+                let json = if self.labeled {
+                    serde_json::to_string(&DeviceReport { device, report })
+                } else {
+                    serde_json::to_string(report)
+                }
+                .map_err(|why| {
+                    Error::IOError(std::io::Error::new(std::io::ErrorKind::InvalidData, why))
+                })?;
+                match self.format {
+                    StreamFormat::Json if self.written => write!(self.output, ",\n{json}")?,
+                    StreamFormat::Json => write!(self.output, "[\n{json}")?,
+                    _ => writeln!(self.output, "{json}")?,
+                }
+            }
+        }
+        self.written = true;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if self.format == StreamFormat::Json {
+            if self.written {
+                writeln!(self.output, "\n]")?;
+            } else {
+                writeln!(self.output, "[]")?;
+            }
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+// Stops with a duration, count or Ctrl-C, a JSON array gets closed either way
+// Several devices are labeled and the count applies to each of them
+pub fn run(devices: &mut [(String, &mut FakeLDAT)], args: &Stream) -> Result<(), Error> {
+    let output: Box<dyn Write> = match args.output {
+        Some(ref path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(stdout().lock()),
+    };
+    let mut writer = Writer {
+        output,
+        format: args.format,
+        labeled: devices.len() > 1,
+        headers: [false; 5],
+        written: false,
+    };
+    crate::handle_interrupts();
+    let start = Instant::now();
+    let mut measurements = vec![0; devices.len()];
+    while !crate::interrupted() {
+        for ((label, fakeldat), measurements) in devices.iter_mut().zip(&mut measurements) {
+            fakeldat.poll_bulk_data()?;
+            for warning in fakeldat.take_warnings() {
+                if writer.labeled {
+                    eprintln!("{label}: {warning}");
+                } else {
+                    eprintln!("{warning}");
+                }
+            }
+            for report in fakeldat.take_report_buffer().unwrap_or_default() {
+                if args.count.is_some_and(|count| *measurements >= count) {
+                    break;
+                }
+                writer.write(label, &report)?;
+                if report.is_measurement() {
+                    *measurements += 1;
+                }
+            }
+        }
+        writer.output.flush()?;
+        if args.count.is_some_and(|count| {
+            measurements
+                .iter()
+                .all(|&measurements| measurements >= count)
+        }) {
+            break;
+        }
+        if args
+            .duration
+            .is_some_and(|duration| start.elapsed() >= duration)
+        {
+            break;
+        }
+        sleep(Duration::from_millis(50));
+    }
+    writer.finish()
+}
@@ -0,0 +1,72 @@
+use std::collections::VecDeque;
+use std::io::{stdout, IsTerminal};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use fakeldat_lib::{Error, FakeLDAT, Report, Stats};
+
+#[derive(clap::Args)]
+pub struct Summary {
+    /// Reprint a table of the statistics every second instead of each delay
+    #[arg(long)]
+    live: bool,
+    /// Only count this many of the latest delays, all of them by default
+    #[arg(long)]
+    window: Option<usize>,
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn print_table(stats: &Stats) {
+    let ms = |delay: Option<f64>| {
+        delay.map_or_else(
+            || "-".to_string(),
+            |delay| format!("{:.2} ms", delay / 1000.0),
+        )
+    };
+    println!(
+        "{:>8} {:>12} {:>12} {:>12} {:>12}",
+        "Delays", "Mean", "Std dev", "Last", "p95"
+    );
+    println!(
+        "{:>8} {:>12} {:>12} {:>12} {:>12}",
+        stats.count(),
+        ms(stats.mean()),
+        ms(stats.std_dev()),
+        ms(stats.last().map(|delay| delay as f64)),
+        ms(stats.percentile(95.0).map(|delay| delay as f64)),
+    );
+}
+
+// Needs the summary or combined report mode, raw samples are ignored
+pub fn run(fakeldat: &mut FakeLDAT, args: &Summary) -> Result<(), Error> {
+    // a terminal is redrawn, anything else gets one table after another
+    let redraw = stdout().is_terminal();
+    let mut delays = VecDeque::new();
+    let mut last_print = Instant::now();
+    loop {
+        fakeldat.poll_bulk_data()?;
+        for warning in fakeldat.take_warnings() {
+            eprintln!("{warning}");
+        }
+        for report in fakeldat.take_report_buffer().unwrap_or_default() {
+            if let Report::Summary(summary_report) = report {
+                if !args.live {
+                    println!("{}", summary_report.to_csv_row());
+                }
+                delays.push_back(summary_report.delay);
+                if args.window.is_some_and(|window| delays.len() > window) {
+                    delays.pop_front();
+                }
+            }
+        }
+        if args.live && last_print.elapsed() >= Duration::from_secs(1) {
+            if redraw {
+                // clear the screen and go back to its top left corner
+                print!("\x1b[2J\x1b[H");
+            }
+            print_table(&delays.iter().copied().collect());
+            last_print = Instant::now();
+        }
+        sleep(Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use fakeldat_lib::analysis::{parse_raw_row, parse_summary_row, DetectorKind, LatencyDetector};
+use fakeldat_lib::{Error, Stats};
+
+use crate::Detector;
+
+#[derive(clap::Args)]
+pub struct Tail {
+    /// Recording to follow
+    path: PathBuf,
+    /// Show statistics every second instead of the data
+    #[arg(long)]
+    stats: bool,
+    /// Algorithm used to derive delays from raw recordings
+    #[arg(long, value_enum, default_value_t = Detector::Fixed)]
+    detector: Detector,
+    /// Threshold used to derive delays from raw recordings
+    #[arg(long, default_value_t = 150, allow_hyphen_values = true)]
+    threshold: i16,
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub fn stats_line(samples: u64, stats: &Stats) -> String {
+    let ms = |delay: u64| delay as f64 / 1000.0;
+    match (stats.mean(), stats.std_dev(), stats.last()) {
+        (Some(mean), Some(std_dev), Some(last)) => format!(
+            "Samples: {samples}, delays: {}, mean: {:.2} ms, std dev: {:.2} ms, min: {:.2} ms, max: {:.2} ms, last: {:.2} ms",
+            stats.count(),
+            mean / 1000.0,
+            std_dev / 1000.0,
+            ms(stats.min().unwrap_or_default()),
+            ms(stats.max().unwrap_or_default()),
+            ms(last),
+        ),
+        _ => format!("Samples: {samples}, delays: 0"),
+    }
+}
+
+pub fn print_stats(samples: u64, stats: &Stats) {
+    println!("{}", stats_line(samples, stats));
+}
+
+// Follows the file until interrupted, like tail -f
+pub fn run(args: &Tail) -> Result<(), Error> {
+    let mut reader = BufReader::new(File::open(&args.path)?);
+    let mut detector = LatencyDetector::with_detector(
+        DetectorKind::from(args.detector.clone()).create(args.threshold),
+    );
+    let mut stats = Stats::default();
+    let mut samples = 0;
+    let mut last_print = Instant::now();
+    let mut line = String::new();
+    loop {
+        // partial line stays in the buffer until the writer finishes it
+        if reader.read_line(&mut line)? == 0 || !line.ends_with('\n') {
+            sleep(Duration::from_millis(100));
+        } else {
+            if !args.stats {
+                print!("{line}");
+            } else if let Some(summary) = parse_summary_row(&line) {
+                stats.push(summary.delay);
+            } else if let Some(raw_report) = parse_raw_row(&line) {
+                samples += 1;
+                if let Some(summary) = detector.process(&raw_report) {
+                    stats.push(summary.delay);
+                }
+            }
+            line.clear();
+        }
+        if args.stats && last_print.elapsed() >= Duration::from_secs(1) {
+            print_stats(samples, &stats);
+            last_print = Instant::now();
+        }
+    }
+}
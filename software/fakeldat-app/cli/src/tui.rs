@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use fakeldat_lib::analysis::LatencyDetector;
+use fakeldat_lib::{Error, FakeLDAT, Report, ReportMode, Stats};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+
+// More than any terminal is wide
+const SAMPLES: usize = 1024;
+
+#[derive(Default)]
+struct Dashboard {
+    // brightness and trigger of the first sensor, newest last
+    samples: VecDeque<(u16, bool)>,
+    poll_rate: Option<u16>,
+    report_mode: Option<ReportMode>,
+    threshold: Option<i16>,
+    // raw mode has no summaries, delays are derived like analyze does
+    detector: Option<LatencyDetector>,
+    stats: Stats,
+    warning: Option<String>,
+}
+
+impl Dashboard {
+    fn update(&mut self, report: &Report) {
+        match report {
+            Report::Raw(raw_report) if raw_report.channel == 0 => {
+                if self.samples.len() == SAMPLES {
+                    self.samples.pop_front();
+                }
+                self.samples
+                    .push_back((raw_report.brightness, raw_report.trigger));
+                if self.report_mode == Some(ReportMode::Raw) {
+                    if let Some(summary) = self
+                        .detector
+                        .as_mut()
+                        .and_then(|detector| detector.process(raw_report))
+                    {
+                        self.stats.push(summary.delay);
+                    }
+                }
+            }
+            Report::Summary(summary_report) => self.stats.push(summary_report.delay),
+            Report::PollRate(poll_rate) => self.poll_rate = Some(*poll_rate),
+            Report::ReportMode(report_mode) => self.report_mode = Some(*report_mode),
+            Report::Threshold(threshold) => {
+                self.threshold = Some(*threshold);
+                self.detector = Some(LatencyDetector::new(*threshold));
+            }
+            _ => {}
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn draw(&self, frame: &mut Frame) {
+        let [settings_area, brightness_area, triggers_area, stats_area] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Min(4),
+            Constraint::Length(1),
+            Constraint::Length(3),
+        ])
+        .areas(frame.area());
+
+        let unknown = |value: Option<String>| value.unwrap_or_else(|| "?".to_string());
+        let settings = format!(
+            "Poll rate: {} Hz, report mode: {}, threshold: {}",
+            unknown(self.poll_rate.map(|poll_rate| poll_rate.to_string())),
+            unknown(self.report_mode.map(|report_mode| report_mode.to_string())),
+            unknown(self.threshold.map(|threshold| threshold.to_string())),
+        );
+        frame.render_widget(
+            Paragraph::new(settings).block(
+                Block::new()
+                    .borders(Borders::TOP)
+                    .title("FakeLDAT, q quits, t triggers"),
+            ),
+            settings_area,
+        );
+
+        // the newest samples that fit, triggers marked under the sparkline
+        let shown = self.samples.len().min(usize::from(brightness_area.width));
+        let samples = self.samples.range(self.samples.len() - shown..);
+        let brightness: Vec<u64> = samples
+            .clone()
+            .map(|&(brightness, _)| u64::from(brightness))
+            .collect();
+        let triggers: String = samples
+            .map(|&(_, trigger)| if trigger { '▲' } else { ' ' })
+            .collect();
+        frame.render_widget(
+            Sparkline::default()
+                .block(Block::new().borders(Borders::TOP).title("Brightness"))
+                .data(&brightness),
+            brightness_area,
+        );
+        frame.render_widget(Paragraph::new(triggers), triggers_area);
+
+        let ms = |delay: Option<f64>| {
+            delay.map_or_else(
+                || "-".to_string(),
+                |delay| format!("{:.2} ms", delay / 1000.0),
+            )
+        };
+        let mut stats = vec![Line::from(format!(
+            "Delays: {}, mean: {}, std dev: {}, last: {}, p95: {}",
+            self.stats.count(),
+            ms(self.stats.mean()),
+            ms(self.stats.std_dev()),
+            ms(self.stats.last().map(|delay| delay as f64)),
+            ms(self.stats.percentile(95.0).map(|delay| delay as f64)),
+        ))];
+        if let Some(ref warning) = self.warning {
+            stats.push(Line::from(warning.as_str()));
+        }
+        frame.render_widget(
+            Paragraph::new(stats).block(Block::new().borders(Borders::TOP).title("Latency")),
+            stats_area,
+        );
+    }
+}
+
+fn run_dashboard(terminal: &mut DefaultTerminal, fakeldat: &mut FakeLDAT) -> Result<(), Error> {
+    let mut dashboard = Dashboard::default();
+    fakeldat.get_poll_rate()?;
+    fakeldat.get_report_mode()?;
+    fakeldat.get_threshold()?;
+    loop {
+        fakeldat.poll_bulk_data()?;
+        if let Some(warning) = fakeldat.take_warnings().pop() {
+            dashboard.warning = Some(warning.to_string());
+        }
+        for report in fakeldat.take_report_buffer().unwrap_or_default() {
+            dashboard.update(&report);
+        }
+        terminal.draw(|frame| dashboard.draw(frame))?;
+        // doubles as the refresh interval
+        if event::poll(Duration::from_millis(50))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        KeyCode::Char('t') => fakeldat.manual_trigger()?,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Takes over the terminal until q or Esc is pressed
+pub fn run(fakeldat: &mut FakeLDAT) -> Result<(), Error> {
+    let mut terminal = ratatui::init();
+    let result = run_dashboard(&mut terminal, fakeldat);
+    ratatui::restore();
+    result
+}
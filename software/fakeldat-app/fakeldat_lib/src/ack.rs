@@ -0,0 +1,66 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::log::debug;
+use crate::{Command, Error, FakeLDAT, Result};
+
+/// How long a setting waits for the device to echo it and how often it's sent again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckPolicy {
+    pub timeout: Duration,
+    pub retries: u32,
+}
+
+impl Default for AckPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_millis(100),
+            retries: 2,
+        }
+    }
+}
+
+impl FakeLDAT {
+    // None sends settings without waiting, like before
+    pub fn set_acknowledge(&mut self, ack: Option<AckPolicy>) {
+        self.ack = ack;
+    }
+
+    // Returns once the echo arrived, reports polled meanwhile stay in the buffer
+    pub(crate) fn send_setting(&mut self, command: Command, payload: &[u8]) -> Result<()> {
+        self.send_payload(command, payload)?;
+        let Some(ack) = self.ack else {
+            return Ok(());
+        };
+        for attempt in 1..=ack.retries + 1 {
+            if attempt > 1 {
+                debug!(%command, attempt, "no echo, sending again");
+                self.send_payload(command, payload)?;
+            }
+            if self.wait_for_echo(command, ack.timeout)? {
+                return Ok(());
+            }
+        }
+        Err(Error::CommandTimeout(command))
+    }
+
+    fn wait_for_echo(&mut self, command: Command, timeout: Duration) -> Result<bool> {
+        let start = Instant::now();
+        loop {
+            self.poll_bulk_data()?;
+            // check_setting takes what track_setting stored once the echo is in
+            if self
+                .sent_settings
+                .get(command as usize)
+                .is_none_or(Option::is_none)
+            {
+                return Ok(true);
+            }
+            // nothing is going to answer until the device is back
+            if !self.connected || start.elapsed() > timeout {
+                return Ok(false);
+            }
+            sleep(Duration::from_millis(5));
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{Command, FakeLDAT, Result};
+
+/// Several summary measurements folded into one report by the device, see
+/// [`FakeLDAT::set_summary_aggregation`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SummaryAggregate {
+    // measurements it covers, fewer than asked for when the setting changed in between
+    pub count: u8,
+    // delays in microseconds
+    pub min: u64,
+    pub mean: u64,
+    pub max: u64,
+}
+
+impl SummaryAggregate {
+    pub(crate) fn from_frame(buf: &[u8]) -> Self {
+        let delay = |offset: usize| {
+            u64::from(u32::from_le_bytes(
+                buf[offset..offset + 4].try_into().unwrap(),
+            ))
+        };
+        Self {
+            count: buf[1],
+            min: delay(2),
+            mean: delay(6),
+            max: delay(10),
+        }
+    }
+
+    pub fn to_csv_row(&self) -> String {
+        format!("{}, {}, {}, {}", self.count, self.min, self.mean, self.max)
+    }
+}
+
+impl FakeLDAT {
+    /// Device sends a single aggregate per this many measurements instead of a summary report
+    /// for each, 0 and 1 go back to one report per trigger
+    ///
+    /// Individual delays are lost, the aggregates only have their min, mean and max
+    pub fn set_summary_aggregation(&mut self, measurements: u8) -> Result<()> {
+        self.send_setting(Command::SetSummaryAggregation, &[measurements, 0])
+    }
+    pub fn get_summary_aggregation(&mut self) -> Result<()> {
+        self.send_command(Command::GetSummaryAggregation, [0, 0])
+    }
+}
@@ -0,0 +1,444 @@
+use std::{fs, path::Path};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{RawReport, Result, SummaryReport};
+
+// Same as HISTORY_SIZE in the firmware
+const HISTORY_SIZE: u16 = 150;
+// Same as the manual trigger length in the firmware
+const TRIGGER_HOLD_US: u64 = 50_000;
+// How long the simulated flash stays fully bright
+const FLASH_HOLD_US: u64 = 100_000;
+
+// Samples taken into account by the adaptive detector
+const ADAPTIVE_WINDOW: usize = 150;
+// Standard deviations from the mean for the adaptive detector
+const ADAPTIVE_K: f64 = 4.0;
+// Samples between the compared values for the derivative detector
+const DERIVATIVE_SPAN: usize = 4;
+// Part of the way from the brightness at a press to the furthest one after it, like calibration
+const AUTO_THRESHOLD_MARGIN: f64 = 0.5;
+
+/// Decides when the brightness changed enough to count as a response
+pub trait Detector {
+    // Returns the absolute threshold if the brightness crossed it
+    fn process(&mut self, brightness: u16) -> Option<u16>;
+}
+
+/// Fixed offset from a rolling baseline, same as on the device
+pub struct EdgeDetector {
+    threshold: i16,
+    history: [u16; HISTORY_SIZE as usize],
+    count: usize,
+}
+
+impl EdgeDetector {
+    pub const fn new(threshold: i16) -> Self {
+        Self {
+            threshold,
+            history: [0; HISTORY_SIZE as usize],
+            count: 0,
+        }
+    }
+}
+
+impl Detector for EdgeDetector {
+    fn process(&mut self, brightness: u16) -> Option<u16> {
+        let sum: u32 = self.history.iter().map(|&value| u32::from(value)).sum();
+        self.history[self.count % usize::from(HISTORY_SIZE)] = brightness;
+        self.count += 1;
+        let absolute_threshold =
+            i64::from(sum / u32::from(HISTORY_SIZE)) + i64::from(self.threshold);
+        let brightness = i64::from(brightness);
+        let crossed = (self.threshold > 0 && brightness > absolute_threshold)
+            || (self.threshold < 0 && brightness < absolute_threshold);
+        crossed.then(|| u16::try_from(absolute_threshold.max(0)).unwrap_or(u16::MAX))
+    }
+}
+
+/// Mean + k·σ of the recent samples, negative k looks for darkening
+pub struct AdaptiveDetector {
+    k: f64,
+    history: [u16; ADAPTIVE_WINDOW],
+    count: usize,
+    sum: f64,
+    sum_of_squares: f64,
+}
+
+impl AdaptiveDetector {
+    pub const fn new(k: f64) -> Self {
+        Self {
+            k,
+            history: [0; ADAPTIVE_WINDOW],
+            count: 0,
+            sum: 0.0,
+            sum_of_squares: 0.0,
+        }
+    }
+}
+
+impl Detector for AdaptiveDetector {
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn process(&mut self, brightness: u16) -> Option<u16> {
+        let filled = self.count >= ADAPTIVE_WINDOW;
+        let mean = self.sum / ADAPTIVE_WINDOW as f64;
+        // at least 1 so a noiseless signal doesn't trigger on the smallest change
+        let std_dev = (self.sum_of_squares / ADAPTIVE_WINDOW as f64 - mean.powi(2))
+            .max(0.0)
+            .sqrt()
+            .max(1.0);
+        let absolute_threshold = self.k.mul_add(std_dev, mean);
+
+        let index = self.count % ADAPTIVE_WINDOW;
+        let old = f64::from(self.history[index]);
+        let new = f64::from(brightness);
+        self.sum += new - old;
+        self.sum_of_squares += new.powi(2) - old.powi(2);
+        self.history[index] = brightness;
+        self.count += 1;
+
+        let crossed = (self.k > 0.0 && new > absolute_threshold)
+            || (self.k < 0.0 && new < absolute_threshold);
+        (filled && crossed).then(|| absolute_threshold.clamp(0.0, f64::from(u16::MAX)) as u16)
+    }
+}
+
+/// Change over a few samples, catches the fast edges regardless of the baseline
+pub struct DerivativeDetector {
+    threshold: i16,
+    history: [u16; DERIVATIVE_SPAN],
+    count: usize,
+}
+
+impl DerivativeDetector {
+    pub const fn new(threshold: i16) -> Self {
+        Self {
+            threshold,
+            history: [0; DERIVATIVE_SPAN],
+            count: 0,
+        }
+    }
+}
+
+impl Detector for DerivativeDetector {
+    fn process(&mut self, brightness: u16) -> Option<u16> {
+        let index = self.count % DERIVATIVE_SPAN;
+        let previous = i64::from(self.history[index]);
+        self.history[index] = brightness;
+        self.count += 1;
+        let absolute_threshold = previous + i64::from(self.threshold);
+        let brightness = i64::from(brightness);
+        let crossed = (self.threshold > 0 && brightness > absolute_threshold)
+            || (self.threshold < 0 && brightness < absolute_threshold);
+        (self.count > DERIVATIVE_SPAN && crossed)
+            .then(|| u16::try_from(absolute_threshold.max(0)).unwrap_or(u16::MAX))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub enum DetectorKind {
+    #[default]
+    Fixed,
+    Adaptive,
+    Derivative,
+}
+
+impl DetectorKind {
+    pub const ALL: [Self; 3] = [Self::Fixed, Self::Adaptive, Self::Derivative];
+
+    // Sign of the threshold sets the direction for all of them, adaptive uses a fixed k
+    pub fn create(self, threshold: i16) -> Box<dyn Detector + Send> {
+        match self {
+            Self::Fixed => Box::new(EdgeDetector::new(threshold)),
+            Self::Adaptive => Box::new(AdaptiveDetector::new(
+                ADAPTIVE_K.copysign(f64::from(threshold)),
+            )),
+            Self::Derivative => Box::new(DerivativeDetector::new(threshold)),
+        }
+    }
+}
+
+impl std::fmt::Display for DetectorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Fixed => "Fixed",
+                Self::Adaptive => "Adaptive",
+                Self::Derivative => "Derivative",
+            }
+        )
+    }
+}
+
+/// Host side replica of the summary logic running on the device
+pub struct LatencyDetector {
+    detector: Box<dyn Detector + Send>,
+    last_trigger: bool,
+    trigger_timestamp: Option<u64>,
+}
+
+impl LatencyDetector {
+    // Same detection as on the device
+    pub fn new(threshold: i16) -> Self {
+        Self::with_detector(Box::new(EdgeDetector::new(threshold)))
+    }
+
+    pub fn with_detector(detector: Box<dyn Detector + Send>) -> Self {
+        Self {
+            detector,
+            last_trigger: false,
+            trigger_timestamp: None,
+        }
+    }
+
+    pub fn process(&mut self, report: &RawReport) -> Option<SummaryReport> {
+        let crossed = self.detector.process(report.brightness);
+        let pressed = report.trigger && !self.last_trigger;
+        self.last_trigger = report.trigger;
+        if pressed {
+            self.trigger_timestamp = Some(report.timestamp);
+            return None;
+        }
+        match (self.trigger_timestamp, crossed) {
+            (Some(trigger_timestamp), Some(threshold)) => {
+                self.trigger_timestamp = None;
+                Some(SummaryReport {
+                    delay: report.timestamp.saturating_sub(trigger_timestamp),
+                    threshold,
+                    arm_id: None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // Press that didn't go through the device, like FakeLDAT::host_trigger, raw reports after it
+    // are measured against it
+    pub fn trigger_at(&mut self, timestamp: u64) {
+        self.trigger_timestamp = Some(timestamp);
+    }
+
+    pub fn detect_all<'a>(
+        &mut self,
+        reports: impl IntoIterator<Item = &'a RawReport>,
+    ) -> Vec<SummaryReport> {
+        reports
+            .into_iter()
+            .filter_map(|report| self.process(report))
+            .collect()
+    }
+}
+
+// First derivative of the brightness, change from the previous sample
+pub fn slew(previous: &RawReport, current: &RawReport) -> i32 {
+    i32::from(current.brightness) - i32::from(previous.brightness)
+}
+
+// Either the GUI recording (timestamp, brightness, audio, trigger, optionally slew) or the CLI output (timestamp, brightness, trigger)
+pub fn parse_raw_row(line: &str) -> Option<RawReport> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let (audio, trigger) = match fields.len() {
+        3 => ("0", fields[2]),
+        4 | 5 => (fields[2], fields[3]),
+        _ => return None,
+    };
+    Some(RawReport {
+        timestamp: fields[0].parse().ok()?,
+        brightness: fields[1].parse().ok()?,
+        audio: audio.parse().ok()?,
+        trigger: match trigger {
+            "1" | "true" => true,
+            "0" | "false" => false,
+            _ => return None,
+        },
+        channel: 0,
+    })
+}
+
+// Delay and threshold, as recorded by the GUI and printed by the CLI
+pub fn parse_summary_row(line: &str) -> Option<SummaryReport> {
+    let (delay, threshold) = line.split_once(',')?;
+    Some(SummaryReport {
+        delay: delay.trim().parse().ok()?,
+        threshold: threshold.trim().parse().ok()?,
+        arm_id: None,
+    })
+}
+
+/// Threshold for a recording made with an unknown one, derived from how far the brightness moves
+/// after the presses, None without presses or any change after them
+///
+/// The median over all shots, so a few missed flashes don't matter
+#[allow(clippy::cast_possible_truncation)]
+pub fn auto_threshold(reports: &[RawReport]) -> Option<i16> {
+    let mut offsets = Vec::new();
+    // brightness at the press and the change furthest from it so far
+    let mut shot: Option<(u16, i32)> = None;
+    let mut last_trigger = false;
+    for report in reports.iter().filter(|report| report.channel == 0) {
+        let pressed = report.trigger && !last_trigger;
+        last_trigger = report.trigger;
+        if pressed {
+            offsets.extend(shot.map(|(_, offset)| offset));
+            shot = Some((report.brightness, 0));
+        } else if let Some((press, ref mut offset)) = shot {
+            let change = i32::from(report.brightness) - i32::from(press);
+            if change.abs() > offset.abs() {
+                *offset = change;
+            }
+        }
+    }
+    offsets.extend(shot.map(|(_, offset)| offset));
+    offsets.sort_unstable();
+    let offset = *offsets.get(offsets.len() / 2)?;
+    let threshold = (f64::from(offset) * AUTO_THRESHOLD_MARGIN) as i16;
+    (threshold != 0).then_some(threshold)
+}
+
+// Lines which aren't raw reports are skipped
+pub fn read_raw_csv<P: AsRef<Path>>(path: P) -> Result<Vec<RawReport>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(parse_raw_row)
+        .collect())
+}
+
+/// Synthetic sensor data with known latencies
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub poll_rate: u16,
+    pub dark: u16,
+    pub bright: u16,
+    // maximum deviation added to every sample
+    pub noise: u16,
+    // time it takes the panel to fully transition
+    pub rise_time_us: u64,
+    pub min_latency_us: u64,
+    pub max_latency_us: u64,
+    pub seed: u64,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self {
+            poll_rate: 2000,
+            dark: 200,
+            bright: 3000,
+            noise: 20,
+            rise_time_us: 2000,
+            min_latency_us: 10_000,
+            max_latency_us: 50_000,
+            seed: 0,
+        }
+    }
+}
+
+impl Simulation {
+    fn interval_us(&self) -> u64 {
+        1_000_000 / u64::from(self.poll_rate.max(1))
+    }
+
+    fn level(&self, timestamp: u64, flash_start: u64, flash_end: u64) -> i64 {
+        let dark = i64::from(self.dark);
+        let bright = i64::from(self.bright);
+        let rise_time = self.rise_time_us.max(1);
+        // 0 to 1000 of the transition
+        let progress = |from: u64| {
+            i64::try_from((timestamp - from).min(rise_time) * 1000 / rise_time).unwrap_or(1000)
+        };
+        if timestamp < flash_start {
+            dark
+        } else if timestamp < flash_end {
+            dark + (bright - dark) * progress(flash_start) / 1000
+        } else {
+            bright - (bright - dark) * progress(flash_end) / 1000
+        }
+    }
+
+    // Baseline settling, the trigger and the flash with its decay
+    fn shot(&self, rng: &mut StdRng, start: u64, latency_us: u64) -> Vec<RawReport> {
+        let interval = self.interval_us();
+        let trigger_timestamp = start + interval * 2 * u64::from(HISTORY_SIZE);
+        let flash_start = trigger_timestamp + latency_us;
+        let flash_end = flash_start + self.rise_time_us + FLASH_HOLD_US;
+        let end = flash_end + self.rise_time_us;
+        let noise = i64::from(self.noise);
+        (0..(end - start).div_ceil(interval))
+            .map(|sample| start + sample * interval)
+            .map(|timestamp| {
+                let level =
+                    self.level(timestamp, flash_start, flash_end) + rng.gen_range(-noise..=noise);
+                RawReport {
+                    timestamp,
+                    brightness: u16::try_from(level.clamp(0, 4095)).unwrap_or_default(),
+                    audio: 0,
+                    trigger: (trigger_timestamp..trigger_timestamp + TRIGGER_HOLD_US)
+                        .contains(&timestamp),
+                    channel: 0,
+                }
+            })
+            .collect()
+    }
+}
+
+/// How far off the detected latencies are from the simulated ones
+#[derive(Debug, Clone, Copy)]
+pub struct ValidationReport {
+    pub trials: usize,
+    pub detected: usize,
+    // mean of detected minus simulated latency
+    pub bias_us: f64,
+    // in microseconds squared
+    pub variance: f64,
+}
+
+impl ValidationReport {
+    pub fn std_dev_us(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub fn validate(simulation: &Simulation, threshold: i16, trials: usize) -> ValidationReport {
+    let mut rng = StdRng::seed_from_u64(simulation.seed);
+    let mut detector = LatencyDetector::new(threshold);
+    let mut errors = Vec::with_capacity(trials);
+    let mut start = 0;
+    for _ in 0..trials {
+        let latency_us = rng.gen_range(
+            simulation.min_latency_us..=simulation.max_latency_us.max(simulation.min_latency_us),
+        );
+        let reports = simulation.shot(&mut rng, start, latency_us);
+        // only the first detection of a shot counts
+        let mut delay = None;
+        for report in &reports {
+            if let Some(summary) = detector.process(report) {
+                delay.get_or_insert(summary.delay);
+            }
+        }
+        if let Some(delay) = delay {
+            errors.push(delay as f64 - latency_us as f64);
+        }
+        start = reports.last().map_or(start, |report| report.timestamp) + simulation.interval_us();
+    }
+    let count = errors.len() as f64;
+    let bias_us = errors.iter().sum::<f64>() / count.max(1.0);
+    let variance = errors
+        .iter()
+        .map(|error| (error - bias_us).powi(2))
+        .sum::<f64>()
+        / (count - 1.0).max(1.0);
+    ValidationReport {
+        trials,
+        detected: errors.len(),
+        bias_us,
+        variance,
+    }
+}
@@ -0,0 +1,28 @@
+use crate::{Command, FakeLDAT, Result};
+
+impl FakeLDAT {
+    /// Opens a measurement window, the device ignores triggers outside of them from now on
+    ///
+    /// Summaries measured inside carry the returned id as their `arm_id`, until `disarm`.
+    /// `release_arming` goes back to measuring every trigger
+    pub fn arm(&mut self) -> Result<u16> {
+        self.next_arm_id = self.next_arm_id.wrapping_add(1);
+        let id = self.next_arm_id;
+        self.send_command(Command::Arm, id.to_le_bytes())?;
+        Ok(id)
+    }
+
+    // A measurement in progress is dropped
+    pub fn disarm(&mut self) -> Result<()> {
+        self.send_command(Command::Disarm, [0, 0])
+    }
+
+    pub fn release_arming(&mut self) -> Result<()> {
+        self.send_command(Command::Disarm, [1, 0])
+    }
+
+    // Id of the open window, once the device confirmed it
+    pub const fn armed(&self) -> Option<u16> {
+        self.armed
+    }
+}
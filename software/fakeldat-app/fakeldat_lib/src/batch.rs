@@ -0,0 +1,99 @@
+use crate::RawReport;
+
+// Samples in one ReportRawBatch frame
+pub(crate) const BATCH_SIZE: u8 = 4;
+
+pub(crate) fn sample_count(buf: &[u8; 16]) -> u8 {
+    (buf[1] & 0x07).min(BATCH_SIZE)
+}
+
+// Frame layout:
+// 1 - bits 0-2 sample count, bits 4-7 trigger state of each sample
+// 2 - 13 samples, 3 bytes each, low 12 bits brightness, high 12 bits microseconds since the previous sample
+// Audio isn't carried and all samples are from channel 0, the first delta is relative to the last raw report or sample
+pub(crate) fn decode(buf: &[u8; 16], mut timestamp: u64) -> Vec<RawReport> {
+    let count = usize::from(sample_count(buf));
+    buf[2..2 + count * 3]
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(i, sample)| {
+            timestamp += u64::from(sample[1] >> 4) | u64::from(sample[2]) << 4;
+            RawReport {
+                timestamp,
+                brightness: u16::from_le_bytes([sample[0], sample[1] & 0x0F]),
+                audio: 0,
+                trigger: buf[1] >> (4 + i) & 1 == 1,
+                channel: 0,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, sample_count};
+
+    // Laid out like add_to_batch and flush_batch in the firmware, (brightness, delta, trigger)
+    fn frame(samples: &[(u16, u16, bool)]) -> [u8; 16] {
+        let mut buf = [0; 16];
+        buf[0] = crate::Command::ReportRawBatch as u8;
+        for (i, &(brightness, delta, trigger)) in samples.iter().enumerate() {
+            let sample = &mut buf[2 + i * 3..5 + i * 3];
+            sample[0] = (brightness & 0xFF) as u8;
+            sample[1] = (brightness >> 8 & 0x0F) as u8 | ((delta & 0x0F) as u8) << 4;
+            sample[2] = (delta >> 4 & 0xFF) as u8;
+            buf[1] |= u8::from(trigger) << (4 + i);
+        }
+        buf[1] |= samples.len() as u8;
+        buf
+    }
+
+    #[test]
+    fn full_batch() {
+        let buf = frame(&[
+            (0x0ABC, 0x0FED, false),
+            (0x0FFF, 0x0001, true),
+            (0x0000, 0x0123, false),
+            (0x0800, 0x0FFF, true),
+        ]);
+        assert_eq!(sample_count(&buf), 4);
+        let reports = decode(&buf, 1000);
+        let decoded: Vec<_> = reports
+            .iter()
+            .map(|report| (report.timestamp, report.brightness, report.trigger))
+            .collect();
+        assert_eq!(
+            decoded,
+            [
+                (1000 + 0x0FED, 0x0ABC, false),
+                (1000 + 0x0FED + 0x0001, 0x0FFF, true),
+                (1000 + 0x0FED + 0x0001 + 0x0123, 0x0000, false),
+                (1000 + 0x0FED + 0x0001 + 0x0123 + 0x0FFF, 0x0800, true),
+            ]
+        );
+        assert!(reports
+            .iter()
+            .all(|report| report.audio == 0 && report.channel == 0));
+    }
+
+    #[test]
+    fn partial_batch() {
+        let buf = frame(&[(0x0123, 500, true), (0x0456, 250, false)]);
+        assert_eq!(sample_count(&buf), 2);
+        let reports = decode(&buf, 0);
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].timestamp, 500);
+        assert_eq!(reports[1].timestamp, 750);
+        assert_eq!(reports[1].brightness, 0x0456);
+        assert!(reports[0].trigger);
+        assert!(!reports[1].trigger);
+    }
+
+    #[test]
+    fn count_is_capped() {
+        let mut buf = frame(&[(1, 1, false); 4]);
+        buf[1] |= 0x07;
+        assert_eq!(sample_count(&buf), 4);
+        assert_eq!(decode(&buf, 0).len(), 4);
+    }
+}
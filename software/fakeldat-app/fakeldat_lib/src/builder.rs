@@ -0,0 +1,236 @@
+use std::{thread::sleep, time::Duration};
+
+use serialport::{ClearBuffer, SerialPortBuilder};
+
+use crate::log::warning;
+use crate::{
+    AckPolicy, Command, Error, FakeLDAT, FrameSize, Integrity, OverflowPolicy, Report, Result,
+    DEFAULT_REPORT_BUFFER_CAPACITY,
+};
+
+/// Opens the port and verifies that the device is on the other side
+#[derive(Debug, Clone)]
+pub struct FakeLDATBuilder {
+    path: Option<String>,
+    baud_rate: u32,
+    timeout: Duration,
+    data_terminal_ready: bool,
+    request_to_send: Option<bool>,
+    retries: u32,
+    retry_delay: Duration,
+    handshake_timeout: Option<Duration>,
+    reconnect_interval: Option<Duration>,
+    integrity: Integrity,
+    frame_size: FrameSize,
+    sequence: bool,
+    switch_baud_rate: Option<u32>,
+    report_buffer: (usize, OverflowPolicy),
+    ack: Option<AckPolicy>,
+    gap_detection: bool,
+}
+
+impl Default for FakeLDATBuilder {
+    fn default() -> Self {
+        Self {
+            path: None,
+            baud_rate: 115_200,
+            timeout: Duration::from_secs(1),
+            data_terminal_ready: true,
+            request_to_send: None,
+            retries: 0,
+            retry_delay: Duration::from_secs(1),
+            handshake_timeout: Some(Duration::from_secs(1)),
+            reconnect_interval: None,
+            integrity: Integrity::Sum,
+            frame_size: FrameSize::Standard,
+            sequence: false,
+            switch_baud_rate: None,
+            report_buffer: (DEFAULT_REPORT_BUFFER_CAPACITY, OverflowPolicy::DropOldest),
+            ack: Some(AckPolicy::default()),
+            gap_detection: false,
+        }
+    }
+}
+
+impl FakeLDATBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // First available port is used when not set
+    #[must_use]
+    pub fn path<S: Into<String>>(mut self, path: S) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    #[must_use]
+    pub const fn baud_rate(mut self, baud_rate: u32) -> Self {
+        self.baud_rate = baud_rate;
+        self
+    }
+
+    // Used for both reading and writing
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    #[must_use]
+    pub const fn data_terminal_ready(mut self, level: bool) -> Self {
+        self.data_terminal_ready = level;
+        self
+    }
+
+    #[must_use]
+    pub const fn request_to_send(mut self, level: bool) -> Self {
+        self.request_to_send = Some(level);
+        self
+    }
+
+    // Extra attempts after the first one fails
+    #[must_use]
+    pub const fn retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+
+    // None skips the verification
+    #[must_use]
+    pub const fn handshake(mut self, timeout: Option<Duration>) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    // Reopen the device after it disappears, checking every interval
+    #[must_use]
+    pub const fn auto_reconnect(mut self, interval: Duration) -> Self {
+        self.reconnect_interval = Some(interval);
+        self
+    }
+
+    // Requested after opening, falls back to Sum when the firmware doesn't support it
+    #[must_use]
+    pub const fn integrity(mut self, integrity: Integrity) -> Self {
+        self.integrity = integrity;
+        self
+    }
+
+    // Requested after the integrity, stays on the standard size when the firmware doesn't support it
+    #[must_use]
+    pub const fn frame_size(mut self, frame_size: FrameSize) -> Self {
+        self.frame_size = frame_size;
+        self
+    }
+
+    // Detects dropped frames, can't be combined with CRC-16
+    #[must_use]
+    pub const fn sequence_numbers(mut self, enabled: bool) -> Self {
+        self.sequence = enabled;
+        self
+    }
+
+    // Opened with baud_rate first, then both sides switch to this one if the device supports it
+    #[must_use]
+    pub const fn switch_baud_rate(mut self, baud_rate: u32) -> Self {
+        self.switch_baud_rate = Some(baud_rate);
+        self
+    }
+
+    // Reports held until taken, the policy decides what happens past the capacity
+    #[must_use]
+    pub const fn report_buffer(mut self, capacity: usize, policy: OverflowPolicy) -> Self {
+        self.report_buffer = (capacity, policy);
+        self
+    }
+
+    // Settings wait for their echo and are sent again when it doesn't come, None doesn't wait
+    #[must_use]
+    pub const fn acknowledge(mut self, ack: Option<AckPolicy>) -> Self {
+        self.ack = ack;
+        self
+    }
+
+    // Reports GapDetected when raw samples are further apart than the poll rate allows
+    #[must_use]
+    pub const fn gap_detection(mut self, enabled: bool) -> Self {
+        self.gap_detection = enabled;
+        self
+    }
+
+    fn port_builder(&self) -> Result<SerialPortBuilder> {
+        let path = match self.path {
+            Some(ref path) => path.clone(),
+            None => serialport::available_ports()?
+                .first()
+                .ok_or_else(|| {
+                    serialport::Error::new(serialport::ErrorKind::NoDevice, "No serial ports")
+                })?
+                .port_name
+                .clone(),
+        };
+        Ok(serialport::new(path, self.baud_rate).timeout(self.timeout))
+    }
+
+    pub(crate) fn try_open(&self) -> Result<FakeLDAT> {
+        let mut port = self.port_builder()?.open()?;
+        port.write_data_terminal_ready(self.data_terminal_ready)?;
+        if let Some(level) = self.request_to_send {
+            port.write_request_to_send(level)?;
+        }
+        let mut fakeldat = FakeLDAT::from_port(port)?;
+        fakeldat.set_report_buffer(self.report_buffer.0, self.report_buffer.1);
+        fakeldat.set_acknowledge(self.ack);
+        fakeldat.set_gap_detection(self.gap_detection);
+        if let Some(timeout) = self.handshake_timeout {
+            // leftovers from before would break the frame alignment
+            fakeldat.port.clear(ClearBuffer::Input)?;
+            fakeldat.get_poll_rate()?;
+            fakeldat.wait_for_report(Command::GetPollRate, timeout, |report| {
+                matches!(report, Report::PollRate(_)).then_some(())
+            })?;
+            // features the firmware lacks fail right away from now on
+            fakeldat.hello(timeout)?;
+        }
+        let timeout = self.handshake_timeout.unwrap_or(Duration::from_secs(1));
+        if self.integrity != Integrity::Sum {
+            fakeldat.negotiate_integrity(self.integrity, timeout)?;
+        }
+        if self.frame_size != FrameSize::Standard {
+            fakeldat.negotiate_frame_size(self.frame_size, timeout)?;
+        }
+        if let Some(baud_rate) = self.switch_baud_rate {
+            fakeldat.switch_baud_rate(baud_rate, timeout)?;
+        }
+        if self.sequence {
+            fakeldat.negotiate_sequence(true, timeout)?;
+        }
+        Ok(fakeldat)
+    }
+
+    // This will block for all of the retries
+    pub fn open(self) -> Result<FakeLDAT> {
+        let mut attempt = 0;
+        loop {
+            match self.try_open() {
+                Ok(mut fakeldat) => {
+                    if let Some(interval) = self.reconnect_interval {
+                        fakeldat.enable_reconnect(self.clone(), interval);
+                    }
+                    return Ok(fakeldat);
+                }
+                Err(why)
+                    if matches!(why, Error::PortFail(_) | Error::CommandTimeout(_))
+                        && attempt < self.retries =>
+                {
+                    warning!(error = ?why, attempt, "can't connect to the device");
+                    attempt += 1;
+                    sleep(self.retry_delay);
+                }
+                Err(why) => return Err(why),
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::{Baseline, Error, FakeLDAT, Report, ReportMode, Result};
+
+// Part of the way from the base to the flash level, leaves room on both sides
+const MARGIN: f64 = 0.5;
+// Spread of the base level which the threshold has to clear
+const NOISE_PERCENTILE: f64 = 0.99;
+
+/// Brightness levels seen by [`FakeLDAT::calibrate_threshold`]
+#[derive(Debug, Clone, Copy)]
+pub struct Calibration {
+    // medians of both phases
+    pub base: u16,
+    pub flash: u16,
+    // distance from the base median most base samples stay within
+    pub noise: u16,
+    // matches the detection mode known at the time
+    pub threshold: i16,
+}
+
+fn median(samples: &mut [u16]) -> Option<u16> {
+    samples.sort_unstable();
+    samples.get(samples.len() / 2).copied()
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::IOError(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message,
+    ))
+}
+
+impl FakeLDAT {
+    // Samples the base brightness, calls show_flash and samples again, each phase takes duration
+    // Summary mode is swapped for combined meanwhile and raw reports are let through the filter,
+    // raw reports taken during it are consumed
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    pub fn calibrate_threshold(
+        &mut self,
+        duration: Duration,
+        apply: bool,
+        show_flash: impl FnOnce(),
+    ) -> Result<Calibration> {
+        let report_mode = self.settings.report_mode;
+        if report_mode == Some(ReportMode::Summary) {
+            self.set_report_mode(ReportMode::Combined)?;
+        }
+        let report_filter = self.report_filter;
+        self.report_filter.raw = true;
+        let samples = self.collect_brightness(duration).and_then(|base| {
+            show_flash();
+            Ok((base, self.collect_brightness(duration)?))
+        });
+        self.report_filter = report_filter;
+        if report_mode == Some(ReportMode::Summary) {
+            self.set_report_mode(ReportMode::Summary)?;
+        }
+        let (mut base_samples, mut flash_samples) = samples?;
+
+        let (Some(base), Some(flash)) = (median(&mut base_samples), median(&mut flash_samples))
+        else {
+            return Err(invalid_data("no raw reports received"));
+        };
+        let mut deviations: Vec<u16> = base_samples
+            .iter()
+            .map(|&sample| sample.abs_diff(base))
+            .collect();
+        deviations.sort_unstable();
+        let noise = deviations[((deviations.len() - 1) as f64 * NOISE_PERCENTILE) as usize];
+
+        let difference = i32::from(flash) - i32::from(base);
+        let margin = (f64::from(difference) * MARGIN) as i32;
+        if margin.unsigned_abs() <= u32::from(noise) {
+            return Err(invalid_data("flash isn't distinguishable from the base"));
+        }
+        let threshold = match self.settings.detection_mode.map(|mode| mode.baseline) {
+            // the sign still picks the edge when it's set to auto
+            Some(Baseline::Absolute) => (i32::from(base) + margin) * difference.signum(),
+            _ => margin,
+        } as i16;
+
+        if apply {
+            self.set_threshold(threshold)?;
+        }
+        Ok(Calibration {
+            base,
+            flash,
+            noise,
+            threshold,
+        })
+    }
+
+    fn collect_brightness(&mut self, duration: Duration) -> Result<Vec<u16>> {
+        let start = Instant::now();
+        let mut samples = Vec::new();
+        while start.elapsed() < duration {
+            self.poll_bulk_data()?;
+            self.report_buffer.retain(|report| match report {
+                Report::Raw(raw_report) => {
+                    samples.push(raw_report.brightness);
+                    false
+                }
+                _ => true,
+            });
+            sleep(Duration::from_millis(10));
+        }
+        Ok(samples)
+    }
+}
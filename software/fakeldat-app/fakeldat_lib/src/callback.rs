@@ -0,0 +1,71 @@
+use crate::{FakeLDAT, Report};
+
+pub(crate) type ReportCallback = Box<dyn FnMut(Report) + Send>;
+pub(crate) type FrameCallback = Box<dyn FnMut(RawFrame) + Send>;
+
+/// Frame as it was read from the port, before anything is decoded, see [`FakeLDAT::on_frame`]
+#[derive(Debug, Clone, Copy)]
+pub struct RawFrame<'a> {
+    // whole frame in the current frame size, the command id first
+    pub bytes: &'a [u8],
+    // always true without integrity checks
+    pub checksum_valid: bool,
+}
+
+impl Report {
+    // Measured data and trigger events, as opposed to setting replies and link events
+    pub const fn is_measurement(&self) -> bool {
+        matches!(
+            self,
+            Self::Raw(_)
+                | Self::Summary(_)
+                | Self::SummaryAggregate(_)
+                | Self::RawAudio(_)
+                | Self::AudioVideo(_)
+                | Self::AutoTrigger(_)
+                | Self::MacroTrigger(_)
+                | Self::ManualTrigger
+        )
+    }
+}
+
+impl FakeLDAT {
+    /// Hands measurements to the callback as soon as their frame is parsed, they skip the report
+    /// buffer so calibration won't see them while it's set
+    ///
+    /// Threading: the callback runs synchronously on the thread which is polling, from
+    /// `poll_bulk_data` and every method waiting for a reply, in the order the frames arrived.
+    /// Parsing stops until it returns, anything slow should be sent to another thread.
+    /// Setting replies and link events are still buffered, replaces the previous callback
+    pub fn on_report(&mut self, callback: impl FnMut(Report) + Send + 'static) {
+        self.report_callback = Some(Box::new(callback));
+    }
+
+    pub fn clear_on_report(&mut self) {
+        self.report_callback = None;
+    }
+
+    /// Hands every frame read to the callback, also the ones dropped for a wrong checksum or an
+    /// unknown command id, for debugging the protocol
+    ///
+    /// Runs on the polling thread like `on_report`, before the frame is parsed.
+    /// Replaces the previous callback
+    pub fn on_frame(&mut self, callback: impl FnMut(RawFrame) + Send + 'static) {
+        self.frame_callback = Some(Box::new(callback));
+    }
+
+    pub fn clear_on_frame(&mut self) {
+        self.frame_callback = None;
+    }
+
+    // Gives the report back when it should be buffered
+    pub(crate) fn dispatch_report(&mut self, report: Report) -> Option<Report> {
+        match self.report_callback {
+            Some(ref mut callback) if report.is_measurement() => {
+                callback(report);
+                None
+            }
+            _ => Some(report),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::time::Duration;
+
+use crate::{Command, Error, FakeLDAT, Report, ReportMode, Result};
+
+// Sent in the hello, bumped whenever frames change in a way older hosts can't follow
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// Optional firmware features, from the bitfield in the hello
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Capabilities {
+    pub integrity: bool,
+    pub sequence: bool,
+    pub raw_batching: bool,
+    pub baud_rate: bool,
+    pub clock: bool,
+    pub action_timing: bool,
+    pub auto_trigger: bool,
+    pub detection_mode: bool,
+    pub sensor_gain: bool,
+    // channel 1 exists
+    pub second_sensor: bool,
+    pub telemetry: bool,
+    pub raw_audio: bool,
+    pub audio_video: bool,
+    pub macro_trigger: bool,
+    pub heartbeat: bool,
+    // 32 byte frames
+    pub extended_frames: bool,
+    pub arming: bool,
+    pub summary_aggregation: bool,
+    pub self_test: bool,
+}
+
+/// Answer to the hello, firmware from before it is reported as version 0 without any capabilities
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Hello {
+    pub protocol_version: u16,
+    pub capabilities: Capabilities,
+}
+
+impl Capabilities {
+    // Bit n of the field, in the order of the struct
+    pub fn from_bits(bits: u32) -> Self {
+        let bit = |n: u32| bits >> n & 1 == 1;
+        Self {
+            integrity: bit(0),
+            sequence: bit(1),
+            raw_batching: bit(2),
+            baud_rate: bit(3),
+            clock: bit(4),
+            action_timing: bit(5),
+            auto_trigger: bit(6),
+            detection_mode: bit(7),
+            sensor_gain: bit(8),
+            second_sensor: bit(9),
+            telemetry: bit(10),
+            raw_audio: bit(11),
+            audio_video: bit(12),
+            macro_trigger: bit(13),
+            heartbeat: bit(14),
+            extended_frames: bit(15),
+            arming: bit(16),
+            summary_aggregation: bit(17),
+            self_test: bit(18),
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        [
+            self.integrity,
+            self.sequence,
+            self.raw_batching,
+            self.baud_rate,
+            self.clock,
+            self.action_timing,
+            self.auto_trigger,
+            self.detection_mode,
+            self.sensor_gain,
+            self.second_sensor,
+            self.telemetry,
+            self.raw_audio,
+            self.audio_video,
+            self.macro_trigger,
+            self.heartbeat,
+            self.extended_frames,
+            self.arming,
+            self.summary_aggregation,
+            self.self_test,
+        ]
+        .into_iter()
+        .zip(0..)
+        .fold(0, |bits, (flag, n)| bits | u32::from(flag) << n)
+    }
+
+    // Some settings only need a feature for some of their values
+    pub fn allows(&self, command: Command, payload: &[u8]) -> bool {
+        match command {
+            Command::SetIntegrity | Command::GetIntegrity => self.integrity,
+            Command::SetSequence | Command::GetSequence => self.sequence,
+            Command::SetRawBatching | Command::GetRawBatching => self.raw_batching,
+            Command::SetBaudRate | Command::GetBaudRate => self.baud_rate,
+            Command::GetClock => self.clock,
+            Command::SetActionTiming | Command::GetActionTiming => self.action_timing,
+            Command::SetAutoTrigger | Command::GetAutoTrigger => self.auto_trigger,
+            Command::SetDetectionMode | Command::GetDetectionMode => self.detection_mode,
+            Command::SetSensorGain | Command::GetSensorGain => self.sensor_gain,
+            Command::SetChannels => {
+                payload.first().is_some_and(|channels| channels & !1 == 0) || self.second_sensor
+            }
+            Command::Ping => self.heartbeat,
+            Command::Arm | Command::Disarm => self.arming,
+            Command::SelfTest => self.self_test,
+            Command::SetSummaryAggregation
+            | Command::GetSummaryAggregation
+            | Command::ReportSummaryAggregate => self.summary_aggregation,
+            Command::SetFrameSize | Command::GetFrameSize => self.extended_frames,
+            Command::ReportTelemetry
+            | Command::SetTelemetryInterval
+            | Command::GetTelemetryInterval => self.telemetry,
+            Command::SetReportMode => match payload.first().map(|mode| ReportMode::try_from(*mode))
+            {
+                Some(Ok(ReportMode::RawAudio)) => self.raw_audio,
+                Some(Ok(ReportMode::AudioVideo)) => self.audio_video,
+                _ => true,
+            },
+            _ => true,
+        }
+    }
+}
+
+impl Hello {
+    pub(crate) fn from_frame(buf: &[u8; 16]) -> Self {
+        Self {
+            protocol_version: u16::from_le_bytes([buf[1], buf[2]]),
+            capabilities: Capabilities::from_bits(u32::from_le_bytes([
+                buf[3], buf[4], buf[5], buf[6],
+            ])),
+        }
+    }
+}
+
+impl FakeLDAT {
+    // Done by the builder, handles from create only know the capabilities once this is called
+    // Firmware which doesn't answer in time is taken for one from before the hello
+    pub fn hello(&mut self, timeout: Duration) -> Result<Hello> {
+        self.send_command(Command::Hello, PROTOCOL_VERSION.to_le_bytes())?;
+        let hello = match self.wait_for_report(Command::Hello, timeout, |report| match report {
+            Report::Hello(hello) => Some(*hello),
+            _ => None,
+        }) {
+            Ok(hello) => hello,
+            Err(Error::CommandTimeout(_)) => Hello::default(),
+            Err(why) => return Err(why),
+        };
+        self.hello = Some(hello);
+        Ok(hello)
+    }
+
+    // Answer of the last hello, None until it was exchanged
+    pub const fn hello_answer(&self) -> Option<Hello> {
+        self.hello
+    }
+
+    // None until the hello was exchanged, everything is tried then
+    pub const fn capabilities(&self) -> Option<Capabilities> {
+        match self.hello {
+            Some(hello) => Some(hello.capabilities),
+            None => None,
+        }
+    }
+
+    pub const fn protocol_version(&self) -> Option<u16> {
+        match self.hello {
+            Some(hello) => Some(hello.protocol_version),
+            None => None,
+        }
+    }
+
+    pub(crate) fn supports(&self, command: Command, payload: &[u8]) -> bool {
+        self.capabilities()
+            .is_none_or(|capabilities| capabilities.allows(command, payload))
+    }
+}
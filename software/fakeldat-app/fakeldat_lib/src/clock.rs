@@ -0,0 +1,139 @@
+use std::time::{Duration, Instant};
+
+use crate::{Command, FakeLDAT, RawReport, Report, Result};
+
+// Older syncs are dropped, drift changes with temperature
+const MAX_SYNCS: usize = 32;
+
+/// Mapping between the device microsecond counter and the host monotonic clock
+#[derive(Debug, Clone)]
+pub struct ClockSync {
+    epoch: Instant,
+    // device timestamp at the epoch
+    pub offset_us: f64,
+    // how much faster the device clock runs, in parts per million
+    pub drift_ppm: f64,
+    // of the exchange used in the last sync
+    pub round_trip: Duration,
+    // host microseconds since the epoch and the device timestamp
+    syncs: Vec<(f64, f64)>,
+}
+
+impl ClockSync {
+    fn new(epoch: Instant) -> Self {
+        Self {
+            epoch,
+            offset_us: 0.0,
+            drift_ppm: 0.0,
+            round_trip: Duration::ZERO,
+            syncs: Vec::new(),
+        }
+    }
+
+    // Drift is only known after at least two syncs some time apart
+    pub fn sync_count(&self) -> usize {
+        self.syncs.len()
+    }
+
+    fn push(&mut self, host: Instant, device_us: u64, round_trip: Duration) {
+        if self.syncs.len() == MAX_SYNCS {
+            self.syncs.remove(0);
+        }
+        #[allow(clippy::cast_precision_loss)]
+        self.syncs.push((
+            host.duration_since(self.epoch).as_secs_f64() * 1_000_000.0,
+            device_us as f64,
+        ));
+        self.round_trip = round_trip;
+        self.fit();
+    }
+
+    // Least squares line through all syncs
+    #[allow(clippy::cast_precision_loss)]
+    fn fit(&mut self) {
+        let count = self.syncs.len() as f64;
+        let mean_host = self.syncs.iter().map(|sync| sync.0).sum::<f64>() / count;
+        let mean_device = self.syncs.iter().map(|sync| sync.1).sum::<f64>() / count;
+        let (covariance, variance) = self.syncs.iter().fold((0.0, 0.0), |(cov, var), sync| {
+            (
+                cov + (sync.0 - mean_host) * (sync.1 - mean_device),
+                var + (sync.0 - mean_host).powi(2),
+            )
+        });
+        let slope = if variance > 0.0 {
+            covariance / variance
+        } else {
+            1.0
+        };
+        self.offset_us = mean_device - slope * mean_host;
+        self.drift_ppm = (slope - 1.0) * 1_000_000.0;
+    }
+
+    fn slope(&self) -> f64 {
+        1.0 + self.drift_ppm / 1_000_000.0
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn to_host(&self, device_us: u64) -> Instant {
+        let host_us = (device_us as f64 - self.offset_us) / self.slope();
+        let since_epoch = Duration::from_secs_f64(host_us.abs() / 1_000_000.0);
+        if host_us >= 0.0 {
+            self.epoch + since_epoch
+        } else {
+            self.epoch.checked_sub(since_epoch).unwrap_or(self.epoch)
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn to_device(&self, host: Instant) -> u64 {
+        let host_us = if host >= self.epoch {
+            host.duration_since(self.epoch).as_secs_f64()
+        } else {
+            -self.epoch.duration_since(host).as_secs_f64()
+        } * 1_000_000.0;
+        (self.offset_us + host_us * self.slope()).max(0.0) as u64
+    }
+}
+
+impl RawReport {
+    pub fn host_time(&self, clock: &ClockSync) -> Instant {
+        clock.to_host(self.timestamp)
+    }
+}
+
+impl FakeLDAT {
+    pub fn get_clock(&mut self) -> Result<()> {
+        self.send_command(Command::GetClock, [0, 0])
+    }
+
+    // Only the exchange with the shortest round trip is kept, call it periodically to track the drift
+    // This will block until all exchanges are done or timeout runs out
+    pub fn sync_clock(&mut self, exchanges: usize, timeout: Duration) -> Result<ClockSync> {
+        let mut best: Option<(Instant, u64, Duration)> = None;
+        for _ in 0..exchanges.max(1) {
+            let sent = Instant::now();
+            self.get_clock()?;
+            let device_us =
+                self.wait_for_report(Command::GetClock, timeout, |report| match report {
+                    Report::Clock(device_us) => Some(*device_us),
+                    _ => None,
+                })?;
+            let round_trip = sent.elapsed();
+            if best.is_none_or(|(_, _, best_round_trip)| round_trip < best_round_trip) {
+                // assume the device answered halfway through
+                best = Some((sent + round_trip / 2, device_us, round_trip));
+            }
+        }
+        let clock = self
+            .clock
+            .get_or_insert_with(|| ClockSync::new(Instant::now()));
+        if let Some((host, device_us, round_trip)) = best {
+            clock.push(host, device_us, round_trip);
+        }
+        Ok(clock.clone())
+    }
+
+    pub const fn clock_sync(&self) -> Option<&ClockSync> {
+        self.clock.as_ref()
+    }
+}
@@ -0,0 +1,63 @@
+use crate::{FakeLDAT, Report};
+
+/// Report kinds let into the buffer and the callback, setting replies always get through
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportFilter {
+    pub raw: bool,
+    pub summary: bool,
+    pub raw_audio: bool,
+    pub audio_video: bool,
+    // auto, macro and manual trigger events
+    pub triggers: bool,
+    pub telemetry: bool,
+}
+
+impl ReportFilter {
+    pub const ALL: Self = Self {
+        raw: true,
+        summary: true,
+        raw_audio: true,
+        audio_video: true,
+        triggers: true,
+        telemetry: true,
+    };
+
+    // Only what summary statistics need
+    pub const SUMMARY: Self = Self {
+        raw: false,
+        raw_audio: false,
+        ..Self::ALL
+    };
+
+    pub const fn allows(&self, report: &Report) -> bool {
+        match report {
+            Report::Raw(_) => self.raw,
+            Report::Summary(_) | Report::SummaryAggregate(_) => self.summary,
+            Report::RawAudio(_) => self.raw_audio,
+            Report::AudioVideo(_) => self.audio_video,
+            Report::AutoTrigger(_) | Report::MacroTrigger(_) | Report::ManualTrigger => {
+                self.triggers
+            }
+            Report::Telemetry(_) => self.telemetry,
+            _ => true,
+        }
+    }
+}
+
+impl Default for ReportFilter {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl FakeLDAT {
+    // Discarded reports are still used for the library's own tracking, like saturation
+    pub fn set_report_filter(&mut self, report_filter: ReportFilter) {
+        self.report_filter = report_filter;
+    }
+
+    pub const fn report_filter(&self) -> ReportFilter {
+        self.report_filter
+    }
+}
@@ -0,0 +1,138 @@
+//! Firmware updates through the UF2 bootloader of the RP2040, no need to hold BOOTSEL
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::log::info;
+use crate::{Error, FakeLDAT, Result};
+
+// Speed the Arduino core takes as the request to reboot into the bootloader
+const TOUCH_BAUD_RATE: u32 = 1200;
+// Volume name of the RP2040 bootloader drive
+const DRIVE_NAME: &str = "RPI-RP2";
+// Every bootloader drive has it
+const INFO_FILE: &str = "INFO_UF2.TXT";
+const UF2_BLOCK_SIZE: usize = 512;
+const UF2_MAGIC_START: [u32; 2] = [0x0A32_4655, 0x9E5D_5157];
+const UF2_MAGIC_END: u32 = 0x0AB1_6F30;
+const COPY_CHUNK: usize = 64 * 1024;
+
+fn invalid_data(message: &str) -> Error {
+    Error::IOError(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message,
+    ))
+}
+
+fn word(block: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        block[offset],
+        block[offset + 1],
+        block[offset + 2],
+        block[offset + 3],
+    ])
+}
+
+impl FakeLDAT {
+    // Closes the port at 1200 baud which reboots the device into the bootloader,
+    // its drive shows up a few seconds later, see find_bootloader_drive
+    pub fn enter_bootloader(mut self) -> Result<()> {
+        self.port.set_baud_rate(TOUCH_BAUD_RATE)?;
+        info!("rebooting into the bootloader");
+        self.release()
+    }
+}
+
+// Checks the magic numbers of every block, so a wrong file never reaches the device
+pub fn validate_uf2(image: &[u8]) -> Result<()> {
+    if image.is_empty() || !image.len().is_multiple_of(UF2_BLOCK_SIZE) {
+        return Err(invalid_data(
+            "Not a UF2 image, size isn't a multiple of 512",
+        ));
+    }
+    for block in image.chunks_exact(UF2_BLOCK_SIZE) {
+        if word(block, 0) != UF2_MAGIC_START[0]
+            || word(block, 4) != UF2_MAGIC_START[1]
+            || word(block, UF2_BLOCK_SIZE - 4) != UF2_MAGIC_END
+        {
+            return Err(invalid_data("Not a UF2 image, wrong block magic"));
+        }
+    }
+    Ok(())
+}
+
+// Places the drive gets mounted at by default
+fn candidate_drives() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        return (b'D'..=b'Z')
+            .map(|letter| PathBuf::from(format!("{}:\\", letter as char)))
+            .collect();
+    }
+    if cfg!(target_os = "macos") {
+        return vec![Path::new("/Volumes").join(DRIVE_NAME)];
+    }
+    let mut drives = Vec::new();
+    for root in ["/media", "/run/media"] {
+        // one folder per user
+        if let Ok(users) = fs::read_dir(root) {
+            drives.extend(users.flatten().map(|user| user.path().join(DRIVE_NAME)));
+        }
+        drives.push(Path::new(root).join(DRIVE_NAME));
+    }
+    drives
+}
+
+// Waits for the bootloader drive to get mounted
+pub fn find_bootloader_drive(timeout: Duration) -> Result<PathBuf> {
+    let start = Instant::now();
+    loop {
+        if let Some(drive) = candidate_drives()
+            .into_iter()
+            .find(|drive| drive.join(INFO_FILE).is_file())
+        {
+            return Ok(drive);
+        }
+        if start.elapsed() > timeout {
+            return Err(Error::IOError(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Bootloader drive didn't show up, it might have to be mounted by hand",
+            )));
+        }
+        sleep(Duration::from_millis(250));
+    }
+}
+
+// Progress gets the bytes written so far and the total, the device reboots on its own once done
+pub fn copy_uf2(image: &[u8], drive: &Path, mut progress: impl FnMut(u64, u64)) -> Result<()> {
+    validate_uf2(image)?;
+    let total = image.len() as u64;
+    let mut file = File::create(drive.join("firmware.uf2"))?;
+    let mut written = 0;
+    progress(0, total);
+    for chunk in image.chunks(COPY_CHUNK) {
+        file.write_all(chunk)?;
+        written += chunk.len() as u64;
+        progress(written, total);
+    }
+    file.flush()?;
+    // the drive tends to vanish before this finishes, the image is in by then
+    _ = file.sync_all();
+    Ok(())
+}
+
+// Whole update from a running device, the image is checked before anything happens
+pub fn update(
+    fakeldat: FakeLDAT,
+    image_path: &Path,
+    timeout: Duration,
+    progress: impl FnMut(u64, u64),
+) -> Result<()> {
+    let image = fs::read(image_path)?;
+    validate_uf2(&image)?;
+    fakeldat.enter_bootloader()?;
+    let drive = find_bootloader_drive(timeout)?;
+    info!(drive = %drive.display(), "copying firmware");
+    copy_uf2(&image, &drive, progress)
+}
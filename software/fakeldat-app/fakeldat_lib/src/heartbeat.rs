@@ -0,0 +1,145 @@
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use crate::log::{debug, info};
+use crate::{Command, Error, FakeLDAT, Report, Result};
+
+/// How often the device gets pinged while it's quiet and how many missed pongs mean trouble
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatPolicy {
+    pub interval: Duration,
+    pub degraded_after: u32,
+    pub lost_after: u32,
+}
+
+impl Default for HeartbeatPolicy {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            degraded_after: 1,
+            lost_after: 3,
+        }
+    }
+}
+
+/// Sent as [`Report::Connection`] whenever it changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ConnectionState {
+    Healthy,
+    // pongs are late, a busy USB bus or a loose cable
+    Degraded,
+    Lost,
+}
+
+impl ConnectionState {
+    pub const ALL: [Self; 3] = [Self::Healthy, Self::Degraded, Self::Lost];
+}
+
+impl Display for ConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Healthy => "Healthy",
+                Self::Degraded => "Degraded",
+                Self::Lost => "Lost",
+            }
+        )
+    }
+}
+
+pub(crate) struct Heartbeat {
+    policy: HeartbeatPolicy,
+    state: ConnectionState,
+    // last frame or ping, whichever came later
+    last_activity: Instant,
+    // a ping went out and no frame came back since
+    pending: bool,
+    missed: u32,
+}
+
+impl Heartbeat {
+    fn new(policy: HeartbeatPolicy) -> Self {
+        Self {
+            policy,
+            state: ConnectionState::Healthy,
+            last_activity: Instant::now(),
+            pending: false,
+            missed: 0,
+        }
+    }
+}
+
+impl FakeLDAT {
+    // Pings are only sent when nothing else arrived for the interval, None stops them
+    pub fn set_heartbeat(&mut self, policy: Option<HeartbeatPolicy>) -> Result<()> {
+        if policy.is_some() && !self.supports(Command::Ping, &[]) {
+            return Err(Error::Unsupported(Command::Ping));
+        }
+        self.heartbeat = policy.map(Heartbeat::new);
+        Ok(())
+    }
+
+    // Healthy without a heartbeat unless the disconnect was noticed
+    pub fn connection_state(&self) -> ConnectionState {
+        if !self.connected {
+            return ConnectionState::Lost;
+        }
+        self.heartbeat
+            .as_ref()
+            .map_or(ConnectionState::Healthy, |heartbeat| heartbeat.state)
+    }
+
+    // Called after every poll, any frame counts as a pong
+    pub(crate) fn check_heartbeat(&mut self, alive: bool) {
+        let Some(ref mut heartbeat) = self.heartbeat else {
+            return;
+        };
+        if alive {
+            heartbeat.last_activity = Instant::now();
+            heartbeat.pending = false;
+            heartbeat.missed = 0;
+            self.change_connection_state(ConnectionState::Healthy);
+            return;
+        }
+        if !self.connected {
+            self.change_connection_state(ConnectionState::Lost);
+            return;
+        }
+        if heartbeat.last_activity.elapsed() < heartbeat.policy.interval {
+            return;
+        }
+        if heartbeat.pending {
+            heartbeat.missed += 1;
+            debug!(missed = heartbeat.missed, "no pong");
+        }
+        heartbeat.last_activity = Instant::now();
+        heartbeat.pending = true;
+        let state = if heartbeat.missed >= heartbeat.policy.lost_after {
+            ConnectionState::Lost
+        } else if heartbeat.missed >= heartbeat.policy.degraded_after {
+            ConnectionState::Degraded
+        } else {
+            ConnectionState::Healthy
+        };
+        // a failing write is as good as a missing pong, reading will tell why
+        let state = match self.send_command(Command::Ping, [0, 0]) {
+            Ok(()) => state,
+            Err(_) => ConnectionState::Lost,
+        };
+        self.change_connection_state(state);
+    }
+
+    fn change_connection_state(&mut self, state: ConnectionState) {
+        let Some(ref mut heartbeat) = self.heartbeat else {
+            return;
+        };
+        if heartbeat.state == state {
+            return;
+        }
+        heartbeat.state = state;
+        info!(%state, "connection state changed");
+        self.push_report(Report::Connection(state));
+    }
+}
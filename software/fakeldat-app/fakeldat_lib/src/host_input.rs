@@ -0,0 +1,120 @@
+//! Fires the action from the host for setups where the device isn't connected inline as a HID,
+//! it only timestamps the light then. Press times come from the host clock through the clock sync
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use enigo::{Axis, Button, Direction, Enigo, Key, Keyboard, Mouse, Settings};
+
+use crate::log::debug;
+use crate::{ActionMode, Error, FakeLDAT, KeyboardKey, MouseButton, Result};
+
+// Exchanges of the clock sync done before the first press
+const CLOCK_SYNC_EXCHANGES: usize = 8;
+
+fn input_error(why: impl std::fmt::Display) -> Error {
+    Error::IOError(std::io::Error::other(why.to_string()))
+}
+
+const fn key(key: KeyboardKey) -> Key {
+    match key {
+        KeyboardKey::Enter => Key::Return,
+        KeyboardKey::Space => Key::Space,
+        KeyboardKey::ArrowRight => Key::RightArrow,
+        KeyboardKey::ArrowLeft => Key::LeftArrow,
+        KeyboardKey::ArrowDown => Key::DownArrow,
+        KeyboardKey::ArrowUp => Key::UpArrow,
+        KeyboardKey::F1 => Key::F1,
+        KeyboardKey::F2 => Key::F2,
+        KeyboardKey::F3 => Key::F3,
+        KeyboardKey::F4 => Key::F4,
+        KeyboardKey::F5 => Key::F5,
+        KeyboardKey::F6 => Key::F6,
+        KeyboardKey::F7 => Key::F7,
+        KeyboardKey::F8 => Key::F8,
+        KeyboardKey::F9 => Key::F9,
+        KeyboardKey::F10 => Key::F10,
+        KeyboardKey::F11 => Key::F11,
+        KeyboardKey::F12 => Key::F12,
+        // sides aren't told apart on every OS
+        KeyboardKey::LeftCtrl | KeyboardKey::RightCtrl => Key::Control,
+        KeyboardKey::LeftShift | KeyboardKey::RightShift => Key::Shift,
+        KeyboardKey::LeftAlt | KeyboardKey::RightAlt => Key::Alt,
+        KeyboardKey::LeftGui | KeyboardKey::RightGui => Key::Meta,
+        // letters and digits are their ASCII code
+        key => Key::Unicode(key as u8 as char),
+    }
+}
+
+/// Emulated input through the OS, replaces the HID output of the device
+pub struct HostInput {
+    enigo: Enigo,
+    // how long the press is held, like ActionTiming on the device
+    pub press_duration: Duration,
+}
+
+impl HostInput {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            enigo: Enigo::new(&Settings::default()).map_err(input_error)?,
+            press_duration: Duration::from_millis(50),
+        })
+    }
+
+    fn send(&mut self, action: ActionMode, direction: Direction) -> Result<()> {
+        match action {
+            // a single wheel step on press, nothing on release
+            ActionMode::Mouse(MouseButton::ScrollUp) if direction == Direction::Press => {
+                self.enigo.scroll(-1, Axis::Vertical)
+            }
+            ActionMode::Mouse(MouseButton::ScrollDown) if direction == Direction::Press => {
+                self.enigo.scroll(1, Axis::Vertical)
+            }
+            ActionMode::Mouse(MouseButton::ScrollUp | MouseButton::ScrollDown) => Ok(()),
+            ActionMode::Mouse(button) => self.enigo.button(
+                match button {
+                    MouseButton::Right => Button::Right,
+                    MouseButton::Middle => Button::Middle,
+                    MouseButton::Back => Button::Back,
+                    MouseButton::Forward => Button::Forward,
+                    // scrolling is handled above
+                    _ => Button::Left,
+                },
+                direction,
+            ),
+            ActionMode::Keyboard(keyboard_key) => self.enigo.key(key(keyboard_key), direction),
+            ActionMode::Gamepad(_) => {
+                return Err(Error::IOError(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "Gamepads can't be emulated from the host",
+                )))
+            }
+        }
+        .map_err(input_error)
+    }
+
+    // Blocks for the press duration, returns when the press was sent
+    pub fn fire(&mut self, action: ActionMode) -> Result<Instant> {
+        let pressed = Instant::now();
+        self.send(action, Direction::Press)?;
+        sleep(self.press_duration);
+        self.send(action, Direction::Release)?;
+        Ok(pressed)
+    }
+}
+
+impl FakeLDAT {
+    /// Fires the action through the OS and returns when it happened in device microseconds,
+    /// for [`crate::analysis::LatencyDetector::trigger_at`] in raw mode
+    ///
+    /// The clock gets synced first if it wasn't yet, sync again now and then on long runs
+    pub fn host_trigger(&mut self, input: &mut HostInput, action: ActionMode) -> Result<u64> {
+        let clock = match self.clock {
+            Some(ref clock) => clock.clone(),
+            None => self.sync_clock(CLOCK_SYNC_EXCHANGES, Duration::from_secs(1))?,
+        };
+        let pressed = input.fire(action)?;
+        let timestamp = clock.to_device(pressed);
+        debug!(timestamp, "host trigger");
+        Ok(timestamp)
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+use crate::FakeLDAT;
+
+// Read buffer of the Linux tty layer and the default one on Windows
+pub const SERIAL_INPUT_BUFFER_SIZE: u32 = 4096;
+// Past this much the OS starts dropping bytes before the next poll
+const NEAR_FULL: f64 = 0.75;
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Throughput over the last second and how far behind the polling is
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IoStats {
+    pub bytes_per_second: f64,
+    pub reports_per_second: f64,
+    // bytes waiting in the OS buffer when the last poll started
+    pub input_buffered: u32,
+    // None before the first report
+    pub since_last_report: Option<Duration>,
+}
+
+impl IoStats {
+    pub fn input_fill(&self) -> f64 {
+        f64::from(self.input_buffered) / f64::from(SERIAL_INPUT_BUFFER_SIZE)
+    }
+
+    // Polling can't keep up, frames are about to get lost in the OS
+    pub fn is_saturated(&self) -> bool {
+        self.input_fill() >= NEAR_FULL
+    }
+}
+
+pub(crate) struct IoMeter {
+    window_start: Instant,
+    bytes: u64,
+    reports: u64,
+    // of the last full window
+    bytes_per_second: f64,
+    reports_per_second: f64,
+    input_buffered: u32,
+    last_report: Option<Instant>,
+}
+
+impl IoMeter {
+    pub(crate) fn new() -> Self {
+        Self {
+            window_start: Instant::now(),
+            bytes: 0,
+            reports: 0,
+            bytes_per_second: 0.0,
+            reports_per_second: 0.0,
+            input_buffered: 0,
+            last_report: None,
+        }
+    }
+
+    pub(crate) fn count_report(&mut self) {
+        self.reports += 1;
+        self.last_report = Some(Instant::now());
+    }
+
+    // Called once per poll with what it read
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn count_poll(&mut self, input_buffered: u32, bytes: u64) {
+        self.input_buffered = input_buffered;
+        self.bytes += bytes;
+        let elapsed = self.window_start.elapsed();
+        if elapsed < RATE_WINDOW {
+            return;
+        }
+        self.bytes_per_second = self.bytes as f64 / elapsed.as_secs_f64();
+        self.reports_per_second = self.reports as f64 / elapsed.as_secs_f64();
+        self.window_start = Instant::now();
+        self.bytes = 0;
+        self.reports = 0;
+    }
+}
+
+impl FakeLDAT {
+    // Updated by poll_bulk_data, rates only change once a second
+    pub fn io_stats(&self) -> IoStats {
+        IoStats {
+            bytes_per_second: self.io.bytes_per_second,
+            reports_per_second: self.io.reports_per_second,
+            input_buffered: self.io.input_buffered,
+            since_last_report: self.io.last_report.map(|last| last.elapsed()),
+        }
+    }
+}
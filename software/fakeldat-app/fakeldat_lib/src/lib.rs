@@ -1,15 +1,87 @@
-use std::{fmt::Display, mem::take};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Display,
+    thread::sleep,
+    time::{Duration, Instant},
+};
 
+use rand::Rng;
 pub use serialport;
 use serialport::SerialPort;
 use std::io::Read;
 
+mod ack;
+mod aggregation;
+pub mod analysis;
+mod arming;
+mod batch;
+mod builder;
+mod calibration;
+mod callback;
+mod capabilities;
+mod clock;
+mod filter;
+pub mod firmware;
+mod heartbeat;
+#[cfg(feature = "host_input")]
+mod host_input;
+mod io_stats;
+mod link;
+mod log;
+mod profile;
+pub mod protocol;
+mod reconnect;
+mod recorder;
+mod replay;
+mod report_buffer;
+pub mod schema;
+mod self_test;
+mod session;
+mod settings;
+mod shared_ring;
+mod shutdown;
+mod sink;
+mod stats;
+mod telemetry;
+mod warning;
+pub use ack::AckPolicy;
+pub use aggregation::SummaryAggregate;
+pub use builder::FakeLDATBuilder;
+pub use calibration::Calibration;
+pub use callback::RawFrame;
+pub use capabilities::{Capabilities, Hello, PROTOCOL_VERSION};
+pub use clock::ClockSync;
+pub use filter::ReportFilter;
+pub use heartbeat::{ConnectionState, HeartbeatPolicy};
+#[cfg(feature = "host_input")]
+pub use host_input::HostInput;
+pub use io_stats::{IoStats, SERIAL_INPUT_BUFFER_SIZE};
+pub use link::{LinkInfo, LinkStats, PollOutcome};
+use log::{debug, info, trace, warning};
+pub use profile::Profile;
+pub use recorder::{RecordFormat, Recorder, Rotation};
+pub use replay::{ReplayDevice, ReplaySpeed};
+use report_buffer::ReportBuffer;
+pub use report_buffer::{OverflowPolicy, DEFAULT_REPORT_BUFFER_CAPACITY};
+pub use self_test::SelfTest;
+pub use session::SessionMetadata;
+pub use shared_ring::{
+    SharedRing, SHARED_RING_HEADER_SIZE, SHARED_RING_MAGIC, SHARED_RING_SLOT_SIZE,
+    SHARED_RING_VERSION,
+};
+pub use sink::{ReportSink, SinkId};
+pub use stats::{Stats, StatsSummary};
+pub use telemetry::Telemetry;
+pub use warning::Warning;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
+const MAX_FRAME_SIZE: usize = FrameSize::Extended as usize;
+
 #[derive(Debug)]
 pub enum Error {
     // command with the error, expected checksum, calculated checksum
-    WrongChecksum(Command, u8, u8),
+    WrongChecksum(Command, u16, u16),
     // command and the invalid settings
     InvalidSetting(Command, [u8; 2]),
     // value of the command received
@@ -19,6 +91,12 @@ pub enum Error {
     SendCommandFail,
     IOError(std::io::Error),
     InvalidEnumConverion,
+    // command which didn't get a response in time
+    CommandTimeout(Command),
+    // with OverflowPolicy::Error, reports were discarded since the last poll
+    ReportBufferFull,
+    // the firmware said in its hello that it doesn't know the command or this value
+    Unsupported(Command),
 }
 
 impl From<serialport::Error> for Error {
@@ -67,13 +145,107 @@ create_try_from! {
         GetThreshold = 0x23,
         SetAction = 0x04,
         GetAction = 0x24,
+        SetIntegrity = 0x05,
+        GetIntegrity = 0x25,
+        SetSequence = 0x06,
+        GetSequence = 0x26,
+        SetRawBatching = 0x07,
+        GetRawBatching = 0x27,
+        SetBaudRate = 0x08,
+        GetBaudRate = 0x28,
+        GetClock = 0x29,
+        SetActionTiming = 0x0A,
+        GetActionTiming = 0x2A,
+        SetAutoTrigger = 0x0B,
+        GetAutoTrigger = 0x2B,
+        SetDetectionMode = 0x0C,
+        GetDetectionMode = 0x2C,
+        SetSensorGain = 0x0D,
+        GetSensorGain = 0x2D,
+        SetChannels = 0x0E,
+        GetChannels = 0x2E,
+        SetTelemetryInterval = 0x0F,
+        GetTelemetryInterval = 0x2F,
+        SetFrameSize = 0x12,
+        GetFrameSize = 0x32,
+        // one report per so many summary measurements
+        SetSummaryAggregation = 0x15,
+        GetSummaryAggregation = 0x35,
+        AutoTrigger = 0x1D,
         MacroTrigger = 0x1E,
         ManualTrigger = 0x1F,
+        // protocol version and capabilities, older firmware doesn't answer
+        Hello = 0x30,
+        // echoed back, only sent while the device is quiet
+        Ping = 0x31,
+        // measurement windows, summaries outside of them aren't measured once armed
+        Arm = 0x33,
+        Disarm = 0x34,
+        // synthetic brightness step through the detection, answered with the delay found
+        SelfTest = 0x36,
         ReportRaw = 0x41,
         ReportSummary = 0x42,
+        ReportRawBatch = 0x43,
+        ReportRawAudio = 0x44,
+        ReportAudioVideo = 0x45,
+        ReportTelemetry = 0x46,
+        ReportSummaryAggregate = 0x47,
     }
 }
 
+impl Command {
+    pub const ALL: [Self; 48] = [
+        Self::SetPollRate,
+        Self::GetPollRate,
+        Self::SetReportMode,
+        Self::GetReportMode,
+        Self::SetThreshold,
+        Self::GetThreshold,
+        Self::SetAction,
+        Self::GetAction,
+        Self::SetIntegrity,
+        Self::GetIntegrity,
+        Self::SetSequence,
+        Self::GetSequence,
+        Self::SetRawBatching,
+        Self::GetRawBatching,
+        Self::SetBaudRate,
+        Self::GetBaudRate,
+        Self::GetClock,
+        Self::SetActionTiming,
+        Self::GetActionTiming,
+        Self::SetAutoTrigger,
+        Self::GetAutoTrigger,
+        Self::SetDetectionMode,
+        Self::GetDetectionMode,
+        Self::SetSensorGain,
+        Self::GetSensorGain,
+        Self::SetChannels,
+        Self::GetChannels,
+        Self::SetTelemetryInterval,
+        Self::GetTelemetryInterval,
+        Self::SetFrameSize,
+        Self::GetFrameSize,
+        Self::SetSummaryAggregation,
+        Self::GetSummaryAggregation,
+        Self::AutoTrigger,
+        Self::MacroTrigger,
+        Self::ManualTrigger,
+        Self::Hello,
+        Self::Ping,
+        Self::Arm,
+        Self::Disarm,
+        Self::SelfTest,
+        Self::ReportRaw,
+        Self::ReportSummary,
+        Self::ReportRawBatch,
+        Self::ReportRawAudio,
+        Self::ReportAudioVideo,
+        Self::ReportTelemetry,
+        Self::ReportSummaryAggregate,
+    ];
+}
+
 impl std::fmt::Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -82,6 +254,11 @@ impl std::fmt::Display for Command {
             match self {
                 Self::ReportRaw => "Raw",
                 Self::ReportSummary => "Summary",
+                Self::ReportRawBatch => "Raw batch",
+                Self::ReportRawAudio => "Raw audio",
+                Self::ReportAudioVideo => "Audio/video summary",
+                Self::ReportTelemetry => "Telemetry",
+                Self::ReportSummaryAggregate => "Summary aggregate",
                 Self::SetPollRate => "Set poll rate",
                 Self::GetPollRate => "Get poll rate",
                 Self::SetReportMode => "Set report mode",
@@ -90,22 +267,67 @@ impl std::fmt::Display for Command {
                 Self::GetThreshold => "Get threshold",
                 Self::SetAction => "Set action",
                 Self::GetAction => "Get action",
+                Self::SetIntegrity => "Set integrity",
+                Self::GetIntegrity => "Get integrity",
+                Self::SetSequence => "Set sequence",
+                Self::GetSequence => "Get sequence",
+                Self::SetRawBatching => "Set raw batching",
+                Self::GetRawBatching => "Get raw batching",
+                Self::SetBaudRate => "Set baud rate",
+                Self::GetBaudRate => "Get baud rate",
+                Self::GetClock => "Get clock",
+                Self::SetActionTiming => "Set action timing",
+                Self::GetActionTiming => "Get action timing",
+                Self::SetAutoTrigger => "Set auto trigger",
+                Self::GetAutoTrigger => "Get auto trigger",
+                Self::SetDetectionMode => "Set detection mode",
+                Self::GetDetectionMode => "Get detection mode",
+                Self::SetSensorGain => "Set sensor gain",
+                Self::GetSensorGain => "Get sensor gain",
+                Self::SetChannels => "Set channels",
+                Self::GetChannels => "Get channels",
+                Self::SetTelemetryInterval => "Set telemetry interval",
+                Self::GetTelemetryInterval => "Get telemetry interval",
+                Self::SetFrameSize => "Set frame size",
+                Self::GetFrameSize => "Get frame size",
+                Self::SetSummaryAggregation => "Set summary aggregation",
+                Self::GetSummaryAggregation => "Get summary aggregation",
+                Self::AutoTrigger => "Auto trigger",
                 Self::MacroTrigger => "Macro trigger",
                 Self::ManualTrigger => "Manual trigger",
+                Self::Hello => "Hello",
+                Self::Ping => "Ping",
+                Self::Arm => "Arm",
+                Self::Disarm => "Disarm",
+                Self::SelfTest => "Self-test",
             }
         )
     }
 }
 
 create_try_from! {
-    #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd)]
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
     pub enum ReportMode {
         Raw,
         Summary,
         Combined,
+        // audio sensor samples only
+        RawAudio,
+        // delays of both the screen and the audio click after each trigger
+        AudioVideo,
     }
 }
 
+impl ReportMode {
+    pub const ALL: [Self; 5] = [
+        Self::Raw,
+        Self::Summary,
+        Self::Combined,
+        Self::RawAudio,
+        Self::AudioVideo,
+    ];
+}
+
 impl std::fmt::Display for ReportMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -115,14 +337,100 @@ impl std::fmt::Display for ReportMode {
                 Self::Raw => "Raw",
                 Self::Summary => "Summary",
                 Self::Combined => "Combined",
+                Self::RawAudio => "Raw audio",
+                Self::AudioVideo => "Audio/video",
             }
         )
     }
 }
 
 create_try_from! {
+    // How frames are protected, firmware without negotiation support only knows Sum
     #[repr(u8)]
-    #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd)]
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+    pub enum Integrity {
+        #[default]
+        Sum = 0,
+        Crc8 = 1,
+        Crc16 = 2,
+    }
+}
+
+impl Integrity {
+    pub const ALL: [Self; 3] = [Self::Sum, Self::Crc8, Self::Crc16];
+
+    // Checksum of a frame of any size, it's always at the end
+    // CRC-16 takes the byte before it too, where the sequence counter would go
+    fn checksum(self, buf: &[u8]) -> u16 {
+        let last = buf.len() - 1;
+        match self {
+            Self::Sum => u16::from(sum_slice(&buf[..last])),
+            Self::Crc8 => u16::from(crc8(&buf[..last])),
+            Self::Crc16 => crc16(&buf[..last - 1]),
+        }
+    }
+
+    fn received_checksum(self, buf: &[u8]) -> u16 {
+        let last = buf.len() - 1;
+        match self {
+            Self::Sum | Self::Crc8 => u16::from(buf[last]),
+            Self::Crc16 => u16::from_le_bytes([buf[last - 1], buf[last]]),
+        }
+    }
+
+    fn seal(self, buf: &mut [u8]) {
+        let last = buf.len() - 1;
+        let checksum = self.checksum(buf).to_le_bytes();
+        match self {
+            Self::Sum | Self::Crc8 => buf[last] = checksum[0],
+            Self::Crc16 => buf[last - 1..].copy_from_slice(&checksum),
+        }
+    }
+}
+
+impl Display for Integrity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Sum => "Sum",
+                Self::Crc8 => "CRC-8",
+                Self::Crc16 => "CRC-16",
+            }
+        )
+    }
+}
+
+create_try_from! {
+    // Bytes per frame in both directions, the extra room of extended frames goes between byte 13
+    // and the sequence counter and checksum, which stay at the end
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+    pub enum FrameSize {
+        #[default]
+        Standard = 16,
+        Extended = 32,
+    }
+}
+
+impl FrameSize {
+    pub const ALL: [Self; 2] = [Self::Standard, Self::Extended];
+
+    pub const fn bytes(self) -> usize {
+        self as usize
+    }
+}
+
+impl Display for FrameSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} bytes", self.bytes())
+    }
+}
+
+create_try_from! {
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
     pub enum KeyboardKey {
         A = b'a',
         B = b'b',
@@ -150,11 +458,48 @@ create_try_from! {
         X = b'x',
         Y = b'y',
         Z = b'z',
+        Num0 = b'0',
+        Num1 = b'1',
+        Num2 = b'2',
+        Num3 = b'3',
+        Num4 = b'4',
+        Num5 = b'5',
+        Num6 = b'6',
+        Num7 = b'7',
+        Num8 = b'8',
+        Num9 = b'9',
+        Space = b' ',
+        // codes above 0x7F follow the Arduino Keyboard library
+        Enter = 0xB0,
+        ArrowRight = 0xD7,
+        ArrowLeft = 0xD8,
+        ArrowDown = 0xD9,
+        ArrowUp = 0xDA,
+        F1 = 0xC2,
+        F2 = 0xC3,
+        F3 = 0xC4,
+        F4 = 0xC5,
+        F5 = 0xC6,
+        F6 = 0xC7,
+        F7 = 0xC8,
+        F8 = 0xC9,
+        F9 = 0xCA,
+        F10 = 0xCB,
+        F11 = 0xCC,
+        F12 = 0xCD,
+        LeftCtrl = 0x80,
+        LeftShift = 0x81,
+        LeftAlt = 0x82,
+        LeftGui = 0x83,
+        RightCtrl = 0x84,
+        RightShift = 0x85,
+        RightAlt = 0x86,
+        RightGui = 0x87,
     }
 }
 
 impl KeyboardKey {
-    pub const ALL: [Self; 26] = [
+    pub const ALL: [Self; 62] = [
         Self::A,
         Self::B,
         Self::C,
@@ -181,27 +526,105 @@ impl KeyboardKey {
         Self::X,
         Self::Y,
         Self::Z,
+        Self::Num0,
+        Self::Num1,
+        Self::Num2,
+        Self::Num3,
+        Self::Num4,
+        Self::Num5,
+        Self::Num6,
+        Self::Num7,
+        Self::Num8,
+        Self::Num9,
+        Self::Space,
+        Self::Enter,
+        Self::ArrowUp,
+        Self::ArrowDown,
+        Self::ArrowLeft,
+        Self::ArrowRight,
+        Self::F1,
+        Self::F2,
+        Self::F3,
+        Self::F4,
+        Self::F5,
+        Self::F6,
+        Self::F7,
+        Self::F8,
+        Self::F9,
+        Self::F10,
+        Self::F11,
+        Self::F12,
+        Self::LeftCtrl,
+        Self::LeftShift,
+        Self::LeftAlt,
+        Self::LeftGui,
+        Self::RightCtrl,
+        Self::RightShift,
+        Self::RightAlt,
+        Self::RightGui,
     ];
 }
 
 impl Display for KeyboardKey {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", (*self as u8 as char).to_uppercase())
+        match self {
+            Self::Space => write!(f, "Space"),
+            Self::Enter => write!(f, "Enter"),
+            Self::ArrowUp => write!(f, "Up"),
+            Self::ArrowDown => write!(f, "Down"),
+            Self::ArrowLeft => write!(f, "Left"),
+            Self::ArrowRight => write!(f, "Right"),
+            Self::LeftCtrl => write!(f, "Left Ctrl"),
+            Self::LeftShift => write!(f, "Left Shift"),
+            Self::LeftAlt => write!(f, "Left Alt"),
+            Self::LeftGui => write!(f, "Left GUI"),
+            Self::RightCtrl => write!(f, "Right Ctrl"),
+            Self::RightShift => write!(f, "Right Shift"),
+            Self::RightAlt => write!(f, "Right Alt"),
+            Self::RightGui => write!(f, "Right GUI"),
+            Self::F1 => write!(f, "F1"),
+            Self::F2 => write!(f, "F2"),
+            Self::F3 => write!(f, "F3"),
+            Self::F4 => write!(f, "F4"),
+            Self::F5 => write!(f, "F5"),
+            Self::F6 => write!(f, "F6"),
+            Self::F7 => write!(f, "F7"),
+            Self::F8 => write!(f, "F8"),
+            Self::F9 => write!(f, "F9"),
+            Self::F10 => write!(f, "F10"),
+            Self::F11 => write!(f, "F11"),
+            Self::F12 => write!(f, "F12"),
+            // letters and digits
+            _ => write!(f, "{}", (*self as u8 as char).to_uppercase()),
+        }
     }
 }
 
 create_try_from! {
     #[repr(u8)]
-    #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd)]
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
     pub enum MouseButton {
         Left = 1,
         Right = 2,
         Middle = 4,
+        Back = 8,
+        Forward = 16,
+        // a single wheel step on press, nothing on release
+        ScrollUp = 32,
+        ScrollDown = 64,
     }
 }
 
 impl MouseButton {
-    pub const ALL: [Self; 3] = [Self::Left, Self::Right, Self::Middle];
+    pub const ALL: [Self; 7] = [
+        Self::Left,
+        Self::Right,
+        Self::Middle,
+        Self::Back,
+        Self::Forward,
+        Self::ScrollUp,
+        Self::ScrollDown,
+    ];
 }
 
 impl std::fmt::Display for MouseButton {
@@ -213,15 +636,74 @@ impl std::fmt::Display for MouseButton {
                 Self::Left => "Left",
                 Self::Right => "Right",
                 Self::Middle => "Middle",
+                Self::Back => "Back",
+                Self::Forward => "Forward",
+                Self::ScrollUp => "Scroll up",
+                Self::ScrollDown => "Scroll down",
             }
         )
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd)]
+create_try_from! {
+    // Button numbers of the HID gamepad, triggers are reported as buttons
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+    pub enum GamepadButton {
+        A = 1,
+        B = 2,
+        X = 3,
+        Y = 4,
+        LeftBumper = 5,
+        RightBumper = 6,
+        LeftTrigger = 7,
+        RightTrigger = 8,
+        Back = 9,
+        Start = 10,
+    }
+}
+
+impl GamepadButton {
+    pub const ALL: [Self; 10] = [
+        Self::A,
+        Self::B,
+        Self::X,
+        Self::Y,
+        Self::LeftBumper,
+        Self::RightBumper,
+        Self::LeftTrigger,
+        Self::RightTrigger,
+        Self::Back,
+        Self::Start,
+    ];
+}
+
+impl std::fmt::Display for GamepadButton {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::A => "A",
+                Self::B => "B",
+                Self::X => "X",
+                Self::Y => "Y",
+                Self::LeftBumper => "Left bumper",
+                Self::RightBumper => "Right bumper",
+                Self::LeftTrigger => "Left trigger",
+                Self::RightTrigger => "Right trigger",
+                Self::Back => "Back",
+                Self::Start => "Start",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
 pub enum ActionMode {
     Mouse(MouseButton),
     Keyboard(KeyboardKey),
+    Gamepad(GamepadButton),
 }
 
 impl ActionMode {
@@ -229,6 +711,7 @@ impl ActionMode {
         match self {
             Self::Mouse(button) => button as u8,
             Self::Keyboard(key) => key as u8,
+            Self::Gamepad(button) => button as u8,
         }
     }
 
@@ -236,6 +719,7 @@ impl ActionMode {
         match mode {
             0 => Ok(Self::Mouse(MouseButton::try_from(key)?)),
             1 => Ok(Self::Keyboard(KeyboardKey::try_from(key)?)),
+            2 => Ok(Self::Gamepad(GamepadButton::try_from(key)?)),
             _ => Err(Error::InvalidSetting(Command::SetAction, [mode, key])),
         }
     }
@@ -246,129 +730,752 @@ impl From<ActionMode> for u8 {
         match value {
             ActionMode::Mouse(_) => 0,
             ActionMode::Keyboard(_) => 1,
+            ActionMode::Gamepad(_) => 2,
         }
     }
 }
 
+/// How long the manual trigger holds the action
+#[derive(Debug, Clone, Copy, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ActionTiming {
+    // milliseconds, up to 32767
+    pub press_duration: u16,
+    // second press after a pause as long as the press
+    pub double_press: bool,
+}
+
+impl Default for ActionTiming {
+    fn default() -> Self {
+        Self {
+            press_duration: 50,
+            double_press: false,
+        }
+    }
+}
+
+impl ActionTiming {
+    pub const MAX_PRESS_DURATION: u16 = 0x7FFF;
+
+    // Duration in the low 15 bits, double press in the highest one
+    const fn to_args(self) -> [u8; 2] {
+        let value = self.press_duration | (self.double_press as u16) << 15;
+        value.to_le_bytes()
+    }
+
+    const fn from_args(args: [u8; 2]) -> Self {
+        let value = u16::from_le_bytes(args);
+        Self {
+            press_duration: value & Self::MAX_PRESS_DURATION,
+            double_press: value >> 15 == 1,
+        }
+    }
+}
+
+/// Action fired by the device itself at random intervals
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AutoTrigger {
+    // milliseconds
+    pub min_interval: u16,
+    pub max_interval: u16,
+    // presses left, 0 when stopped
+    pub remaining: u16,
+}
+
+impl AutoTrigger {
+    // Bytes 1 to 6
+    fn to_payload(self) -> [u8; 6] {
+        let mut payload = [0; 6];
+        payload[0..2].copy_from_slice(&self.min_interval.to_le_bytes());
+        payload[2..4].copy_from_slice(&self.max_interval.to_le_bytes());
+        payload[4..6].copy_from_slice(&self.remaining.to_le_bytes());
+        payload
+    }
+
+    fn from_frame(buf: &[u8; 16]) -> Self {
+        Self {
+            min_interval: u16::from_le_bytes([buf[1], buf[2]]),
+            max_interval: u16::from_le_bytes([buf[3], buf[4]]),
+            remaining: u16::from_le_bytes([buf[5], buf[6]]),
+        }
+    }
+}
+
+create_try_from! {
+    // Brightness change which ends the measurement, Auto follows the sign of the threshold
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+    pub enum Edge {
+        #[default]
+        Auto = 0,
+        Rising = 1,
+        Falling = 2,
+    }
+}
+
+impl Edge {
+    pub const ALL: [Self; 3] = [Self::Auto, Self::Rising, Self::Falling];
+}
+
+impl Display for Edge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Auto => "Auto",
+                Self::Rising => "Dark to light",
+                Self::Falling => "Light to dark",
+            }
+        )
+    }
+}
+
+create_try_from! {
+    // What the threshold is compared against
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+    pub enum Baseline {
+        // threshold added to the average of the recent samples
+        #[default]
+        Relative = 0,
+        // threshold used as the brightness level itself
+        Absolute = 1,
+    }
+}
+
+impl Baseline {
+    pub const ALL: [Self; 2] = [Self::Relative, Self::Absolute];
+}
+
+impl Display for Baseline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Relative => "Relative",
+                Self::Absolute => "Absolute",
+            }
+        )
+    }
+}
+
+create_try_from! {
+    // Readings summed per sample, helps with dark content, the top of the range can't be extended
+    // Each reading takes about 2 µs which limits the highest poll rates
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+    pub enum SensorGain {
+        #[default]
+        X1 = 1,
+        X2 = 2,
+        X4 = 4,
+        X8 = 8,
+        X16 = 16,
+    }
+}
+
+impl SensorGain {
+    pub const ALL: [Self; 5] = [Self::X1, Self::X2, Self::X4, Self::X8, Self::X16];
+}
+
+impl Display for SensorGain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "x{}", *self as u8)
+    }
+}
+
+/// How the summary mode decides the screen reacted
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DetectionMode {
+    pub edge: Edge,
+    pub baseline: Baseline,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum Report {
     Raw(RawReport),
     Summary(SummaryReport),
+    SummaryAggregate(SummaryAggregate),
+    RawAudio(RawAudioReport),
+    AudioVideo(AudioVideoReport),
+    Telemetry(Telemetry),
     PollRate(u16),
     ReportMode(ReportMode),
     Threshold(i16),
     Action(ActionMode), // action and key
+    ActionTiming(ActionTiming),
+    AutoTriggerSettings(AutoTrigger),
+    DetectionMode(DetectionMode),
+    SensorGain(SensorGain),
+    // Bit n set when sensor channel n is streamed
+    Channels(u8),
+    // Seconds between telemetry reports, 0 when only sent on request
+    TelemetryInterval(u16),
+    // Measurements per aggregate, 0 or 1 when every one is reported
+    SummaryAggregation(u8),
+    // Device timestamp of an automatic press
+    AutoTrigger(u64),
     MacroTrigger(u64),
     ManualTrigger,
+    Integrity(Integrity),
+    Sequence(bool),
+    RawBatching(bool),
+    BaudRate(u32),
+    FrameSize(FrameSize),
+    // Device microsecond counter when the request got handled
+    Clock(u64),
+    Hello(Hello),
+    SelfTest(SelfTest),
+    // Echo of FakeLDAT::arm with its id
+    Armed(u16),
+    Disarmed,
+    // Frames lost between the last two received ones
+    Dropped { missed: u32 },
+    // Raw samples further apart than the poll rate allows, comes before the late sample
+    GapDetected { expected_us: u64, actual_us: u64 },
+    // Connection got restored and the last known settings were applied again
+    Reconnected,
+    // Answer to a heartbeat ping, never buffered
+    Pong,
+    Connection(ConnectionState),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct RawReport {
     pub timestamp: u64,
     pub brightness: u16,
     pub audio: u16,
     pub trigger: bool,
+    // photodiode the brightness comes from, 0 on single sensor devices
+    #[serde(default)]
+    pub channel: u8,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct SummaryReport {
     pub delay: u64,
     pub threshold: u16,
+    // window of FakeLDAT::arm it was measured in
+    #[serde(default)]
+    pub arm_id: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RawAudioReport {
+    // of the first sample, the following ones are a poll interval apart
+    pub timestamp: u64,
+    pub amplitudes: Vec<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AudioVideoReport {
+    // missing when nothing was detected within a second of the trigger
+    pub video_delay: Option<u64>,
+    pub audio_delay: Option<u64>,
+    // brightness level the screen got compared to
+    pub threshold: u16,
+}
+
+// Rows as printed by the CLI, the parsers in analysis read them back
+impl RawReport {
+    // Only other channels get the extra column, analyze skips those rows then
+    pub fn to_csv_row(&self) -> String {
+        if self.channel == 0 {
+            format!("{}, {}, {}", self.timestamp, self.brightness, self.trigger)
+        } else {
+            format!(
+                "{}, {}, {}, {}",
+                self.timestamp, self.brightness, self.trigger, self.channel
+            )
+        }
+    }
+}
+
+impl SummaryReport {
+    pub fn to_csv_row(&self) -> String {
+        format!("{}, {}", self.delay, self.threshold)
+    }
+}
+
+impl RawAudioReport {
+    // Samples follow each other a poll interval apart
+    pub fn to_csv_row(&self) -> String {
+        let amplitudes: Vec<String> = self.amplitudes.iter().map(ToString::to_string).collect();
+        format!("{}, {}", self.timestamp, amplitudes.join(", "))
+    }
+}
+
+impl AudioVideoReport {
+    // Delays are empty when not detected
+    pub fn to_csv_row(&self) -> String {
+        let delay = |delay: Option<u64>| delay.map_or_else(String::new, |delay| delay.to_string());
+        format!(
+            "{}, {}, {}",
+            delay(self.video_delay),
+            delay(self.audio_delay),
+            self.threshold
+        )
+    }
+}
+
+/// Summary reports collected by [`FakeLDAT::run_trigger_sequence`]
+#[derive(Debug, Clone, Default)]
+pub struct SequenceResult {
+    pub reports: Vec<SummaryReport>,
+    // triggers which didn't result in a summary report
+    pub missed: usize,
 }
 
 pub fn sum_slice(data: &[u8]) -> u8 {
     data.iter().fold(0, |acc, &x| acc.wrapping_add(x))
 }
 
+// CRC-8/SMBUS
+pub fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, &x| {
+        (0..8).fold(crc ^ x, |crc, _| {
+            if crc & 0x80 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x07
+            }
+        })
+    })
+}
+
+// CRC-16/CCITT-FALSE
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, &x| {
+        (0..8).fold(crc ^ (u16::from(x) << 8), |crc, _| {
+            if crc & 0x8000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x1021
+            }
+        })
+    })
+}
+
+// Last settings reported by the device
+#[derive(Default, Clone, Copy)]
+struct KnownSettings {
+    poll_rate: Option<u16>,
+    report_mode: Option<ReportMode>,
+    threshold: Option<i16>,
+    action: Option<ActionMode>,
+    action_timing: Option<ActionTiming>,
+    raw_batching: Option<bool>,
+    auto_trigger: Option<AutoTrigger>,
+    detection_mode: Option<DetectionMode>,
+    sensor_gain: Option<SensorGain>,
+    channels: Option<u8>,
+    telemetry_interval: Option<u16>,
+    summary_aggregation: Option<u8>,
+}
+
 pub struct FakeLDAT {
-    report_buffer: Option<Vec<Report>>,
+    report_buffer: ReportBuffer,
     read: Box<dyn SerialPort>,
     port: Box<dyn SerialPort>,
+    connected: bool,
+    reconnect: Option<reconnect::Reconnect>,
+    settings: KnownSettings,
+    link: LinkInfo,
+    link_stats: LinkStats,
+    io: io_stats::IoMeter,
+    last_sequence: Option<u8>,
+    // batched samples are relative to this
+    last_raw_timestamp: Option<u64>,
+    // device timestamp of the first sample stuck at the end of the range
+    saturated_since: Option<u64>,
+    // off unless asked for, previous channel 0 timestamp while on
+    gap_detection: bool,
+    last_gap_timestamp: Option<u64>,
+    clock: Option<ClockSync>,
+    shared_ring: Option<SharedRing>,
+    report_callback: Option<callback::ReportCallback>,
+    frame_callback: Option<callback::FrameCallback>,
+    telemetry: Option<Telemetry>,
+    report_filter: ReportFilter,
+    warnings: VecDeque<Warning>,
+    // last payload of every set command still waiting for its answer, set ids are all below 0x20
+    sent_settings: [Option<[u8; 2]>; 0x20],
+    ack: Option<AckPolicy>,
+    hello: Option<Hello>,
+    heartbeat: Option<heartbeat::Heartbeat>,
+    next_arm_id: u16,
+    armed: Option<u16>,
+    sinks: Vec<(SinkId, Box<dyn ReportSink>)>,
+    next_sink_id: u32,
 }
 
 impl FakeLDAT {
+    pub fn builder() -> FakeLDATBuilder {
+        FakeLDATBuilder::new()
+    }
     pub fn create(mut port: Box<dyn SerialPort>) -> Result<Self> {
-        // TODO: create port here given some unique characteristic
         port.write_data_terminal_ready(true)?;
+        Self::from_port(port)
+    }
+    fn from_port(port: Box<dyn SerialPort>) -> Result<Self> {
+        let link = LinkInfo {
+            baud_rate: port.baud_rate()?,
+            ..LinkInfo::default()
+        };
         Ok(Self {
-            report_buffer: Some(Vec::new()),
+            report_buffer: ReportBuffer::new(
+                DEFAULT_REPORT_BUFFER_CAPACITY,
+                OverflowPolicy::default(),
+            ),
             read: port.try_clone()?,
             port,
+            connected: true,
+            reconnect: None,
+            settings: KnownSettings::default(),
+            link,
+            link_stats: LinkStats::default(),
+            io: io_stats::IoMeter::new(),
+            last_sequence: None,
+            last_raw_timestamp: None,
+            saturated_since: None,
+            gap_detection: false,
+            last_gap_timestamp: None,
+            clock: None,
+            shared_ring: None,
+            report_callback: None,
+            frame_callback: None,
+            telemetry: None,
+            report_filter: ReportFilter::ALL,
+            warnings: VecDeque::new(),
+            sent_settings: [None; 0x20],
+            ack: Some(AckPolicy::default()),
+            hello: None,
+            heartbeat: None,
+            next_arm_id: 0,
+            armed: None,
+            sinks: Vec::new(),
+            next_sink_id: 0,
         })
     }
-    fn send_command<T: std::io::Write>(
-        command: Command,
-        args: [u8; 2],
-        port: &mut T,
-    ) -> Result<()> {
-        let mut buf = [0; 16];
+
+    // Usually set up with FakeLDATBuilder::auto_reconnect
+    pub fn enable_reconnect(&mut self, builder: FakeLDATBuilder, interval: Duration) {
+        self.reconnect = Some(reconnect::Reconnect::new(builder, interval));
+    }
+
+    pub const fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    fn send_command(&mut self, command: Command, args: [u8; 2]) -> Result<()> {
+        self.send_payload(command, &args)
+    }
+
+    // Payload starts at byte 1, up to byte 13 so it fits any integrity mode
+    fn send_payload(&mut self, command: Command, payload: &[u8]) -> Result<()> {
+        if !self.supports(command, payload) {
+            return Err(Error::Unsupported(command));
+        }
+        let mut frame = [0; MAX_FRAME_SIZE];
+        let buf = &mut frame[..self.link.frame_size.bytes()];
         buf[0] = command as u8;
-        buf[1] = args[0];
-        buf[2] = args[1];
-        // 3 - 14 unused
-        buf[15] = sum_slice(&buf[..3]);
-        port.write_all(&buf).map_err(|_| Error::SendCommandFail)
+        buf[1..=payload.len()].copy_from_slice(payload);
+        self.track_setting(command, payload);
+        self.link.integrity.seal(buf);
+        trace!(
+            target: "fakeldat_lib::frames",
+            %command,
+            frame = %log::Hex(buf),
+            "sending command"
+        );
+        self.port.write_all(buf).map_err(|_| {
+            warning!(%command, "sending command failed");
+            Error::SendCommandFail
+        })
     }
 
     pub fn set_poll_rate(&mut self, pollrate_hz: u16) -> Result<()> {
-        Self::send_command(
-            Command::SetPollRate,
-            pollrate_hz.to_le_bytes(),
-            &mut self.port,
-        )
+        self.send_setting(Command::SetPollRate, &pollrate_hz.to_le_bytes())
     }
     pub fn set_threshold(&mut self, threshold: i16) -> Result<()> {
-        Self::send_command(
-            Command::SetThreshold,
-            threshold.to_le_bytes(),
-            &mut self.port,
-        )
+        self.send_setting(Command::SetThreshold, &threshold.to_le_bytes())
     }
     pub fn set_report_mode(&mut self, report_mode: ReportMode) -> Result<()> {
-        Self::send_command(
-            Command::SetReportMode,
-            [report_mode as u8, 0],
-            &mut self.port,
-        )
+        self.send_setting(Command::SetReportMode, &[report_mode as u8, 0])
     }
     pub fn set_action(&mut self, action_mode: ActionMode) -> Result<()> {
-        Self::send_command(
+        self.send_setting(
             Command::SetAction,
-            [action_mode.into(), action_mode.get_key()],
-            &mut self.port,
+            &[action_mode.into(), action_mode.get_key()],
         )
     }
 
+    // Only used by the manual trigger, older firmware always presses for 50 ms
+    pub fn set_action_timing(&mut self, action_timing: ActionTiming) -> Result<()> {
+        if action_timing.press_duration == 0
+            || action_timing.press_duration > ActionTiming::MAX_PRESS_DURATION
+        {
+            return Err(Error::InvalidSetting(
+                Command::SetActionTiming,
+                action_timing.to_args(),
+            ));
+        }
+        self.send_setting(Command::SetActionTiming, &action_timing.to_args())
+    }
+    pub fn get_action_timing(&mut self) -> Result<()> {
+        self.send_command(Command::GetActionTiming, [0, 0])
+    }
+
+    // Older firmware only knows the relative threshold with the edge from its sign
+    pub fn set_detection_mode(&mut self, detection_mode: DetectionMode) -> Result<()> {
+        self.send_setting(
+            Command::SetDetectionMode,
+            &[detection_mode.edge as u8, detection_mode.baseline as u8],
+        )
+    }
+    pub fn get_detection_mode(&mut self) -> Result<()> {
+        self.send_command(Command::GetDetectionMode, [0, 0])
+    }
+
+    pub fn set_sensor_gain(&mut self, sensor_gain: SensorGain) -> Result<()> {
+        self.send_setting(Command::SetSensorGain, &[sensor_gain as u8, 0])
+    }
+    pub fn get_sensor_gain(&mut self) -> Result<()> {
+        self.send_command(Command::GetSensorGain, [0, 0])
+    }
+
+    // Bit n enables sensor channel n, at least one has to stay enabled
+    pub fn set_channels(&mut self, channels: u8) -> Result<()> {
+        if channels == 0 {
+            return Err(Error::InvalidSetting(Command::SetChannels, [0, 0]));
+        }
+        self.send_setting(Command::SetChannels, &[channels, 0])
+    }
+    pub fn get_channels(&mut self) -> Result<()> {
+        self.send_command(Command::GetChannels, [0, 0])
+    }
+
     pub fn get_poll_rate(&mut self) -> Result<()> {
-        Self::send_command(Command::GetPollRate, [0, 0], &mut self.port)
+        self.send_command(Command::GetPollRate, [0, 0])
     }
     pub fn get_threshold(&mut self) -> Result<()> {
-        Self::send_command(Command::GetThreshold, [0, 0], &mut self.port)
+        self.send_command(Command::GetThreshold, [0, 0])
     }
     pub fn get_report_mode(&mut self) -> Result<()> {
-        Self::send_command(Command::GetReportMode, [0, 0], &mut self.port)
+        self.send_command(Command::GetReportMode, [0, 0])
     }
     pub fn get_action(&mut self) -> Result<()> {
-        Self::send_command(Command::GetAction, [0, 0], &mut self.port)
+        self.send_command(Command::GetAction, [0, 0])
+    }
+
+    // The device switches after responding, see negotiate_integrity
+    pub fn set_integrity(&mut self, integrity: Integrity) -> Result<()> {
+        self.send_command(Command::SetIntegrity, [integrity as u8, 0])
+    }
+    pub fn get_integrity(&mut self) -> Result<()> {
+        self.send_command(Command::GetIntegrity, [0, 0])
+    }
+
+    // Same as with the integrity, see negotiate_sequence
+    pub fn set_sequence(&mut self, enabled: bool) -> Result<()> {
+        self.send_command(Command::SetSequence, [u8::from(enabled), 0])
+    }
+    pub fn get_sequence(&mut self) -> Result<()> {
+        self.send_command(Command::GetSequence, [0, 0])
+    }
+
+    // Same as with the integrity, see negotiate_frame_size
+    pub fn set_frame_size(&mut self, frame_size: FrameSize) -> Result<()> {
+        self.send_command(Command::SetFrameSize, [frame_size as u8, 0])
+    }
+    pub fn get_frame_size(&mut self) -> Result<()> {
+        self.send_command(Command::GetFrameSize, [0, 0])
+    }
+
+    // Packs raw samples into fewer frames, audio isn't reported then
+    pub fn set_raw_batching(&mut self, enabled: bool) -> Result<()> {
+        self.send_setting(Command::SetRawBatching, &[u8::from(enabled), 0])
+    }
+    pub fn get_raw_batching(&mut self) -> Result<()> {
+        self.send_command(Command::GetRawBatching, [0, 0])
+    }
+
+    // Only changes the device side, see switch_baud_rate
+    pub fn set_baud_rate(&mut self, baud_rate: u32) -> Result<()> {
+        let args = u16::try_from(baud_rate / 100)
+            .ok()
+            .filter(|args| u32::from(*args) * 100 == baud_rate)
+            .ok_or(Error::InvalidSetting(Command::SetBaudRate, [0, 0]))?;
+        self.send_command(Command::SetBaudRate, args.to_le_bytes())
+    }
+    pub fn get_baud_rate(&mut self) -> Result<()> {
+        self.send_command(Command::GetBaudRate, [0, 0])
+    }
+
+    // Fires count times, waiting a random amount of milliseconds between min and max in between
+    pub fn start_auto_trigger(
+        &mut self,
+        min_interval: u16,
+        max_interval: u16,
+        count: u16,
+    ) -> Result<()> {
+        let auto_trigger = AutoTrigger {
+            min_interval,
+            max_interval,
+            remaining: count,
+        };
+        if min_interval == 0 || min_interval > max_interval || count == 0 {
+            let payload = auto_trigger.to_payload();
+            return Err(Error::InvalidSetting(
+                Command::SetAutoTrigger,
+                [payload[0], payload[1]],
+            ));
+        }
+        self.send_setting(Command::SetAutoTrigger, &auto_trigger.to_payload())
+    }
+    pub fn stop_auto_trigger(&mut self) -> Result<()> {
+        self.send_setting(
+            Command::SetAutoTrigger,
+            &AutoTrigger::default().to_payload(),
+        )
+    }
+    pub fn get_auto_trigger(&mut self) -> Result<()> {
+        self.send_command(Command::GetAutoTrigger, [0, 0])
+    }
+    // Last known amount of automatic presses left
+    pub fn auto_trigger_remaining(&self) -> u16 {
+        self.settings
+            .auto_trigger
+            .map_or(0, |auto_trigger| auto_trigger.remaining)
     }
 
     pub fn manual_trigger(&mut self) -> Result<()> {
-        Self::send_command(Command::ManualTrigger, [0, 0], &mut self.port)
+        self.send_command(Command::ManualTrigger, [0, 0])
+    }
+
+    // Requires summary or combined report mode, this will block until the delay is measured
+    pub fn manual_trigger_and_wait(&mut self, timeout: Duration) -> Result<SummaryReport> {
+        self.manual_trigger()?;
+        self.wait_for_report(Command::ManualTrigger, timeout, |report| match report {
+            Report::Summary(summary) => Some(*summary),
+            _ => None,
+        })
+    }
+
+    // Requires summary or combined report mode, this will block for the whole sequence
+    pub fn run_trigger_sequence(
+        &mut self,
+        count: usize,
+        interval: Duration,
+        jitter: Duration,
+    ) -> Result<SequenceResult> {
+        let mut rng = rand::thread_rng();
+        let mut result = SequenceResult::default();
+        for _ in 0..count {
+            let start = Instant::now();
+            let wait = interval + rng.gen_range(Duration::ZERO..=jitter);
+            match self.manual_trigger_and_wait(wait) {
+                Ok(summary) => result.reports.push(summary),
+                Err(Error::CommandTimeout(_)) => result.missed += 1,
+                Err(why) => return Err(why),
+            }
+            sleep(wait.saturating_sub(start.elapsed()));
+        }
+        Ok(result)
+    }
+
+    pub fn apply_profile(&mut self, profile: &Profile) -> Result<()> {
+        self.set_poll_rate(profile.poll_rate)?;
+        self.set_report_mode(profile.report_mode)?;
+        self.set_threshold(profile.threshold)?;
+        self.set_action(profile.action)
+    }
+
+    // This will block until all settings are received or timeout runs out
+    pub fn read_profile(&mut self, timeout: Duration) -> Result<Profile> {
+        self.get_poll_rate()?;
+        let poll_rate =
+            self.wait_for_report(Command::GetPollRate, timeout, |report| match report {
+                Report::PollRate(poll_rate) => Some(*poll_rate),
+                _ => None,
+            })?;
+        self.get_report_mode()?;
+        let report_mode =
+            self.wait_for_report(Command::GetReportMode, timeout, |report| match report {
+                Report::ReportMode(report_mode) => Some(*report_mode),
+                _ => None,
+            })?;
+        self.get_threshold()?;
+        let threshold =
+            self.wait_for_report(Command::GetThreshold, timeout, |report| match report {
+                Report::Threshold(threshold) => Some(*threshold),
+                _ => None,
+            })?;
+        self.get_action()?;
+        let action = self.wait_for_report(Command::GetAction, timeout, |report| match report {
+            Report::Action(action) => Some(*action),
+            _ => None,
+        })?;
+        Ok(Profile {
+            poll_rate,
+            report_mode,
+            threshold,
+            action,
+        })
+    }
+
+    // Matching report is removed from the buffer, the rest is kept for the regular consumer
+    fn wait_for_report<T>(
+        &mut self,
+        command: Command,
+        timeout: Duration,
+        mut extract: impl FnMut(&Report) -> Option<T>,
+    ) -> Result<T> {
+        let start = Instant::now();
+        let mut checked = self.report_buffer.end();
+        loop {
+            self.poll_bulk_data()?;
+            if let Some(value) = self.report_buffer.find_from(checked, &mut extract) {
+                return Ok(value);
+            }
+            checked = self.report_buffer.end();
+            if start.elapsed() > timeout {
+                return Err(Error::CommandTimeout(command));
+            }
+            sleep(Duration::from_millis(10));
+        }
     }
 
     #[allow(clippy::too_many_lines)]
     // This will block
     fn poll_data(&mut self) -> Result<Report> {
-        if self.port.bytes_to_read()? < 16 {
+        let frame_size = self.link.frame_size.bytes();
+        if (self.port.bytes_to_read()? as usize) < frame_size {
             return Err(Error::ReadTooLittleData);
         }
 
-        let mut buf = [0u8; 16];
-        self.read.read_exact(&mut buf)?;
+        let mut frame = [0u8; MAX_FRAME_SIZE];
+        self.read.read_exact(&mut frame[..frame_size])?;
+        trace!(
+            target: "fakeldat_lib::frames",
+            frame = %log::Hex(&frame[..frame_size]),
+            "received frame"
+        );
 
-        let Ok(command) = buf[0].try_into() else {
-            return Err(Error::InvalidCommand(buf[0]));
-        };
+        let calculated_checksum = self.link.integrity.checksum(&frame[..frame_size]);
+        let received_checksum = self.link.integrity.received_checksum(&frame[..frame_size]);
+        if let Some(ref mut callback) = self.frame_callback {
+            callback(RawFrame {
+                bytes: &frame[..frame_size],
+                checksum_valid: received_checksum == calculated_checksum,
+            });
+        }
 
-        let calculated_checksum: u8 = sum_slice(&buf[..=14]);
-        let received_checksum = buf[15];
+        let Ok(command) = frame[0].try_into() else {
+            debug!(id = frame[0], "unknown command id");
+            return Err(Error::InvalidCommand(frame[0]));
+        };
         if received_checksum != calculated_checksum {
             return Err(Error::WrongChecksum(
                 command,
@@ -376,19 +1483,78 @@ impl FakeLDAT {
                 calculated_checksum,
             ));
         }
+        self.link_stats.frames += 1;
+        if self.link.sequence {
+            self.check_sequence(frame[frame_size - 2]);
+        }
+        // every report still fits in bytes 0 to 13, the rest of extended frames is unused so far
+        let buf: [u8; 16] = frame[..16].try_into().unwrap();
         let settings_buffer: [u8; 2] = buf[1..=2].try_into().unwrap();
+        self.check_setting(command, settings_buffer);
 
         match command {
-            Command::ReportRaw => Ok(Report::Raw(RawReport {
-                timestamp: u64::from_le_bytes(buf[1..=8].try_into().unwrap()),
-                brightness: u16::from_le_bytes(buf[9..=10].try_into().unwrap()),
-                audio: u16::from_le_bytes(buf[11..=12].try_into().unwrap()),
-                trigger: buf[13] == 1,
-            })),
+            Command::ReportRaw => {
+                let timestamp = u64::from_le_bytes(buf[1..=8].try_into().unwrap());
+                // bit 0 trigger, bits 4-7 channel
+                let channel = buf[13] >> 4;
+                // batches only carry the first channel
+                if channel == 0 {
+                    self.last_raw_timestamp = Some(timestamp);
+                }
+                Ok(Report::Raw(RawReport {
+                    timestamp,
+                    brightness: u16::from_le_bytes(buf[9..=10].try_into().unwrap()),
+                    audio: u16::from_le_bytes(buf[11..=12].try_into().unwrap()),
+                    trigger: buf[13] & 1 == 1,
+                    channel,
+                }))
+            }
+            Command::ReportRawBatch => {
+                let Some(timestamp) = self.last_raw_timestamp else {
+                    // nothing to place the samples against until the next raw report
+                    let missed = batch::sample_count(&buf);
+                    self.link_stats.dropped += u64::from(missed);
+                    return Ok(Report::Dropped {
+                        missed: u32::from(missed),
+                    });
+                };
+                let mut raw_reports = batch::decode(&buf, timestamp);
+                let last = raw_reports
+                    .pop()
+                    .ok_or(Error::InvalidSetting(command, settings_buffer))?;
+                self.last_raw_timestamp = Some(last.timestamp);
+                for raw_report in raw_reports {
+                    self.push_report(Report::Raw(raw_report));
+                }
+                Ok(Report::Raw(last))
+            }
             Command::ReportSummary => Ok(Report::Summary(SummaryReport {
                 delay: u64::from_le_bytes(buf[1..=8].try_into().unwrap()),
                 threshold: u16::from_le_bytes(buf[9..=10].try_into().unwrap()),
+                // bit 1 set when measured while armed
+                arm_id: (buf[13] & 2 != 0)
+                    .then(|| u16::from_le_bytes(buf[11..=12].try_into().unwrap())),
             })),
+            Command::ReportRawAudio => Ok(Report::RawAudio(RawAudioReport {
+                timestamp: u64::from_le_bytes(buf[1..=8].try_into().unwrap()),
+                amplitudes: buf[9..=12]
+                    .chunks_exact(2)
+                    .take(usize::from(buf[13]))
+                    .map(|sample| u16::from_le_bytes([sample[0], sample[1]]))
+                    .collect(),
+            })),
+            Command::ReportAudioVideo => {
+                // all bits set when not detected
+                let delay = |bytes: &[u8]| {
+                    let delay = u32::from_le_bytes(bytes.try_into().unwrap());
+                    (delay != u32::MAX).then_some(u64::from(delay))
+                };
+                Ok(Report::AudioVideo(AudioVideoReport {
+                    video_delay: delay(&buf[1..=4]),
+                    audio_delay: delay(&buf[5..=8]),
+                    threshold: u16::from_le_bytes(buf[9..=10].try_into().unwrap()),
+                }))
+            }
             Command::GetPollRate | Command::SetPollRate => {
                 Ok(Report::PollRate(u16::from_le_bytes(settings_buffer)))
             }
@@ -411,39 +1577,284 @@ impl FakeLDAT {
                 buf[1..=8].try_into().unwrap(),
             ))),
             Command::ManualTrigger => Ok(Report::ManualTrigger),
+            Command::GetAutoTrigger | Command::SetAutoTrigger => {
+                Ok(Report::AutoTriggerSettings(AutoTrigger::from_frame(&buf)))
+            }
+            Command::AutoTrigger => {
+                if let Some(ref mut auto_trigger) = self.settings.auto_trigger {
+                    auto_trigger.remaining = u16::from_le_bytes([buf[9], buf[10]]);
+                }
+                Ok(Report::AutoTrigger(u64::from_le_bytes(
+                    buf[1..=8].try_into().unwrap(),
+                )))
+            }
+            Command::GetIntegrity | Command::SetIntegrity => {
+                Integrity::try_from(settings_buffer[0]).map_or_else(
+                    |_| Err(Error::InvalidSetting(command, settings_buffer)),
+                    |integrity| Ok(Report::Integrity(integrity)),
+                )
+            }
+            Command::GetSequence | Command::SetSequence => match settings_buffer[0] {
+                0 => Ok(Report::Sequence(false)),
+                1 => Ok(Report::Sequence(true)),
+                _ => Err(Error::InvalidSetting(command, settings_buffer)),
+            },
+            Command::GetFrameSize | Command::SetFrameSize => {
+                FrameSize::try_from(settings_buffer[0]).map_or_else(
+                    |_| Err(Error::InvalidSetting(command, settings_buffer)),
+                    |frame_size| Ok(Report::FrameSize(frame_size)),
+                )
+            }
+            Command::GetBaudRate | Command::SetBaudRate => Ok(Report::BaudRate(
+                u32::from(u16::from_le_bytes(settings_buffer)) * 100,
+            )),
+            Command::GetActionTiming | Command::SetActionTiming => Ok(Report::ActionTiming(
+                ActionTiming::from_args(settings_buffer),
+            )),
+            Command::GetDetectionMode | Command::SetDetectionMode => {
+                match (
+                    Edge::try_from(settings_buffer[0]),
+                    Baseline::try_from(settings_buffer[1]),
+                ) {
+                    (Ok(edge), Ok(baseline)) => {
+                        Ok(Report::DetectionMode(DetectionMode { edge, baseline }))
+                    }
+                    _ => Err(Error::InvalidSetting(command, settings_buffer)),
+                }
+            }
+            Command::GetSensorGain | Command::SetSensorGain => {
+                SensorGain::try_from(settings_buffer[0]).map_or_else(
+                    |_| Err(Error::InvalidSetting(command, settings_buffer)),
+                    |sensor_gain| Ok(Report::SensorGain(sensor_gain)),
+                )
+            }
+            Command::ReportTelemetry => Ok(Report::Telemetry(Telemetry {
+                timestamp: u64::from_le_bytes(buf[1..=8].try_into().unwrap()),
+                temperature: i16::from_le_bytes(buf[9..=10].try_into().unwrap()),
+                supply: u16::from_le_bytes(buf[11..=12].try_into().unwrap()),
+            })),
+            Command::GetTelemetryInterval | Command::SetTelemetryInterval => Ok(
+                Report::TelemetryInterval(u16::from_le_bytes(settings_buffer)),
+            ),
+            Command::ReportSummaryAggregate => {
+                Ok(Report::SummaryAggregate(SummaryAggregate::from_frame(&buf)))
+            }
+            Command::GetSummaryAggregation | Command::SetSummaryAggregation => {
+                Ok(Report::SummaryAggregation(settings_buffer[0]))
+            }
+            Command::GetChannels | Command::SetChannels => match settings_buffer[0] {
+                0 => Err(Error::InvalidSetting(command, settings_buffer)),
+                channels => Ok(Report::Channels(channels)),
+            },
+            Command::GetClock => Ok(Report::Clock(u64::from_le_bytes(
+                buf[1..=8].try_into().unwrap(),
+            ))),
+            Command::Hello => Ok(Report::Hello(Hello::from_frame(&buf))),
+            Command::Ping => Ok(Report::Pong),
+            Command::Arm => Ok(Report::Armed(u16::from_le_bytes(settings_buffer))),
+            Command::Disarm => Ok(Report::Disarmed),
+            Command::SelfTest => Ok(Report::SelfTest(SelfTest::from_frame(&buf))),
+            Command::GetRawBatching | Command::SetRawBatching => match settings_buffer[0] {
+                0 => Ok(Report::RawBatching(false)),
+                1 => Ok(Report::RawBatching(true)),
+                _ => Err(Error::InvalidSetting(command, settings_buffer)),
+            },
         }
     }
 
+    // None when nothing arrived since the last call
     pub fn take_report_buffer(&mut self) -> Option<Vec<Report>> {
-        if self.report_buffer.is_some() {
-            take(&mut self.report_buffer)
-        } else {
-            None
+        let reports = self.report_buffer.take();
+        (!reports.is_empty()).then_some(reports)
+    }
+
+    // Raw reports taken out of the buffer and grouped by their sensor channel
+    pub fn take_channel_buffers(&mut self) -> BTreeMap<u8, Vec<RawReport>> {
+        let mut channels: BTreeMap<u8, Vec<RawReport>> = BTreeMap::new();
+        for report in self.report_buffer.take() {
+            match report {
+                Report::Raw(raw_report) => channels
+                    .entry(raw_report.channel)
+                    .or_default()
+                    .push(raw_report),
+                // fits, the buffer was holding it already
+                report => _ = self.report_buffer.push(report),
+            }
+        }
+        channels
+    }
+
+    fn push_report(&mut self, report: Report) {
+        self.io.count_report();
+        match report {
+            Report::PollRate(poll_rate) => {
+                self.settings.poll_rate = Some(poll_rate);
+                // the interval changed in between
+                self.last_gap_timestamp = None;
+            }
+            Report::ReportMode(report_mode) => self.settings.report_mode = Some(report_mode),
+            Report::Threshold(threshold) => self.settings.threshold = Some(threshold),
+            Report::Action(action) => self.settings.action = Some(action),
+            Report::ActionTiming(action_timing) => {
+                self.settings.action_timing = Some(action_timing);
+            }
+            Report::AutoTriggerSettings(auto_trigger) => {
+                self.settings.auto_trigger = Some(auto_trigger);
+            }
+            Report::DetectionMode(detection_mode) => {
+                self.settings.detection_mode = Some(detection_mode);
+            }
+            Report::SensorGain(sensor_gain) => self.settings.sensor_gain = Some(sensor_gain),
+            Report::Channels(channels) => self.settings.channels = Some(channels),
+            Report::TelemetryInterval(interval) => {
+                self.settings.telemetry_interval = Some(interval);
+            }
+            Report::Telemetry(telemetry) => self.telemetry = Some(telemetry),
+            Report::SummaryAggregation(measurements) => {
+                self.settings.summary_aggregation = Some(measurements);
+            }
+            Report::Armed(id) => self.armed = Some(id),
+            Report::Disarmed => self.armed = None,
+            Report::RawBatching(enabled) => self.settings.raw_batching = Some(enabled),
+            Report::Integrity(_) | Report::Sequence(_) | Report::FrameSize(_) => {
+                self.update_link(&report);
+            }
+            // other channels would reset the first one's state in between
+            Report::Raw(ref raw_report) if raw_report.channel == 0 => {
+                self.check_saturation(raw_report);
+                self.check_gap(raw_report);
+            }
+            // it already counted as a sign of life
+            Report::Pong => return,
+            _ => {}
+        }
+        self.publish_report(&report);
+        if !self.report_filter.allows(&report) {
+            return;
+        }
+        self.publish_to_sinks(&report);
+        let Some(report) = self.dispatch_report(report) else {
+            return;
+        };
+        if !self.report_buffer.push(report) {
+            self.link_stats.overflowed += 1;
         }
     }
 
-    pub fn poll_bulk_data(&mut self) -> Result<()> {
+    pub fn poll_bulk_data(&mut self) -> Result<PollOutcome> {
+        if !self.connected {
+            self.check_heartbeat(false);
+            return self.try_reconnect().map(|()| PollOutcome::default());
+        }
         // TODO: what if serial buffer gets full in the meantime
+        let overflowed = self.link_stats.overflowed;
+        let frames = self.link_stats.frames;
+        // a failing port shows up in the reads below
+        let input_buffered = self.port.bytes_to_read().unwrap_or(0);
+        let mut outcome = PollOutcome::default();
         let mut read_next = true;
         while read_next {
             match self.poll_data() {
                 Ok(report) => {
-                    if let Some(ref mut report_buffer) = self.report_buffer {
-                        report_buffer.push(report);
-                    } else {
-                        self.report_buffer = Some(vec![report]);
-                    }
+                    outcome.reports_read += 1;
+                    self.push_report(report);
                 }
                 Err(why) => match why {
                     Error::ReadTooLittleData => read_next = false,
-                    Error::WrongChecksum(a, b, c) => {
-                        println!("Wrong checksum: {a}, {b}, {c}");
+                    Error::WrongChecksum(command, expected, calculated) => {
+                        self.link_stats.checksum_errors += 1;
+                        outcome.checksum_errors += 1;
+                        // the broken frame itself was already read
+                        let discarded = self.link.frame_size.bytes() as u64
+                            + u64::from(self.port.bytes_to_read()?);
                         self.port.clear(serialport::ClearBuffer::Input)?;
+                        self.link_stats.discarded_bytes += discarded;
+                        // a raw report may have been cleared, later batches can't be placed
+                        self.last_raw_timestamp = None;
+                        outcome.bytes_discarded += discarded;
+                        warning!(
+                            %command,
+                            expected,
+                            calculated,
+                            discarded,
+                            "wrong checksum, input cleared"
+                        );
+                        self.warn(Warning::ChecksumRecovered {
+                            command,
+                            expected,
+                            calculated,
+                        });
+                    }
+                    why if self.is_disconnect(&why) => {
+                        info!(error = ?why, "device disconnected");
+                        self.connected = false;
+                        read_next = false;
+                    }
+                    why => {
+                        debug!(error = ?why, "polling failed");
+                        return Result::Err(why);
                     }
-                    why => return Result::Err(why),
                 },
             }
         }
-        Ok(())
+        self.check_heartbeat(self.link_stats.frames > frames);
+        self.io.count_poll(
+            input_buffered,
+            (self.link_stats.frames - frames) * self.link.frame_size.bytes() as u64
+                + outcome.bytes_discarded,
+        );
+        let discarded = self.link_stats.overflowed - overflowed;
+        if discarded > 0 {
+            warning!(discarded, "report buffer full");
+            self.warn(Warning::BufferTrimmed { discarded });
+        }
+        if self.report_buffer.take_overflow() {
+            return Err(Error::ReportBufferFull);
+        }
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc16, crc8, Integrity};
+
+    // the standard check input of both CRC catalogues
+    const CHECK: &[u8] = b"123456789";
+
+    #[test]
+    fn crc8_check_value() {
+        assert_eq!(crc8(CHECK), 0xF4);
+    }
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(CHECK), 0x29B1);
+    }
+
+    #[test]
+    fn sealed_frames_verify() {
+        for integrity in Integrity::ALL {
+            let mut frame: [u8; 16] = std::array::from_fn(|i| i as u8 * 7);
+            integrity.seal(&mut frame);
+            assert_eq!(
+                integrity.checksum(&frame),
+                integrity.received_checksum(&frame),
+                "{integrity}"
+            );
+        }
+    }
+
+    #[test]
+    fn crc16_is_in_the_last_two_bytes() {
+        let mut frame: [u8; 16] = std::array::from_fn(|i| i as u8 * 7);
+        Integrity::Crc16.seal(&mut frame);
+        assert_eq!(frame[14..], crc16(&frame[..14]).to_le_bytes());
+        // anything covered by it breaks the match
+        frame[13] ^= 1;
+        assert_ne!(
+            Integrity::Crc16.checksum(&frame),
+            Integrity::Crc16.received_checksum(&frame)
+        );
     }
 }
@@ -0,0 +1,248 @@
+use std::{thread::sleep, time::Duration};
+
+use crate::log::warning;
+use crate::{Command, Error, FakeLDAT, FrameSize, Integrity, RawReport, Report, Result, Warning};
+
+// Same as in the firmware
+const BAUD_RATE_CONFIRM_TIMEOUT: Duration = Duration::from_secs(1);
+// 12-bit ADC
+const MAX_BRIGHTNESS: u16 = 4095;
+// Brightness stuck at either end for this long means the sensor is covered, blinded or off the screen
+const SATURATION_TIME: u64 = 500_000;
+// Samples are sent on a timer, some jitter is normal
+const GAP_TOLERANCE: f64 = 1.5;
+
+/// State of the link with the device
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct LinkInfo {
+    pub integrity: Integrity,
+    // firmware answered the integrity negotiation
+    pub negotiated: bool,
+    // frames carry a sequence counter before the checksum, not available with CRC-16
+    pub sequence: bool,
+    pub baud_rate: u32,
+    pub frame_size: FrameSize,
+}
+
+/// Cumulative counters since opening or the last reset
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinkStats {
+    // frames which passed the checksum
+    pub frames: u64,
+    pub checksum_errors: u64,
+    // cleared from the input to find the next frame boundary after a checksum error
+    pub discarded_bytes: u64,
+    // only counted with sequence numbers enabled
+    pub dropped: u64,
+    // brightness currently stuck at 0 or 4095, delays measured meanwhile are nonsense
+    // only raw reports carry the brightness
+    pub saturated: bool,
+    pub saturations: u64,
+    // reports discarded because the report buffer was full
+    pub overflowed: u64,
+    // only counted with gap detection enabled
+    pub gaps: u64,
+}
+
+/// What a single [`FakeLDAT::poll_bulk_data`] call read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PollOutcome {
+    // frames which passed the check, a raw batch counts once
+    pub reports_read: u64,
+    pub checksum_errors: u64,
+    pub bytes_discarded: u64,
+}
+
+impl FakeLDAT {
+    pub const fn link_info(&self) -> LinkInfo {
+        self.link
+    }
+
+    pub const fn link_stats(&self) -> LinkStats {
+        self.link_stats
+    }
+
+    pub fn reset_link_stats(&mut self) {
+        self.link_stats = LinkStats::default();
+    }
+
+    // Compares raw timestamps with the poll rate, catches overruns the checksum can't see
+    pub fn set_gap_detection(&mut self, enabled: bool) {
+        self.gap_detection = enabled;
+        self.last_gap_timestamp = None;
+    }
+
+    // Older firmware doesn't respond, the additive checksum stays in use then
+    pub fn negotiate_integrity(
+        &mut self,
+        integrity: Integrity,
+        timeout: Duration,
+    ) -> Result<Integrity> {
+        if !self.supports(Command::SetIntegrity, &[integrity as u8]) {
+            return Ok(self.link.integrity);
+        }
+        self.set_integrity(integrity)?;
+        match self.wait_for_report(Command::SetIntegrity, timeout, |report| match report {
+            Report::Integrity(integrity) => Some(*integrity),
+            _ => None,
+        }) {
+            Ok(integrity) => Ok(integrity),
+            Err(Error::CommandTimeout(_)) => Ok(self.link.integrity),
+            Err(why) => Err(why),
+        }
+    }
+
+    // Returns whether the firmware agreed to send sequence numbers
+    pub fn negotiate_sequence(&mut self, enabled: bool, timeout: Duration) -> Result<bool> {
+        if !self.supports(Command::SetSequence, &[u8::from(enabled)]) {
+            return Ok(self.link.sequence);
+        }
+        self.set_sequence(enabled)?;
+        match self.wait_for_report(Command::SetSequence, timeout, |report| match report {
+            Report::Sequence(enabled) => Some(*enabled),
+            _ => None,
+        }) {
+            Ok(enabled) => Ok(enabled),
+            Err(Error::CommandTimeout(_)) => Ok(self.link.sequence),
+            Err(why) => Err(why),
+        }
+    }
+
+    // Only firmware with the capability answers, the standard size stays in use otherwise
+    pub fn negotiate_frame_size(
+        &mut self,
+        frame_size: FrameSize,
+        timeout: Duration,
+    ) -> Result<FrameSize> {
+        if !self.supports(Command::SetFrameSize, &[frame_size as u8]) {
+            return Ok(self.link.frame_size);
+        }
+        self.set_frame_size(frame_size)?;
+        match self.wait_for_report(Command::SetFrameSize, timeout, |report| match report {
+            Report::FrameSize(frame_size) => Some(*frame_size),
+            _ => None,
+        }) {
+            Ok(frame_size) => Ok(frame_size),
+            Err(Error::CommandTimeout(_)) => Ok(self.link.frame_size),
+            Err(why) => Err(why),
+        }
+    }
+
+    // Device switches after acknowledging, the host follows and verifies the link with a poll rate request
+    // Falls back to the current rate when that fails, older firmware doesn't acknowledge at all
+    pub fn switch_baud_rate(&mut self, baud_rate: u32, timeout: Duration) -> Result<u32> {
+        let previous = self.port.baud_rate()?;
+        if !self.supports(Command::SetBaudRate, &[]) {
+            return Ok(previous);
+        }
+        self.set_baud_rate(baud_rate)?;
+        let acknowledged =
+            match self.wait_for_report(Command::SetBaudRate, timeout, |report| match report {
+                Report::BaudRate(baud_rate) => Some(*baud_rate),
+                _ => None,
+            }) {
+                Ok(acknowledged) => acknowledged,
+                Err(Error::CommandTimeout(_)) => return Ok(previous),
+                Err(why) => return Err(why),
+            };
+        self.port.set_baud_rate(acknowledged)?;
+        if self.verify_link(timeout).is_ok() {
+            self.link.baud_rate = acknowledged;
+            return Ok(acknowledged);
+        }
+        // device gives up on the new rate without a valid command in time
+        self.port.set_baud_rate(previous)?;
+        sleep(BAUD_RATE_CONFIRM_TIMEOUT);
+        self.port.clear(serialport::ClearBuffer::All)?;
+        self.verify_link(timeout)?;
+        Ok(previous)
+    }
+
+    fn verify_link(&mut self, timeout: Duration) -> Result<()> {
+        self.port.clear(serialport::ClearBuffer::Input)?;
+        self.get_poll_rate()?;
+        self.wait_for_report(Command::GetPollRate, timeout, |report| {
+            matches!(report, Report::PollRate(_)).then_some(())
+        })
+    }
+
+    // Following frames already use the new settings
+    pub(crate) fn update_link(&mut self, report: &Report) {
+        match *report {
+            Report::Integrity(integrity) => {
+                self.link.integrity = integrity;
+                self.link.negotiated = true;
+                // the counter byte is taken by the checksum
+                if integrity == Integrity::Crc16 {
+                    self.link.sequence = false;
+                }
+            }
+            Report::Sequence(enabled) => {
+                self.link.sequence = enabled;
+                self.last_sequence = None;
+            }
+            Report::FrameSize(frame_size) => self.link.frame_size = frame_size,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn check_saturation(&mut self, raw_report: &RawReport) {
+        if raw_report.brightness != 0 && raw_report.brightness < MAX_BRIGHTNESS {
+            self.saturated_since = None;
+            self.link_stats.saturated = false;
+            return;
+        }
+        let since = *self.saturated_since.get_or_insert(raw_report.timestamp);
+        if !self.link_stats.saturated
+            && raw_report.timestamp.saturating_sub(since) >= SATURATION_TIME
+        {
+            self.link_stats.saturated = true;
+            self.link_stats.saturations += 1;
+        }
+    }
+
+    // Needs the poll rate, nothing is checked until it was set or requested
+    pub(crate) fn check_gap(&mut self, raw_report: &RawReport) {
+        if !self.gap_detection {
+            return;
+        }
+        let previous = self.last_gap_timestamp.replace(raw_report.timestamp);
+        let (Some(previous), Some(poll_rate)) = (previous, self.settings.poll_rate) else {
+            return;
+        };
+        if poll_rate == 0 {
+            return;
+        }
+        let expected_us = 1_000_000 / u64::from(poll_rate);
+        let actual_us = raw_report.timestamp.saturating_sub(previous);
+        #[allow(clippy::cast_precision_loss)]
+        if actual_us as f64 > expected_us as f64 * GAP_TOLERANCE {
+            warning!(expected_us, actual_us, "gap in raw timestamps");
+            self.link_stats.gaps += 1;
+            self.push_report(Report::GapDetected {
+                expected_us,
+                actual_us,
+            });
+        }
+    }
+
+    // Gaps longer than 255 frames wrap around and can't be told apart
+    pub(crate) fn check_sequence(&mut self, sequence: u8) {
+        if let Some(last) = self.last_sequence {
+            let missed = sequence.wrapping_sub(last).wrapping_sub(1);
+            if missed > 0 {
+                warning!(missed, "frames dropped");
+                self.link_stats.dropped += u64::from(missed);
+                self.warn(Warning::FramesDropped {
+                    missed: u32::from(missed),
+                });
+                self.push_report(Report::Dropped {
+                    missed: u32::from(missed),
+                });
+                // the batches after a lost raw report would be placed against an older one
+                self.last_raw_timestamp = None;
+            }
+        }
+        self.last_sequence = Some(sequence);
+    }
+}
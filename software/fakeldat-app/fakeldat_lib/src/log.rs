@@ -0,0 +1,51 @@
+// Diagnostics go to tracing with the feature on, otherwise they compile to nothing
+// Arguments aren't evaluated then, so they shouldn't be the only use of a variable
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::info!($($arg)*);
+    };
+}
+
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+    };
+}
+
+// warn would clash with the builtin attribute
+pub(crate) use {debug, info, trace, warning};
+
+// Frames as they go over the wire, logged with the fakeldat_lib::frames target so they can be
+// enabled on their own
+#[cfg(feature = "tracing")]
+pub(crate) struct Hex<'a>(pub &'a [u8]);
+
+#[cfg(feature = "tracing")]
+impl std::fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, byte) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
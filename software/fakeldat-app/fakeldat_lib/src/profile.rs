@@ -0,0 +1,35 @@
+use std::{fs, path::Path};
+
+use crate::{ActionMode, Error, ReportMode, Result};
+
+/// All device settings, so they can be stored and applied at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Profile {
+    pub poll_rate: u16,
+    pub report_mode: ReportMode,
+    pub threshold: i16,
+    pub action: ActionMode,
+}
+
+impl Profile {
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str(toml).map_err(|why| {
+            Error::IOError(std::io::Error::new(std::io::ErrorKind::InvalidData, why))
+        })
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|why| {
+            Error::IOError(std::io::Error::new(std::io::ErrorKind::InvalidData, why))
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, self.to_toml()?)?;
+        Ok(())
+    }
+}
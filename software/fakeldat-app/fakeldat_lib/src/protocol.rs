@@ -0,0 +1,411 @@
+use crate::{
+    Baseline, Command, Edge, Error, FrameSize, GamepadButton, Integrity, KeyboardKey, MouseButton,
+    ReportMode, Result, SensorGain,
+};
+
+pub const FRAME_SIZE: usize = FrameSize::Standard as usize;
+
+/// Description of the serial protocol for firmware and third-party implementations
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Protocol {
+    pub frame_size: usize,
+    // after Command::SetFrameSize, the extra bytes come before the sequence counter and checksum
+    // which stay at the end, offsets below are for the standard size
+    pub extended_frame_size: usize,
+    // byte 0 of every frame
+    pub command_offset: usize,
+    pub integrity: Vec<IntegrityInfo>,
+    // only present when enabled, see Command::SetSequence
+    pub sequence: Field,
+    pub commands: Vec<CommandInfo>,
+    pub enums: Vec<EnumInfo>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct IntegrityInfo {
+    pub name: String,
+    pub value: u8,
+    pub algorithm: &'static str,
+    // checksum is calculated over bytes from 0 to this one
+    pub covers_until: usize,
+    pub checksum: Field,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CommandInfo {
+    pub name: String,
+    pub id: u8,
+    // who starts the exchange, host or device
+    pub sender: &'static str,
+    pub request: Vec<Field>,
+    pub response: Vec<Field>,
+}
+
+/// Little endian value inside a frame
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Field {
+    pub name: &'static str,
+    pub offset: usize,
+    pub size: usize,
+    // primitive type or the name of an enum
+    pub kind: &'static str,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EnumInfo {
+    pub name: &'static str,
+    pub values: Vec<EnumValue>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EnumValue {
+    pub name: String,
+    pub value: u8,
+}
+
+impl Protocol {
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|why| {
+            Error::IOError(std::io::Error::new(std::io::ErrorKind::InvalidData, why))
+        })
+    }
+}
+
+const fn field(name: &'static str, offset: usize, size: usize, kind: &'static str) -> Field {
+    Field {
+        name,
+        offset,
+        size,
+        kind,
+    }
+}
+
+// Setting carried from byte 1 on, same for setting and getting
+fn setting_fields(command: Command) -> Vec<Field> {
+    match command {
+        Command::SetPollRate | Command::GetPollRate => vec![field("poll_rate", 1, 2, "u16")],
+        Command::SetReportMode | Command::GetReportMode => {
+            vec![field("report_mode", 1, 1, "ReportMode")]
+        }
+        Command::SetThreshold | Command::GetThreshold => vec![field("threshold", 1, 2, "i16")],
+        Command::SetAction | Command::GetAction => vec![
+            field("action_mode", 1, 1, "ActionMode"),
+            field("key", 2, 1, "MouseButton, KeyboardKey or GamepadButton"),
+        ],
+        Command::SetIntegrity | Command::GetIntegrity => {
+            vec![field("integrity", 1, 1, "Integrity")]
+        }
+        Command::SetSequence | Command::GetSequence => vec![field("enabled", 1, 1, "bool")],
+        // the answer still comes in the old size
+        Command::SetFrameSize | Command::GetFrameSize => {
+            vec![field("frame_size", 1, 1, "FrameSize")]
+        }
+        Command::SetRawBatching | Command::GetRawBatching => {
+            vec![field("enabled", 1, 1, "bool")]
+        }
+        Command::SetActionTiming | Command::GetActionTiming => {
+            // bits 0-14 press duration in milliseconds, bit 15 double press
+            vec![field("action_timing", 1, 2, "u16")]
+        }
+        Command::SetAutoTrigger | Command::GetAutoTrigger => vec![
+            field("min_interval_ms", 1, 2, "u16"),
+            field("max_interval_ms", 3, 2, "u16"),
+            // 0 stops, presses left in the response
+            field("count", 5, 2, "u16"),
+        ],
+        Command::SetBaudRate | Command::GetBaudRate => {
+            vec![field("baud_rate_hundreds", 1, 2, "u16")]
+        }
+        Command::SetSensorGain | Command::GetSensorGain => {
+            vec![field("gain", 1, 1, "SensorGain")]
+        }
+        // bit n enables channel n
+        Command::SetChannels | Command::GetChannels => vec![field("channels", 1, 1, "u8")],
+        // 0 turns it off
+        Command::SetTelemetryInterval | Command::GetTelemetryInterval => {
+            vec![field("seconds", 1, 2, "u16")]
+        }
+        // 0 and 1 send every summary
+        Command::SetSummaryAggregation | Command::GetSummaryAggregation => {
+            vec![field("measurements", 1, 1, "u8")]
+        }
+        Command::SetDetectionMode | Command::GetDetectionMode => vec![
+            field("edge", 1, 1, "Edge"),
+            field("baseline", 2, 1, "Baseline"),
+        ],
+        Command::GetClock
+        | Command::Hello
+        | Command::Ping
+        | Command::Arm
+        | Command::Disarm
+        | Command::SelfTest
+        | Command::MacroTrigger
+        | Command::AutoTrigger
+        | Command::ManualTrigger
+        | Command::ReportRaw
+        | Command::ReportSummary
+        | Command::ReportRawBatch
+        | Command::ReportRawAudio
+        | Command::ReportAudioVideo
+        | Command::ReportTelemetry
+        | Command::ReportSummaryAggregate => vec![],
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+fn describe_command(command: Command) -> CommandInfo {
+    let (sender, request, response) = match command {
+        Command::SetPollRate
+        | Command::SetReportMode
+        | Command::SetThreshold
+        | Command::SetAction
+        | Command::SetIntegrity
+        | Command::SetSequence
+        | Command::SetRawBatching
+        | Command::SetBaudRate
+        | Command::SetActionTiming
+        | Command::SetAutoTrigger
+        | Command::SetDetectionMode
+        | Command::SetSensorGain
+        | Command::SetChannels
+        | Command::SetTelemetryInterval
+        | Command::SetSummaryAggregation
+        | Command::SetFrameSize => ("host", setting_fields(command), setting_fields(command)),
+        Command::GetPollRate
+        | Command::GetReportMode
+        | Command::GetThreshold
+        | Command::GetAction
+        | Command::GetIntegrity
+        | Command::GetSequence
+        | Command::GetRawBatching
+        | Command::GetBaudRate
+        | Command::GetActionTiming
+        | Command::GetAutoTrigger
+        | Command::GetDetectionMode
+        | Command::GetSensorGain
+        | Command::GetChannels
+        | Command::GetTelemetryInterval
+        | Command::GetSummaryAggregation
+        | Command::GetFrameSize => ("host", vec![], setting_fields(command)),
+        Command::ManualTrigger | Command::Ping => ("host", vec![], vec![]),
+        Command::GetClock => ("host", vec![], vec![field("timestamp", 1, 8, "u64")]),
+        Command::Hello => (
+            "host",
+            vec![field("protocol_version", 1, 2, "u16")],
+            vec![
+                field("protocol_version", 1, 2, "u16"),
+                // bit n per Capabilities field, in their order
+                field("capabilities", 3, 4, "u32"),
+            ],
+        ),
+        Command::MacroTrigger => ("device", vec![field("timestamp", 1, 8, "u64")], vec![]),
+        Command::AutoTrigger => (
+            "device",
+            vec![
+                field("timestamp", 1, 8, "u64"),
+                field("remaining", 9, 2, "u16"),
+            ],
+            vec![],
+        ),
+        Command::ReportRaw => (
+            "device",
+            vec![
+                field("timestamp", 1, 8, "u64"),
+                field("brightness", 9, 2, "u16"),
+                field("audio", 11, 2, "u16"),
+                // bit 0 trigger, bits 4-7 channel
+                field("trigger_and_channel", 13, 1, "u8"),
+            ],
+            vec![],
+        ),
+        Command::ReportSummary => (
+            "device",
+            vec![
+                field("delay", 1, 8, "u64"),
+                field("threshold", 9, 2, "u16"),
+                // only valid with bit 1 of the flags set
+                field("arm_id", 11, 2, "u16"),
+                field("flags", 13, 1, "u8"),
+            ],
+            vec![],
+        ),
+        // instead of the summaries when aggregation is set, delays in microseconds
+        Command::ReportSummaryAggregate => (
+            "device",
+            vec![
+                field("count", 1, 1, "u8"),
+                field("min", 2, 4, "u32"),
+                field("mean", 6, 4, "u32"),
+                field("max", 10, 4, "u32"),
+            ],
+            vec![],
+        ),
+        Command::SelfTest => (
+            "host",
+            vec![field("step_ms", 1, 2, "u16")],
+            vec![
+                field("step_ms", 1, 2, "u16"),
+                // 0xFFFFFFFF when not detected
+                field("detected_us", 3, 4, "u32"),
+                field("sample_interval_us", 7, 4, "u32"),
+            ],
+        ),
+        Command::Arm => (
+            "host",
+            vec![field("arm_id", 1, 2, "u16")],
+            vec![field("arm_id", 1, 2, "u16")],
+        ),
+        // 1 goes back to measuring every trigger
+        Command::Disarm => (
+            "host",
+            vec![field("release", 1, 1, "bool")],
+            vec![field("release", 1, 1, "bool")],
+        ),
+        Command::ReportRawAudio => (
+            "device",
+            vec![
+                // of the first sample, the second one follows a poll interval later
+                field("timestamp", 1, 8, "u64"),
+                field("samples", 9, 4, "u16[2]"),
+                field("count", 13, 1, "u8"),
+            ],
+            vec![],
+        ),
+        Command::ReportAudioVideo => (
+            "device",
+            vec![
+                // 0xFFFFFFFF when not detected
+                field("video_delay", 1, 4, "u32"),
+                field("audio_delay", 5, 4, "u32"),
+                field("threshold", 9, 2, "u16"),
+            ],
+            vec![],
+        ),
+        // also sent by the device on its own when the interval is set
+        Command::ReportTelemetry => (
+            "host",
+            vec![],
+            vec![
+                field("timestamp", 1, 8, "u64"),
+                field("temperature_centidegrees", 9, 2, "i16"),
+                field("supply_millivolts", 11, 2, "u16"),
+            ],
+        ),
+        Command::ReportRawBatch => (
+            "device",
+            vec![
+                // bits 0-2 sample count, bits 4-7 trigger state of each sample
+                field("count_and_triggers", 1, 1, "u8"),
+                // 3 bytes per sample, low 12 bits brightness, high 12 bits microseconds since the previous sample
+                field("samples", 2, 12, "u24[4]"),
+            ],
+            vec![],
+        ),
+    };
+    CommandInfo {
+        name: command.to_string(),
+        id: command as u8,
+        sender,
+        request,
+        response,
+    }
+}
+
+fn describe_integrity(integrity: Integrity) -> IntegrityInfo {
+    let (algorithm, covers_until, checksum) = match integrity {
+        Integrity::Sum => ("8-bit wrapping sum", 14, field("checksum", 15, 1, "u8")),
+        Integrity::Crc8 => (
+            "CRC-8/SMBUS, poly 0x07, init 0x00",
+            14,
+            field("checksum", 15, 1, "u8"),
+        ),
+        Integrity::Crc16 => (
+            "CRC-16/CCITT-FALSE, poly 0x1021, init 0xFFFF",
+            13,
+            field("checksum", 14, 2, "u16"),
+        ),
+    };
+    IntegrityInfo {
+        name: integrity.to_string(),
+        value: integrity as u8,
+        algorithm,
+        covers_until,
+        checksum,
+    }
+}
+
+fn enum_values<T: Copy + ToString>(values: &[T], to_u8: impl Fn(T) -> u8) -> Vec<EnumValue> {
+    values
+        .iter()
+        .map(|&value| EnumValue {
+            name: value.to_string(),
+            value: to_u8(value),
+        })
+        .collect()
+}
+
+pub fn describe() -> Protocol {
+    Protocol {
+        frame_size: FRAME_SIZE,
+        extended_frame_size: FrameSize::Extended.bytes(),
+        command_offset: 0,
+        integrity: Integrity::ALL.into_iter().map(describe_integrity).collect(),
+        sequence: field("sequence", 14, 1, "u8"),
+        commands: Command::ALL.into_iter().map(describe_command).collect(),
+        enums: vec![
+            EnumInfo {
+                name: "ReportMode",
+                values: enum_values(&ReportMode::ALL, |value| value as u8),
+            },
+            EnumInfo {
+                name: "ActionMode",
+                values: vec![
+                    EnumValue {
+                        name: "Mouse".to_string(),
+                        value: 0,
+                    },
+                    EnumValue {
+                        name: "Keyboard".to_string(),
+                        value: 1,
+                    },
+                    EnumValue {
+                        name: "Gamepad".to_string(),
+                        value: 2,
+                    },
+                ],
+            },
+            EnumInfo {
+                name: "MouseButton",
+                values: enum_values(&MouseButton::ALL, |value| value as u8),
+            },
+            EnumInfo {
+                name: "KeyboardKey",
+                values: enum_values(&KeyboardKey::ALL, |value| value as u8),
+            },
+            EnumInfo {
+                name: "GamepadButton",
+                values: enum_values(&GamepadButton::ALL, |value| value as u8),
+            },
+            EnumInfo {
+                name: "Integrity",
+                values: enum_values(&Integrity::ALL, |value| value as u8),
+            },
+            EnumInfo {
+                name: "Edge",
+                values: enum_values(&Edge::ALL, |value| value as u8),
+            },
+            EnumInfo {
+                name: "Baseline",
+                values: enum_values(&Baseline::ALL, |value| value as u8),
+            },
+            EnumInfo {
+                name: "SensorGain",
+                values: enum_values(&SensorGain::ALL, |value| value as u8),
+            },
+            EnumInfo {
+                name: "FrameSize",
+                values: enum_values(&FrameSize::ALL, |value| value as u8),
+            },
+        ],
+    }
+}
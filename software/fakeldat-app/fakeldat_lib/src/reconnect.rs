@@ -0,0 +1,113 @@
+use std::time::{Duration, Instant};
+
+use crate::log::{debug, info};
+use crate::{Error, FakeLDAT, FakeLDATBuilder, Report, Result};
+
+pub(crate) struct Reconnect {
+    builder: FakeLDATBuilder,
+    interval: Duration,
+    last_attempt: Instant,
+}
+
+impl Reconnect {
+    pub(crate) fn new(builder: FakeLDATBuilder, interval: Duration) -> Self {
+        Self {
+            builder,
+            interval,
+            last_attempt: Instant::now(),
+        }
+    }
+}
+
+impl FakeLDAT {
+    pub(crate) fn is_disconnect(&self, error: &Error) -> bool {
+        self.reconnect.is_some()
+            && matches!(
+                error,
+                Error::PortFail(serialport::Error {
+                    kind: serialport::ErrorKind::NoDevice | serialport::ErrorKind::Unknown,
+                    ..
+                })
+            )
+    }
+
+    // Doesn't block, only one attempt is made per interval
+    pub(crate) fn try_reconnect(&mut self) -> Result<()> {
+        let Some(ref mut reconnect) = self.reconnect else {
+            return Ok(());
+        };
+        if reconnect.last_attempt.elapsed() < reconnect.interval {
+            return Ok(());
+        }
+        reconnect.last_attempt = Instant::now();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("reconnect").entered();
+        let mut fakeldat = match reconnect.builder.try_open() {
+            Ok(fakeldat) => fakeldat,
+            Err(Error::PortFail(_) | Error::CommandTimeout(_)) => {
+                debug!("device not back yet");
+                return Ok(());
+            }
+            Err(why) => return Err(why),
+        };
+        info!("reconnected, reapplying settings");
+        std::mem::swap(&mut self.port, &mut fakeldat.port);
+        std::mem::swap(&mut self.read, &mut fakeldat.read);
+        // the old handles are left behind with nothing to release
+        fakeldat.connected = false;
+        self.link = fakeldat.link;
+        self.last_sequence = None;
+        self.last_raw_timestamp = None;
+        self.saturated_since = None;
+        self.last_gap_timestamp = None;
+        // a window doesn't survive the device restarting
+        self.armed = None;
+        // device counter starts over
+        self.clock = None;
+        self.connected = true;
+        let settings = self.settings;
+        if let Some(poll_rate) = settings.poll_rate {
+            self.set_poll_rate(poll_rate)?;
+        }
+        if let Some(report_mode) = settings.report_mode {
+            self.set_report_mode(report_mode)?;
+        }
+        if let Some(threshold) = settings.threshold {
+            self.set_threshold(threshold)?;
+        }
+        if let Some(action) = settings.action {
+            self.set_action(action)?;
+        }
+        if let Some(action_timing) = settings.action_timing {
+            self.set_action_timing(action_timing)?;
+        }
+        if let Some(detection_mode) = settings.detection_mode {
+            self.set_detection_mode(detection_mode)?;
+        }
+        if let Some(sensor_gain) = settings.sensor_gain {
+            self.set_sensor_gain(sensor_gain)?;
+        }
+        if let Some(channels) = settings.channels {
+            self.set_channels(channels)?;
+        }
+        if let Some(interval) = settings.telemetry_interval {
+            self.set_telemetry_interval(interval)?;
+        }
+        if let Some(measurements) = settings.summary_aggregation {
+            self.set_summary_aggregation(measurements)?;
+        }
+        // continues with the presses that were left
+        if let Some(auto_trigger) = settings.auto_trigger.filter(|auto| auto.remaining > 0) {
+            self.start_auto_trigger(
+                auto_trigger.min_interval,
+                auto_trigger.max_interval,
+                auto_trigger.remaining,
+            )?;
+        }
+        if let Some(raw_batching) = settings.raw_batching {
+            self.set_raw_batching(raw_batching)?;
+        }
+        self.push_report(Report::Reconnected);
+        Ok(())
+    }
+}
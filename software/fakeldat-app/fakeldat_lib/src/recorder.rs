@@ -0,0 +1,290 @@
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::analysis::slew;
+use crate::{Error, RawReport, Report, Result, SessionMetadata};
+
+/// Layout of the reports written by [`Recorder::write_report`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    // measurements only, as analyze and tail read them, every kind gets a header line before its first row
+    Csv,
+    // every report, one JSON object per line as described by the reports schema
+    Ndjson,
+}
+
+/// When a recording continues in a new file, name-2.csv, name-3.csv and so on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rotation {
+    pub max_bytes: Option<u64>,
+    pub max_age: Option<Duration>,
+}
+
+// Header line of every CSV row kind, written once per file
+#[derive(Clone, Copy)]
+enum RowKind {
+    Raw,
+    Summary,
+    RawAudio,
+    AudioVideo,
+}
+
+/// Buffered recording file, at most the flush interval worth of data is lost on power loss
+pub struct Recorder {
+    file: BufWriter<File>,
+    path: PathBuf,
+    format: RecordFormat,
+    flush_interval: Duration,
+    last_flush: Instant,
+    rotation: Rotation,
+    part: u32,
+    opened: Instant,
+    written: u64,
+    headers: [bool; 4],
+    // extra column with the brightness change from the previous raw report
+    slew: bool,
+    last_raw: Option<RawReport>,
+}
+
+fn open(path: &Path) -> Result<(BufWriter<File>, u64)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let written = file.metadata()?.len();
+    Ok((BufWriter::new(file), written))
+}
+
+impl Recorder {
+    pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+    // Appends when the file already exists
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::csv(path)
+    }
+
+    pub fn csv<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_format(path, RecordFormat::Csv)
+    }
+
+    pub fn ndjson<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_format(path, RecordFormat::Ndjson)
+    }
+
+    pub fn with_format<P: AsRef<Path>>(path: P, format: RecordFormat) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let (file, written) = open(&path)?;
+        Ok(Self {
+            file,
+            path,
+            format,
+            flush_interval: Self::DEFAULT_FLUSH_INTERVAL,
+            last_flush: Instant::now(),
+            rotation: Rotation::default(),
+            part: 1,
+            opened: Instant::now(),
+            written,
+            headers: [false; 4],
+            slew: false,
+            last_raw: None,
+        })
+    }
+
+    #[must_use]
+    pub const fn flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    #[must_use]
+    pub const fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    // Only affects CSV raw rows written from now on
+    pub fn set_slew(&mut self, enabled: bool) {
+        self.slew = enabled;
+    }
+
+    // File currently written to, changes with every rotation
+    pub fn path(&self) -> PathBuf {
+        self.part_path(self.part)
+    }
+
+    fn part_path(&self, part: u32) -> PathBuf {
+        if part == 1 {
+            return self.path.clone();
+        }
+        let stem = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = match self.path.extension() {
+            Some(extension) => format!("{stem}-{part}.{}", extension.to_string_lossy()),
+            None => format!("{stem}-{part}"),
+        };
+        self.path.with_file_name(name)
+    }
+
+    fn rotate_if_due(&mut self) -> Result<()> {
+        let full = self
+            .rotation
+            .max_bytes
+            .is_some_and(|max_bytes| self.written >= max_bytes);
+        let old = self
+            .rotation
+            .max_age
+            .is_some_and(|max_age| self.opened.elapsed() >= max_age);
+        if !full && !old {
+            return Ok(());
+        }
+        self.flush()?;
+        self.part += 1;
+        let (file, written) = open(&self.part_path(self.part))?;
+        self.file = file;
+        self.written = written;
+        self.opened = Instant::now();
+        self.headers = [false; 4];
+        Ok(())
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.rotate_if_due()?;
+        self.append(data)
+    }
+
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        self.file.write_all(data)?;
+        self.written += data.len() as u64;
+        self.flush_if_due()
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        self.write(format!("{line}\n").as_bytes())
+    }
+
+    // CSV readers skip it, NDJSON has no room for comments
+    pub fn write_comment(&mut self, comment: &str) -> Result<()> {
+        match self.format {
+            RecordFormat::Csv => self.write_line(&format!("# {comment}")),
+            RecordFormat::Ndjson => Ok(()),
+        }
+    }
+
+    // Goes first, CSV has it as a comment and NDJSON as an object which isn't a report
+    pub fn write_metadata(&mut self, metadata: &SessionMetadata) -> Result<()> {
+        let json = serde_json::to_string(metadata).map_err(|why| {
+            Error::IOError(std::io::Error::new(std::io::ErrorKind::InvalidData, why))
+        })?;
+        match self.format {
+            RecordFormat::Csv => self.write_line(&format!("# metadata: {json}")),
+            RecordFormat::Ndjson => self.write_line(&format!("{{\"metadata\":{json}}}")),
+        }
+    }
+
+    // CSV only keeps measurements of the first sensor, the others aren't analyzed
+    pub fn write_report(&mut self, report: &Report) -> Result<()> {
+        match self.format {
+            RecordFormat::Ndjson => {
+                let line = serde_json::to_string(report).map_err(|why| {
+                    Error::IOError(std::io::Error::new(std::io::ErrorKind::InvalidData, why))
+                })?;
+                self.write_line(&line)
+            }
+            RecordFormat::Csv => match report {
+                Report::Raw(raw_report) if raw_report.channel == 0 => {
+                    let mut row = format!(
+                        "{},{},{},{}",
+                        raw_report.timestamp,
+                        raw_report.brightness,
+                        raw_report.audio,
+                        u8::from(raw_report.trigger)
+                    );
+                    if self.slew {
+                        let slew = self
+                            .last_raw
+                            .map_or(0, |previous| slew(&previous, raw_report));
+                        _ = write!(row, ",{slew}");
+                    }
+                    self.last_raw = Some(*raw_report);
+                    self.write_row(RowKind::Raw, &row)
+                }
+                Report::Summary(summary_report) => self.write_row(
+                    RowKind::Summary,
+                    &format!("{},{}", summary_report.delay, summary_report.threshold),
+                ),
+                Report::RawAudio(raw_audio) => {
+                    let mut row = raw_audio.timestamp.to_string();
+                    for amplitude in &raw_audio.amplitudes {
+                        _ = write!(row, ",{amplitude}");
+                    }
+                    self.write_row(RowKind::RawAudio, &row)
+                }
+                Report::AudioVideo(audio_video) => {
+                    // empty when not detected
+                    let delay = |delay: Option<u64>| {
+                        delay.map_or_else(String::new, |delay| delay.to_string())
+                    };
+                    self.write_row(
+                        RowKind::AudioVideo,
+                        &format!(
+                            "{},{},{}",
+                            delay(audio_video.video_delay),
+                            delay(audio_video.audio_delay),
+                            audio_video.threshold
+                        ),
+                    )
+                }
+                // device timestamps might have restarted
+                Report::Reconnected => {
+                    self.last_raw = None;
+                    Ok(())
+                }
+                _ => Ok(()),
+            },
+        }
+    }
+
+    fn write_row(&mut self, kind: RowKind, row: &str) -> Result<()> {
+        // rotation has to happen first, the header belongs to the new file
+        self.rotate_if_due()?;
+        let mut text = String::new();
+        if !self.headers[kind as usize] {
+            let header = match kind {
+                RowKind::Raw if self.slew => "timestamp,brightness,audio,trigger,slew",
+                RowKind::Raw => "timestamp,brightness,audio,trigger",
+                RowKind::Summary => "delay,threshold",
+                RowKind::RawAudio => "timestamp,amplitudes",
+                RowKind::AudioVideo => "video_delay,audio_delay,threshold",
+            };
+            text.push_str(header);
+            text.push('\n');
+            self.headers[kind as usize] = true;
+        }
+        text.push_str(row);
+        text.push('\n');
+        self.append(text.as_bytes())
+    }
+
+    // Writes only happen when data arrives, call this on a timer to keep the bound without it
+    pub fn flush_if_due(&mut self) -> Result<()> {
+        if self.last_flush.elapsed() >= self.flush_interval {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        _ = self.flush();
+    }
+}
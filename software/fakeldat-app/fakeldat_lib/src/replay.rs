@@ -0,0 +1,439 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+use crate::analysis::{parse_raw_row, parse_summary_row};
+use crate::{Command, FrameSize, Integrity, Report, ReportMode, Result, PROTOCOL_VERSION};
+
+// Pace of summary-only recordings, they have no timestamps to go by
+const SUMMARY_INTERVAL: Duration = Duration::from_millis(500);
+// Frames handed out per poll when replaying as fast as possible, keeps the report buffer from overflowing
+const FASTEST_CHUNK: usize = 1024;
+
+/// How quickly [`ReplayDevice`] hands out the recorded reports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReplaySpeed {
+    // as far apart as the device timestamps were
+    #[default]
+    Original,
+    // limited by how often the port is polled
+    Fastest,
+}
+
+struct State {
+    // unsealed frames and when they're due from the start
+    frames: VecDeque<(Duration, [u8; 16])>,
+    output: VecDeque<u8>,
+    started: Instant,
+    last_refill: Instant,
+    speed: ReplaySpeed,
+    integrity: Integrity,
+    // last payload of every set command, get commands are answered with it
+    settings: [Option<[u8; 2]>; 0x10],
+}
+
+impl State {
+    fn send(&mut self, mut frame: [u8; 16]) {
+        self.integrity.seal(&mut frame);
+        self.output.extend(frame);
+    }
+
+    fn release_due(&mut self) {
+        let due = match self.speed {
+            ReplaySpeed::Original => {
+                let elapsed = self.started.elapsed();
+                self.frames
+                    .iter()
+                    .position(|(at, _)| *at > elapsed)
+                    .unwrap_or(self.frames.len())
+            }
+            // a whole poll would otherwise go through the recording at once
+            ReplaySpeed::Fastest
+                if self.output.is_empty()
+                    && self.last_refill.elapsed() >= Duration::from_millis(1) =>
+            {
+                self.last_refill = Instant::now();
+                FASTEST_CHUNK.min(self.frames.len())
+            }
+            ReplaySpeed::Fastest => 0,
+        };
+        for (_, frame) in self.frames.drain(..due).collect::<Vec<_>>() {
+            self.send(frame);
+        }
+    }
+
+    // Answered like the firmware does, every command is echoed
+    fn handle_command(&mut self, mut frame: [u8; 16]) {
+        let id = frame[0];
+        let integrity = match Command::try_from(id) {
+            Ok(Command::SetIntegrity) => Integrity::try_from(frame[1]).ok(),
+            _ => None,
+        };
+        match id {
+            // sequence numbers aren't recorded, so they stay off
+            _ if id == Command::SetSequence as u8 => frame[1..=2].fill(0),
+            // frames are encoded in the standard size only
+            _ if id == Command::SetFrameSize as u8 || id == Command::GetFrameSize as u8 => {
+                frame[1..=2].copy_from_slice(&[FrameSize::Standard as u8, 0]);
+            }
+            // anything the recording could have used
+            _ if id == Command::Hello as u8 => {
+                frame[1..=2].copy_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+                frame[3..=6].copy_from_slice(&u32::MAX.to_le_bytes());
+            }
+            0x01..=0x0F => self.settings[usize::from(id)] = Some([frame[1], frame[2]]),
+            0x21..=0x2F => {
+                if let Some(setting) = self.settings[usize::from(id - 0x20)] {
+                    frame[1..=2].copy_from_slice(&setting);
+                }
+            }
+            _ => {}
+        }
+        frame[14..].fill(0);
+        self.send(frame);
+        // switches after answering, same as the firmware
+        if let Some(integrity) = integrity {
+            self.integrity = integrity;
+        }
+    }
+}
+
+/// Plays a recording back as if it came from a device, for [`crate::FakeLDAT::create`]
+/// Raw, summary, raw audio and audio-video reports are replayed, CSV rows or NDJSON lines
+#[derive(Clone)]
+pub struct ReplayDevice {
+    name: String,
+    state: Arc<Mutex<State>>,
+    timeout: Duration,
+}
+
+fn frame(command: Command) -> [u8; 16] {
+    let mut frame = [0; 16];
+    frame[0] = command as u8;
+    frame
+}
+
+// Device timestamp of reports which have one, the rest is encoded as is
+#[allow(clippy::cast_possible_truncation)]
+fn encode(report: &Report, poll_interval: u64) -> Vec<(Option<u64>, [u8; 16])> {
+    match report {
+        Report::Raw(raw_report) => {
+            let mut buf = frame(Command::ReportRaw);
+            buf[1..=8].copy_from_slice(&raw_report.timestamp.to_le_bytes());
+            buf[9..=10].copy_from_slice(&raw_report.brightness.to_le_bytes());
+            buf[11..=12].copy_from_slice(&raw_report.audio.to_le_bytes());
+            buf[13] = u8::from(raw_report.trigger) | raw_report.channel << 4;
+            vec![(Some(raw_report.timestamp), buf)]
+        }
+        Report::Summary(summary_report) => {
+            let mut buf = frame(Command::ReportSummary);
+            buf[1..=8].copy_from_slice(&summary_report.delay.to_le_bytes());
+            buf[9..=10].copy_from_slice(&summary_report.threshold.to_le_bytes());
+            buf[13] = 1;
+            if let Some(arm_id) = summary_report.arm_id {
+                buf[11..=12].copy_from_slice(&arm_id.to_le_bytes());
+                buf[13] |= 2;
+            }
+            vec![(None, buf)]
+        }
+        Report::SummaryAggregate(aggregate) => {
+            let delay = |delay: u64| u32::try_from(delay).unwrap_or(u32::MAX).to_le_bytes();
+            let mut buf = frame(Command::ReportSummaryAggregate);
+            buf[1] = aggregate.count;
+            buf[2..=5].copy_from_slice(&delay(aggregate.min));
+            buf[6..=9].copy_from_slice(&delay(aggregate.mean));
+            buf[10..=13].copy_from_slice(&delay(aggregate.max));
+            vec![(None, buf)]
+        }
+        // two samples fit a frame
+        Report::RawAudio(raw_audio) => (0..)
+            .zip(raw_audio.amplitudes.chunks(2))
+            .map(|(index, samples)| {
+                let timestamp = raw_audio.timestamp + index * 2 * poll_interval;
+                let mut buf = frame(Command::ReportRawAudio);
+                buf[1..=8].copy_from_slice(&timestamp.to_le_bytes());
+                for (slot, sample) in buf[9..=12].chunks_exact_mut(2).zip(samples) {
+                    slot.copy_from_slice(&sample.to_le_bytes());
+                }
+                buf[13] = samples.len() as u8;
+                (Some(timestamp), buf)
+            })
+            .collect(),
+        Report::AudioVideo(audio_video) => {
+            let delay = |delay: Option<u64>| {
+                delay
+                    .and_then(|delay| u32::try_from(delay).ok())
+                    .unwrap_or(u32::MAX)
+                    .to_le_bytes()
+            };
+            let mut buf = frame(Command::ReportAudioVideo);
+            buf[1..=4].copy_from_slice(&delay(audio_video.video_delay));
+            buf[5..=8].copy_from_slice(&delay(audio_video.audio_delay));
+            buf[9..=10].copy_from_slice(&audio_video.threshold.to_le_bytes());
+            vec![(None, buf)]
+        }
+        _ => Vec::new(),
+    }
+}
+
+// Settings the recording was most likely made with, answered until the host sets others
+#[allow(clippy::cast_possible_truncation)]
+fn guess_settings(reports: &[Report]) -> (u16, ReportMode) {
+    let mut deltas: Vec<u64> = reports
+        .iter()
+        .filter_map(|report| match report {
+            Report::Raw(raw_report) if raw_report.channel == 0 => Some(raw_report.timestamp),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .windows(2)
+        .map(|pair| pair[1].saturating_sub(pair[0]))
+        .filter(|&delta| delta > 0)
+        .collect();
+    deltas.sort_unstable();
+    let poll_rate = deltas.get(deltas.len() / 2).map_or(1000, |&delta| {
+        (1_000_000 / delta).min(u64::from(u16::MAX)) as u16
+    });
+    let has = |kind: fn(&Report) -> bool| reports.iter().any(kind);
+    let report_mode = match (
+        has(|report| matches!(report, Report::Raw(_))),
+        has(|report| matches!(report, Report::Summary(_))),
+    ) {
+        (true, true) => ReportMode::Combined,
+        (true, false) => ReportMode::Raw,
+        _ if has(|report| matches!(report, Report::AudioVideo(_))) => ReportMode::AudioVideo,
+        _ if has(|report| matches!(report, Report::RawAudio(_))) => ReportMode::RawAudio,
+        _ => ReportMode::Summary,
+    };
+    (poll_rate, report_mode)
+}
+
+impl ReplayDevice {
+    // Lines which are neither a report nor a measurement row are skipped, like comments and headers
+    pub fn open<P: AsRef<Path>>(path: P, speed: ReplaySpeed) -> Result<Self> {
+        let path = path.as_ref();
+        let reports: Vec<Report> = fs::read_to_string(path)?
+            .lines()
+            .filter_map(|line| {
+                if line.trim_start().starts_with('{') {
+                    serde_json::from_str(line).ok()
+                } else if let Some(summary_report) = parse_summary_row(line) {
+                    Some(Report::Summary(summary_report))
+                } else {
+                    parse_raw_row(line).map(Report::Raw)
+                }
+            })
+            .collect();
+        Ok(Self::from_reports(
+            path.display().to_string(),
+            &reports,
+            speed,
+        ))
+    }
+
+    pub fn from_reports(name: String, reports: &[Report], speed: ReplaySpeed) -> Self {
+        let (poll_rate, report_mode) = guess_settings(reports);
+        let poll_interval = 1_000_000 / u64::from(poll_rate.max(1));
+        let mut settings = [None; 0x10];
+        settings[Command::SetPollRate as usize] = Some(poll_rate.to_le_bytes());
+        settings[Command::SetReportMode as usize] = Some([report_mode as u8, 0]);
+        settings[Command::SetThreshold as usize] = Some(150i16.to_le_bytes());
+        // mouse, left button
+        settings[Command::SetAction as usize] = Some([0, 1]);
+
+        let frames: Vec<(Option<u64>, [u8; 16])> = reports
+            .iter()
+            .flat_map(|report| encode(report, poll_interval))
+            .collect();
+        let first = frames.iter().find_map(|(timestamp, _)| *timestamp);
+        let mut at = Duration::ZERO;
+        let frames = (0..)
+            .zip(frames)
+            .map(|(index, (timestamp, buf))| {
+                match (first, timestamp) {
+                    (Some(first), Some(timestamp)) => {
+                        at = Duration::from_micros(timestamp.saturating_sub(first));
+                    }
+                    // follows the report before it
+                    (Some(_), None) => {}
+                    (None, _) => at = SUMMARY_INTERVAL * index,
+                }
+                (at, buf)
+            })
+            .collect();
+
+        Self {
+            name,
+            state: Arc::new(Mutex::new(State {
+                frames,
+                output: VecDeque::new(),
+                started: Instant::now(),
+                last_refill: Instant::now(),
+                speed,
+                integrity: Integrity::Sum,
+                settings,
+            })),
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    // Nothing left to hand out
+    pub fn is_finished(&self) -> bool {
+        let state = self.state();
+        state.frames.is_empty() && state.output.is_empty()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Read for ReplayDevice {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut state = self.state();
+        state.release_due();
+        if state.output.is_empty() {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+        let count = buf.len().min(state.output.len());
+        for (slot, byte) in buf.iter_mut().zip(state.output.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for ReplayDevice {
+    // Whole frames only, the library never splits them
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut state = self.state();
+        for chunk in buf.chunks_exact(16) {
+            state.handle_command(chunk.try_into().unwrap());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn unsupported() -> serialport::Error {
+    serialport::Error::new(
+        serialport::ErrorKind::Io(std::io::ErrorKind::Unsupported),
+        "Not available when replaying",
+    )
+}
+
+impl SerialPort for ReplayDevice {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(115_200)
+    }
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(DataBits::Eight)
+    }
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(FlowControl::None)
+    }
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(Parity::None)
+    }
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(StopBits::One)
+    }
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+    // Any speed works, nothing goes over a wire
+    fn set_baud_rate(&mut self, _baud_rate: u32) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn set_data_bits(&mut self, _data_bits: DataBits) -> serialport::Result<()> {
+        Err(unsupported())
+    }
+    fn set_flow_control(&mut self, _flow_control: FlowControl) -> serialport::Result<()> {
+        Err(unsupported())
+    }
+    fn set_parity(&mut self, _parity: Parity) -> serialport::Result<()> {
+        Err(unsupported())
+    }
+    fn set_stop_bits(&mut self, _stop_bits: StopBits) -> serialport::Result<()> {
+        Err(unsupported())
+    }
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+    fn write_request_to_send(&mut self, _level: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn write_data_terminal_ready(&mut self, _level: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        let mut state = self.state();
+        state.release_due();
+        Ok(state.output.len().min(u32::MAX as usize) as u32)
+    }
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        if buffer_to_clear != ClearBuffer::Output {
+            self.state().output.clear();
+        }
+        Ok(())
+    }
+    // Both handles share the recording, like two handles of a real port
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(self.clone()))
+    }
+    fn set_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+    fn clear_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+}
+
+impl From<ReplayDevice> for Box<dyn SerialPort> {
+    fn from(value: ReplayDevice) -> Self {
+        Box::new(value)
+    }
+}
+
+impl std::fmt::Debug for ReplayDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplayDevice")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+// Opened like a device, so the rest of the library can't tell the difference
+impl crate::FakeLDAT {
+    pub fn replay<P: AsRef<Path>>(path: P, speed: ReplaySpeed) -> Result<Self> {
+        Self::create(ReplayDevice::open(path, speed)?.into())
+    }
+}
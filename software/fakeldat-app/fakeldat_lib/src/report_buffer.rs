@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+
+use crate::{FakeLDAT, Report};
+
+// Over a minute of raw reports at 1000 Hz
+pub const DEFAULT_REPORT_BUFFER_CAPACITY: usize = 65536;
+
+/// What happens to new reports when the consumer doesn't keep up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    #[default]
+    DropOldest,
+    DropNewest,
+    // new reports are dropped and polling returns Error::ReportBufferFull
+    Error,
+}
+
+// Reports waiting for take_report_buffer, bounded so a stalled consumer can't grow it forever
+pub(crate) struct ReportBuffer {
+    reports: VecDeque<Report>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    // reports ever removed from the front, keeps positions stable while waiting for a reply
+    removed: u64,
+    // full with the error policy since the last poll
+    overflowed: bool,
+}
+
+impl ReportBuffer {
+    pub(crate) fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            reports: VecDeque::new(),
+            capacity: capacity.max(1),
+            policy,
+            removed: 0,
+            overflowed: false,
+        }
+    }
+
+    // False when a report had to be discarded
+    pub(crate) fn push(&mut self, report: Report) -> bool {
+        if self.reports.len() < self.capacity {
+            self.reports.push_back(report);
+            return true;
+        }
+        match self.policy {
+            OverflowPolicy::DropOldest => {
+                self.reports.pop_front();
+                self.removed += 1;
+                self.reports.push_back(report);
+            }
+            OverflowPolicy::DropNewest => {}
+            OverflowPolicy::Error => self.overflowed = true,
+        }
+        false
+    }
+
+    pub(crate) fn take(&mut self) -> Vec<Report> {
+        self.removed += self.reports.len() as u64;
+        Vec::from(std::mem::take(&mut self.reports))
+    }
+
+    pub(crate) fn take_overflow(&mut self) -> bool {
+        std::mem::take(&mut self.overflowed)
+    }
+
+    // Position right after the last report, for find_from
+    pub(crate) fn end(&self) -> u64 {
+        self.removed + self.reports.len() as u64
+    }
+
+    // First match at or after the position is removed and returned
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn find_from<T>(
+        &mut self,
+        position: u64,
+        mut extract: impl FnMut(&Report) -> Option<T>,
+    ) -> Option<T> {
+        let start = position.saturating_sub(self.removed) as usize;
+        let (index, value) = self
+            .reports
+            .iter()
+            .enumerate()
+            .skip(start)
+            .find_map(|(index, report)| extract(report).map(|value| (index, value)))?;
+        self.reports.remove(index);
+        Some(value)
+    }
+
+    pub(crate) fn retain(&mut self, keep: impl FnMut(&mut Report) -> bool) {
+        self.reports.retain_mut(keep);
+    }
+}
+
+impl FakeLDAT {
+    // Reports already waiting are kept, the oldest ones go when they don't fit
+    pub fn set_report_buffer(&mut self, capacity: usize, policy: OverflowPolicy) {
+        let mut reports = self.report_buffer.take();
+        let mut report_buffer = ReportBuffer::new(capacity, policy);
+        reports.drain(..reports.len().saturating_sub(report_buffer.capacity));
+        report_buffer.reports = reports.into();
+        self.report_buffer = report_buffer;
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use crate::{
+    Baseline, ConnectionState, Edge, Error, FrameSize, GamepadButton, Integrity, KeyboardKey,
+    MouseButton, ReportMode, Result, SensorGain,
+};
+
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Part of JSON Schema needed to describe what the library serializes
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct Schema {
+    #[serde(rename = "$schema", skip_serializing_if = "Option::is_none")]
+    pub dialect: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<&'static str>,
+    #[serde(rename = "type", skip_serializing_if = "Vec::is_empty")]
+    pub kinds: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<u64>,
+    #[serde(rename = "enum", skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Box<Schema>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<&'static str, Schema>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<&'static str>,
+    #[serde(
+        rename = "additionalProperties",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub additional_properties: Option<bool>,
+    #[serde(rename = "oneOf", skip_serializing_if = "Vec::is_empty")]
+    pub one_of: Vec<Schema>,
+}
+
+impl Schema {
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|why| {
+            Error::IOError(std::io::Error::new(std::io::ErrorKind::InvalidData, why))
+        })
+    }
+}
+
+fn typed(kind: &'static str) -> Schema {
+    Schema {
+        kinds: vec![kind],
+        ..Schema::default()
+    }
+}
+
+fn integer(minimum: i64, maximum: u64) -> Schema {
+    Schema {
+        minimum: Some(minimum),
+        maximum: Some(maximum),
+        ..typed("integer")
+    }
+}
+
+fn unsigned(maximum: u64) -> Schema {
+    integer(0, maximum)
+}
+
+fn threshold() -> Schema {
+    integer(i16::MIN.into(), i16::MAX.unsigned_abs().into())
+}
+
+fn nullable(mut schema: Schema) -> Schema {
+    schema.kinds.push("null");
+    schema
+}
+
+// Unit enums are serialized as their variant names
+fn names<T: Debug>(values: &[T]) -> Schema {
+    Schema {
+        values: values.iter().map(|value| format!("{value:?}")).collect(),
+        ..typed("string")
+    }
+}
+
+fn object(properties: Vec<(&'static str, Schema)>) -> Schema {
+    Schema {
+        required: properties.iter().map(|(name, _)| *name).collect(),
+        properties: properties.into_iter().collect(),
+        additional_properties: Some(false),
+        ..typed("object")
+    }
+}
+
+// Externally tagged, { "Variant": content }
+fn variant(name: &'static str, content: Schema) -> Schema {
+    object(vec![(name, content)])
+}
+
+fn unit_variant(name: &str) -> Schema {
+    Schema {
+        values: vec![name.to_string()],
+        ..typed("string")
+    }
+}
+
+fn document(title: &'static str, description: &'static str, schema: Schema) -> Schema {
+    Schema {
+        dialect: Some(DIALECT),
+        title: Some(title),
+        description: Some(description),
+        ..schema
+    }
+}
+
+fn action_mode() -> Schema {
+    Schema {
+        one_of: vec![
+            variant("Mouse", names(&MouseButton::ALL)),
+            variant("Keyboard", names(&KeyboardKey::ALL)),
+            variant("Gamepad", names(&GamepadButton::ALL)),
+        ],
+        ..Schema::default()
+    }
+}
+
+fn profile() -> Schema {
+    object(vec![
+        ("poll_rate", unsigned(u16::MAX.into())),
+        ("report_mode", names(&ReportMode::ALL)),
+        ("threshold", threshold()),
+        ("action", action_mode()),
+    ])
+}
+
+// One Report per line, kept in sync with the Report enum
+#[allow(clippy::too_many_lines)]
+pub fn reports() -> Schema {
+    let u16_max = u64::from(u16::MAX);
+    document(
+        "Report",
+        "Single report from the device, one JSON document per line in NDJSON streams",
+        Schema {
+            one_of: vec![
+                variant(
+                    "Raw",
+                    object(vec![
+                        ("timestamp", unsigned(u64::MAX)),
+                        ("brightness", unsigned(u16_max)),
+                        ("audio", unsigned(u16_max)),
+                        ("trigger", typed("boolean")),
+                        ("channel", unsigned(15)),
+                    ]),
+                ),
+                variant(
+                    "Summary",
+                    object(vec![
+                        ("delay", unsigned(u64::MAX)),
+                        ("threshold", unsigned(u16_max)),
+                        ("arm_id", nullable(unsigned(u16_max))),
+                    ]),
+                ),
+                variant(
+                    "SummaryAggregate",
+                    object(vec![
+                        ("count", unsigned(u8::MAX.into())),
+                        ("min", unsigned(u64::MAX)),
+                        ("mean", unsigned(u64::MAX)),
+                        ("max", unsigned(u64::MAX)),
+                    ]),
+                ),
+                variant(
+                    "RawAudio",
+                    object(vec![
+                        ("timestamp", unsigned(u64::MAX)),
+                        (
+                            "amplitudes",
+                            Schema {
+                                items: Some(Box::new(unsigned(u16_max))),
+                                ..typed("array")
+                            },
+                        ),
+                    ]),
+                ),
+                variant(
+                    "AudioVideo",
+                    object(vec![
+                        ("video_delay", nullable(unsigned(u32::MAX.into()))),
+                        ("audio_delay", nullable(unsigned(u32::MAX.into()))),
+                        ("threshold", unsigned(u16_max)),
+                    ]),
+                ),
+                variant(
+                    "Telemetry",
+                    object(vec![
+                        ("timestamp", unsigned(u64::MAX)),
+                        (
+                            "temperature",
+                            integer(i16::MIN.into(), i16::MAX.unsigned_abs().into()),
+                        ),
+                        ("supply", unsigned(u16_max)),
+                    ]),
+                ),
+                variant("PollRate", unsigned(u16_max)),
+                variant("ReportMode", names(&ReportMode::ALL)),
+                variant("Threshold", threshold()),
+                variant("Action", action_mode()),
+                variant(
+                    "ActionTiming",
+                    object(vec![
+                        ("press_duration", unsigned(0x7FFF)),
+                        ("double_press", typed("boolean")),
+                    ]),
+                ),
+                variant(
+                    "AutoTriggerSettings",
+                    object(vec![
+                        ("min_interval", unsigned(u16_max)),
+                        ("max_interval", unsigned(u16_max)),
+                        ("remaining", unsigned(u16_max)),
+                    ]),
+                ),
+                variant(
+                    "DetectionMode",
+                    object(vec![
+                        ("edge", names(&Edge::ALL)),
+                        ("baseline", names(&Baseline::ALL)),
+                    ]),
+                ),
+                variant("SensorGain", names(&SensorGain::ALL)),
+                variant("Channels", integer(1, u8::MAX.into())),
+                variant("TelemetryInterval", unsigned(u16_max)),
+                variant("SummaryAggregation", unsigned(u8::MAX.into())),
+                variant("AutoTrigger", unsigned(u64::MAX)),
+                variant("MacroTrigger", unsigned(u64::MAX)),
+                unit_variant("ManualTrigger"),
+                variant("Integrity", names(&Integrity::ALL)),
+                variant("Sequence", typed("boolean")),
+                variant("RawBatching", typed("boolean")),
+                variant("BaudRate", unsigned(u32::MAX.into())),
+                variant("FrameSize", names(&FrameSize::ALL)),
+                variant("Clock", unsigned(u64::MAX)),
+                variant(
+                    "Hello",
+                    object(vec![
+                        ("protocol_version", unsigned(u16_max)),
+                        (
+                            "capabilities",
+                            object(
+                                [
+                                    "integrity",
+                                    "sequence",
+                                    "raw_batching",
+                                    "baud_rate",
+                                    "clock",
+                                    "action_timing",
+                                    "auto_trigger",
+                                    "detection_mode",
+                                    "sensor_gain",
+                                    "second_sensor",
+                                    "telemetry",
+                                    "raw_audio",
+                                    "audio_video",
+                                    "macro_trigger",
+                                    "heartbeat",
+                                    "extended_frames",
+                                    "arming",
+                                    "summary_aggregation",
+                                    "self_test",
+                                ]
+                                .into_iter()
+                                .map(|name| (name, typed("boolean")))
+                                .collect(),
+                            ),
+                        ),
+                    ]),
+                ),
+                variant(
+                    "SelfTest",
+                    object(vec![
+                        ("step_us", unsigned(u64::MAX)),
+                        ("detected_us", nullable(unsigned(u64::MAX))),
+                        ("sample_interval_us", unsigned(u64::MAX)),
+                    ]),
+                ),
+                variant("Armed", unsigned(u16_max)),
+                unit_variant("Disarmed"),
+                variant(
+                    "Dropped",
+                    object(vec![("missed", unsigned(u32::MAX.into()))]),
+                ),
+                variant(
+                    "GapDetected",
+                    object(vec![
+                        ("expected_us", unsigned(u64::MAX)),
+                        ("actual_us", unsigned(u64::MAX)),
+                    ]),
+                ),
+                unit_variant("Reconnected"),
+                unit_variant("Pong"),
+                variant("Connection", names(&ConnectionState::ALL)),
+            ],
+            ..Schema::default()
+        },
+    )
+}
+
+pub fn stats() -> Schema {
+    let delay = || nullable(unsigned(u64::MAX));
+    document(
+        "StatsSummary",
+        "Statistics of measured delays in microseconds, null until there is a delay",
+        object(vec![
+            ("count", unsigned(u64::MAX)),
+            ("mean", nullable(typed("number"))),
+            ("std_dev", nullable(typed("number"))),
+            ("min", delay()),
+            ("max", delay()),
+            ("p95", delay()),
+            ("last", delay()),
+        ]),
+    )
+}
+
+pub fn session() -> Schema {
+    let mut metadata = object(vec![
+        ("started", unsigned(u64::MAX)),
+        ("port", nullable(typed("string"))),
+        ("library_version", typed("string")),
+        ("profile", nullable(profile())),
+        (
+            "link",
+            object(vec![
+                ("integrity", names(&Integrity::ALL)),
+                ("negotiated", typed("boolean")),
+                ("sequence", typed("boolean")),
+                ("baud_rate", unsigned(u32::MAX.into())),
+            ]),
+        ),
+        // string values under any name
+        ("meta", typed("object")),
+    ]);
+    // older sessions don't have it
+    metadata.required.retain(|name| *name != "meta");
+    document(
+        "SessionMetadata",
+        "Describes where measured data came from",
+        metadata,
+    )
+}
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use crate::{Command, FakeLDAT, Report, Result};
+
+/// Result of [`FakeLDAT::self_test`], the device ran a synthetic brightness step through its own
+/// threshold detection instead of reading the sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SelfTest {
+    // when the synthetic step came after the simulated trigger
+    pub step_us: u64,
+    // None when the threshold never caught the step, like with a threshold of 0
+    pub detected_us: Option<u64>,
+    // the step is simulated at the poll rate, detection can't be more precise
+    pub sample_interval_us: u64,
+}
+
+impl SelfTest {
+    pub(crate) fn from_frame(buf: &[u8]) -> Self {
+        let detected = u32::from_le_bytes(buf[3..=6].try_into().unwrap());
+        Self {
+            step_us: u64::from(u16::from_le_bytes([buf[1], buf[2]])) * 1000,
+            // 0xFFFFFFFF when not detected
+            detected_us: (detected != u32::MAX).then_some(u64::from(detected)),
+            sample_interval_us: u64::from(u32::from_le_bytes(buf[7..=10].try_into().unwrap())),
+        }
+    }
+
+    // Caught within two samples of the step, one for the step falling between samples
+    // and one for the sample the threshold is compared on
+    pub fn passed(&self) -> bool {
+        self.detected_us.is_some_and(|detected| {
+            (self.step_us..=self.step_us + 2 * self.sample_interval_us).contains(&detected)
+        })
+    }
+}
+
+impl FakeLDAT {
+    /// Lets the device measure a synthetic step of the given delay, millisecond precision,
+    /// with the current poll rate, threshold and detection mode
+    ///
+    /// Simulated faster than real time, blocks until the answer.
+    /// `SelfTest::passed` tells if the unit measured what it should
+    pub fn self_test(&mut self, step: Duration) -> Result<SelfTest> {
+        let step_ms = u16::try_from(step.as_millis()).unwrap_or(u16::MAX);
+        self.send_command(Command::SelfTest, step_ms.to_le_bytes())?;
+        self.wait_for_report(
+            Command::SelfTest,
+            Duration::from_secs(1),
+            |report| match report {
+                Report::SelfTest(self_test) => Some(*self_test),
+                _ => None,
+            },
+        )
+    }
+}
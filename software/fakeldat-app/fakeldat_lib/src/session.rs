@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{Error, FakeLDAT, LinkInfo, Profile, Result};
+
+/// Describes where measured data came from, meant to be stored next to it
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SessionMetadata {
+    // seconds since the Unix epoch
+    pub started: u64,
+    pub port: Option<String>,
+    pub library_version: String,
+    // missing until the device reported every setting
+    pub profile: Option<Profile>,
+    pub link: LinkInfo,
+    // filled in by the user, like the display model, reports group sessions by these
+    #[serde(default)]
+    pub meta: BTreeMap<String, String>,
+}
+
+impl SessionMetadata {
+    // Stored next to the recording, with the same name
+    pub fn sidecar_path<P: AsRef<Path>>(recording: P) -> PathBuf {
+        recording.as_ref().with_extension("json")
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        serde_json::from_str(&fs::read_to_string(path)?).map_err(|why| {
+            Error::IOError(std::io::Error::new(std::io::ErrorKind::InvalidData, why))
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|why| {
+            Error::IOError(std::io::Error::new(std::io::ErrorKind::InvalidData, why))
+        })?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+impl FakeLDAT {
+    pub fn session_metadata(&self) -> SessionMetadata {
+        let settings = self.settings;
+        let profile = match (
+            settings.poll_rate,
+            settings.report_mode,
+            settings.threshold,
+            settings.action,
+        ) {
+            (Some(poll_rate), Some(report_mode), Some(threshold), Some(action)) => Some(Profile {
+                poll_rate,
+                report_mode,
+                threshold,
+                action,
+            }),
+            _ => None,
+        };
+        SessionMetadata {
+            started: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            port: self.port.name(),
+            library_version: env!("CARGO_PKG_VERSION").to_string(),
+            profile,
+            link: self.link,
+            meta: BTreeMap::new(),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{FakeLDAT, Report, Result};
+
+impl FakeLDAT {
+    /// Sends the setting a report echoes, like the ones remote clients send as JSON.
+    /// Returns false without sending anything when the report isn't a setting
+    ///
+    /// Auto trigger settings with nothing `remaining` stop it. Integrity, baud rate and frame
+    /// size aren't covered, changing them would cut off whoever is relaying the setting
+    pub fn apply_setting(&mut self, setting: &Report) -> Result<bool> {
+        match *setting {
+            Report::PollRate(poll_rate) => self.set_poll_rate(poll_rate),
+            Report::ReportMode(report_mode) => self.set_report_mode(report_mode),
+            Report::Threshold(threshold) => self.set_threshold(threshold),
+            Report::Action(action) => self.set_action(action),
+            Report::ActionTiming(action_timing) => self.set_action_timing(action_timing),
+            Report::AutoTriggerSettings(auto_trigger) if auto_trigger.remaining == 0 => {
+                self.stop_auto_trigger()
+            }
+            Report::AutoTriggerSettings(auto_trigger) => self.start_auto_trigger(
+                auto_trigger.min_interval,
+                auto_trigger.max_interval,
+                auto_trigger.remaining,
+            ),
+            Report::DetectionMode(detection_mode) => self.set_detection_mode(detection_mode),
+            Report::SensorGain(sensor_gain) => self.set_sensor_gain(sensor_gain),
+            Report::Channels(channels) => self.set_channels(channels),
+            Report::TelemetryInterval(interval) => self.set_telemetry_interval(interval),
+            Report::SummaryAggregation(measurements) => self.set_summary_aggregation(measurements),
+            Report::RawBatching(enabled) => self.set_raw_batching(enabled),
+            _ => return Ok(false),
+        }?;
+        Ok(true)
+    }
+
+    /// Asks for the setting by the name of the report it comes back as, e.g. `"Threshold"`.
+    /// Returns false without sending anything for an unknown name
+    pub fn request_setting(&mut self, name: &str) -> Result<bool> {
+        match name {
+            "PollRate" => self.get_poll_rate(),
+            "ReportMode" => self.get_report_mode(),
+            "Threshold" => self.get_threshold(),
+            "Action" => self.get_action(),
+            "ActionTiming" => self.get_action_timing(),
+            "AutoTriggerSettings" => self.get_auto_trigger(),
+            "DetectionMode" => self.get_detection_mode(),
+            "SensorGain" => self.get_sensor_gain(),
+            "Channels" => self.get_channels(),
+            "TelemetryInterval" => self.get_telemetry_interval(),
+            "SummaryAggregation" => self.get_summary_aggregation(),
+            "RawBatching" => self.get_raw_batching(),
+            "Integrity" => self.get_integrity(),
+            "BaudRate" => self.get_baud_rate(),
+            "FrameSize" => self.get_frame_size(),
+            _ => return Ok(false),
+        }?;
+        Ok(true)
+    }
+}
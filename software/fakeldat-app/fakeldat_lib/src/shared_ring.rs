@@ -0,0 +1,142 @@
+use std::{
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use memmap2::MmapMut;
+
+use crate::{Error, FakeLDAT, RawReport, Report, Result};
+
+// Layout, all values little endian
+// Header, 64 bytes:
+//   0  u32  magic "FLDR"
+//   4  u32  layout version
+//   8  u32  capacity, number of slots
+//   12 u32  slot size in bytes
+//   16 u64  samples written so far, sample n is in slot n % capacity
+//   24      reserved
+// Slots follow the header, 16 bytes each:
+//   0  u64  timestamp in microseconds
+//   8  u16  brightness
+//   10 u16  audio
+//   12 u8   trigger
+//   13 u8   channel
+//   14      padding
+// A slot is written before the counter gets increased, a reader copying slot n should check
+// that the counter is still below n + capacity afterwards, otherwise the slot got overwritten
+pub const SHARED_RING_MAGIC: [u8; 4] = *b"FLDR";
+pub const SHARED_RING_VERSION: u32 = 1;
+pub const SHARED_RING_HEADER_SIZE: usize = 64;
+pub const SHARED_RING_SLOT_SIZE: usize = 16;
+const WRITTEN_OFFSET: usize = 16;
+
+/// Raw reports published into a memory mapped file for external readers
+pub struct SharedRing {
+    map: MmapMut,
+    path: PathBuf,
+    capacity: u64,
+    written: u64,
+    // keeps the mapping backed
+    _file: File,
+}
+
+impl SharedRing {
+    // Ends up in /dev/shm on Linux so it never touches the disk, temporary directory elsewhere
+    pub fn path_for(name: &str) -> PathBuf {
+        let shm = Path::new("/dev/shm");
+        if shm.is_dir() {
+            shm.join(name)
+        } else {
+            std::env::temp_dir().join(name)
+        }
+    }
+
+    pub fn create(name: &str, capacity: u32) -> Result<Self> {
+        if capacity == 0 {
+            return Err(Error::IOError(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "shared ring capacity can't be 0",
+            )));
+        }
+        let path = Self::path_for(name);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        let size = SHARED_RING_HEADER_SIZE + capacity as usize * SHARED_RING_SLOT_SIZE;
+        file.set_len(size as u64)?;
+        // the file is only resized by us, readers map it read-only
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        map[0..4].copy_from_slice(&SHARED_RING_MAGIC);
+        map[4..8].copy_from_slice(&SHARED_RING_VERSION.to_le_bytes());
+        map[8..12].copy_from_slice(&capacity.to_le_bytes());
+        #[allow(clippy::cast_possible_truncation)]
+        map[12..16].copy_from_slice(&(SHARED_RING_SLOT_SIZE as u32).to_le_bytes());
+        let mut ring = Self {
+            map,
+            path,
+            capacity: u64::from(capacity),
+            written: 0,
+            _file: file,
+        };
+        ring.written_counter().store(0, Ordering::Release);
+        Ok(ring)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub const fn written(&self) -> u64 {
+        self.written
+    }
+
+    // Mappings are page aligned so the counter is 8 byte aligned, the map outlives the reference
+    #[allow(clippy::cast_ptr_alignment)]
+    fn written_counter(&mut self) -> &AtomicU64 {
+        unsafe { AtomicU64::from_ptr(self.map.as_mut_ptr().add(WRITTEN_OFFSET).cast::<u64>()) }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn push(&mut self, report: &RawReport) {
+        let offset = SHARED_RING_HEADER_SIZE
+            + (self.written % self.capacity) as usize * SHARED_RING_SLOT_SIZE;
+        let slot = &mut self.map[offset..offset + SHARED_RING_SLOT_SIZE];
+        slot[0..8].copy_from_slice(&report.timestamp.to_le_bytes());
+        slot[8..10].copy_from_slice(&report.brightness.to_le_bytes());
+        slot[10..12].copy_from_slice(&report.audio.to_le_bytes());
+        slot[12] = u8::from(report.trigger);
+        slot[13] = report.channel;
+        self.written += 1;
+        let written = self.written;
+        self.written_counter().store(written, Ordering::Release);
+    }
+}
+
+impl Drop for SharedRing {
+    // Readers which already mapped it keep their copy
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl FakeLDAT {
+    // Every raw report from now on also lands in the ring, replaces the previous one
+    pub fn publish_shared_ring(&mut self, name: &str, capacity: u32) -> Result<&Path> {
+        let ring = self.shared_ring.insert(SharedRing::create(name, capacity)?);
+        Ok(ring.path())
+    }
+
+    pub fn stop_shared_ring(&mut self) {
+        self.shared_ring = None;
+    }
+
+    pub(crate) fn publish_report(&mut self, report: &Report) {
+        if let (Report::Raw(raw_report), Some(ring)) = (report, &mut self.shared_ring) {
+            ring.push(raw_report);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use std::io::Write;
+
+use crate::log::debug;
+use crate::{FakeLDAT, Result};
+
+impl FakeLDAT {
+    // Waits for the commands sent so far to go out, then lets go of the port
+    // Both handles are gone once this returns, so another program can open it right away
+    pub fn close(mut self) -> Result<()> {
+        self.release()
+    }
+
+    // Done only once, a closed or lost device has nothing to flush
+    pub(crate) fn release(&mut self) -> Result<()> {
+        if !self.connected {
+            return Ok(());
+        }
+        self.connected = false;
+        self.port.flush()?;
+        // Windows keeps the port busy for a while when it's dropped with DTR still set
+        self.port.write_data_terminal_ready(false)?;
+        debug!("port released");
+        Ok(())
+    }
+}
+
+impl Drop for FakeLDAT {
+    // Best effort, errors can't go anywhere from here
+    fn drop(&mut self) {
+        _ = self.release();
+    }
+}
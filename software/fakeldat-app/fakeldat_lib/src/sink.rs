@@ -0,0 +1,72 @@
+use std::sync::mpsc::Sender;
+
+use crate::log::warning;
+use crate::{Error, FakeLDAT, Recorder, Report, Result, Warning};
+
+/// Receives every report which passed the filter, next to the report buffer instead of taking
+/// from it, so a chart, a recording and the stats can all see the same data
+///
+/// Runs on the polling thread like [`FakeLDAT::on_report`], keep it quick
+pub trait ReportSink: Send {
+    // An error removes the sink, it shows up as Warning::SinkRemoved
+    fn report(&mut self, report: &Report) -> Result<()>;
+}
+
+/// Returned by [`FakeLDAT::add_sink`] to remove the sink again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SinkId(u32);
+
+impl std::fmt::Display for SinkId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl ReportSink for Recorder {
+    fn report(&mut self, report: &Report) -> Result<()> {
+        self.write_report(report)
+    }
+}
+
+// For consumers on another thread, dropping the receiver removes the sink
+impl ReportSink for Sender<Report> {
+    fn report(&mut self, report: &Report) -> Result<()> {
+        self.send(report.clone()).map_err(|_| {
+            Error::IOError(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "Report receiver dropped",
+            ))
+        })
+    }
+}
+
+impl FakeLDAT {
+    // Sinks get the reports in the order they were added
+    pub fn add_sink(&mut self, sink: Box<dyn ReportSink>) -> SinkId {
+        self.next_sink_id += 1;
+        let id = SinkId(self.next_sink_id);
+        self.sinks.push((id, sink));
+        id
+    }
+
+    // None when it was removed already
+    pub fn remove_sink(&mut self, id: SinkId) -> Option<Box<dyn ReportSink>> {
+        let index = self.sinks.iter().position(|(sink_id, _)| *sink_id == id)?;
+        Some(self.sinks.remove(index).1)
+    }
+
+    pub(crate) fn publish_to_sinks(&mut self, report: &Report) {
+        let mut failed = Vec::new();
+        self.sinks.retain_mut(|(id, sink)| {
+            let kept = sink.report(report).is_ok();
+            if !kept {
+                warning!(%id, "report sink failed, removed");
+                failed.push(*id);
+            }
+            kept
+        });
+        for id in failed {
+            self.warn(Warning::SinkRemoved { id });
+        }
+    }
+}
@@ -0,0 +1,107 @@
+/// Statistics of measured delays in microseconds
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    sorted: Vec<u64>,
+    sum: u64,
+    last: Option<u64>,
+}
+
+impl Stats {
+    pub fn push(&mut self, delay: u64) {
+        let index = self.sorted.partition_point(|&value| value < delay);
+        self.sorted.insert(index, delay);
+        self.sum += delay;
+        self.last = Some(delay);
+    }
+
+    pub fn count(&self) -> usize {
+        self.sorted.len()
+    }
+
+    pub const fn last(&self) -> Option<u64> {
+        self.last
+    }
+
+    pub fn min(&self) -> Option<u64> {
+        self.sorted.first().copied()
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        self.sorted.last().copied()
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn mean(&self) -> Option<f64> {
+        (!self.sorted.is_empty()).then(|| self.sum as f64 / self.sorted.len() as f64)
+    }
+
+    // Sample standard deviation
+    #[allow(clippy::cast_precision_loss)]
+    pub fn std_dev(&self) -> Option<f64> {
+        let mean = self.mean()?;
+        let squares: f64 = self
+            .sorted
+            .iter()
+            .map(|&delay| (delay as f64 - mean).powi(2))
+            .sum();
+        Some((squares / (self.sorted.len().max(2) - 1) as f64).sqrt())
+    }
+
+    // Nearest-rank percentile, from 0 to 100
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn percentile(&self, percentile: f64) -> Option<u64> {
+        let rank = (percentile / 100.0 * self.sorted.len() as f64).ceil() as usize;
+        self.sorted
+            .get(rank.clamp(1, self.sorted.len().max(1)) - 1)
+            .copied()
+    }
+
+    pub fn median(&self) -> Option<u64> {
+        self.percentile(50.0)
+    }
+
+    // Sorted from the lowest
+    pub fn delays(&self) -> &[u64] {
+        &self.sorted
+    }
+}
+
+impl FromIterator<u64> for Stats {
+    fn from_iter<T: IntoIterator<Item = u64>>(iter: T) -> Self {
+        let mut stats = Self::default();
+        for delay in iter {
+            stats.push(delay);
+        }
+        stats
+    }
+}
+
+/// Snapshot of [`Stats`] for machine-readable output, delays in microseconds
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StatsSummary {
+    pub count: usize,
+    pub mean: Option<f64>,
+    pub std_dev: Option<f64>,
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+    pub p95: Option<u64>,
+    pub last: Option<u64>,
+}
+
+impl Stats {
+    pub fn summary(&self) -> StatsSummary {
+        StatsSummary {
+            count: self.count(),
+            mean: self.mean(),
+            std_dev: self.std_dev(),
+            min: self.min(),
+            max: self.max(),
+            p95: self.percentile(95.0),
+            last: self.last(),
+        }
+    }
+}
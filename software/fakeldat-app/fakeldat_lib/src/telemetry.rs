@@ -0,0 +1,54 @@
+use std::fmt::Display;
+
+use crate::{Command, FakeLDAT, Result};
+
+/// Health of the device itself, slow drifts of the sensor readings tend to follow these
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Telemetry {
+    // device time of the measurement
+    pub timestamp: u64,
+    // RP2040 internal sensor in hundredths of a degree Celsius
+    pub temperature: i16,
+    // supply voltage in millivolts
+    pub supply: u16,
+}
+
+impl Telemetry {
+    pub fn temperature_celsius(&self) -> f64 {
+        f64::from(self.temperature) / 100.0
+    }
+    pub fn supply_volts(&self) -> f64 {
+        f64::from(self.supply) / 1000.0
+    }
+}
+
+impl Display for Telemetry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.1} °C, {:.2} V",
+            self.temperature_celsius(),
+            self.supply_volts()
+        )
+    }
+}
+
+impl FakeLDAT {
+    // Last telemetry report received, either requested or sent on the interval
+    pub const fn telemetry(&self) -> Option<Telemetry> {
+        self.telemetry
+    }
+
+    // Answered with a single telemetry report
+    pub fn request_telemetry(&mut self) -> Result<()> {
+        self.send_command(Command::ReportTelemetry, [0, 0])
+    }
+
+    // Device sends telemetry on its own every so many seconds, 0 turns it off
+    pub fn set_telemetry_interval(&mut self, seconds: u16) -> Result<()> {
+        self.send_setting(Command::SetTelemetryInterval, &seconds.to_le_bytes())
+    }
+    pub fn get_telemetry_interval(&mut self) -> Result<()> {
+        self.send_command(Command::GetTelemetryInterval, [0, 0])
+    }
+}
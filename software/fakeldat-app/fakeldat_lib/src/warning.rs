@@ -0,0 +1,106 @@
+use std::fmt::Display;
+
+use crate::log::warning;
+use crate::{Command, FakeLDAT, SinkId};
+
+// Oldest warnings go first when nobody takes them
+const MAX_WARNINGS: usize = 256;
+
+/// Non-fatal conditions, measuring goes on but the user should know about them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warning {
+    // frame failed the check, input got cleared to find the next frame boundary
+    ChecksumRecovered {
+        command: Command,
+        expected: u16,
+        calculated: u16,
+    },
+    // gap in the sequence numbers
+    FramesDropped {
+        missed: u32,
+    },
+    // reports the report buffer had no room for during a single poll
+    BufferTrimmed {
+        discarded: u64,
+    },
+    // device answered a setting with something else than requested, like a rounded poll rate
+    SettingMismatch {
+        command: Command,
+        sent: [u8; 2],
+        received: [u8; 2],
+    },
+    // the sink returned an error and doesn't get reports anymore
+    SinkRemoved {
+        id: SinkId,
+    },
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ChecksumRecovered {
+                command,
+                expected,
+                calculated,
+            } => write!(
+                f,
+                "Wrong checksum for {command}: {expected:x}, calculated {calculated:x}"
+            ),
+            Self::FramesDropped { missed } => write!(f, "Lost {missed} frames"),
+            Self::BufferTrimmed { discarded } => {
+                write!(f, "Report buffer full, {discarded} reports discarded")
+            }
+            Self::SettingMismatch {
+                command,
+                sent,
+                received,
+            } => write!(
+                f,
+                "{command}: sent {:x} {:x}, device answered {:x} {:x}",
+                sent[0], sent[1], received[0], received[1]
+            ),
+            Self::SinkRemoved { id } => write!(f, "Report sink {id} failed and was removed"),
+        }
+    }
+}
+
+impl FakeLDAT {
+    // Collected while polling, in the order they happened
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        Vec::from(std::mem::take(&mut self.warnings))
+    }
+
+    pub(crate) fn warn(&mut self, warning: Warning) {
+        if self.warnings.len() == MAX_WARNINGS {
+            self.warnings.pop_front();
+        }
+        self.warnings.push_back(warning);
+    }
+
+    // Remembers what a set command asked for, to compare with the answer
+    pub(crate) fn track_setting(&mut self, command: Command, payload: &[u8]) {
+        if let (Some(sent), [first, second, ..]) =
+            (self.sent_settings.get_mut(command as usize), payload)
+        {
+            *sent = Some([*first, *second]);
+        }
+    }
+
+    pub(crate) fn check_setting(&mut self, command: Command, received: [u8; 2]) {
+        let Some(sent) = self
+            .sent_settings
+            .get_mut(command as usize)
+            .and_then(Option::take)
+        else {
+            return;
+        };
+        if sent != received {
+            warning!(%command, ?sent, ?received, "setting not applied as sent");
+            self.warn(Warning::SettingMismatch {
+                command,
+                sent,
+                received,
+            });
+        }
+    }
+}
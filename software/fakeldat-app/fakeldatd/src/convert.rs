@@ -0,0 +1,221 @@
+use fakeldat_lib::{
+    ActionMode, ActionTiming, Baseline, ConnectionState, DetectionMode, Edge, Profile, Report,
+    ReportMode, SensorGain,
+};
+use tonic::Status;
+
+use crate::proto::{self, report, setting};
+
+fn invalid<T: TryFrom<u8>>(value: impl TryInto<u8>, name: &str) -> Result<T, Status> {
+    value
+        .try_into()
+        .ok()
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| Status::invalid_argument(format!("Invalid {name}")))
+}
+
+fn narrow<T: TryFrom<u32>>(value: u32, name: &str) -> Result<T, Status> {
+    T::try_from(value).map_err(|_| Status::invalid_argument(format!("{name} out of range")))
+}
+
+// Enums share the numbering of the device protocol
+fn report_mode(value: i32) -> Result<ReportMode, Status> {
+    invalid(value, "report mode")
+}
+
+fn action(action: Option<proto::Action>) -> Result<ActionMode, Status> {
+    let action = action.ok_or_else(|| Status::invalid_argument("Missing action"))?;
+    let (Ok(kind), Ok(key)) = (u8::try_from(action.kind), u8::try_from(action.key)) else {
+        return Err(Status::invalid_argument("Invalid action"));
+    };
+    ActionMode::try_from(kind, key).map_err(|_| Status::invalid_argument("Invalid action"))
+}
+
+fn to_action(action: ActionMode) -> proto::Action {
+    let key = match action {
+        ActionMode::Mouse(button) => button as u8,
+        ActionMode::Keyboard(key) => key as u8,
+        ActionMode::Gamepad(button) => button as u8,
+    };
+    proto::Action {
+        kind: i32::from(u8::from(action)),
+        key: u32::from(key),
+    }
+}
+
+// Setting reports are what the device echoes, the same variants describe a change
+pub fn setting(setting: proto::Setting) -> Result<Report, Status> {
+    let value = setting
+        .value
+        .ok_or_else(|| Status::invalid_argument("Missing setting"))?;
+    Ok(match value {
+        setting::Value::PollRate(poll_rate) => Report::PollRate(narrow(poll_rate, "Poll rate")?),
+        setting::Value::ReportMode(mode) => Report::ReportMode(report_mode(mode)?),
+        setting::Value::Threshold(threshold) => Report::Threshold(
+            i16::try_from(threshold)
+                .map_err(|_| Status::invalid_argument("Threshold out of range"))?,
+        ),
+        setting::Value::Action(value) => Report::Action(action(Some(value))?),
+        setting::Value::ActionTiming(timing) => Report::ActionTiming(ActionTiming {
+            press_duration: narrow(timing.press_duration, "Press duration")?,
+            double_press: timing.double_press,
+        }),
+        setting::Value::AutoTrigger(auto_trigger) => {
+            Report::AutoTriggerSettings(fakeldat_lib::AutoTrigger {
+                min_interval: narrow(auto_trigger.min_interval, "Minimal interval")?,
+                max_interval: narrow(auto_trigger.max_interval, "Maximal interval")?,
+                remaining: narrow(auto_trigger.remaining, "Count")?,
+            })
+        }
+        setting::Value::DetectionMode(detection_mode) => Report::DetectionMode(DetectionMode {
+            edge: invalid::<Edge>(detection_mode.edge, "edge")?,
+            baseline: invalid::<Baseline>(detection_mode.baseline, "baseline")?,
+        }),
+        setting::Value::SensorGain(gain) => {
+            Report::SensorGain(invalid::<SensorGain>(gain, "sensor gain")?)
+        }
+        setting::Value::Channels(channels) => Report::Channels(narrow(channels, "Channels")?),
+        setting::Value::TelemetryInterval(interval) => {
+            Report::TelemetryInterval(narrow(interval, "Telemetry interval")?)
+        }
+        setting::Value::RawBatching(enabled) => Report::RawBatching(enabled),
+        setting::Value::SummaryAggregation(measurements) => {
+            Report::SummaryAggregation(narrow(measurements, "Summary aggregation")?)
+        }
+    })
+}
+
+pub fn profile(profile: proto::Profile) -> Result<Profile, Status> {
+    Ok(Profile {
+        poll_rate: narrow(profile.poll_rate, "Poll rate")?,
+        report_mode: report_mode(profile.report_mode)?,
+        threshold: i16::try_from(profile.threshold)
+            .map_err(|_| Status::invalid_argument("Threshold out of range"))?,
+        action: action(profile.action)?,
+    })
+}
+
+pub fn to_profile(profile: &Profile) -> proto::Profile {
+    proto::Profile {
+        poll_rate: u32::from(profile.poll_rate),
+        report_mode: profile.report_mode as i32,
+        threshold: i32::from(profile.threshold),
+        action: Some(to_action(profile.action)),
+    }
+}
+
+pub fn to_summary(summary: &fakeldat_lib::SummaryReport) -> proto::SummaryReport {
+    proto::SummaryReport {
+        delay: summary.delay,
+        threshold: u32::from(summary.threshold),
+        arm_id: summary.arm_id.map(u32::from),
+    }
+}
+
+// Link level reports like integrity or the clock stay with the daemon
+pub fn to_report(report: &Report) -> Option<proto::Report> {
+    let with_setting = |value| report::Value::Setting(proto::Setting { value: Some(value) });
+    let value = match report {
+        Report::Raw(raw_report) => report::Value::Raw(proto::RawReport {
+            timestamp: raw_report.timestamp,
+            brightness: u32::from(raw_report.brightness),
+            audio: u32::from(raw_report.audio),
+            trigger: raw_report.trigger,
+            channel: u32::from(raw_report.channel),
+        }),
+        Report::Summary(summary_report) => report::Value::Summary(to_summary(summary_report)),
+        Report::SummaryAggregate(aggregate) => {
+            report::Value::SummaryAggregate(proto::SummaryAggregate {
+                count: u32::from(aggregate.count),
+                min: aggregate.min,
+                mean: aggregate.mean,
+                max: aggregate.max,
+            })
+        }
+        Report::RawAudio(raw_audio) => report::Value::RawAudio(proto::RawAudioReport {
+            timestamp: raw_audio.timestamp,
+            amplitudes: raw_audio
+                .amplitudes
+                .iter()
+                .copied()
+                .map(u32::from)
+                .collect(),
+        }),
+        Report::AudioVideo(audio_video) => report::Value::AudioVideo(proto::AudioVideoReport {
+            video_delay: audio_video.video_delay,
+            audio_delay: audio_video.audio_delay,
+            threshold: u32::from(audio_video.threshold),
+        }),
+        Report::Telemetry(telemetry) => report::Value::Telemetry(proto::Telemetry {
+            timestamp: telemetry.timestamp,
+            temperature: i32::from(telemetry.temperature),
+            supply: u32::from(telemetry.supply),
+        }),
+        Report::PollRate(poll_rate) => {
+            with_setting(setting::Value::PollRate(u32::from(*poll_rate)))
+        }
+        Report::ReportMode(report_mode) => {
+            with_setting(setting::Value::ReportMode(*report_mode as i32))
+        }
+        Report::Threshold(threshold) => {
+            with_setting(setting::Value::Threshold(i32::from(*threshold)))
+        }
+        Report::Action(action) => with_setting(setting::Value::Action(to_action(*action))),
+        Report::ActionTiming(timing) => {
+            with_setting(setting::Value::ActionTiming(proto::ActionTiming {
+                press_duration: u32::from(timing.press_duration),
+                double_press: timing.double_press,
+            }))
+        }
+        Report::AutoTriggerSettings(auto_trigger) => {
+            with_setting(setting::Value::AutoTrigger(proto::AutoTrigger {
+                min_interval: u32::from(auto_trigger.min_interval),
+                max_interval: u32::from(auto_trigger.max_interval),
+                remaining: u32::from(auto_trigger.remaining),
+            }))
+        }
+        Report::DetectionMode(detection_mode) => {
+            with_setting(setting::Value::DetectionMode(proto::DetectionMode {
+                edge: detection_mode.edge as i32,
+                baseline: detection_mode.baseline as i32,
+            }))
+        }
+        Report::SensorGain(gain) => with_setting(setting::Value::SensorGain(*gain as u32)),
+        Report::Channels(channels) => with_setting(setting::Value::Channels(u32::from(*channels))),
+        Report::TelemetryInterval(interval) => {
+            with_setting(setting::Value::TelemetryInterval(u32::from(*interval)))
+        }
+        Report::RawBatching(enabled) => with_setting(setting::Value::RawBatching(*enabled)),
+        Report::SummaryAggregation(measurements) => {
+            with_setting(setting::Value::SummaryAggregation(u32::from(*measurements)))
+        }
+        Report::ManualTrigger => report::Value::ManualTrigger(proto::Empty {}),
+        Report::AutoTrigger(timestamp) => report::Value::AutoTrigger(*timestamp),
+        Report::Dropped { missed } => report::Value::Dropped(*missed),
+        Report::GapDetected {
+            expected_us,
+            actual_us,
+        } => report::Value::GapDetected(proto::Gap {
+            expected_us: *expected_us,
+            actual_us: *actual_us,
+        }),
+        Report::Reconnected => report::Value::Reconnected(proto::Empty {}),
+        Report::Connection(state) => report::Value::Connection(match state {
+            ConnectionState::Healthy => proto::ConnectionState::Healthy,
+            ConnectionState::Degraded => proto::ConnectionState::Degraded,
+            ConnectionState::Lost => proto::ConnectionState::Lost,
+        } as i32),
+        Report::MacroTrigger(_)
+        | Report::Integrity(_)
+        | Report::Sequence(_)
+        | Report::BaudRate(_)
+        | Report::FrameSize(_)
+        | Report::Clock(_)
+        | Report::Hello(_)
+        | Report::SelfTest(_)
+        | Report::Pong
+        | Report::Armed(_)
+        | Report::Disarmed => return None,
+    };
+    Some(proto::Report { value: Some(value) })
+}
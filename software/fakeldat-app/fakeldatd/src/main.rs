@@ -0,0 +1,201 @@
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+use clap::Parser;
+use fakeldat_lib::{Error, FakeLDAT, HeartbeatPolicy, Report};
+use tokio::sync::{broadcast, oneshot};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use tonic::{transport::Server, Request, Response, Status};
+
+use proto::device_server::{Device, DeviceServer};
+
+mod convert;
+
+pub mod proto {
+    tonic::include_proto!("fakeldat.v1");
+}
+
+#[derive(Parser)]
+struct Args {
+    /// Name of the port, i.e. /dev/ttyACM0 on Linux or COM1 on Windows
+    #[arg(short, long)]
+    port: String,
+    /// Address the gRPC service listens on
+    #[arg(long, default_value = "127.0.0.1:50051")]
+    listen: std::net::SocketAddr,
+    /// Reports kept for every stream which falls behind
+    #[arg(long, default_value_t = 65536)]
+    stream_buffer: usize,
+    /// Stream gap reports when raw samples are further apart than the poll rate allows
+    #[arg(long)]
+    detect_gaps: bool,
+}
+
+// Runs on the thread owning the device, between polls
+type Job = Box<dyn FnOnce(&mut FakeLDAT) + Send>;
+
+struct Daemon {
+    jobs: mpsc::Sender<Job>,
+    reports: broadcast::Sender<Report>,
+}
+
+fn status(why: Error) -> Status {
+    match why {
+        Error::InvalidSetting(..) | Error::InvalidEnumConverion => {
+            Status::invalid_argument(format!("{why:?}"))
+        }
+        Error::CommandTimeout(_) => Status::deadline_exceeded(format!("{why:?}")),
+        Error::Unsupported(_) => Status::failed_precondition(format!("{why:?}")),
+        _ => Status::unavailable(format!("{why:?}")),
+    }
+}
+
+impl Daemon {
+    async fn run<T, F>(&self, job: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut FakeLDAT) -> fakeldat_lib::Result<T> + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        self.jobs
+            .send(Box::new(move |fakeldat| {
+                _ = sender.send(job(fakeldat));
+            }))
+            .map_err(|_| Status::unavailable("Device is gone"))?;
+        receiver
+            .await
+            .map_err(|_| Status::unavailable("Device is gone"))?
+            .map_err(status)
+    }
+}
+
+#[tonic::async_trait]
+impl Device for Daemon {
+    async fn set_setting(
+        &self,
+        request: Request<proto::Setting>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let setting = convert::setting(request.into_inner())?;
+        // convert::setting only gives the ones the device accepts
+        self.run(move |fakeldat| fakeldat.apply_setting(&setting).map(drop))
+            .await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn get_profile(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<proto::Profile>, Status> {
+        let profile = self
+            .run(|fakeldat| fakeldat.read_profile(Duration::from_secs(1)))
+            .await?;
+        Ok(Response::new(convert::to_profile(&profile)))
+    }
+
+    async fn apply_profile(
+        &self,
+        request: Request<proto::Profile>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let profile = convert::profile(request.into_inner())?;
+        self.run(move |fakeldat| fakeldat.apply_profile(&profile))
+            .await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn manual_trigger(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        self.run(FakeLDAT::manual_trigger).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    // Streams pause for the length of the sequence, the device is busy with it
+    async fn run_trigger_sequence(
+        &self,
+        request: Request<proto::TriggerSequenceRequest>,
+    ) -> Result<Response<proto::TriggerSequenceResult>, Status> {
+        let request = request.into_inner();
+        let result = self
+            .run(move |fakeldat| {
+                fakeldat.run_trigger_sequence(
+                    request.count as usize,
+                    Duration::from_micros(request.interval_us),
+                    Duration::from_micros(request.jitter_us),
+                )
+            })
+            .await?;
+        Ok(Response::new(proto::TriggerSequenceResult {
+            reports: result.reports.iter().map(convert::to_summary).collect(),
+            missed: u32::try_from(result.missed).unwrap_or(u32::MAX),
+        }))
+    }
+
+    type StreamReportsStream = Pin<Box<dyn Stream<Item = Result<proto::Report, Status>> + Send>>;
+
+    async fn stream_reports(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<Self::StreamReportsStream>, Status> {
+        let stream =
+            BroadcastStream::new(self.reports.subscribe()).filter_map(|report| match report {
+                Ok(report) => convert::to_report(&report).map(Ok),
+                Err(why) => Some(Err(Status::data_loss(why.to_string()))),
+            });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+// Owns the device, jobs run between polls and every report goes to the streams
+fn serve_device(
+    mut fakeldat: FakeLDAT,
+    jobs: &mpsc::Receiver<Job>,
+    reports: &broadcast::Sender<Report>,
+) -> Result<(), Error> {
+    loop {
+        for job in jobs.try_iter() {
+            job(&mut fakeldat);
+        }
+        fakeldat.poll_bulk_data()?;
+        let Some(buffer) = fakeldat.take_report_buffer() else {
+            sleep(Duration::from_millis(1));
+            continue;
+        };
+        for report in buffer {
+            // nobody listening is fine
+            _ = reports.send(report);
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let mut fakeldat = FakeLDAT::builder()
+        .path(args.port)
+        .timeout(Duration::from_secs(100_000))
+        .auto_reconnect(Duration::from_secs(1))
+        .open()
+        .map_err(|why| format!("Couldn't open the device: {why:?}"))?;
+    // older firmware can't answer pings, streams just won't see the state change then
+    _ = fakeldat.set_heartbeat(Some(HeartbeatPolicy::default()));
+    fakeldat.set_gap_detection(args.detect_gaps);
+
+    let (jobs, pending) = mpsc::channel();
+    let (reports, _) = broadcast::channel(args.stream_buffer);
+    let device_reports = reports.clone();
+    spawn(move || {
+        if let Err(why) = serve_device(fakeldat, &pending, &device_reports) {
+            eprintln!("Device stopped: {why:?}");
+        }
+    });
+
+    eprintln!("Listening on {}", args.listen);
+    Server::builder()
+        .add_service(DeviceServer::new(Daemon { jobs, reports }))
+        .serve(args.listen)
+        .await?;
+    Ok(())
+}
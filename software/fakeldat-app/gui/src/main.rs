@@ -2,8 +2,11 @@ use ui::UI;
 mod ui;
 
 fn main() -> iced::Result {
-    let program = iced::program("FakeLDAT", UI::update, UI::view)
+    let mut program = iced::program("FakeLDAT", UI::update, UI::view)
         .theme(UI::theme)
         .subscription(UI::subscription);
+    if let Some(size) = UI::saved_window_size() {
+        program = program.window_size(size);
+    }
     program.run()
 }
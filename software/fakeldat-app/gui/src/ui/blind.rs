@@ -0,0 +1,97 @@
+use super::enums::Condition;
+use fakeldat_lib::Stats;
+use rand::seq::SliceRandom;
+
+const ROUNDS: usize = 10;
+
+// Alternates between two conditions without showing which one is active until all rounds are done
+pub struct BlindTest {
+    order: Vec<Condition>,
+    guesses: Vec<Condition>,
+    delays: [Stats; 2],
+}
+
+impl BlindTest {
+    // Order comes from the session rng so journal replays match
+    pub fn new(rng: &mut impl rand::Rng) -> Self {
+        // same amount of rounds for both
+        let mut order: Vec<Condition> = (0..ROUNDS)
+            .map(|i| {
+                if i % 2 == 0 {
+                    Condition::A
+                } else {
+                    Condition::B
+                }
+            })
+            .collect();
+        order.shuffle(rng);
+        Self {
+            order,
+            guesses: Vec::with_capacity(ROUNDS),
+            delays: [Stats::default(), Stats::default()],
+        }
+    }
+
+    // None after the last round
+    pub fn current(&self) -> Option<Condition> {
+        self.order.get(self.guesses.len()).copied()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current().is_none()
+    }
+
+    pub fn push_delay(&mut self, delay: u64) {
+        if let Some(condition) = self.current() {
+            self.delays[condition as usize].push(delay);
+        }
+    }
+
+    // Returns the condition to apply for the next round
+    pub fn guess(&mut self, condition: Condition) -> Option<Condition> {
+        if !self.is_finished() {
+            self.guesses.push(condition);
+        }
+        self.current()
+    }
+
+    pub fn status(&self) -> String {
+        if !self.is_finished() {
+            return format!(
+                "Round {}/{ROUNDS}, which one does it feel like?",
+                self.guesses.len() + 1
+            );
+        }
+        let correct = self
+            .order
+            .iter()
+            .zip(&self.guesses)
+            .filter(|(condition, guess)| condition == guess)
+            .count();
+        let order = self
+            .order
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>()
+            .join(" ");
+        format!(
+            "{}\n{}\nGuessed {correct}/{ROUNDS} correctly, order: {order}",
+            Self::condition_stats(Condition::A, &self.delays[Condition::A as usize]),
+            Self::condition_stats(Condition::B, &self.delays[Condition::B as usize]),
+        )
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn condition_stats(condition: Condition, stats: &Stats) -> String {
+        match (stats.mean(), stats.std_dev(), stats.median()) {
+            (Some(mean), Some(std_dev), Some(median)) => format!(
+                "{condition}: {} delays, mean {:.2} ms, median {:.2} ms, std dev {:.2} ms",
+                stats.count(),
+                mean / 1000.0,
+                median as f64 / 1000.0,
+                std_dev / 1000.0,
+            ),
+            _ => format!("{condition}: no delays measured"),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use fakeldat_lib::{analysis::slew, RawReport};
+use iced::widget::canvas::Cache;
+use std::collections::{BTreeMap, VecDeque};
+
+// More points than that wouldn't be visible anyway
+pub const MAX_POINTS: usize = 4096;
+// Same as HISTORY_SIZE in the firmware, the relative threshold is added to their average
+const HISTORY_SIZE: usize = 150;
+
+// Decimated copy of the raw data, appended to as reports come in so drawing doesn't go over every sample
+pub struct ChartCache {
+    pub geometry: Cache,
+    pub brightness: VecDeque<(u64, u64)>,
+    pub audio: VecDeque<(u64, u64)>,
+    pub slew: VecDeque<(u64, i64)>,
+    // rolling average of the brightness, like the device compares against
+    pub baseline: VecDeque<(u64, u64)>,
+    // brightness of the sensors past the first one
+    pub channels: BTreeMap<u8, ChannelLine>,
+    stride: usize,
+    position: usize,
+    // every sample goes into the average, not only the kept ones
+    history: VecDeque<u16>,
+    history_sum: u64,
+}
+
+#[derive(Default)]
+pub struct ChannelLine {
+    pub points: VecDeque<(u64, u64)>,
+    position: usize,
+}
+
+impl ChartCache {
+    // Capacity of the raw data buffer decides how many samples are skipped
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            geometry: Cache::new(),
+            brightness: VecDeque::new(),
+            audio: VecDeque::new(),
+            slew: VecDeque::new(),
+            baseline: VecDeque::new(),
+            channels: BTreeMap::new(),
+            stride: capacity / MAX_POINTS + 1,
+            position: 0,
+            history: VecDeque::with_capacity(HISTORY_SIZE),
+            history_sum: 0,
+        }
+    }
+
+    // Previous report is needed for the slew
+    pub fn push(&mut self, previous: Option<&RawReport>, report: &RawReport) {
+        if report.channel != 0 {
+            let line = self.channels.entry(report.channel).or_default();
+            if line.position == 0 {
+                line.points
+                    .push_back((report.timestamp, report.brightness.into()));
+                // trimming follows the first channel, which might be disabled
+                if line.points.len() > MAX_POINTS {
+                    line.points.pop_front();
+                }
+            }
+            line.position = (line.position + 1) % self.stride;
+            return;
+        }
+        // the device averages the samples before the current one
+        let baseline = self.baseline_level();
+        if self.history.len() == HISTORY_SIZE {
+            self.history_sum -= self.history.pop_front().map_or(0, u64::from);
+        }
+        self.history.push_back(report.brightness);
+        self.history_sum += u64::from(report.brightness);
+        let keep = self.position == 0;
+        self.position = (self.position + 1) % self.stride;
+        if !keep {
+            return;
+        }
+        self.brightness
+            .push_back((report.timestamp, report.brightness.into()));
+        self.audio
+            .push_back((report.timestamp, report.audio.into()));
+        if let Some(baseline) = baseline {
+            self.baseline.push_back((report.timestamp, baseline));
+        }
+        if let Some(previous) = previous {
+            self.slew
+                .push_back((report.timestamp, i64::from(slew(previous, report))));
+        }
+    }
+
+    // Drops points older than the oldest sample still kept
+    pub fn trim(&mut self, oldest: u64) {
+        while self
+            .brightness
+            .front()
+            .is_some_and(|point| point.0 < oldest)
+        {
+            self.brightness.pop_front();
+        }
+        while self.audio.front().is_some_and(|point| point.0 < oldest) {
+            self.audio.pop_front();
+        }
+        while self.slew.front().is_some_and(|point| point.0 < oldest) {
+            self.slew.pop_front();
+        }
+        while self.baseline.front().is_some_and(|point| point.0 < oldest) {
+            self.baseline.pop_front();
+        }
+        for line in self.channels.values_mut() {
+            while line.points.front().is_some_and(|point| point.0 < oldest) {
+                line.points.pop_front();
+            }
+        }
+    }
+
+    // After the capacity changes
+    pub fn rebuild(&mut self, capacity: usize, data: &VecDeque<RawReport>) {
+        *self = Self::new(capacity);
+        for (previous, report) in std::iter::once(None).chain(data.iter().map(Some)).zip(data) {
+            self.push(previous, report);
+        }
+    }
+
+    // Average of the recent samples of the first channel
+    pub fn baseline_level(&self) -> Option<u64> {
+        u64::try_from(self.history.len())
+            .ok()
+            .filter(|&len| len > 0)
+            .map(|len| self.history_sum / len)
+    }
+
+    pub fn clear(&mut self) {
+        self.brightness.clear();
+        self.audio.clear();
+        self.slew.clear();
+        self.baseline.clear();
+        self.channels.clear();
+        self.position = 0;
+        self.history.clear();
+        self.history_sum = 0;
+        self.geometry.clear();
+    }
+}
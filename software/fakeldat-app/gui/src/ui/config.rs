@@ -0,0 +1,156 @@
+use super::enums::{AppTheme, SummarySource};
+use super::{invalid_data, DEFAULT_BIN_WIDTH, DEFAULT_WINDOW};
+use fakeldat_lib::{Error, Profile};
+use std::path::{Path, PathBuf};
+
+const VERSION: u32 = 1;
+
+// Everything needed to set up another machine the same way, stored as TOML
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AppConfig {
+    pub version: u32,
+    pub preferences: Preferences,
+    // applied to the device on import, missing when the action wasn't known yet
+    pub device: Option<Profile>,
+    pub blind_a: Option<Profile>,
+    pub blind_b: Option<Profile>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Preferences {
+    pub show_graph: bool,
+    pub show_percentiles: bool,
+    // missing from configurations exported before the histogram
+    #[serde(default)]
+    pub show_histogram: bool,
+    #[serde(default = "default_bin_width")]
+    pub histogram_bin_width: u16,
+    #[serde(default = "default_window")]
+    pub window: u8,
+    #[serde(default)]
+    pub auto_scale: bool,
+    pub show_slew: bool,
+    // kept last, it can end up as a table
+    pub summary_source: SummarySource,
+}
+
+const fn default_bin_width() -> u16 {
+    DEFAULT_BIN_WIDTH
+}
+
+const fn default_window() -> u8 {
+    DEFAULT_WINDOW
+}
+
+impl AppConfig {
+    pub fn new(
+        preferences: Preferences,
+        device: Option<Profile>,
+        blind_profiles: [Option<Profile>; 2],
+    ) -> Self {
+        Self {
+            version: VERSION,
+            preferences,
+            device,
+            blind_a: blind_profiles[0],
+            blind_b: blind_profiles[1],
+        }
+    }
+
+    pub const fn blind_profiles(&self) -> [Option<Profile>; 2] {
+        [self.blind_a, self.blind_b]
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let config: Self = toml::from_str(&std::fs::read_to_string(path).map_err(Error::IOError)?)
+            .map_err(invalid_data)?;
+        if config.version > VERSION {
+            return Err(invalid_data(format!(
+                "configuration version {} is newer than supported",
+                config.version
+            )));
+        }
+        Ok(config)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        std::fs::write(path, toml::to_string(self).map_err(invalid_data)?).map_err(Error::IOError)
+    }
+}
+
+// Application window in logical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+// Restored on the next start, unlike the exported configuration it belongs to this machine
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SessionConfig {
+    pub version: u32,
+    pub theme: AppTheme,
+    // last folder recorded to
+    pub record_dir: Option<PathBuf>,
+    pub preferences: Preferences,
+    // applied again after connecting
+    pub device: Option<Profile>,
+    // None until the window got resized
+    #[serde(default)]
+    pub window_size: Option<WindowSize>,
+}
+
+impl SessionConfig {
+    pub const fn new(
+        theme: AppTheme,
+        record_dir: Option<PathBuf>,
+        preferences: Preferences,
+        device: Option<Profile>,
+        window_size: Option<WindowSize>,
+    ) -> Self {
+        Self {
+            version: VERSION,
+            theme,
+            record_dir,
+            preferences,
+            device,
+            window_size,
+        }
+    }
+
+    // In the user's configuration folder, None without a home to put it in
+    pub fn path() -> Option<PathBuf> {
+        let env_path = |name: &str| std::env::var_os(name).map(PathBuf::from);
+        let dir = if cfg!(windows) {
+            env_path("APPDATA")
+        } else {
+            env_path("XDG_CONFIG_HOME")
+                .or_else(|| env_path("HOME").map(|home| home.join(".config")))
+        }?;
+        Some(dir.join("fakeldat").join("gui.toml"))
+    }
+
+    // None on the first start
+    pub fn load(path: &Path) -> Result<Option<Self>, Error> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(why) if why.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(why) => return Err(Error::IOError(why)),
+        };
+        let config: Self = toml::from_str(&contents).map_err(invalid_data)?;
+        if config.version > VERSION {
+            return Err(invalid_data(format!(
+                "configuration version {} is newer than supported",
+                config.version
+            )));
+        }
+        Ok(Some(config))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(Error::IOError)?;
+        }
+        std::fs::write(path, toml::to_string(self).map_err(invalid_data)?).map_err(Error::IOError)
+    }
+}
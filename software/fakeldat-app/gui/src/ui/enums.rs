@@ -1,32 +1,154 @@
-use fakeldat_lib::{KeyboardKey, MouseButton, ReportMode};
+use super::worker::DeviceEvent;
+use fakeldat_lib::{
+    analysis::DetectorKind, Baseline, GamepadButton, KeyboardKey, MouseButton, RecordFormat,
+    ReportMode, SensorGain,
+};
+use std::time::Instant;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum Message {
     Tick,
     RecordStart,
     RecordStop,
+    RecordFormatChanged(RecordingFormat),
     Clear,
+    PauseToggle,
     GraphToggle,
+    ThemeToggle,
+    // logical pixels, only remembered for the next start
+    WindowResized(u32, u32),
+    NotificationsClear,
+    PercentilesToggle,
+    HistogramToggle,
+    // microseconds
+    HistogramBinWidthChanged(u16),
+    ChartSettingsToggle,
+    // seconds
+    WindowChanged(u8),
+    AutoScaleToggle,
+    SlewToggle,
     ManualTrigger,
     PollRateChanged(PollRate),
     ReportModeChanged(ReportMode),
+    SensorGainChanged(SensorGain),
+    BaselineChanged(Baseline),
     ActionModeChanged(ActionType),
     ActionKeyChanged(u8),
     ThresholdChanged(i16),
     ThresholdReleased,
+    SummarySourceChanged(SummarySource),
+    LoopbackStart,
+    LoopbackStop,
+    LoopbackInput(bool),
+    // when the frame was asked for, only used right away
+    #[serde(skip)]
+    LoopbackFrame(Instant),
+    PlacementToggle,
+    BlindCapture(Condition),
+    BlindStart,
+    BlindGuess(Condition),
+    BlindStop,
+    TestSetupToggle,
+    TestShotsChanged(u16),
+    // milliseconds
+    TestMinIntervalChanged(u16),
+    TestMaxIntervalChanged(u16),
+    // seconds
+    TestCountdownChanged(u8),
+    TestStart,
+    TestStop,
+    // start and end as fractions of the plotting area width
+    SegmentSelected(f32, f32),
+    SegmentClear,
+    // cursor position as a fraction of the plotting area width and how much the range is scaled
+    ChartZoom(f32, f32),
+    // dragged distance as a fraction of the plotting area width
+    ChartPan(f32),
+    ViewReset,
+    SegmentExport(ExportFormat),
+    ResultsExport,
+    ConfigExport,
+    ConfigImport,
+    PortsRefresh,
+    PortSelected(String),
+    Connect,
+    Disconnect,
+    // from the worker, the journal keeps the reports on its own
+    #[serde(skip)]
+    Device(DeviceEvent),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ExportFormat {
+    Csv,
+    Png,
+}
+
+// Layout of the recordings, see RecordFormat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum RecordingFormat {
+    Csv,
+    Ndjson,
+}
+
+impl RecordingFormat {
+    pub const ALL: [Self; 2] = [Self::Csv, Self::Ndjson];
+
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+        }
+    }
+}
+
+impl From<RecordingFormat> for RecordFormat {
+    fn from(value: RecordingFormat) -> Self {
+        match value {
+            RecordingFormat::Csv => Self::Csv,
+            RecordingFormat::Ndjson => Self::Ndjson,
+        }
+    }
+}
+
+impl std::fmt::Display for RecordingFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Csv => write!(f, "CSV"),
+            Self::Ndjson => write!(f, "NDJSON"),
+        }
+    }
+}
+
+// Configurations compared in the blind test
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Condition {
+    A,
+    B,
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::A => write!(f, "A"),
+            Self::B => write!(f, "B"),
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct ActionKey {
     pub mouse: Option<MouseButton>,
     pub keyboard: Option<KeyboardKey>,
+    pub gamepad: Option<GamepadButton>,
 }
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum ActionType {
     Mouse,
     Keyboard,
+    Gamepad,
 }
 
 impl std::fmt::Display for ActionType {
@@ -34,11 +156,12 @@ impl std::fmt::Display for ActionType {
         match self {
             Self::Mouse => write!(f, "Mouse"),
             Self::Keyboard => write!(f, "Keyboard"),
+            Self::Gamepad => write!(f, "Gamepad"),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum PollRate {
     _500,
     _1000,
@@ -94,3 +217,43 @@ impl From<u16> for PollRate {
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum AppTheme {
+    Light,
+    Dark,
+}
+
+impl From<AppTheme> for iced::Theme {
+    fn from(value: AppTheme) -> Self {
+        match value {
+            AppTheme::Light => Self::Light,
+            AppTheme::Dark => Self::Dark,
+        }
+    }
+}
+
+// Where the summary delays come from, host uses the raw data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum SummarySource {
+    Device,
+    Host(DetectorKind),
+}
+
+impl SummarySource {
+    pub const ALL: [Self; 4] = [
+        Self::Device,
+        Self::Host(DetectorKind::Fixed),
+        Self::Host(DetectorKind::Adaptive),
+        Self::Host(DetectorKind::Derivative),
+    ];
+}
+
+impl std::fmt::Display for SummarySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Device => write!(f, "Device"),
+            Self::Host(detector) => write!(f, "Host, {detector}"),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use super::enums::Message;
+use fakeldat_lib::{Stats, SummaryReport};
+use plotters::coord::Shift;
+use plotters::element::{EmptyElement, PathElement, Rectangle, Text};
+use plotters::style::{Color, RGBColor, BLACK, BLUE, GREEN, MAGENTA, RED, WHITE};
+use plotters_iced::{Chart, ChartBuilder, DrawingArea, DrawingBackend};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+// Delays counted into bins, with the mean and percentiles marked on top
+pub struct HistogramChart<'a> {
+    pub data: &'a [(Duration, SummaryReport)],
+    // microseconds
+    pub bin_width: u16,
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn ms(delay: u64) -> f64 {
+    delay as f64 / 1000.0
+}
+
+impl Chart<Message> for HistogramChart<'_> {
+    type State = ();
+    fn draw_chart<DB: DrawingBackend>(&self, state: &Self::State, root: DrawingArea<DB, Shift>) {
+        _ = root.fill(&WHITE);
+        let builder = ChartBuilder::on(&root);
+        self.build_chart(state, builder);
+    }
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut builder: ChartBuilder<DB>) {
+        let bin_width = u64::from(self.bin_width.max(1));
+        let mut bins: BTreeMap<u64, u32> = BTreeMap::new();
+        for (_, summary) in self.data {
+            *bins.entry(summary.delay / bin_width).or_default() += 1;
+        }
+        let stats: Stats = self.data.iter().map(|(_, summary)| summary.delay).collect();
+        // delays in milliseconds
+        let min_x = bins.keys().next().map_or(0.0, |&bin| ms(bin * bin_width));
+        let max_x = bins
+            .keys()
+            .next_back()
+            .map_or(0.0, |&bin| ms((bin + 1) * bin_width))
+            .max(min_x + 1.0);
+        let max_y = f64::from(bins.values().copied().max().unwrap_or(1)) * 1.1;
+        let mut chart = builder
+            .set_all_label_area_size(45)
+            .top_x_label_area_size(20)
+            .build_cartesian_2d(min_x..max_x, 0.0..max_y)
+            .unwrap();
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .x_desc("Delay [ms]")
+            .y_desc("Measurements")
+            .draw()
+            .expect("Draw mesh");
+        chart
+            .draw_series(bins.iter().map(|(&bin, &count)| {
+                Rectangle::new(
+                    [
+                        (ms(bin * bin_width), f64::from(count)),
+                        (ms((bin + 1) * bin_width), 0.0),
+                    ],
+                    BLUE.mix(0.5).filled(),
+                )
+            }))
+            .expect("Draw bins");
+        let markers: [(&str, Option<f64>, RGBColor); 4] = [
+            ("mean", stats.mean().map(|mean| mean / 1000.0), GREEN),
+            ("p50", stats.percentile(50.0).map(ms), BLACK),
+            ("p95", stats.percentile(95.0).map(ms), RED),
+            ("p99", stats.percentile(99.0).map(ms), MAGENTA),
+        ];
+        for (name, delay, color) in markers {
+            let Some(delay) = delay else {
+                continue;
+            };
+            chart
+                .draw_series(std::iter::once(PathElement::new(
+                    vec![(delay, 0.0), (delay, max_y)],
+                    color.stroke_width(2),
+                )))
+                .expect("Draw marker")
+                .label(format!("{name}: {delay:.2} ms"))
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+            chart
+                .draw_series(std::iter::once(
+                    EmptyElement::at((delay, max_y)) + Text::new(name, (4, 4), ("sans-serif", 12)),
+                ))
+                .expect("Draw marker label");
+        }
+        chart
+            .configure_series_labels()
+            .background_style(WHITE)
+            .border_style(BLACK)
+            .draw()
+            .expect("Draw legend");
+    }
+}
@@ -0,0 +1,107 @@
+use super::enums::Message;
+use super::invalid_data;
+use fakeldat_lib::{Error, Report};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+// One JSON object per line, the seed always comes first
+#[derive(serde::Serialize)]
+enum EntryRef<'a> {
+    Seed(u64),
+    Message(&'a Message),
+    // everything taken from the device during one tick
+    Reports(&'a [Report]),
+}
+
+#[derive(serde::Deserialize)]
+pub enum Entry {
+    Seed(u64),
+    Message(Message),
+    Reports(Vec<Report>),
+}
+
+// Opt-in with --journal <path> to record or --replay <path> to play one back
+pub enum Journal {
+    Off,
+    Record(BufWriter<File>),
+    Replay(VecDeque<Entry>),
+}
+
+impl Journal {
+    // Returns the journal and the seed for everything random in the session
+    pub fn from_args() -> Result<(Self, u64), Error> {
+        let args: Vec<String> = std::env::args().collect();
+        let value_of = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|index| args.get(index + 1))
+        };
+        if let Some(path) = value_of("--replay") {
+            return Self::replay(Path::new(path));
+        }
+        let seed = rand::random();
+        match value_of("--journal") {
+            Some(path) => Ok((Self::record(Path::new(path), seed)?, seed)),
+            None => Ok((Self::Off, seed)),
+        }
+    }
+
+    fn record(path: &Path, seed: u64) -> Result<Self, Error> {
+        let mut journal = Self::Record(BufWriter::new(File::create(path).map_err(Error::IOError)?));
+        journal.write(&EntryRef::Seed(seed))?;
+        Ok(journal)
+    }
+
+    fn replay(path: &Path) -> Result<(Self, u64), Error> {
+        let reader = BufReader::new(File::open(path).map_err(Error::IOError)?);
+        let mut entries = reader
+            .lines()
+            .map(|line| serde_json::from_str(&line.map_err(Error::IOError)?).map_err(invalid_data))
+            .collect::<Result<VecDeque<Entry>, Error>>()?;
+        let Some(Entry::Seed(seed)) = entries.pop_front() else {
+            return Err(invalid_data("journal doesn't start with a seed"));
+        };
+        Ok((Self::Replay(entries), seed))
+    }
+
+    pub const fn is_replaying(&self) -> bool {
+        matches!(self, Self::Replay(_))
+    }
+
+    fn write(&mut self, entry: &EntryRef) -> Result<(), Error> {
+        if let Self::Record(ref mut file) = self {
+            serde_json::to_writer(&mut *file, entry).map_err(invalid_data)?;
+            // flushed every time so the journal survives a crash
+            file.write_all(b"\n")
+                .and_then(|()| file.flush())
+                .map_err(Error::IOError)?;
+        }
+        Ok(())
+    }
+
+    pub fn record_message(&mut self, message: &Message) -> Result<(), Error> {
+        self.write(&EntryRef::Message(message))
+    }
+
+    pub fn record_reports(&mut self, reports: &[Report]) -> Result<(), Error> {
+        self.write(&EntryRef::Reports(reports))
+    }
+
+    // Switches off after the last entry, which comes with true
+    pub fn next_entry(&mut self) -> Option<(Entry, bool)> {
+        let Self::Replay(ref mut entries) = self else {
+            return None;
+        };
+        let Some(entry) = entries.pop_front() else {
+            *self = Self::Off;
+            return None;
+        };
+        let last = entries.is_empty();
+        if last {
+            *self = Self::Off;
+        }
+        Some((entry, last))
+    }
+}
@@ -0,0 +1,160 @@
+use super::enums::Message;
+use iced::event::Status;
+use iced::mouse::{self, Cursor};
+use iced::widget::canvas::Event;
+use iced::{keyboard, Rectangle};
+use plotters::coord::Shift;
+use plotters::style::{BLACK, WHITE};
+use plotters_iced::{Chart, ChartBuilder, DrawingArea, DrawingBackend};
+use std::time::{Duration, Instant};
+
+const TRIALS: usize = 20;
+const INTERVAL: Duration = Duration::from_secs(1);
+
+// One flash, in microseconds
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct Trial {
+    // from the input reaching the app until iced asked for the frame showing it,
+    // the present time isn't exposed
+    pub to_redraw: Option<u64>,
+    // from the device pressing until the sensor saw the flash
+    pub total: Option<u64>,
+}
+
+// Area flashing on any input, the sensor pointed at it measures input to photon latency of this app
+pub struct Loopback {
+    flash: bool,
+    trials: Vec<Trial>,
+    input_at: Option<Instant>,
+    remaining: usize,
+    next_trigger: Instant,
+}
+
+impl Loopback {
+    pub fn new() -> Self {
+        Self {
+            flash: false,
+            trials: Vec::with_capacity(TRIALS),
+            input_at: None,
+            remaining: TRIALS,
+            next_trigger: Instant::now() + INTERVAL,
+        }
+    }
+
+    // Returns true when the next trial should be triggered
+    pub fn next_trial(&mut self) -> bool {
+        if self.remaining == 0 || Instant::now() < self.next_trigger {
+            return false;
+        }
+        self.remaining -= 1;
+        self.next_trigger = Instant::now() + INTERVAL;
+        true
+    }
+
+    pub fn set_flash(&mut self, flash: bool) {
+        if flash && !self.flash {
+            self.input_at = Some(Instant::now());
+            self.trials.push(Trial::default());
+        }
+        self.flash = flash;
+    }
+
+    // Rendering, presenting and the display all come after this, the input delivery before it
+    pub fn redraw_requested(&mut self, at: Instant) {
+        if let (Some(input_at), Some(trial)) = (self.input_at.take(), self.trials.last_mut()) {
+            trial.to_redraw = Some(micros(at.saturating_duration_since(input_at)));
+        }
+    }
+
+    pub const fn is_waiting_for_frame(&self) -> bool {
+        self.input_at.is_some()
+    }
+
+    // A delay without an input of its own still counts, it just can't be split
+    pub fn push_delay(&mut self, delay: u64) {
+        match self.trials.last_mut() {
+            Some(trial) if trial.total.is_none() => trial.total = Some(delay),
+            _ => self.trials.push(Trial {
+                to_redraw: None,
+                total: Some(delay),
+            }),
+        }
+    }
+
+    pub fn trials(&self) -> &[Trial] {
+        &self.trials
+    }
+
+    pub fn into_trials(self) -> Vec<Trial> {
+        self.trials
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn status(&self) -> String {
+        let done = TRIALS - self.remaining;
+        let delays: Vec<u64> = self.trials.iter().filter_map(|trial| trial.total).collect();
+        if delays.is_empty() {
+            return format!("Point the sensor at the black area and keep the cursor over the window, trial {done}/{TRIALS}");
+        }
+        let mean = delays.iter().sum::<u64>() as f64 / delays.len() as f64 / 1000.0;
+        let min = delays.iter().min().copied().unwrap_or_default() as f64 / 1000.0;
+        let max = delays.iter().max().copied().unwrap_or_default() as f64 / 1000.0;
+        let mut status = format!(
+            "Trial {done}/{TRIALS}, measured {}: mean {mean:.2} ms, min {min:.2} ms, max {max:.2} ms",
+            delays.len()
+        );
+        // only trials with both parts, so the two add up to their own mean
+        let split: Vec<(u64, u64)> = self
+            .trials
+            .iter()
+            .filter_map(|trial| trial.to_redraw.zip(trial.total))
+            .filter(|(to_redraw, total)| to_redraw <= total)
+            .collect();
+        if !split.is_empty() {
+            let count = split.len() as f64;
+            let to_redraw = split.iter().map(|(to_redraw, _)| to_redraw).sum::<u64>() as f64;
+            let rest = split
+                .iter()
+                .map(|(to_redraw, total)| total - to_redraw)
+                .sum::<u64>() as f64;
+            status.push_str(&format!(
+                ", split over {}: input to redraw {:.2} ms, the rest {:.2} ms",
+                split.len(),
+                to_redraw / count / 1000.0,
+                rest / count / 1000.0
+            ));
+        }
+        status
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
+impl Chart<Message> for Loopback {
+    type State = ();
+    fn draw_chart<DB: DrawingBackend>(&self, _state: &Self::State, root: DrawingArea<DB, Shift>) {
+        _ = root.fill(if self.flash { &WHITE } else { &BLACK });
+    }
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, _builder: ChartBuilder<DB>) {}
+    fn update(
+        &self,
+        _state: &mut Self::State,
+        event: Event,
+        _bounds: Rectangle,
+        _cursor: Cursor,
+    ) -> (Status, Option<Message>) {
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(_))
+            | Event::Keyboard(keyboard::Event::KeyPressed { .. }) => {
+                (Status::Captured, Some(Message::LoopbackInput(true)))
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(_))
+            | Event::Keyboard(keyboard::Event::KeyReleased { .. }) => {
+                (Status::Captured, Some(Message::LoopbackInput(false)))
+            }
+            _ => (Status::Ignored, None),
+        }
+    }
+}
@@ -1,124 +1,311 @@
+mod blind;
+mod chart_cache;
+mod config;
 mod enums;
+mod histogram;
+mod journal;
+mod loopback;
+mod notifications;
+mod percentiles;
+mod placement;
+mod port;
+mod results;
+mod segment;
+mod test_run;
+mod worker;
+mod zoom;
+use blind::BlindTest;
+use chart_cache::{ChartCache, MAX_POINTS};
 use chrono::{DateTime, Utc};
+use config::{AppConfig, Preferences, SessionConfig, WindowSize};
 #[allow(clippy::wildcard_imports)]
 use enums::*;
 use fakeldat_lib::{
-    serialport::{self, SerialPort},
-    ActionMode, Error, FakeLDAT, KeyboardKey, MouseButton, RawReport, Report, ReportMode,
-    SummaryReport,
+    analysis::LatencyDetector, serialport, ActionMode, Baseline, Capabilities, DetectionMode,
+    Error, FakeLDAT, GamepadButton, Hello, KeyboardKey, MouseButton, Profile, RawReport, Recorder,
+    Report, ReportMode, SensorGain, SequenceResult, SessionMetadata, SummaryReport,
 };
+use histogram::HistogramChart;
+use iced::event::Status;
+use iced::mouse::{self, Cursor};
+use iced::widget::canvas::{Event, Frame, Geometry};
 use iced::widget::{
-    button, column, container, pick_list, radio, row, scrollable, slider, text, Container, Rule,
-    Scrollable, Space,
+    button, column, container, pick_list, progress_bar, radio, row, scrollable, slider, text,
+    tooltip, Container, Rule, Scrollable, Space,
 };
-use iced::{Alignment, Length, Subscription, Theme};
-use plotters::{coord::Shift, style::full_palette::ORANGE};
-use plotters::element::Rectangle;
+use iced::{Alignment, Length, Size, Subscription, Theme};
+use journal::{Entry, Journal};
+use loopback::Loopback;
+use notifications::{Level, Notifications};
+use percentiles::PercentileChart;
+use placement::Placement;
+use plotters::coord::Shift;
+use plotters::element::{EmptyElement, PathElement, Rectangle, Text};
 use plotters::series::LineSeries;
-use plotters::style::{Color, BLUE, GREEN, RED, WHITE};
-use plotters_iced::{Chart, ChartBuilder, ChartWidget, DrawingArea, DrawingBackend};
+use plotters::style::full_palette::{GREY, ORANGE, PURPLE};
+use plotters::style::{Color, Palette, Palette99, BLACK, BLUE, GREEN, MAGENTA, RED, WHITE};
+use plotters_iced::{Chart, ChartBuilder, ChartWidget, DrawingArea, DrawingBackend, Renderer};
+use port::Port;
+use rand::{rngs::StdRng, SeedableRng};
+use results::Results;
 use rfd::FileDialog;
+use segment::Segment;
 use std::collections::VecDeque;
-use std::fs::{File, OpenOptions};
-use std::io::Write;
-use std::time::Duration;
-use std::{cmp::Ordering, process::exit, thread::sleep};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::{cmp::Ordering, process::exit};
+use test_run::{TestRun, TestSettings};
+use worker::{DeviceEvent, DeviceState, Worker};
+use zoom::Zoom;
+
+// Setting acknowledged by the device mid-session, delays on both sides shouldn't be pooled
+pub struct SettingChange {
+    // device time of the last raw report, raw reports might not be enabled
+    timestamp: Option<u64>,
+    session_time: Duration,
+    label: String,
+}
+
+// Recording waiting for the device to describe itself for the header, nothing is lost meanwhile
+struct PendingRecording {
+    path: PathBuf,
+    format: RecordingFormat,
+    reports: Vec<Report>,
+}
 
 pub struct UI {
-    fakeldat: FakeLDAT,
-    theme: Theme,
+    // None until connected from the port picker
+    worker: Option<Worker>,
+    device_state: DeviceState,
+    // until the device first shows up as connected
+    connecting: bool,
+    ports: Vec<Port>,
+    selected_port: Option<String>,
+    // why the last connect failed, cleared on the next attempt
+    connection_error: Option<String>,
+    theme: AppTheme,
+    window_size: Option<WindowSize>,
+    // last folder recorded to, the dialog starts there
+    record_dir: Option<PathBuf>,
+    // what's in the session file, it's only written when this changes
+    saved_session: Option<SessionConfig>,
     selected_pollrate: PollRate,
     selected_reportmode: ReportMode,
     selected_action_type: ActionType,
     selected_action_key: ActionKey,
     threshold: i16,
     show_graph: bool,
-    record_file: Option<File>,
-    raw_data: VecDeque<RawReport>,    // data refactor?
-    summary_data: Vec<SummaryReport>, // TODO: old data is not being removed
-    macro_timestamps: Vec<u64>,       // TODO: old data is not being removed
-    trigger_timestamps: Vec<u64>,     // TODO: old data is not being removed
-    init_process: u8,
-    forced_tick_rate: Option<u16>,
+    show_percentiles: bool,
+    show_histogram: bool,
+    // microseconds
+    histogram_bin_width: u16,
+    show_slew: bool,
+    detection_mode: DetectionMode,
+    sensor_gain: SensorGain,
+    // None until connected, everything is offered then
+    hello: Option<Hello>,
+    show_chart_settings: bool,
+    // seconds of raw data kept
+    window: u8,
+    // Y range of the raw chart follows the visible data
+    auto_scale: bool,
+    record_file: Option<Recorder>,
+    pending_recording: Option<PendingRecording>,
+    record_format: RecordingFormat,
+    raw_data: VecDeque<RawReport>, // data refactor?
+    chart_cache: ChartCache,
+    summary_data: Vec<(Duration, SummaryReport)>, // TODO: old data is not being removed
+    macro_timestamps: Vec<u64>,                   // TODO: old data is not being removed
+    trigger_timestamps: Vec<u64>,                 // TODO: old data is not being removed
+    setting_changes: Vec<SettingChange>,
+    // summaries shown while paused, the raw data stops being added to
+    paused: Option<usize>,
+    // last values confirmed by the device, the slider changes before that
+    acknowledged_pollrate: Option<u16>,
+    acknowledged_threshold: Option<i16>,
+    notifications: Notifications,
+    loopback: Option<Loopback>,
+    // of the last finished loopback test
+    loopback_trials: Vec<loopback::Trial>,
+    placement: Option<Placement>,
+    session_start: Instant,
+    summary_source: SummarySource,
+    host_detector: Option<LatencyDetector>,
+    blind_profiles: [Option<Profile>; 2],
+    blind_test: Option<BlindTest>,
+    show_test_setup: bool,
+    test_settings: TestSettings,
+    test_run: Option<TestRun>,
+    segment: Option<Segment>,
+    // None follows the whole buffer
+    zoom: Option<Zoom>,
+    journal: Journal,
+    rng: StdRng,
 }
 
 impl Default for UI {
     fn default() -> Self {
-        let port;
-        let mut error_count = 0;
-        loop {
-            if let Ok(new_port) = Self::get_port() {
-                port = new_port;
-                break;
-            }
-            eprintln!("Can't find device");
-            error_count += 1;
-            if error_count == 30 {
-                exit(1)
-            }
-            sleep(Duration::from_secs(2));
-        }
-        Self {
-            fakeldat: FakeLDAT::create(port).expect("Couldn't create FakeLDAT"),
-            theme: Theme::Dark,
+        let ports = Port::list();
+        let selected_port = Port::preferred(&ports).map(|port| port.name.clone());
+        let (journal, seed) = Journal::from_args().unwrap_or_else(|why| {
+            eprintln!("Can't open the journal: {why:?}");
+            exit(1)
+        });
+        let mut ui = Self {
+            worker: None,
+            device_state: DeviceState::default(),
+            connecting: false,
+            ports,
+            selected_port,
+            connection_error: None,
+            theme: AppTheme::Dark,
+            window_size: None,
+            record_dir: None,
+            saved_session: None,
             selected_pollrate: PollRate::_2000,
             selected_reportmode: ReportMode::Raw,
             selected_action_type: ActionType::Mouse,
             selected_action_key: ActionKey::default(),
             threshold: 150,
             show_graph: true,
+            show_percentiles: false,
+            show_histogram: false,
+            histogram_bin_width: DEFAULT_BIN_WIDTH,
+            show_slew: false,
+            detection_mode: DetectionMode::default(),
+            sensor_gain: SensorGain::default(),
+            hello: None,
+            show_chart_settings: false,
+            window: DEFAULT_WINDOW,
+            auto_scale: false,
             record_file: None,
+            pending_recording: None,
+            record_format: RecordingFormat::Csv,
             raw_data: VecDeque::new(),
+            chart_cache: ChartCache::new(sample_capacity(PollRate::_2000, DEFAULT_WINDOW)),
             summary_data: Vec::new(),
             macro_timestamps: Vec::new(),
             trigger_timestamps: Vec::new(),
-            init_process: 0,
-            forced_tick_rate: None,
+            setting_changes: Vec::new(),
+            paused: None,
+            acknowledged_pollrate: None,
+            acknowledged_threshold: None,
+            notifications: Notifications::default(),
+            loopback: None,
+            loopback_trials: Vec::new(),
+            placement: None,
+            session_start: Instant::now(),
+            summary_source: SummarySource::Device,
+            host_detector: None,
+            blind_profiles: [None, None],
+            blind_test: None,
+            show_test_setup: false,
+            test_settings: TestSettings::default(),
+            test_run: None,
+            segment: None,
+            zoom: None,
+            journal,
+            rng: StdRng::seed_from_u64(seed),
+        };
+        // replays start from the defaults like the recorded session did
+        if !ui.journal.is_replaying() {
+            ui.restore_session();
         }
+        ui
     }
 }
 
 impl UI {
     #[allow(clippy::needless_pass_by_value)]
     pub fn update(&mut self, message: Message) {
-        if let Err(why) = self.update_with_error(message) {
-            match why {
-                Error::WrongChecksum(_, _, _) | Error::ReadTooLittleData => unreachable!(), // Those should be internal
-                Error::InvalidSetting(command, buf) => {
-                    eprintln!("Invalid setting for {command}: {:x} {:x}", buf[0], buf[1]);
-                }
-                Error::InvalidCommand(command_id) => eprintln!("Invalid command id: {command_id}"),
-                Error::PortFail(serialport_error) => {
-                    match serialport_error.kind {
-                        serialport::ErrorKind::NoDevice | serialport::ErrorKind::Unknown => {
-                            self.forced_tick_rate = Some(1);
-                            // This allows the UI to not freeze
-                            if Self::get_port().is_ok() {
-                                *self = Self::default();
-                            }
-                        }
-                        _ => todo!(),
-                    };
-                    eprintln!("Port fail: {}", serialport_error.description);
-                }
-                Error::SendCommandFail => eprintln!("Issue with sending a command"),
-                Error::IOError(io_error) => eprintln!("Issue with saving a file: {io_error}"),
-                Error::InvalidEnumConverion => eprintln!("TryFrom enum conversion error"),
+        // anything but new data is handled in tick
+        if !matches!(
+            message,
+            Message::Tick | Message::LoopbackFrame(_) | Message::Device(_)
+        ) {
+            // user input would make the replay diverge
+            if self.journal.is_replaying() {
+                return;
+            }
+            self.chart_cache.geometry.clear();
+        }
+        let result = match message {
+            Message::Tick | Message::LoopbackFrame(_) | Message::Device(_) => {
+                self.update_with_error(message)
             }
+            _ => self
+                .journal
+                .record_message(&message)
+                .and_then(|()| self.update_with_error(message)),
         };
+        if let Err(why) = result {
+            self.notifications.push(Level::Error, error_message(&why));
+        }
     }
 
     pub fn view(&self) -> iced::Element<Message> {
         let spacer = Rule::horizontal(1);
-        let main_stack = column![
-            self.draw_graph(),
-            self.draw_buttons(),
-            spacer,
-            self.draw_rate_selection(),
-            self.draw_mode_selection(),
-            self.draw_action_selection(),
-            self.threshold_selection(),
-        ];
+        let graph = self
+            .loopback
+            .as_ref()
+            .map_or_else(|| self.draw_graph(), Self::draw_loopback);
+        let graph = match self.placement {
+            Some(ref placement) => column![Self::draw_placement(placement), graph].into(),
+            None => graph,
+        };
+        // settings would give away the active condition
+        let main_stack = match self.blind_test {
+            Some(ref blind_test) => column![
+                graph,
+                self.draw_buttons(),
+                self.draw_chart_settings(),
+                spacer,
+                Self::draw_blind_test(blind_test)
+            ],
+            None => column![
+                graph,
+                self.draw_buttons(),
+                self.draw_chart_settings(),
+                self.draw_test_run(),
+                spacer,
+                self.draw_rate_selection(),
+                self.draw_mode_selection(),
+                self.draw_action_selection(),
+                self.threshold_selection(),
+                self.draw_blind_setup(),
+            ],
+        };
+        let main_stack = column![self.draw_connection(), main_stack];
+        // banner on top, saturated data gives nonsense delays
+        let main_stack = if self.device_state.sensor_saturated {
+            column![
+                text("Sensor saturated, brightness is stuck at 0 or 4095. Check if it's placed on the screen"),
+                main_stack
+            ]
+            .align_items(Alignment::Center)
+        } else {
+            main_stack
+        };
+        // status bar, drifting readings over long sessions can come from the device warming up
+        let mut status = Vec::new();
+        if let Some(telemetry) = self.device_state.telemetry {
+            status.push(format!("Device: {telemetry}"));
+        }
+        if self.device_state.link_saturated {
+            status.push(String::from(
+                "Link saturated, reports might get lost. Lower the poll rate or enable raw batching",
+            ));
+        }
+        let main_stack = if status.is_empty() {
+            main_stack
+        } else {
+            column![main_stack, text(status.join(" | ")).size(14)].align_items(Alignment::Center)
+        };
+        let main_stack = if self.notifications.is_empty() {
+            main_stack
+        } else {
+            column![main_stack, self.draw_notifications()].align_items(Alignment::Center)
+        };
 
         container(main_stack)
             .center_x()
@@ -133,144 +320,642 @@ impl UI {
     fn update_with_error(&mut self, message: Message) -> Result<(), Error> {
         match message {
             Message::Tick => {
+                self.notifications.expire();
                 self.tick()?;
             }
+            Message::Device(event) => self.device_event(event)?,
             Message::RecordStart => {
-                let now: DateTime<Utc> = Utc::now();
-                let path = FileDialog::new()
-                    .set_directory("/")
-                    .pick_folder()
-                    .map(|record_dir| {
-                        record_dir.join(format!(
-                            "{}_report {}.csv",
-                            self.selected_reportmode.to_string().to_lowercase(),
-                            now.format("%d-%m-%Y %H.%M.%S")
-                        ))
-                    });
-                if let Some(path) = path {
-                    self.record_file = Some(
-                        OpenOptions::new()
-                            .create(true)
-                            .append(true)
-                            .open(path)
-                            .map_err(Error::IOError)?,
-                    );
+                let record_dir = FileDialog::new()
+                    .set_directory(self.record_dir.as_deref().unwrap_or(Path::new("/")))
+                    .pick_folder();
+                if let Some(record_dir) = record_dir {
+                    self.record_dir = Some(record_dir);
+                    self.start_recording()?;
                 }
             }
-            Message::RecordStop => self.record_file = None,
+            Message::RecordStop => {
+                self.record_file = None;
+                self.pending_recording = None;
+            }
+            Message::RecordFormatChanged(record_format) => self.record_format = record_format,
             Message::Clear => {
                 self.raw_data = vec![].into();
+                self.chart_cache.clear();
                 self.summary_data = vec![];
+                // stays paused on the now empty view
+                self.paused = self.paused.map(|_| 0);
+                self.setting_changes.clear();
+                self.session_start = Instant::now();
+                self.segment = None;
+                self.zoom = None;
+            }
+            Message::PauseToggle => {
+                self.paused = match self.paused {
+                    Some(_) => None,
+                    None => Some(self.summary_data.len()),
+                };
+            }
+            Message::NotificationsClear => self.notifications.clear(),
+            Message::WindowResized(width, height) => {
+                self.window_size = Some(WindowSize { width, height });
+            }
+            Message::ThemeToggle => {
+                self.theme = match self.theme {
+                    AppTheme::Light => AppTheme::Dark,
+                    AppTheme::Dark => AppTheme::Light,
+                };
             }
             Message::GraphToggle => self.show_graph = !self.show_graph,
+            // both take the place of the summary list
+            Message::PercentilesToggle => {
+                self.show_percentiles = !self.show_percentiles;
+                self.show_histogram &= !self.show_percentiles;
+            }
+            Message::HistogramToggle => {
+                self.show_histogram = !self.show_histogram;
+                self.show_percentiles &= !self.show_histogram;
+            }
+            Message::HistogramBinWidthChanged(bin_width) => self.histogram_bin_width = bin_width,
+            Message::ChartSettingsToggle => self.show_chart_settings = !self.show_chart_settings,
+            Message::WindowChanged(window) => {
+                self.window = window.clamp(MIN_WINDOW, MAX_WINDOW);
+                self.resize_window();
+            }
+            Message::AutoScaleToggle => self.auto_scale = !self.auto_scale,
+            Message::SlewToggle => {
+                self.show_slew = !self.show_slew;
+                if let Some(ref mut record_file) = self.record_file {
+                    record_file.set_slew(self.show_slew);
+                }
+            }
             Message::ManualTrigger => {
-                self.fakeldat.manual_trigger()?;
+                device(self.worker.as_ref())?.run(FakeLDAT::manual_trigger);
             }
             Message::PollRateChanged(pollrate) => {
-                self.fakeldat.set_poll_rate(pollrate.into())?;
+                device(self.worker.as_ref())?
+                    .run(move |fakeldat| fakeldat.set_poll_rate(pollrate.into()));
             }
             Message::ReportModeChanged(report_mode) => {
-                self.fakeldat.set_report_mode(report_mode)?;
-                self.record_file = None;
+                device(self.worker.as_ref())?
+                    .run(move |fakeldat| fakeldat.set_report_mode(report_mode));
+            }
+            Message::SensorGainChanged(sensor_gain) => {
+                device(self.worker.as_ref())?
+                    .run(move |fakeldat| fakeldat.set_sensor_gain(sensor_gain));
+            }
+            // the edge stays as the device has it
+            Message::BaselineChanged(baseline) => {
+                let detection_mode = DetectionMode {
+                    baseline,
+                    ..self.detection_mode
+                };
+                device(self.worker.as_ref())?
+                    .run(move |fakeldat| fakeldat.set_detection_mode(detection_mode));
             }
             Message::ActionModeChanged(action_type) => {
                 self.selected_action_type = action_type;
                 let key_option = match action_type {
                     ActionType::Mouse => self.selected_action_key.mouse.map(|v| v as u8),
                     ActionType::Keyboard => self.selected_action_key.keyboard.map(|v| v as u8),
+                    ActionType::Gamepad => self.selected_action_key.gamepad.map(|v| v as u8),
                 };
                 if let Some(key) = key_option {
                     let action_mode = ActionMode::try_from(self.selected_action_type as u8, key)?;
-                    self.fakeldat.set_action(action_mode)?;
+                    device(self.worker.as_ref())?
+                        .run(move |fakeldat| fakeldat.set_action(action_mode));
                 }
             }
             Message::ActionKeyChanged(key) => {
                 let action_mode = ActionMode::try_from(self.selected_action_type as u8, key)?;
-                self.fakeldat.set_action(action_mode)?;
+                device(self.worker.as_ref())?.run(move |fakeldat| fakeldat.set_action(action_mode));
             }
             Message::ThresholdChanged(threshold) => self.threshold = threshold,
             Message::ThresholdReleased => {
-                self.fakeldat.set_threshold(self.threshold)?;
+                let threshold = self.threshold;
+                device(self.worker.as_ref())?
+                    .run(move |fakeldat| fakeldat.set_threshold(threshold));
+                self.reset_host_detector();
+            }
+            Message::SummarySourceChanged(summary_source) => {
+                self.summary_source = summary_source;
+                self.reset_host_detector();
+            }
+            Message::LoopbackStart => {
+                if self.selected_reportmode == ReportMode::Raw {
+                    device(self.worker.as_ref())?
+                        .run(|fakeldat| fakeldat.set_report_mode(ReportMode::Summary));
+                }
+                self.loopback = Some(Loopback::new());
+            }
+            // kept for the results export
+            Message::LoopbackStop => {
+                if let Some(loopback) = self.loopback.take() {
+                    self.loopback_trials = loopback.into_trials();
+                }
+            }
+            Message::LoopbackInput(pressed) => {
+                if let Some(ref mut loopback) = self.loopback {
+                    loopback.set_flash(pressed);
+                }
+            }
+            Message::LoopbackFrame(at) => {
+                if let Some(ref mut loopback) = self.loopback {
+                    loopback.redraw_requested(at);
+                }
+            }
+            Message::PlacementToggle => {
+                if self.placement.take().is_none() {
+                    // the brightness only comes with raw reports
+                    if !matches!(
+                        self.selected_reportmode,
+                        ReportMode::Raw | ReportMode::Combined
+                    ) {
+                        device(self.worker.as_ref())?
+                            .run(|fakeldat| fakeldat.set_report_mode(ReportMode::Combined));
+                    }
+                    self.placement = Some(Placement::new());
+                }
+            }
+            Message::BlindCapture(condition) => {
+                self.blind_profiles[condition as usize] = self.current_profile();
+            }
+            Message::BlindStart => {
+                let blind_test = BlindTest::new(&mut self.rng);
+                if let Some(condition) = blind_test.current() {
+                    self.apply_condition(condition)?;
+                }
+                self.blind_test = Some(blind_test);
+            }
+            Message::BlindGuess(guess) => {
+                if let Some(condition) = self
+                    .blind_test
+                    .as_mut()
+                    .and_then(|blind_test| blind_test.guess(guess))
+                {
+                    self.apply_condition(condition)?;
+                }
+            }
+            Message::BlindStop => self.blind_test = None,
+            Message::TestSetupToggle => self.show_test_setup = !self.show_test_setup,
+            Message::TestShotsChanged(shots) => self.test_settings.shots = shots,
+            // the range stays the right way around
+            Message::TestMinIntervalChanged(interval) => {
+                self.test_settings.min_interval = interval;
+                self.test_settings.max_interval = self.test_settings.max_interval.max(interval);
+            }
+            Message::TestMaxIntervalChanged(interval) => {
+                self.test_settings.max_interval = interval;
+                self.test_settings.min_interval = self.test_settings.min_interval.min(interval);
+            }
+            Message::TestCountdownChanged(countdown) => self.test_settings.countdown = countdown,
+            Message::TestStart => {
+                let report_mode = self.selected_reportmode;
+                device(self.worker.as_ref())?.run(move |fakeldat| {
+                    if !matches!(report_mode, ReportMode::Summary | ReportMode::Combined) {
+                        fakeldat.set_report_mode(ReportMode::Summary)?;
+                    }
+                    // every trigger needs its own summary
+                    match fakeldat.set_summary_aggregation(0) {
+                        Ok(()) | Err(Error::Unsupported(_)) => Ok(()),
+                        Err(why) => Err(why),
+                    }
+                });
+                self.test_run = Some(TestRun::new(self.test_settings));
+                self.show_test_setup = false;
+            }
+            Message::TestStop => self.test_run = None,
+            Message::SegmentSelected(start, end) => {
+                self.segment = Some(Segment::new(
+                    self.timestamp_at(start),
+                    self.timestamp_at(end),
+                ));
+            }
+            Message::SegmentClear => self.segment = None,
+            Message::ChartZoom(at, factor) => {
+                self.zoom = Zoom::zoomed(self.visible_range(), self.time_range(), at, factor);
+            }
+            Message::ChartPan(delta) => self.zoom = Zoom::panned(self.visible_range(), delta),
+            Message::ViewReset => self.zoom = None,
+            Message::ConfigExport => {
+                let path = FileDialog::new()
+                    .add_filter("TOML", &["toml"])
+                    .set_file_name("fakeldat.toml")
+                    .save_file();
+                if let Some(path) = path {
+                    AppConfig::new(
+                        self.preferences(),
+                        self.current_profile(),
+                        self.blind_profiles,
+                    )
+                    .save(&path)?;
+                }
+            }
+            Message::ConfigImport => {
+                let path = FileDialog::new().add_filter("TOML", &["toml"]).pick_file();
+                if let Some(path) = path {
+                    self.apply_config(&AppConfig::load(&path)?)?;
+                }
+            }
+            Message::SegmentExport(format) => {
+                if let Some(segment) = self.segment {
+                    let (extension, name) = match format {
+                        ExportFormat::Csv => ("csv", "CSV"),
+                        ExportFormat::Png => ("png", "PNG"),
+                    };
+                    let path = FileDialog::new()
+                        .add_filter(name, &[extension])
+                        .set_file_name(format!(
+                            "segment {}-{}.{extension}",
+                            segment.start, segment.end
+                        ))
+                        .save_file();
+                    if let Some(path) = path {
+                        match format {
+                            ExportFormat::Csv => {
+                                std::fs::write(path, segment.to_csv(&self.raw_data))
+                                    .map_err(Error::IOError)?;
+                            }
+                            ExportFormat::Png => segment.save_png(&self.raw_data, &path)?,
+                        }
+                    }
+                }
+            }
+            Message::ResultsExport => {
+                let now: DateTime<Utc> = Utc::now();
+                let path = FileDialog::new()
+                    .add_filter("CSV", &["csv"])
+                    .add_filter("JSON", &["json"])
+                    .set_file_name(format!("results {}.csv", now.format("%d-%m-%Y %H.%M.%S")))
+                    .save_file();
+                if let Some(path) = path {
+                    let mut results = Results::new(
+                        &self.summary_data,
+                        &self.setting_changes,
+                        self.session_start.elapsed(),
+                        self.loopback
+                            .as_ref()
+                            .map_or(&self.loopback_trials[..], Loopback::trials),
+                    );
+                    // the settings and the firmware are only known on the device thread
+                    match self.worker {
+                        Some(ref worker) => worker.run(move |fakeldat| {
+                            results.device = Some(fakeldat.session_metadata());
+                            results.hello = fakeldat
+                                .protocol_version()
+                                .zip(fakeldat.capabilities())
+                                .map(|(protocol_version, capabilities)| Hello {
+                                    protocol_version,
+                                    capabilities,
+                                });
+                            results.save(&path)
+                        }),
+                        None => results.save(&path)?,
+                    }
+                }
+            }
+            Message::PortsRefresh => {
+                self.ports = Port::list();
+                // keep the choice while its port is still there
+                if !self
+                    .ports
+                    .iter()
+                    .any(|port| Some(&port.name) == self.selected_port.as_ref())
+                {
+                    self.selected_port = Port::preferred(&self.ports).map(|port| port.name.clone());
+                }
+            }
+            // the status names the selected port, it can't change while connected
+            Message::PortSelected(port) => {
+                if self.worker.is_none() {
+                    self.selected_port = Some(port);
+                }
+            }
+            Message::Connect => {
+                if let Some(ref port) = self.selected_port {
+                    self.worker = Some(Worker::spawn(port.clone()));
+                    self.connecting = true;
+                    self.connection_error = None;
+                }
+            }
+            // the worker closes the device once it's dropped
+            Message::Disconnect => {
+                self.worker = None;
+                // the header won't come anymore
+                if let Some(pending) = self.pending_recording.take() {
+                    self.open_recording(pending, None)?;
+                }
+                self.test_run = None;
+                self.device_state = DeviceState::default();
+                self.hello = None;
             }
         }
         Ok(())
     }
 
-    // Only for polling data, window refresh is separate
+    // Timing of the loopback trials, flushing and the replay, the device is polled by the worker
     fn tick(&mut self) -> Result<(), Error> {
-        self.fakeldat.poll_bulk_data()?;
-        if self.init_process < 10 {
-            _ = self.fakeldat.take_report_buffer();
-        }
-        if let Some(reports) = self.fakeldat.take_report_buffer() {
-            let mut record_buffer = vec![];
-            for report in reports {
-                match report {
-                    Report::Raw(raw_report) => {
-                        if let Some(last_record) = self.raw_data.back() {
-                            if !last_record.trigger && raw_report.trigger {
-                                self.trigger_timestamps.push(raw_report.timestamp);
-                            }
+        if self.journal.is_replaying() {
+            if let Some(reports) = self.replay_tick()? {
+                self.process_reports(reports)?;
+            }
+        }
+        // bounds the data lost on power loss while no reports arrive
+        if let Some(ref mut record_file) = self.record_file {
+            record_file.flush_if_due()?;
+        }
+        if let Some(ref mut loopback) = self.loopback {
+            if loopback.next_trial() {
+                device(self.worker.as_ref())?.run(FakeLDAT::manual_trigger);
+            }
+        }
+        if let Some(ref mut placement) = self.placement {
+            placement.tick();
+        }
+        if self.test_run.as_mut().is_some_and(TestRun::take_start) {
+            self.queue_test_shot()?;
+        }
+        // saved as soon as it changes, nothing is lost when the app gets killed
+        if !self.journal.is_replaying() {
+            self.save_session()?;
+        }
+        Ok(())
+    }
+
+    fn device_event(&mut self, event: DeviceEvent) -> Result<(), Error> {
+        match event {
+            DeviceEvent::Connected => {
+                // settings get read again, timestamps start over
+                self.forget_device_time();
+                self.detection_mode = DetectionMode::default();
+                self.sensor_gain = SensorGain::default();
+                // the last used settings, from the previous session or device
+                let profile = self.current_profile();
+                // features the firmware didn't report aren't even asked for
+                let telemetry = self.supports(|capabilities| capabilities.telemetry);
+                let detection_mode = self.supports(|capabilities| capabilities.detection_mode);
+                let sensor_gain = self.supports(|capabilities| capabilities.sensor_gain);
+                device(self.worker.as_ref())?.run(move |fakeldat| {
+                    if let Some(profile) = profile {
+                        fakeldat.apply_profile(&profile)?;
+                    }
+                    fakeldat.get_action()?;
+                    fakeldat.get_poll_rate()?;
+                    fakeldat.get_threshold()?;
+                    fakeldat.get_report_mode()?;
+                    if telemetry {
+                        fakeldat.set_telemetry_interval(TELEMETRY_INTERVAL)?;
+                    }
+                    // older firmware only knows the relative threshold
+                    if detection_mode {
+                        fakeldat.get_detection_mode()?;
+                    }
+                    if sensor_gain {
+                        fakeldat.get_sensor_gain()?;
+                    }
+                    Ok(())
+                });
+            }
+            // comes right before Connected
+            DeviceEvent::Hello(hello) => self.hello = hello,
+            DeviceEvent::ConnectFailed(why) => {
+                self.worker = None;
+                self.connecting = false;
+                self.connection_error = Some(why);
+            }
+            // device keeps running but only the journal drives the UI
+            DeviceEvent::Reports(reports) => {
+                if !self.journal.is_replaying() {
+                    self.process_reports(reports)?;
+                }
+            }
+            DeviceEvent::Metadata(path, metadata) => {
+                if let Some(pending) = self
+                    .pending_recording
+                    .take_if(|pending| pending.path == path)
+                {
+                    self.open_recording(pending, Some(&metadata))?;
+                }
+            }
+            DeviceEvent::TestShot(result) => {
+                let due = self
+                    .test_run
+                    .as_mut()
+                    .is_some_and(|test_run| test_run.push(&result));
+                if !self.journal.is_replaying() {
+                    self.process_reports(
+                        result.reports.into_iter().map(Report::Summary).collect(),
+                    )?;
+                }
+                if due {
+                    self.queue_test_shot()?;
+                }
+            }
+            DeviceEvent::Warning(warning) => {
+                self.notifications.push(Level::Warning, warning.to_string());
+            }
+            DeviceEvent::Error(why) => self.notifications.push(Level::Error, why),
+            DeviceEvent::State(state) => {
+                // the status bar keeps showing it, this is for noticing when it starts
+                if state.link_saturated && !self.device_state.link_saturated {
+                    self.notifications.push(
+                        Level::Warning,
+                        String::from("Link saturated, reports might get lost"),
+                    );
+                }
+                self.connecting &= !state.connected;
+                self.device_state = state;
+            }
+        }
+        Ok(())
+    }
+
+    fn process_reports(&mut self, reports: Vec<Report>) -> Result<(), Error> {
+        self.journal.record_reports(&reports)?;
+        if self.paused.is_none() {
+            self.chart_cache.geometry.clear();
+        }
+        for report in reports {
+            if let Some(ref mut record_file) = self.record_file {
+                record_file.write_report(&report)?;
+            } else if let Some(ref mut pending) = self.pending_recording {
+                pending.reports.push(report.clone());
+            }
+            match report {
+                // only the first sensor is recorded and analyzed, the others are just plotted
+                Report::Raw(raw_report) if raw_report.channel != 0 => {
+                    if self.paused.is_none() {
+                        self.chart_cache.push(None, &raw_report);
+                    }
+                }
+                Report::Raw(raw_report) => {
+                    if let Some(last_record) =
+                        self.raw_data.back().filter(|_| self.paused.is_none())
+                    {
+                        if !last_record.trigger && raw_report.trigger {
+                            self.trigger_timestamps.push(raw_report.timestamp);
                         }
-                        record_buffer.push(format!(
-                            "{},{},{},{}",
-                            raw_report.timestamp,
-                            raw_report.brightness,
-                            raw_report.audio,
-                            u8::from(raw_report.trigger)
-                        ));
-                        self.push_data(raw_report);
                     }
-                    Report::Summary(summary_report) => {
-                        record_buffer.push(format!(
-                            "{},{}",
-                            summary_report.delay, summary_report.threshold
-                        ));
-                        self.summary_data.push(summary_report);
+                    if let Some(ref mut placement) = self.placement {
+                        placement.push(raw_report.brightness);
                     }
-                    Report::PollRate(pollrate) => {
-                        self.selected_pollrate = pollrate.into();
+                    if let Some(summary_report) = self
+                        .host_detector
+                        .as_mut()
+                        .and_then(|detector| detector.process(&raw_report))
+                    {
+                        self.push_summary(summary_report);
+                    }
+                    // still recorded and measured, only the chart is frozen
+                    if self.paused.is_none() {
+                        self.push_data(raw_report);
                     }
-                    Report::Action(action_mode) => match action_mode {
-                        ActionMode::Mouse(button) => {
-                            self.selected_action_type = ActionType::Mouse;
-                            self.selected_action_key.mouse = Some(button);
+                }
+                Report::Summary(summary_report) => {
+                    if self.summary_source == SummarySource::Device {
+                        self.push_summary(summary_report);
+                    }
+                }
+                Report::RawAudio(_) => { /* Only recorded */ }
+                Report::SummaryAggregate(_) => { /* Not charted, it needs every delay */ }
+                Report::AudioVideo(audio_video) => {
+                    // only the screen part is charted
+                    if let Some(delay) = audio_video.video_delay {
+                        if self.summary_source == SummarySource::Device {
+                            self.push_summary(SummaryReport {
+                                delay,
+                                threshold: audio_video.threshold,
+                                arm_id: None,
+                            });
                         }
-                        ActionMode::Keyboard(keyboard_key) => {
-                            self.selected_action_type = ActionType::Keyboard;
-                            self.selected_action_key.keyboard = Some(keyboard_key);
+                    }
+                }
+                Report::PollRate(pollrate) => {
+                    if self
+                        .acknowledged_pollrate
+                        .is_some_and(|previous| previous != pollrate)
+                    {
+                        self.mark_setting_change(format!("Poll rate {pollrate}"))?;
+                    }
+                    self.acknowledged_pollrate = Some(pollrate);
+                    self.selected_pollrate = pollrate.into();
+                    self.resize_window();
+                }
+                Report::Action(action_mode) => match action_mode {
+                    ActionMode::Mouse(button) => {
+                        self.selected_action_type = ActionType::Mouse;
+                        self.selected_action_key.mouse = Some(button);
+                    }
+                    ActionMode::Keyboard(keyboard_key) => {
+                        self.selected_action_type = ActionType::Keyboard;
+                        self.selected_action_key.keyboard = Some(keyboard_key);
+                    }
+                    ActionMode::Gamepad(button) => {
+                        self.selected_action_type = ActionType::Gamepad;
+                        self.selected_action_key.gamepad = Some(button);
+                    }
+                },
+                // the rows would change in the middle of the file
+                Report::ReportMode(report_mode) => {
+                    let changed = report_mode != self.selected_reportmode;
+                    self.selected_reportmode = report_mode;
+                    if changed && (self.record_file.is_some() || self.pending_recording.is_some()) {
+                        // still without a header, its reports are kept anyway
+                        if let Some(pending) = self.pending_recording.take() {
+                            self.open_recording(pending, None)?;
                         }
-                    },
-                    Report::ReportMode(report_mode) => {
-                        self.selected_reportmode = report_mode;
+                        self.record_file = None;
+                        self.start_recording()?;
                     }
-                    Report::Threshold(threshold) => {
-                        self.threshold = threshold;
+                }
+                Report::Threshold(threshold) => {
+                    if self
+                        .acknowledged_threshold
+                        .is_some_and(|previous| previous != threshold)
+                    {
+                        self.mark_setting_change(format!("Threshold {threshold}"))?;
                     }
-                    Report::MacroTrigger(timestamp) => self.macro_timestamps.push(timestamp),
-                    Report::ManualTrigger => { /* Manual trigger successful */ }
+                    self.acknowledged_threshold = Some(threshold);
+                    self.threshold = threshold;
                 }
-            }
-            if let Some(ref mut record_file) = &mut self.record_file {
-                let mut data = record_buffer.join("\n");
-                data.push('\n');
-                record_file
-                    .write_all(data.as_ref())
-                    .map_err(Error::IOError)?;
+                Report::DetectionMode(detection_mode) => self.detection_mode = detection_mode,
+                Report::SensorGain(sensor_gain) => self.sensor_gain = sensor_gain,
+                Report::MacroTrigger(timestamp) => {
+                    if self.paused.is_none() {
+                        self.macro_timestamps.push(timestamp);
+                    }
+                }
+                Report::ManualTrigger => { /* Manual trigger successful */ }
+                Report::AutoTrigger(_) => { /* Shows up in the raw data */ }
+                Report::Integrity(_)
+                | Report::Sequence(_)
+                | Report::RawBatching(_)
+                | Report::BaudRate(_)
+                | Report::FrameSize(_)
+                | Report::Clock(_)
+                | Report::Hello(_)
+                | Report::SelfTest(_)
+                | Report::Pong
+                | Report::Armed(_)
+                | Report::Disarmed
+                | Report::ActionTiming(_)
+                | Report::AutoTriggerSettings(_)
+                | Report::Channels(_)
+                | Report::TelemetryInterval(_)
+                | Report::SummaryAggregation(_)
+                | Report::Telemetry(_) => { /* Tracked by the library */ }
+                Report::Dropped { .. } => { /* Shown with the warnings */ }
+                Report::Connection(state) => self
+                    .notifications
+                    .push(Level::Info, format!("Connection: {state}")),
+                Report::GapDetected {
+                    expected_us,
+                    actual_us,
+                } => self.notifications.push(
+                    Level::Warning,
+                    format!("Samples missing: {actual_us} µs apart, expected {expected_us} µs"),
+                ),
+                Report::Reconnected => self.forget_device_time(),
             }
         }
-        if self.init_process <= 10 {
-            self.init_process += 1;
-        }
-        if self.init_process == 10 {
-            self.fakeldat.get_action()?;
-            self.fakeldat.get_poll_rate()?;
-            self.fakeldat.get_threshold()?;
-            self.fakeldat.get_report_mode()?;
-        };
         Ok(())
     }
 
+    // Applies journaled messages up to the next batch of reports
+    fn replay_tick(&mut self) -> Result<Option<Vec<Report>>, Error> {
+        while let Some((entry, last)) = self.journal.next_entry() {
+            if last {
+                self.notifications
+                    .push(Level::Info, String::from("Replay finished"));
+            }
+            match entry {
+                Entry::Reports(reports) => return Ok(Some(reports)),
+                // file dialogs would block the replay
+                Entry::Seed(_)
+                // and the device in use is whichever is connected now
+                | Entry::Message(
+                    Message::RecordStart
+                    | Message::SegmentExport(_)
+                    | Message::ResultsExport
+                    // the triggers are in the journal as reports
+                    | Message::TestStart
+                    | Message::ConfigExport
+                    | Message::ConfigImport
+                    | Message::PortsRefresh
+                    | Message::PortSelected(_)
+                    | Message::Connect
+                    | Message::Disconnect
+                    // and the window stays as it is
+                    | Message::WindowResized(_, _),
+                ) => {}
+                Entry::Message(message) => {
+                    self.chart_cache.geometry.clear();
+                    self.update_with_error(message)?;
+                }
+            }
+        }
+        Ok(None)
+    }
+
     fn draw_graph(&self) -> iced::Element<Message> {
         let graph_raw = if self.show_graph
             && (self.selected_reportmode == ReportMode::Raw
@@ -287,16 +972,51 @@ impl UI {
             // When showing the other graph
             container(Space::new(Length::Shrink, Length::Shrink))
         };
-        let graph_summary = if self.show_graph
-            && (self.selected_reportmode == ReportMode::Summary
-                || self.selected_reportmode == ReportMode::Combined)
-        {
+        let show_summary = match self.selected_reportmode {
+            ReportMode::Raw => self.summary_source != SummarySource::Device,
+            ReportMode::Summary | ReportMode::Combined | ReportMode::AudioVideo => true,
+            ReportMode::RawAudio => false,
+        };
+        let graph_summary = if self.show_graph && self.show_percentiles && show_summary {
+            container(
+                ChartWidget::new(PercentileChart {
+                    data: self.visible_summaries(),
+                    setting_changes: &self.setting_changes,
+                })
+                .width(Length::Fill)
+                .height(Length::Fill),
+            )
+        } else if self.show_graph && self.show_histogram && show_summary {
+            container(
+                column![
+                    ChartWidget::new(HistogramChart {
+                        data: self.visible_summaries(),
+                        bin_width: self.histogram_bin_width,
+                    })
+                    .width(Length::Fill)
+                    .height(Length::Fill),
+                    row![
+                        text(format!("Bin width: {} µs", self.histogram_bin_width)),
+                        slider(
+                            MIN_BIN_WIDTH..=MAX_BIN_WIDTH,
+                            self.histogram_bin_width,
+                            Message::HistogramBinWidthChanged,
+                        )
+                        .step(100u16)
+                        .shift_step(10u16),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(20),
+                ]
+                .spacing(10),
+            )
+        } else if self.show_graph && show_summary {
             container(
                 Scrollable::with_direction(
                     text(
-                        self.summary_data
+                        self.visible_summaries()
                             .iter()
-                            .map(|summary| format!("{}, {}", summary.delay, summary.threshold))
+                            .map(|(_, summary)| format!("{}, {}", summary.delay, summary.threshold))
                             .collect::<Vec<String>>()
                             .join("\n"),
                     )
@@ -322,24 +1042,373 @@ impl UI {
             .into()
     }
 
+    fn draw_loopback(loopback: &Loopback) -> iced::Element<Message> {
+        container(
+            column![
+                ChartWidget::new(loopback)
+                    .width(Length::Fill)
+                    .height(Length::Fill),
+                text(loopback.status()),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(10),
+        )
+        .center_x()
+        .width(iced::Length::Fill)
+        .padding(10)
+        .into()
+    }
+
+    // Picture in picture next to the graph, the graph shows how stable the levels are
+    fn draw_placement(placement: &Placement) -> iced::Element<Message> {
+        let brightness = placement
+            .brightness()
+            .map_or_else(|| "-".to_string(), |brightness| brightness.to_string());
+        container(
+            row![
+                ChartWidget::new(placement)
+                    .width(Length::Fixed(120.0))
+                    .height(Length::Fixed(120.0)),
+                column![
+                    text(format!("Brightness: {brightness}")).size(32),
+                    progress_bar(0.0..=4095.0, f32::from(placement.strength())),
+                    text(format!(
+                        "Signal strength: {}. {}",
+                        placement.strength(),
+                        placement.hint()
+                    )),
+                ]
+                .spacing(10),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(20),
+        )
+        .width(iced::Length::Fill)
+        .padding(10)
+        .into()
+    }
+
     fn draw_buttons(&self) -> iced::Element<Message> {
-        let record = container(match self.record_file {
-            Some(_) => button("Stop recording").on_press(Message::RecordStop),
-            None => button("Record").on_press(Message::RecordStart),
-        })
+        let record = container(
+            row![
+                if self.record_file.is_some() || self.pending_recording.is_some() {
+                    button("Stop recording").on_press(Message::RecordStop)
+                } else {
+                    button("Record").on_press(Message::RecordStart)
+                },
+                pick_list(
+                    &RecordingFormat::ALL[..],
+                    Some(self.record_format),
+                    Message::RecordFormatChanged
+                ),
+            ]
+            .spacing(10),
+        )
         .padding(10);
         let clear = container(button("Clear").on_press(Message::Clear)).padding(10);
+        let pause = container(match self.paused {
+            Some(_) => button("Resume").on_press(Message::PauseToggle),
+            None => button("Pause").on_press(Message::PauseToggle),
+        })
+        .padding(10);
         let toggle_graph =
             container(button("Toggle graph").on_press(Message::GraphToggle)).padding(10);
+        let toggle_slew =
+            container(button("Toggle slew").on_press(Message::SlewToggle)).padding(10);
+        let toggle_percentiles =
+            container(button("Toggle percentiles").on_press(Message::PercentilesToggle))
+                .padding(10);
+        let toggle_histogram =
+            container(button("Toggle histogram").on_press(Message::HistogramToggle)).padding(10);
+        let chart_settings =
+            container(button("Chart settings").on_press(Message::ChartSettingsToggle)).padding(10);
         let manual_trigger =
             container(button("Manual Trigger").on_press(Message::ManualTrigger)).padding(10);
-        container(row![record, clear, toggle_graph, manual_trigger])
+        let test_run = container(
+            button("Run test")
+                .on_press_maybe(self.test_run.is_none().then_some(Message::TestSetupToggle)),
+        )
+        .padding(10);
+        let loopback = container(match self.loopback {
+            Some(_) => button("Stop loopback").on_press(Message::LoopbackStop),
+            None => button("Loopback test").on_press(Message::LoopbackStart),
+        })
+        .padding(10);
+        let placement = container(match self.placement {
+            Some(_) => button("Hide placement helper").on_press(Message::PlacementToggle),
+            None => button("Placement helper").on_press(Message::PlacementToggle),
+        })
+        .padding(10);
+        // drag on the raw chart to select
+        let segment = container(
+            row![
+                button("Reset view").on_press_maybe(self.zoom.map(|_| Message::ViewReset)),
+                button("Export CSV").on_press_maybe(
+                    self.segment
+                        .map(|_| Message::SegmentExport(ExportFormat::Csv))
+                ),
+                button("Export PNG").on_press_maybe(
+                    self.segment
+                        .map(|_| Message::SegmentExport(ExportFormat::Png))
+                ),
+            ]
+            .spacing(10),
+        )
+        .padding(10);
+        let config = container(
+            row![
+                button("Export results").on_press(Message::ResultsExport),
+                button("Export config").on_press(Message::ConfigExport),
+                button("Import config").on_press(Message::ConfigImport),
+            ]
+            .spacing(10),
+        )
+        .padding(10);
+        container(row![
+            record,
+            clear,
+            pause,
+            toggle_graph,
+            toggle_slew,
+            toggle_percentiles,
+            toggle_histogram,
+            chart_settings,
+            manual_trigger,
+            test_run,
+            loopback,
+            placement,
+            segment,
+            config
+        ])
+        .center_x()
+        .width(iced::Length::Fill)
+        .padding(10)
+        .into()
+    }
+
+    // Shown below the buttons once opened
+    fn draw_chart_settings(&self) -> iced::Element<Message> {
+        if !self.show_chart_settings {
+            return Space::new(Length::Shrink, Length::Shrink).into();
+        }
+        let auto_scale = if self.auto_scale {
+            button("Fixed Y range").on_press(Message::AutoScaleToggle)
+        } else {
+            button("Auto scale Y").on_press(Message::AutoScaleToggle)
+        };
+        container(
+            row![
+                text(format!("Time window: {} s", self.window)),
+                slider(MIN_WINDOW..=MAX_WINDOW, self.window, Message::WindowChanged),
+                auto_scale,
+            ]
+            .align_items(Alignment::Center)
+            .spacing(20),
+        )
+        .center_x()
+        .width(iced::Length::Fill)
+        .padding(10)
+        .into()
+    }
+
+    // Newest at the bottom, right above the window edge
+    fn draw_notifications(&self) -> iced::Element<Message> {
+        let mut notifications = column![].spacing(5).align_items(Alignment::Center);
+        for notification in self.notifications.iter() {
+            notifications = notifications.push(text(notification.to_string()).size(14));
+        }
+        container(
+            row![
+                notifications,
+                button("Dismiss").on_press(Message::NotificationsClear)
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center),
+        )
+        .padding(5)
+        .into()
+    }
+
+    // Setup until started, then the progress and the results
+    fn draw_test_run(&self) -> iced::Element<Message> {
+        let content: iced::Element<Message> = match self.test_run {
+            Some(ref test_run) => column![
+                text(test_run.status()),
+                progress_bar(0.0..=1.0, test_run.progress()),
+                if test_run.is_finished() {
+                    button("Close").on_press(Message::TestStop)
+                } else {
+                    button("Stop").on_press(Message::TestStop)
+                },
+            ]
+            .align_items(Alignment::Center)
+            .spacing(10)
+            .into(),
+            None if self.show_test_setup => {
+                let settings = self.test_settings;
+                row![
+                    text(format!("Shots: {}", settings.shots)),
+                    slider(1..=1000, settings.shots, Message::TestShotsChanged),
+                    text(format!(
+                        "Interval: {}-{} ms",
+                        settings.min_interval, settings.max_interval
+                    )),
+                    slider(
+                        100..=5000,
+                        settings.min_interval,
+                        Message::TestMinIntervalChanged
+                    )
+                    .step(100u16),
+                    slider(
+                        100..=5000,
+                        settings.max_interval,
+                        Message::TestMaxIntervalChanged
+                    )
+                    .step(100u16),
+                    text(format!("Countdown: {} s", settings.countdown)),
+                    slider(0..=10, settings.countdown, Message::TestCountdownChanged),
+                    button("Start")
+                        .on_press_maybe(self.worker.as_ref().map(|_| Message::TestStart)),
+                    button("Cancel").on_press(Message::TestSetupToggle),
+                ]
+                .align_items(Alignment::Center)
+                .spacing(20)
+                .into()
+            }
+            None => return Space::new(Length::Shrink, Length::Shrink).into(),
+        };
+        container(content)
             .center_x()
             .width(iced::Length::Fill)
             .padding(10)
             .into()
     }
 
+    fn draw_connection(&self) -> iced::Element<Message> {
+        let selected = self
+            .ports
+            .iter()
+            .find(|port| Some(&port.name) == self.selected_port.as_ref())
+            .cloned();
+        let port_options = pick_list(&self.ports[..], selected, |port| {
+            Message::PortSelected(port.name)
+        });
+        let connect = match self.worker {
+            Some(_) => button("Disconnect").on_press(Message::Disconnect),
+            None => button("Connect")
+                .on_press_maybe(self.selected_port.as_ref().map(|_| Message::Connect)),
+        };
+        let port = self.selected_port.as_deref().unwrap_or_default();
+        let status = match (&self.worker, &self.connection_error) {
+            (Some(_), _) if self.device_state.connected => format!("Connected to {port}"),
+            (Some(_), _) if self.connecting => format!("Connecting to {port}"),
+            (Some(_), _) => format!("Lost {port}, reconnecting"),
+            (None, Some(why)) => format!("Can't connect to {port}: {why}"),
+            (None, None) => String::from("Disconnected"),
+        };
+        container(
+            row![
+                text("Port"),
+                port_options,
+                button("Refresh").on_press(Message::PortsRefresh),
+                connect,
+                text(status),
+                button(match self.theme {
+                    AppTheme::Light => "Dark theme",
+                    AppTheme::Dark => "Light theme",
+                })
+                .on_press(Message::ThemeToggle),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(20),
+        )
+        .center_x()
+        .width(iced::Length::Fill)
+        .padding(10)
+        .into()
+    }
+
+    fn draw_blind_setup(&self) -> iced::Element<Message> {
+        let describe = |condition: Condition| {
+            self.blind_profiles[condition as usize].map_or_else(
+                || format!("{condition}: not set"),
+                |profile| {
+                    format!(
+                        "{condition}: {} Hz, {}, threshold {}",
+                        profile.poll_rate, profile.report_mode, profile.threshold
+                    )
+                },
+            )
+        };
+        let ready = self.blind_profiles.iter().all(Option::is_some);
+        container(
+            row![
+                button("Current settings as A").on_press(Message::BlindCapture(Condition::A)),
+                text(describe(Condition::A)),
+                button("Current settings as B").on_press(Message::BlindCapture(Condition::B)),
+                text(describe(Condition::B)),
+                button("Blind test").on_press_maybe(ready.then_some(Message::BlindStart)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(20),
+        )
+        .center_x()
+        .width(iced::Length::Fill)
+        .padding(10)
+        .into()
+    }
+
+    fn draw_blind_test(blind_test: &BlindTest) -> iced::Element<Message> {
+        let controls = if blind_test.is_finished() {
+            row![button("Close").on_press(Message::BlindStop)]
+        } else {
+            row![
+                button("Felt like A").on_press(Message::BlindGuess(Condition::A)),
+                button("Felt like B").on_press(Message::BlindGuess(Condition::B)),
+                button("Stop").on_press(Message::BlindStop),
+            ]
+        };
+        container(
+            column![
+                text(blind_test.status()),
+                controls.align_items(Alignment::Center).spacing(20)
+            ]
+            .align_items(Alignment::Center)
+            .spacing(10),
+        )
+        .center_x()
+        .width(iced::Length::Fill)
+        .padding(10)
+        .into()
+    }
+
+    // Everything is offered until the firmware said otherwise
+    fn supports(&self, feature: fn(&Capabilities) -> bool) -> bool {
+        self.hello.is_none_or(|hello| feature(&hello.capabilities))
+    }
+
+    // Controls the firmware didn't report support for turn into their label, the tooltip says why
+    fn gated<'a>(
+        &self,
+        supported: fn(&Capabilities) -> bool,
+        feature: &str,
+        label: String,
+        control: impl Into<iced::Element<'a, Message>>,
+    ) -> iced::Element<'a, Message> {
+        match self.hello {
+            Some(hello) if !supported(&hello.capabilities) => tooltip(
+                text(label),
+                text(format!(
+                    "The connected firmware, protocol version {}, doesn't report {feature} support",
+                    hello.protocol_version
+                )),
+                tooltip::Position::Top,
+            )
+            .into(),
+            _ => control.into(),
+        }
+    }
+
     fn draw_rate_selection(&self) -> iced::Element<Message> {
         let poll_rate_text = text("Poll rate");
         let poll_rate_options: Container<'_, Message> = container(pick_list(
@@ -347,8 +1416,38 @@ impl UI {
             Some(self.selected_pollrate),
             Message::PollRateChanged,
         ));
+        let gain = self.gated(
+            |capabilities| capabilities.sensor_gain,
+            "sensor gain",
+            String::from("Gain"),
+            row![
+                text("Gain"),
+                pick_list(
+                    &SensorGain::ALL[..],
+                    Some(self.sensor_gain),
+                    Message::SensorGainChanged
+                )
+            ]
+            .align_items(Alignment::Center)
+            .spacing(20),
+        );
+        let baseline = self.gated(
+            |capabilities| capabilities.detection_mode,
+            "detection modes",
+            String::from("Baseline"),
+            row![
+                text("Baseline"),
+                pick_list(
+                    &Baseline::ALL[..],
+                    Some(self.detection_mode.baseline),
+                    Message::BaselineChanged
+                )
+            ]
+            .align_items(Alignment::Center)
+            .spacing(20),
+        );
         container(
-            row![poll_rate_text, poll_rate_options]
+            row![poll_rate_text, poll_rate_options, gain, baseline]
                 .align_items(Alignment::Center)
                 .spacing(20),
         )
@@ -378,6 +1477,28 @@ impl UI {
                 ReportMode::Combined,
                 Some(self.selected_reportmode),
                 Message::ReportModeChanged
+            ),
+            self.gated(
+                |capabilities| capabilities.raw_audio,
+                "raw audio",
+                ReportMode::RawAudio.to_string(),
+                radio(
+                    ReportMode::RawAudio.to_string(),
+                    ReportMode::RawAudio,
+                    Some(self.selected_reportmode),
+                    Message::ReportModeChanged
+                )
+            ),
+            self.gated(
+                |capabilities| capabilities.audio_video,
+                "audio/video",
+                ReportMode::AudioVideo.to_string(),
+                radio(
+                    ReportMode::AudioVideo.to_string(),
+                    ReportMode::AudioVideo,
+                    Some(self.selected_reportmode),
+                    Message::ReportModeChanged
+                )
             )
         ]
         .spacing(20);
@@ -407,6 +1528,12 @@ impl UI {
                 Some(self.selected_action_type),
                 Message::ActionModeChanged
             ),
+            radio(
+                ActionType::Gamepad.to_string(),
+                ActionType::Gamepad,
+                Some(self.selected_action_type),
+                Message::ActionModeChanged
+            ),
         ]
         .spacing(20);
         container(
@@ -428,6 +1555,13 @@ impl UI {
                             |key| Message::ActionKeyChanged(key as u8),
                         ))
                     }
+                    ActionType::Gamepad => {
+                        container(pick_list(
+                            &GamepadButton::ALL[..],
+                            self.selected_action_key.gamepad,
+                            |key| Message::ActionKeyChanged(key as u8),
+                        ))
+                    }
                 },
             ]
             .align_items(Alignment::Center)
@@ -450,10 +1584,21 @@ impl UI {
         .on_release(Message::ThresholdReleased)
         .step(10i16)
         .shift_step(1i16);
+        let summary_source_text = text("Summary from");
+        let summary_source_options = pick_list(
+            &SummarySource::ALL[..],
+            Some(self.summary_source),
+            Message::SummarySourceChanged,
+        );
         container(
-            row![threshold_text, threshold_slider]
-                .align_items(Alignment::Center)
-                .spacing(20),
+            row![
+                threshold_text,
+                threshold_slider,
+                summary_source_text,
+                summary_source_options
+            ]
+            .align_items(Alignment::Center)
+            .spacing(20),
         )
         .center_x()
         .width(iced::Length::Fill)
@@ -461,102 +1606,587 @@ impl UI {
         .into()
     }
 
-    fn get_port() -> Result<Box<dyn SerialPort>, serialport::Error> {
-        let ports = serialport::available_ports()?;
-        serialport::new(&ports.first().expect("No Serial Ports").port_name, 115_200)
-            .timeout(Duration::from_secs(100_000))
-            .open()
+    // Where the previous session left off, read before the window opens
+    #[allow(clippy::cast_precision_loss)]
+    pub fn saved_window_size() -> Option<Size> {
+        let session = SessionConfig::load(&SessionConfig::path()?).ok()??;
+        let size = session.window_size?;
+        Some(Size::new(size.width as f32, size.height as f32))
     }
 
     pub fn theme(&self) -> Theme {
-        self.theme.clone()
+        self.theme.into()
     }
 
     #[allow(clippy::unused_self)]
-    // just for polling fakeldat
+    // reports come from the worker, ticks only drive the timing and the replay
     pub fn subscription(&self) -> Subscription<Message> {
-        // for raw it needs to be at least (pollrate/256)
-        let hertz = self.forced_tick_rate.map_or_else(
-            || {
-                match self.selected_reportmode {
-                    ReportMode::Raw | ReportMode::Combined => {
-                        std::convert::Into::<u16>::into(self.selected_pollrate) / 200
-                    }
-                    ReportMode::Summary => 10,
+        let hertz = if self.device_state.connected || self.journal.is_replaying() {
+            TICK_RATE
+        } else {
+            // only waiting for the device to come back or to be connected
+            1
+        };
+        let tick = iced::time::every(Duration::from_micros(1_000_000 / u64::from(hertz)))
+            .map(|_| Message::Tick);
+        let device = self
+            .worker
+            .as_ref()
+            .map_or_else(Subscription::none, |worker| {
+                worker.subscription().map(Message::Device)
+            });
+        // frames are only needed while a flash waits to be shown
+        let frames = match self.loopback {
+            Some(ref loopback) if loopback.is_waiting_for_frame() => {
+                iced::window::frames().map(Message::LoopbackFrame)
+            }
+            _ => Subscription::none(),
+        };
+        let resize = iced::event::listen_with(|event, _| match event {
+            iced::Event::Window(_, iced::window::Event::Resized { width, height }) => {
+                Some(Message::WindowResized(width, height))
+            }
+            _ => None,
+        });
+        Subscription::batch([tick, device, frames, resize])
+    }
+
+    // Recordings get a comment line, the CSV parsers skip it
+    fn mark_setting_change(&mut self, label: String) -> Result<(), Error> {
+        let timestamp = self.raw_data.back().map(|raw_report| raw_report.timestamp);
+        if let Some(ref mut record_file) = self.record_file {
+            record_file.write_comment(&label)?;
+        }
+        self.setting_changes.push(SettingChange {
+            timestamp,
+            session_time: self.session_start.elapsed(),
+            label,
+        });
+        Ok(())
+    }
+
+    fn push_summary(&mut self, summary_report: SummaryReport) {
+        if let Some(ref mut loopback) = self.loopback {
+            loopback.push_delay(summary_report.delay);
+        }
+        if let Some(ref mut blind_test) = self.blind_test {
+            blind_test.push_delay(summary_report.delay);
+        }
+        self.summary_data
+            .push((self.session_start.elapsed(), summary_report));
+    }
+
+    // New file in the record folder, named after the report mode
+    fn start_recording(&mut self) -> Result<(), Error> {
+        let Some(ref record_dir) = self.record_dir else {
+            return Ok(());
+        };
+        let now: DateTime<Utc> = Utc::now();
+        let path = record_dir.join(format!(
+            "{}_report {}.{}",
+            self.selected_reportmode.to_string().to_lowercase(),
+            now.format("%d-%m-%Y %H.%M.%S"),
+            self.record_format.extension()
+        ));
+        let pending = PendingRecording {
+            path: path.clone(),
+            format: self.record_format,
+            reports: Vec::new(),
+        };
+        if self.worker.is_none() {
+            // replays have no device to describe
+            return self.open_recording(pending, None);
+        }
+        self.pending_recording = Some(pending);
+        device(self.worker.as_ref())?.run_with_events(move |fakeldat, events| {
+            let metadata = fakeldat.session_metadata();
+            let sidecar = SessionMetadata::sidecar_path(&path);
+            // the recording starts even if the sidecar can't be saved
+            _ = events.unbounded_send(DeviceEvent::Metadata(path, metadata.clone()));
+            // lets fakeldat-cli report tell sessions apart
+            metadata.save(sidecar)
+        });
+        Ok(())
+    }
+
+    // Reports which came in while waiting go right after the header
+    fn open_recording(
+        &mut self,
+        pending: PendingRecording,
+        metadata: Option<&SessionMetadata>,
+    ) -> Result<(), Error> {
+        let mut record_file = Recorder::with_format(&pending.path, pending.format.into())?;
+        if let Some(metadata) = metadata {
+            record_file.write_metadata(metadata)?;
+        }
+        record_file.set_slew(self.show_slew);
+        for report in &pending.reports {
+            record_file.write_report(report)?;
+        }
+        self.record_file = Some(record_file);
+        Ok(())
+    }
+
+    // Next trigger of the test run, run on the worker so the UI keeps going meanwhile
+    fn queue_test_shot(&self) -> Result<(), Error> {
+        let Some(ref test_run) = self.test_run else {
+            return Ok(());
+        };
+        let (min, max) = test_run.interval();
+        device(self.worker.as_ref())?.run_with_events(move |fakeldat, events| {
+            let result = fakeldat.run_trigger_sequence(1, min, max.saturating_sub(min));
+            // a failed trigger counts as missed, the run would wait for it forever
+            let shot = result.as_ref().map_or_else(
+                |_| SequenceResult {
+                    reports: Vec::new(),
+                    missed: 1,
+                },
+                Clone::clone,
+            );
+            _ = events.unbounded_send(DeviceEvent::TestShot(shot));
+            result.map(drop)
+        });
+        Ok(())
+    }
+
+    // Up to where the view was paused
+    fn visible_summaries(&self) -> &[(Duration, SummaryReport)] {
+        let end = self.paused.map_or(self.summary_data.len(), |paused| {
+            paused.min(self.summary_data.len())
+        });
+        &self.summary_data[..end]
+    }
+
+    fn current_profile(&self) -> Option<Profile> {
+        let action = match self.selected_action_type {
+            ActionType::Mouse => self.selected_action_key.mouse.map(ActionMode::Mouse),
+            ActionType::Keyboard => self.selected_action_key.keyboard.map(ActionMode::Keyboard),
+            ActionType::Gamepad => self.selected_action_key.gamepad.map(ActionMode::Gamepad),
+        }?;
+        Some(Profile {
+            poll_rate: self.selected_pollrate.into(),
+            report_mode: self.selected_reportmode,
+            threshold: self.threshold,
+            action,
+        })
+    }
+
+    fn preferences(&self) -> Preferences {
+        Preferences {
+            show_graph: self.show_graph,
+            show_percentiles: self.show_percentiles,
+            show_histogram: self.show_histogram,
+            histogram_bin_width: self.histogram_bin_width,
+            window: self.window,
+            auto_scale: self.auto_scale,
+            show_slew: self.show_slew,
+            summary_source: self.summary_source,
+        }
+    }
+
+    fn apply_config(&mut self, config: &AppConfig) -> Result<(), Error> {
+        self.apply_preferences(config.preferences);
+        if let Some(profile) = config.device {
+            device(self.worker.as_ref())?.run(move |fakeldat| fakeldat.apply_profile(&profile));
+            self.threshold = profile.threshold;
+        }
+        self.blind_profiles = config.blind_profiles();
+        self.reset_host_detector();
+        Ok(())
+    }
+
+    fn apply_preferences(&mut self, preferences: Preferences) {
+        self.show_graph = preferences.show_graph;
+        self.show_percentiles = preferences.show_percentiles;
+        self.show_histogram = preferences.show_histogram;
+        self.histogram_bin_width = preferences
+            .histogram_bin_width
+            .clamp(MIN_BIN_WIDTH, MAX_BIN_WIDTH);
+        self.show_slew = preferences.show_slew;
+        self.window = preferences.window.clamp(MIN_WINDOW, MAX_WINDOW);
+        self.auto_scale = preferences.auto_scale;
+        self.resize_window();
+        self.summary_source = preferences.summary_source;
+    }
+
+    // The device settings are only shown until a device is connected, they're applied then
+    fn restore_session(&mut self) {
+        let Some(path) = SessionConfig::path() else {
+            return;
+        };
+        let session = match SessionConfig::load(&path) {
+            Ok(Some(session)) => session,
+            Ok(None) => return,
+            Err(why) => return self.notifications.push(Level::Error, error_message(&why)),
+        };
+        self.theme = session.theme;
+        self.window_size = session.window_size;
+        self.record_dir.clone_from(&session.record_dir);
+        self.apply_preferences(session.preferences);
+        if let Some(profile) = session.device {
+            self.selected_pollrate = profile.poll_rate.into();
+            self.selected_reportmode = profile.report_mode;
+            self.threshold = profile.threshold;
+            match profile.action {
+                ActionMode::Mouse(button) => {
+                    self.selected_action_type = ActionType::Mouse;
+                    self.selected_action_key.mouse = Some(button);
                 }
-                .clamp(10, u16::MAX)
-            },
-            |forced_tick_rate| forced_tick_rate,
+                ActionMode::Keyboard(keyboard_key) => {
+                    self.selected_action_type = ActionType::Keyboard;
+                    self.selected_action_key.keyboard = Some(keyboard_key);
+                }
+                ActionMode::Gamepad(button) => {
+                    self.selected_action_type = ActionType::Gamepad;
+                    self.selected_action_key.gamepad = Some(button);
+                }
+            }
+            self.resize_window();
+        }
+        self.reset_host_detector();
+        self.saved_session = Some(session);
+    }
+
+    fn save_session(&mut self) -> Result<(), Error> {
+        let session = SessionConfig::new(
+            self.theme,
+            self.record_dir.clone(),
+            self.preferences(),
+            self.current_profile(),
+            self.window_size,
         );
-        iced::time::every(Duration::from_micros(1_000_000 / u64::from(hertz)))
-            .map(|_| Message::Tick)
+        if self.saved_session.as_ref() == Some(&session) {
+            return Ok(());
+        }
+        let Some(path) = SessionConfig::path() else {
+            return Ok(());
+        };
+        // a failing write isn't retried every tick
+        self.saved_session = Some(session.clone());
+        session.save(&path)
+    }
+
+    fn apply_condition(&mut self, condition: Condition) -> Result<(), Error> {
+        if let Some(profile) = self.blind_profiles[condition as usize] {
+            device(self.worker.as_ref())?.run(move |fakeldat| fakeldat.apply_profile(&profile));
+            self.threshold = profile.threshold;
+            self.reset_host_detector();
+        }
+        Ok(())
+    }
+
+    // Device timestamps might have restarted
+    fn forget_device_time(&mut self) {
+        self.raw_data.clear();
+        self.chart_cache.clear();
+        self.trigger_timestamps.clear();
+        self.macro_timestamps.clear();
+        self.setting_changes
+            .retain(|change| change.timestamp.is_none());
+    }
+
+    fn reset_host_detector(&mut self) {
+        self.host_detector = match self.summary_source {
+            SummarySource::Device => None,
+            SummarySource::Host(detector) => Some(LatencyDetector::with_detector(
+                detector.create(self.threshold),
+            )),
+        };
+    }
+
+    // Data is kept in order
+    fn time_range(&self) -> (u64, u64) {
+        match (self.raw_data.front(), self.raw_data.back()) {
+            (Some(first), Some(last)) => (first.timestamp, last.timestamp),
+            _ => (std::u64::MAX, std::u64::MIN),
+        }
+    }
+
+    // Zoomed in part of the data or all of it
+    fn visible_range(&self) -> (u64, u64) {
+        self.zoom
+            .map_or_else(|| self.time_range(), |zoom| (zoom.start, zoom.end))
+    }
+
+    // Fraction of the raw chart width to the timestamp shown there
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn timestamp_at(&self, fraction: f32) -> u64 {
+        let (min, max) = self.visible_range();
+        min + (max.saturating_sub(min) as f64 * f64::from(fraction.clamp(0.0, 1.0))) as u64
+    }
+
+    // After the poll rate or the time window changes, the oldest data goes first
+    fn resize_window(&mut self) {
+        let capacity = sample_capacity(self.selected_pollrate, self.window);
+        let excess = self.raw_data.len().saturating_sub(capacity);
+        self.raw_data.drain(..excess);
+        self.chart_cache.rebuild(capacity, &self.raw_data);
     }
 
     fn push_data(&mut self, data: RawReport) {
-        // 4 seconds of data
-        let sample_count = std::convert::Into::<u16>::into(self.selected_pollrate) as usize * 4;
-        match self.raw_data.len().cmp(&sample_count) {
+        match self
+            .raw_data
+            .len()
+            .cmp(&sample_capacity(self.selected_pollrate, self.window))
+        {
             Ordering::Less => {}
             Ordering::Equal => _ = self.raw_data.pop_front(),
-            Ordering::Greater => self.raw_data = vec![].into(),
+            Ordering::Greater => {
+                self.raw_data = vec![].into();
+                self.chart_cache.clear();
+            }
         };
+        self.chart_cache.push(self.raw_data.back(), &data);
         self.raw_data.push_back(data);
+        if let Some(oldest) = self.raw_data.front() {
+            self.chart_cache.trim(oldest.timestamp);
+        }
     }
 }
 
+// Commands sent while disconnected fail like on a closed port
+fn device(worker: Option<&Worker>) -> Result<&Worker, Error> {
+    worker.ok_or_else(|| {
+        Error::PortFail(serialport::Error::new(
+            serialport::ErrorKind::NoDevice,
+            "Not connected",
+        ))
+    })
+}
+
+// From both the UI and the worker thread, shown as a notification
+fn error_message(why: &Error) -> String {
+    match why {
+        Error::WrongChecksum(_, _, _) | Error::ReadTooLittleData => unreachable!(), // Those should be internal
+        Error::InvalidSetting(command, buf) => {
+            format!("Invalid setting for {command}: {:x} {:x}", buf[0], buf[1])
+        }
+        Error::InvalidCommand(command_id) => format!("Invalid command id: {command_id}"),
+        Error::PortFail(serialport_error) => {
+            format!("Port fail: {}", serialport_error.description)
+        }
+        Error::SendCommandFail => String::from("Issue with sending a command"),
+        Error::IOError(io_error) => format!("Issue with saving a file: {io_error}"),
+        Error::InvalidEnumConverion => String::from("TryFrom enum conversion error"),
+        Error::CommandTimeout(command) => format!("No response for: {command}"),
+        Error::ReportBufferFull => String::from("Reports are coming in faster than handled"),
+        Error::Unsupported(command) => format!("The firmware doesn't support: {command}"),
+    }
+}
+
+fn invalid_data(why: impl std::fmt::Display) -> Error {
+    Error::IOError(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        why.to_string(),
+    ))
+}
+
+// Ticks per second while connected
+const TICK_RATE: u16 = 60;
+
+// Seconds between telemetry reports
+const TELEMETRY_INTERVAL: u16 = 10;
+
+// Histogram bin widths in microseconds
+const DEFAULT_BIN_WIDTH: u16 = 1000;
+const MIN_BIN_WIDTH: u16 = 100;
+const MAX_BIN_WIDTH: u16 = 10000;
+
+// Seconds of raw data kept and charted
+const DEFAULT_WINDOW: u8 = 4;
+const MIN_WINDOW: u8 = 1;
+const MAX_WINDOW: u8 = 60;
+
+// Smallest brightness range auto scaling zooms into
+const MIN_Y_RANGE: u64 = 32;
+
+fn sample_capacity(poll_rate: PollRate, window: u8) -> usize {
+    std::convert::Into::<u16>::into(poll_rate) as usize * usize::from(window)
+}
+
+// Left and right label areas of the raw chart
+const LABEL_AREA_SIZE: u32 = 45;
+
+// How much one wheel step scales the shown time range
+const ZOOM_STEP: f32 = 0.8;
+
+// Mouse on the raw chart, as fractions of the plotting area width
+#[derive(Default)]
+pub struct ChartState {
+    // where dragging with the left button started
+    selecting: Option<f32>,
+    // last position while dragging with the right button
+    panning: Option<f32>,
+    hover: Option<f32>,
+}
+
 impl Chart<Message> for UI {
-    type State = ();
+    type State = ChartState;
     fn draw_chart<DB: DrawingBackend>(&self, state: &Self::State, root: DrawingArea<DB, Shift>) {
         _ = root.fill(&WHITE);
         let builder = ChartBuilder::on(&root);
         self.build_chart(state, builder);
     }
-    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut builder: ChartBuilder<DB>) {
-        let min = self
-            .raw_data
-            .iter()
-            .fold(std::u64::MAX, |a, b| a.min(b.timestamp));
-        let max = self
-            .raw_data
-            .iter()
-            .fold(std::u64::MIN, |a, b| a.max(b.timestamp));
+    fn build_chart<DB: DrawingBackend>(&self, state: &Self::State, mut builder: ChartBuilder<DB>) {
+        let (min, max) = self.visible_range();
+        let visible = |point: &(u64, u64)| (min..=max).contains(&point.0);
+        // zoomed in transitions need every sample, the cache skips them
+        let (brightness, audio): (Vec<(u64, u64)>, Vec<(u64, u64)>) = match self.zoom {
+            Some(_) => {
+                let first = self
+                    .raw_data
+                    .partition_point(|report| report.timestamp < min);
+                let last = self
+                    .raw_data
+                    .partition_point(|report| report.timestamp <= max);
+                self.raw_data
+                    .range(first..last)
+                    .step_by((last - first) / MAX_POINTS + 1)
+                    .map(|report| {
+                        (
+                            (report.timestamp, report.brightness.into()),
+                            (report.timestamp, report.audio.into()),
+                        )
+                    })
+                    .unzip()
+            }
+            None => (
+                self.chart_cache.brightness.iter().copied().collect(),
+                self.chart_cache.audio.iter().copied().collect(),
+            ),
+        };
+        // the threshold follows the baseline, the line is where it is now
+        let threshold_level = match self.detection_mode.baseline {
+            Baseline::Relative => self.chart_cache.baseline_level().map(|baseline| {
+                i64::try_from(baseline).unwrap_or_default() + i64::from(self.threshold)
+            }),
+            Baseline::Absolute => Some(i64::from(self.threshold)),
+        }
+        .map(|level| u64::try_from(level.clamp(0, 4095)).unwrap_or_default());
+        let (y_min, y_max) = if self.auto_scale {
+            // audio stays at 0 without a microphone, it would squash the range
+            let audio_levels = audio
+                .iter()
+                .any(|point| point.1 > 0)
+                .then_some(&audio)
+                .into_iter()
+                .flatten();
+            let channel_levels = self
+                .chart_cache
+                .channels
+                .values()
+                .flat_map(|line| line.points.iter().filter(|&point| visible(point)));
+            let levels = brightness
+                .iter()
+                .chain(audio_levels)
+                .chain(channel_levels)
+                .map(|point| point.1)
+                .chain(threshold_level);
+            let (low, high) = levels.fold((u64::MAX, u64::MIN), |(low, high), level| {
+                (low.min(level), high.max(level))
+            });
+            if low > high {
+                (0, 4095)
+            } else {
+                let margin = ((high - low) / 20).max(MIN_Y_RANGE / 2);
+                (low.saturating_sub(margin), (high + margin).min(4095))
+            }
+        } else {
+            (0, 4095)
+        };
         let mut chart = builder
-            .set_all_label_area_size(45)
+            .set_all_label_area_size(LABEL_AREA_SIZE)
             .top_x_label_area_size(20)
             .x_label_area_size(20)
-            .build_cartesian_2d(min..max, 0u64..4096)
-            .unwrap();
-        
-        let amount_to_skip = self.raw_data.len() / 4096 + 1;
+            .build_cartesian_2d(min..max, y_min..y_max + 1)
+            .unwrap()
+            .set_secondary_coord(min..max, -4096i64..4096);
+
+        if let Some(segment) = self.segment {
+            chart
+                .draw_series(std::iter::once(Rectangle::new(
+                    [
+                        (segment.start.max(min), y_max),
+                        (segment.end.min(max), y_min),
+                    ],
+                    BLUE.mix(0.2).filled(),
+                )))
+                .expect("Draw segment");
+        }
         chart
-            .draw_series(LineSeries::new(
-                self.raw_data
-                    .iter()
-                    .enumerate()
-                    .filter(|(i, _)| i % amount_to_skip == 0)
-                    .map(|(_, report)| (report.timestamp, report.brightness.into())),
-                BLUE.stroke_width(2),
-            ))
+            .draw_series(LineSeries::new(brightness, BLUE.stroke_width(2)))
             .expect("Draw brightness line");
         chart
             .draw_series(LineSeries::new(
-                self.raw_data
-                    .iter()
-                    .enumerate()
-                    .filter(|(i, _)| i % amount_to_skip == 0)
-                    .map(|(_, report)| (report.timestamp, report.audio.into())),
+                audio
+                    .into_iter()
+                    .map(|(timestamp, level)| (timestamp, level.clamp(y_min, y_max))),
                 ORANGE.stroke_width(2),
             ))
             .expect("Draw audio line");
+        if self.detection_mode.baseline == Baseline::Relative {
+            chart
+                .draw_series(LineSeries::new(
+                    self.chart_cache.baseline.iter().copied().filter(visible),
+                    GREY.stroke_width(1),
+                ))
+                .expect("Draw baseline line");
+        }
+        if let Some(level) = threshold_level {
+            chart
+                .draw_series(std::iter::once(PathElement::new(
+                    vec![(min, level), (max, level)],
+                    PURPLE.stroke_width(1),
+                )))
+                .expect("Draw threshold line");
+            chart
+                .draw_series(std::iter::once(
+                    EmptyElement::at((min, level))
+                        + Text::new(format!("Threshold {level}"), (4, -16), ("sans-serif", 12)),
+                ))
+                .expect("Draw threshold label");
+        }
+        for (channel, line) in &self.chart_cache.channels {
+            chart
+                .draw_series(LineSeries::new(
+                    line.points.iter().copied().filter(visible),
+                    Palette99::pick(usize::from(*channel)).stroke_width(2),
+                ))
+                .expect("Draw channel line");
+        }
+        if self.show_slew {
+            chart
+                .draw_secondary_series(LineSeries::new(
+                    self.chart_cache
+                        .slew
+                        .iter()
+                        .copied()
+                        .filter(|point| (min..=max).contains(&point.0)),
+                    RED.stroke_width(1),
+                ))
+                .expect("Draw slew line");
+            chart
+                .configure_secondary_axes()
+                .y_label_formatter(&ToString::to_string)
+                .draw()
+                .expect("Draw slew axis");
+        }
         chart
-        .draw_series(self.trigger_timestamps.iter().filter_map(|trigger| {
-            if *trigger > min {
-                Some(Rectangle::new([(*trigger, 4095), (*trigger, 0)], GREEN))
-            } else {
-                None
-            }
-        }))
-        .expect("Draw triggers");
+            .draw_series(self.trigger_timestamps.iter().filter_map(|trigger| {
+                if *trigger > min && *trigger <= max {
+                    Some(Rectangle::new(
+                        [(*trigger, y_max), (*trigger, y_min)],
+                        GREEN,
+                    ))
+                } else {
+                    None
+                }
+            }))
+            .expect("Draw triggers");
         chart
             .configure_mesh()
             .disable_mesh()
@@ -566,13 +2196,148 @@ impl Chart<Message> for UI {
             .expect("Draw mesh");
         chart
             .draw_series(self.macro_timestamps.iter().filter_map(|timestamp| {
-                if *timestamp > min {
-                    Some(Rectangle::new([(*timestamp, 4095), (*timestamp, 0)], GREEN))
+                if *timestamp > min && *timestamp <= max {
+                    Some(Rectangle::new(
+                        [(*timestamp, y_max), (*timestamp, y_min)],
+                        GREEN,
+                    ))
                 } else {
                     None
                 }
             }))
             .expect("Draw macros");
-        // TODO: visualize the threshold
+        let setting_changes = self.setting_changes.iter().filter_map(|change| {
+            Some((
+                change
+                    .timestamp
+                    .filter(|timestamp| *timestamp > min && *timestamp <= max)?,
+                &change.label,
+            ))
+        });
+        chart
+            .draw_series(setting_changes.clone().map(|(timestamp, _)| {
+                Rectangle::new([(timestamp, y_max), (timestamp, y_min)], MAGENTA)
+            }))
+            .expect("Draw setting changes");
+        chart
+            .draw_series(setting_changes.map(|(timestamp, label)| {
+                EmptyElement::at((timestamp, y_max))
+                    + Text::new(label.clone(), (4, 4), ("sans-serif", 12))
+            }))
+            .expect("Draw setting change labels");
+        // readout of the sample closest to the cursor
+        let hovered = state.hover.and_then(|fraction| {
+            let timestamp = self.timestamp_at(fraction);
+            let index = self
+                .raw_data
+                .partition_point(|report| report.timestamp < timestamp);
+            [index.checked_sub(1), Some(index)]
+                .into_iter()
+                .flatten()
+                .filter_map(|index| self.raw_data.get(index))
+                .min_by_key(|report| report.timestamp.abs_diff(timestamp))
+        });
+        if let Some(report) = hovered {
+            let (timestamp, brightness) = (report.timestamp, u64::from(report.brightness));
+            chart
+                .draw_series([
+                    PathElement::new(vec![(timestamp, y_min), (timestamp, y_max)], BLACK.mix(0.5)),
+                    PathElement::new(vec![(min, brightness), (max, brightness)], BLACK.mix(0.5)),
+                ])
+                .expect("Draw crosshair");
+            chart
+                .draw_series(std::iter::once(
+                    EmptyElement::at((timestamp, brightness))
+                        + Text::new(
+                            format!("{timestamp} µs, {brightness}"),
+                            (4, -16),
+                            ("sans-serif", 12),
+                        ),
+                ))
+                .expect("Draw cursor readout");
+        }
+    }
+    // Redrawn only after the cache gets cleared
+    fn draw<R: Renderer, F: Fn(&mut Frame)>(&self, renderer: &R, size: Size, f: F) -> Geometry {
+        R::draw_cache(renderer, &self.chart_cache.geometry, size, f)
+    }
+    #[allow(clippy::cast_precision_loss)]
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: Event,
+        bounds: iced::Rectangle,
+        cursor: Cursor,
+    ) -> (Status, Option<Message>) {
+        let Some(position) = cursor.position_in(bounds) else {
+            // the crosshair is drawn into the cached geometry
+            if state.hover.take().is_some() {
+                self.chart_cache.geometry.clear();
+            }
+            state.panning = None;
+            return (Status::Ignored, None);
+        };
+        let label_area = LABEL_AREA_SIZE as f32;
+        let fraction = (position.x - label_area) / (bounds.width - 2.0 * label_area);
+        match event {
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                state.hover = Some(fraction);
+                self.chart_cache.geometry.clear();
+                match state.panning.replace(fraction) {
+                    Some(last) => (Status::Captured, Some(Message::ChartPan(fraction - last))),
+                    None => (Status::Ignored, None),
+                }
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                let steps = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } => y,
+                    mouse::ScrollDelta::Pixels { y, .. } => y / 50.0,
+                };
+                (
+                    Status::Captured,
+                    Some(Message::ChartZoom(fraction, ZOOM_STEP.powf(steps))),
+                )
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) => {
+                state.panning = Some(fraction);
+                (Status::Captured, None)
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Right)) => {
+                state.panning = None;
+                (Status::Captured, None)
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                state.selecting = Some(fraction);
+                (Status::Captured, None)
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                match state.selecting.take() {
+                    // a click without dragging
+                    Some(start) if (start - fraction).abs() < 0.005 => {
+                        (Status::Captured, Some(Message::SegmentClear))
+                    }
+                    Some(start) => (
+                        Status::Captured,
+                        Some(Message::SegmentSelected(start, fraction)),
+                    ),
+                    None => (Status::Ignored, None),
+                }
+            }
+            _ => (Status::Ignored, None),
+        }
+    }
+    fn mouse_interaction(
+        &self,
+        state: &Self::State,
+        bounds: iced::Rectangle,
+        cursor: Cursor,
+    ) -> mouse::Interaction {
+        if state.panning.is_some() {
+            mouse::Interaction::Grabbing
+        } else if cursor.is_over(bounds) {
+            mouse::Interaction::Crosshair
+        } else {
+            mouse::Interaction::Idle
+        }
     }
 }
@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// Most notifications on screen at once, the oldest go first
+const MAX_SHOWN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    // errors stay for longer, they usually need the user to do something
+    const fn duration(self) -> Duration {
+        match self {
+            Self::Info | Self::Warning => Duration::from_secs(5),
+            Self::Error => Duration::from_secs(10),
+        }
+    }
+}
+
+pub struct Notification {
+    pub level: Level,
+    pub message: String,
+    // repeats of the same message, a checksum storm shouldn't push everything else out
+    pub count: u32,
+    since: Instant,
+}
+
+impl fmt::Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level {
+            Level::Info => write!(f, "{}", self.message)?,
+            Level::Warning => write!(f, "Warning: {}", self.message)?,
+            Level::Error => write!(f, "Error: {}", self.message)?,
+        }
+        if self.count > 1 {
+            write!(f, " (x{})", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+// Toasts shown over the status bar until they expire or get dismissed
+#[derive(Default)]
+pub struct Notifications {
+    shown: VecDeque<Notification>,
+}
+
+impl Notifications {
+    pub fn push(&mut self, level: Level, message: String) {
+        if let Some(notification) = self
+            .shown
+            .iter_mut()
+            .find(|notification| notification.level == level && notification.message == message)
+        {
+            notification.count = notification.count.saturating_add(1);
+            notification.since = Instant::now();
+            return;
+        }
+        if self.shown.len() >= MAX_SHOWN {
+            self.shown.pop_front();
+        }
+        self.shown.push_back(Notification {
+            level,
+            message,
+            count: 1,
+            since: Instant::now(),
+        });
+    }
+
+    pub fn expire(&mut self) {
+        self.shown
+            .retain(|notification| notification.since.elapsed() < notification.level.duration());
+    }
+
+    pub fn clear(&mut self) {
+        self.shown.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shown.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Notification> {
+        self.shown.iter()
+    }
+}
@@ -0,0 +1,105 @@
+use super::enums::Message;
+use super::SettingChange;
+use fakeldat_lib::SummaryReport;
+use plotters::coord::Shift;
+use plotters::element::{EmptyElement, PathElement, Text};
+use plotters::series::LineSeries;
+use plotters::style::{Color, BLACK, BLUE, MAGENTA, RED, WHITE};
+use plotters_iced::{Chart, ChartBuilder, DrawingArea, DrawingBackend};
+use std::time::Duration;
+
+// Number of measurements each point is calculated from
+const WINDOW: usize = 20;
+
+// Rolling p50 and p95 of the delays over the session time
+pub struct PercentileChart<'a> {
+    pub data: &'a [(Duration, SummaryReport)],
+    pub setting_changes: &'a [SettingChange],
+}
+
+fn percentile(sorted: &[u64], percentile: usize) -> u64 {
+    sorted[(sorted.len() * percentile).div_ceil(100).saturating_sub(1)]
+}
+
+impl Chart<Message> for PercentileChart<'_> {
+    type State = ();
+    fn draw_chart<DB: DrawingBackend>(&self, state: &Self::State, root: DrawingArea<DB, Shift>) {
+        _ = root.fill(&WHITE);
+        let builder = ChartBuilder::on(&root);
+        self.build_chart(state, builder);
+    }
+    #[allow(clippy::cast_precision_loss)]
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut builder: ChartBuilder<DB>) {
+        // session time in seconds, p50 and p95 in milliseconds
+        let points: Vec<(f64, f64, f64)> = (0..self.data.len())
+            .map(|i| {
+                let mut delays: Vec<u64> = self.data[(i + 1).saturating_sub(WINDOW)..=i]
+                    .iter()
+                    .map(|(_, summary)| summary.delay)
+                    .collect();
+                delays.sort_unstable();
+                (
+                    self.data[i].0.as_secs_f64(),
+                    percentile(&delays, 50) as f64 / 1000.0,
+                    percentile(&delays, 95) as f64 / 1000.0,
+                )
+            })
+            .collect();
+        let min_x = points.first().map_or(0.0, |point| point.0);
+        let max_x = points.last().map_or(0.0, |point| point.0).max(min_x + 1.0);
+        let max_y = points.iter().fold(1.0, |max, point| point.2.max(max)) * 1.1;
+        let mut chart = builder
+            .set_all_label_area_size(45)
+            .top_x_label_area_size(20)
+            .build_cartesian_2d(min_x..max_x, 0.0..max_y)
+            .unwrap();
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .x_desc("Session time [s]")
+            .y_desc("Delay [ms]")
+            .draw()
+            .expect("Draw mesh");
+        chart
+            .draw_series(LineSeries::new(
+                points.iter().map(|point| (point.0, point.1)),
+                BLUE.stroke_width(2),
+            ))
+            .expect("Draw p50 line")
+            .label("p50")
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLUE));
+        chart
+            .draw_series(LineSeries::new(
+                points.iter().map(|point| (point.0, point.2)),
+                RED.stroke_width(2),
+            ))
+            .expect("Draw p95 line")
+            .label("p95")
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], RED));
+        let setting_changes = self.setting_changes.iter().filter_map(|change| {
+            let time = change.session_time.as_secs_f64();
+            (min_x..=max_x)
+                .contains(&time)
+                .then_some((time, &change.label))
+        });
+        chart
+            .draw_series(
+                setting_changes
+                    .clone()
+                    .map(|(time, _)| PathElement::new(vec![(time, 0.0), (time, max_y)], MAGENTA)),
+            )
+            .expect("Draw setting changes");
+        chart
+            .draw_series(setting_changes.map(|(time, label)| {
+                EmptyElement::at((time, max_y))
+                    + Text::new(label.clone(), (4, 4), ("sans-serif", 12))
+            }))
+            .expect("Draw setting change labels");
+        chart
+            .configure_series_labels()
+            .background_style(WHITE)
+            .border_style(BLACK)
+            .draw()
+            .expect("Draw legend");
+    }
+}
@@ -0,0 +1,104 @@
+use super::enums::Message;
+use plotters::coord::Shift;
+use plotters::style::{BLACK, WHITE};
+use plotters_iced::{Chart, ChartBuilder, DrawingArea, DrawingBackend};
+use std::time::{Duration, Instant};
+
+// Long enough for the sensor to settle on both levels
+const PHASE: Duration = Duration::from_millis(500);
+// Difference between the levels which is still just noise
+const NO_SIGNAL: u16 = 50;
+// Part of the best difference which still counts as the best spot
+const BEST_FRACTION: f32 = 0.9;
+
+// Small flashing square with a live readout, the sensor should end up where the levels differ the most
+pub struct Placement {
+    flash: bool,
+    next_toggle: Instant,
+    // brightness seen during the current phase
+    samples: Vec<u16>,
+    bright: Option<u16>,
+    dark: Option<u16>,
+    best: u16,
+    brightness: Option<u16>,
+}
+
+fn median(samples: &mut [u16]) -> Option<u16> {
+    samples.sort_unstable();
+    samples.get(samples.len() / 2).copied()
+}
+
+impl Placement {
+    pub fn new() -> Self {
+        Self {
+            flash: false,
+            next_toggle: Instant::now() + PHASE,
+            samples: Vec::new(),
+            bright: None,
+            dark: None,
+            best: 0,
+            brightness: None,
+        }
+    }
+
+    pub fn push(&mut self, brightness: u16) {
+        self.samples.push(brightness);
+        self.brightness = Some(brightness);
+    }
+
+    // Switches the square once the phase is over
+    pub fn tick(&mut self) {
+        if Instant::now() < self.next_toggle {
+            return;
+        }
+        // the first samples still show the previous level
+        let settled = self.samples.len() / 4;
+        let level = median(&mut self.samples[settled..]);
+        if self.flash {
+            self.bright = level;
+        } else {
+            self.dark = level;
+        }
+        self.best = self.best.max(self.strength());
+        self.samples.clear();
+        self.flash = !self.flash;
+        self.next_toggle = Instant::now() + PHASE;
+    }
+
+    // Difference between the bright and dark levels
+    pub fn strength(&self) -> u16 {
+        match (self.bright, self.dark) {
+            (Some(bright), Some(dark)) => bright.abs_diff(dark),
+            _ => 0,
+        }
+    }
+
+    pub const fn brightness(&self) -> Option<u16> {
+        self.brightness
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn hint(&self) -> String {
+        let strength = self.strength();
+        if self.brightness.is_none() {
+            "Waiting for raw reports".to_string()
+        } else if strength < NO_SIGNAL {
+            "No change seen, move the sensor over the flashing square".to_string()
+        } else if f32::from(strength) >= f32::from(self.best) * BEST_FRACTION {
+            "Best spot so far, keep the sensor here".to_string()
+        } else {
+            format!(
+                "Weaker than the best spot so far ({}), move back towards it",
+                self.best
+            )
+        }
+    }
+}
+
+impl Chart<Message> for Placement {
+    type State = ();
+    fn draw_chart<DB: DrawingBackend>(&self, _state: &Self::State, root: DrawingArea<DB, Shift>) {
+        _ = root.fill(if self.flash { &WHITE } else { &BLACK });
+    }
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, _builder: ChartBuilder<DB>) {}
+}
@@ -0,0 +1,56 @@
+use fakeldat_lib::serialport::{self, SerialPortType};
+use std::fmt::Display;
+
+// USB vendor id of the Raspberry Pi Pico the firmware runs on
+const PICO_VID: u16 = 0x2e8a;
+
+// Serial port offered in the picker, the USB details tell the device apart from other CDC devices
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Port {
+    pub name: String,
+    description: String,
+    pico: bool,
+}
+
+impl Port {
+    // Empty when the ports can't be listed, there's nothing to pick from either way
+    pub fn list() -> Vec<Self> {
+        serialport::available_ports()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|port| {
+                let (description, pico) = match port.port_type {
+                    SerialPortType::UsbPort(usb) => (
+                        usb.product
+                            .unwrap_or_else(|| format!("USB {:04x}:{:04x}", usb.vid, usb.pid)),
+                        usb.vid == PICO_VID,
+                    ),
+                    _ => (String::new(), false),
+                };
+                Self {
+                    name: port.port_name,
+                    description,
+                    pico,
+                }
+            })
+            .collect()
+    }
+
+    // Pico first, otherwise whatever comes first like before there was a picker
+    pub fn preferred(ports: &[Self]) -> Option<&Self> {
+        ports
+            .iter()
+            .find(|port| port.pico)
+            .or_else(|| ports.first())
+    }
+}
+
+impl Display for Port {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.description.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} ({})", self.name, self.description)
+        }
+    }
+}
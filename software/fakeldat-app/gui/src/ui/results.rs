@@ -0,0 +1,169 @@
+use super::loopback::Trial;
+use super::{invalid_data, SettingChange};
+use chrono::{DateTime, Utc};
+use fakeldat_lib::{Error, Hello, SessionMetadata, Stats, StatsSummary, SummaryReport};
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+// Delays of a session with everything needed to make sense of them later, times as RFC 3339
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Results {
+    pub exported: String,
+    pub session_started: String,
+    // missing when exported without a device connected
+    pub device: Option<SessionMetadata>,
+    pub hello: Option<Hello>,
+    pub stats: StatsSummary,
+    pub median: Option<u64>,
+    pub p99: Option<u64>,
+    pub shots: Vec<Shot>,
+    pub setting_changes: Vec<Change>,
+    // empty unless a loopback test ran
+    pub loopback: Vec<Trial>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Shot {
+    // seconds since the session started
+    pub session_time: f64,
+    // microseconds
+    pub delay: u64,
+    pub threshold: u16,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Change {
+    pub session_time: f64,
+    pub label: String,
+}
+
+impl Results {
+    pub fn new(
+        summary_data: &[(Duration, SummaryReport)],
+        setting_changes: &[SettingChange],
+        session_age: Duration,
+        loopback: &[Trial],
+    ) -> Self {
+        let now: DateTime<Utc> = Utc::now();
+        let stats: Stats = summary_data
+            .iter()
+            .map(|(_, summary)| summary.delay)
+            .collect();
+        Self {
+            exported: now.to_rfc3339(),
+            session_started: chrono::Duration::from_std(session_age)
+                .map_or(now, |age| now - age)
+                .to_rfc3339(),
+            device: None,
+            hello: None,
+            stats: stats.summary(),
+            median: stats.median(),
+            p99: stats.percentile(99.0),
+            shots: summary_data
+                .iter()
+                .map(|(session_time, summary)| Shot {
+                    session_time: session_time.as_secs_f64(),
+                    delay: summary.delay,
+                    threshold: summary.threshold,
+                })
+                .collect(),
+            setting_changes: setting_changes
+                .iter()
+                .map(|change| Change {
+                    session_time: change.session_time.as_secs_f64(),
+                    label: change.label.clone(),
+                })
+                .collect(),
+            loopback: loopback.to_vec(),
+        }
+    }
+
+    // JSON for anything ending in .json, CSV with the rest as comment lines otherwise
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let contents = if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+        {
+            serde_json::to_string_pretty(self).map_err(invalid_data)?
+        } else {
+            self.to_csv().map_err(invalid_data)?
+        };
+        std::fs::write(path, contents).map_err(Error::IOError)
+    }
+
+    fn to_csv(&self) -> Result<String, std::fmt::Error> {
+        let optional = |value: Option<String>| value.unwrap_or_else(|| String::from("-"));
+        let mut csv = String::new();
+        writeln!(csv, "# exported: {}", self.exported)?;
+        writeln!(csv, "# session started: {}", self.session_started)?;
+        if let Some(ref device) = self.device {
+            writeln!(csv, "# port: {}", optional(device.port.clone()))?;
+            writeln!(csv, "# library version: {}", device.library_version)?;
+            if let Some(profile) = device.profile {
+                writeln!(
+                    csv,
+                    "# settings: {} Hz, {}, threshold {}, {:?}",
+                    profile.poll_rate, profile.report_mode, profile.threshold, profile.action
+                )?;
+            }
+            for (key, value) in &device.meta {
+                writeln!(csv, "# {key}: {value}")?;
+            }
+        }
+        if let Some(hello) = self.hello {
+            writeln!(csv, "# protocol version: {}", hello.protocol_version)?;
+        }
+        let stats = self.stats;
+        writeln!(csv, "# count: {}", stats.count)?;
+        writeln!(
+            csv,
+            "# mean: {}",
+            optional(stats.mean.map(|mean| format!("{mean:.1}")))
+        )?;
+        writeln!(
+            csv,
+            "# std dev: {}",
+            optional(stats.std_dev.map(|std_dev| format!("{std_dev:.1}")))
+        )?;
+        for (name, value) in [
+            ("min", stats.min),
+            ("median", self.median),
+            ("p95", stats.p95),
+            ("p99", self.p99),
+            ("max", stats.max),
+        ] {
+            writeln!(
+                csv,
+                "# {name}: {}",
+                optional(value.map(|value| value.to_string()))
+            )?;
+        }
+        for change in &self.setting_changes {
+            writeln!(
+                csv,
+                "# setting change at {:.3} s: {}",
+                change.session_time, change.label
+            )?;
+        }
+        let microseconds = |value: Option<u64>| optional(value.map(|value| value.to_string()));
+        for (i, trial) in self.loopback.iter().enumerate() {
+            writeln!(
+                csv,
+                "# loopback trial {}: to redraw {}, total {}",
+                i + 1,
+                microseconds(trial.to_redraw),
+                microseconds(trial.total)
+            )?;
+        }
+        writeln!(csv, "session_time,delay,threshold")?;
+        for shot in &self.shots {
+            writeln!(
+                csv,
+                "{:.6},{},{}",
+                shot.session_time, shot.delay, shot.threshold
+            )?;
+        }
+        Ok(csv)
+    }
+}
@@ -0,0 +1,93 @@
+use fakeldat_lib::{Error, RawReport};
+use plotters::backend::BitMapBackend;
+use plotters::drawing::IntoDrawingArea;
+use plotters::element::Rectangle;
+use plotters::series::LineSeries;
+use plotters::style::full_palette::ORANGE;
+use plotters::style::{Color, BLUE, GREEN, WHITE};
+use plotters_iced::ChartBuilder;
+use std::collections::VecDeque;
+use std::path::Path;
+
+const PNG_SIZE: (u32, u32) = (1600, 900);
+
+// Time range selected on the raw chart
+#[derive(Debug, Clone, Copy)]
+pub struct Segment {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Segment {
+    pub fn new(a: u64, b: u64) -> Self {
+        Self {
+            start: a.min(b),
+            end: a.max(b),
+        }
+    }
+
+    fn reports<'a>(&self, data: &'a VecDeque<RawReport>) -> impl Iterator<Item = &'a RawReport> {
+        let (start, end) = (self.start, self.end);
+        data.iter()
+            .filter(move |report| (start..=end).contains(&report.timestamp))
+    }
+
+    // Same columns as the recording
+    pub fn to_csv(&self, data: &VecDeque<RawReport>) -> String {
+        self.reports(data)
+            .map(|report| {
+                format!(
+                    "{},{},{},{}\n",
+                    report.timestamp,
+                    report.brightness,
+                    report.audio,
+                    u8::from(report.trigger)
+                )
+            })
+            .collect()
+    }
+
+    pub fn save_png(&self, data: &VecDeque<RawReport>, path: &Path) -> Result<(), Error> {
+        let plot_error = |why: String| Error::IOError(std::io::Error::other(why));
+        let root = BitMapBackend::new(path, PNG_SIZE).into_drawing_area();
+        root.fill(&WHITE)
+            .map_err(|why| plot_error(why.to_string()))?;
+        let mut chart = ChartBuilder::on(&root)
+            .set_all_label_area_size(60)
+            .margin(10)
+            .build_cartesian_2d(self.start..self.end.max(self.start + 1), 0u64..4096)
+            .map_err(|why| plot_error(why.to_string()))?;
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .x_desc("Timestamp [us]")
+            .y_label_formatter(&ToString::to_string)
+            .draw()
+            .map_err(|why| plot_error(why.to_string()))?;
+        chart
+            .draw_series(LineSeries::new(
+                self.reports(data)
+                    .map(|report| (report.timestamp, report.brightness.into())),
+                BLUE.stroke_width(2),
+            ))
+            .map_err(|why| plot_error(why.to_string()))?;
+        chart
+            .draw_series(LineSeries::new(
+                self.reports(data)
+                    .map(|report| (report.timestamp, report.audio.into())),
+                ORANGE.stroke_width(2),
+            ))
+            .map_err(|why| plot_error(why.to_string()))?;
+        chart
+            .draw_series(
+                self.reports(data)
+                    .zip(self.reports(data).skip(1))
+                    .filter(|(previous, report)| !previous.trigger && report.trigger)
+                    .map(|(_, report)| {
+                        Rectangle::new([(report.timestamp, 4095), (report.timestamp, 0)], GREEN)
+                    }),
+            )
+            .map_err(|why| plot_error(why.to_string()))?;
+        root.present().map_err(|why| plot_error(why.to_string()))
+    }
+}
@@ -0,0 +1,113 @@
+use fakeldat_lib::{SequenceResult, Stats};
+use std::time::{Duration, Instant};
+
+// What the run test dialog sets up, intervals in milliseconds
+#[derive(Debug, Clone, Copy)]
+pub struct TestSettings {
+    pub shots: u16,
+    pub min_interval: u16,
+    pub max_interval: u16,
+    // seconds before the first trigger
+    pub countdown: u8,
+}
+
+impl Default for TestSettings {
+    fn default() -> Self {
+        Self {
+            shots: 100,
+            min_interval: 500,
+            max_interval: 1500,
+            countdown: 3,
+        }
+    }
+}
+
+// Triggers fired one at a time on the worker, the next one only after the previous came back
+pub struct TestRun {
+    settings: TestSettings,
+    first_trigger: Instant,
+    started: bool,
+    stats: Stats,
+    missed: usize,
+    done: u16,
+}
+
+impl TestRun {
+    pub fn new(settings: TestSettings) -> Self {
+        Self {
+            settings,
+            first_trigger: Instant::now() + Duration::from_secs(settings.countdown.into()),
+            started: false,
+            stats: Stats::default(),
+            missed: 0,
+            done: 0,
+        }
+    }
+
+    // Returns true once, when the countdown is over
+    pub fn take_start(&mut self) -> bool {
+        if self.started || Instant::now() < self.first_trigger {
+            return false;
+        }
+        self.started = true;
+        true
+    }
+
+    // Random pause between the triggers is picked by the library within it
+    pub fn interval(&self) -> (Duration, Duration) {
+        let min = self.settings.min_interval.min(self.settings.max_interval);
+        let max = self.settings.max_interval.max(min);
+        (
+            Duration::from_millis(min.into()),
+            Duration::from_millis(max.into()),
+        )
+    }
+
+    // Returns true when another trigger is due
+    pub fn push(&mut self, result: &SequenceResult) -> bool {
+        for summary in &result.reports {
+            self.stats.push(summary.delay);
+        }
+        self.missed += result.missed;
+        self.done = self.done.saturating_add(1);
+        !self.is_finished()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.done >= self.settings.shots
+    }
+
+    pub fn progress(&self) -> f32 {
+        f32::from(self.done) / f32::from(self.settings.shots.max(1))
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn status(&self) -> String {
+        if !self.started {
+            let left = self.first_trigger.saturating_duration_since(Instant::now());
+            return format!("Starting in {} s", left.as_secs() + 1);
+        }
+        let progress = format!(
+            "{}/{} triggers, {} missed",
+            self.done, self.settings.shots, self.missed
+        );
+        if !self.is_finished() {
+            return progress;
+        }
+        let ms = |delay: Option<u64>| delay.map_or(0.0, |delay| delay as f64 / 1000.0);
+        let summary = match (self.stats.mean(), self.stats.std_dev()) {
+            (Some(mean), Some(std_dev)) => format!(
+                "Mean {:.2} ms, std dev {:.2} ms, min {:.2} ms, max {:.2} ms\nMedian {:.2} ms, p95 {:.2} ms, p99 {:.2} ms",
+                mean / 1000.0,
+                std_dev / 1000.0,
+                ms(self.stats.min()),
+                ms(self.stats.max()),
+                ms(self.stats.median()),
+                ms(self.stats.percentile(95.0)),
+                ms(self.stats.percentile(99.0)),
+            ),
+            _ => String::from("No delays measured, check the threshold"),
+        };
+        format!("{progress}\n{summary}")
+    }
+}
@@ -0,0 +1,181 @@
+use super::error_message;
+use fakeldat_lib::{
+    Error, FakeLDAT, HeartbeatPolicy, Hello, Report, SequenceResult, SessionMetadata, Telemetry,
+    Warning,
+};
+use iced::futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use iced::futures::{SinkExt, StreamExt};
+use iced::Subscription;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+// Runs on the thread owning the device, between polls
+type Job =
+    Box<dyn FnOnce(&mut FakeLDAT, &UnboundedSender<DeviceEvent>) -> Result<(), Error> + Send>;
+
+// Reports are handed over at most this often, every message redraws the UI
+const REPORT_INTERVAL: Duration = Duration::from_millis(16);
+
+// A broken port fails every poll, the errors would flood the output
+const ERROR_DELAY: Duration = Duration::from_millis(100);
+
+// Every worker gets its own subscription, a new connection mustn't reuse the old one
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// What the view shows about the device, sent again whenever it changes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DeviceState {
+    pub connected: bool,
+    pub sensor_saturated: bool,
+    pub link_saturated: bool,
+    pub telemetry: Option<Telemetry>,
+}
+
+impl DeviceState {
+    fn of(fakeldat: &FakeLDAT) -> Self {
+        Self {
+            connected: fakeldat.is_connected(),
+            sensor_saturated: fakeldat.link_stats().saturated,
+            link_saturated: fakeldat.io_stats().is_saturated(),
+            telemetry: fakeldat.telemetry(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum DeviceEvent {
+    Connected,
+    // what the firmware can do, None when it wasn't asked
+    Hello(Option<Hello>),
+    ConnectFailed(String),
+    Reports(Vec<Report>),
+    Warning(Warning),
+    // a job or the polling failed, the device might still be fine
+    Error(String),
+    State(DeviceState),
+    // for the header of the recording at the path
+    Metadata(PathBuf, SessionMetadata),
+    // one trigger of a test run, its summary is taken out of the reports
+    TestShot(SequenceResult),
+}
+
+// Owns the device on a thread of its own, a redraw can't hold up the polling
+pub struct Worker {
+    id: u64,
+    jobs: mpsc::Sender<Job>,
+    // taken by the subscription once it starts
+    events: Arc<Mutex<Option<UnboundedReceiver<DeviceEvent>>>>,
+}
+
+impl Worker {
+    // Opening happens on the thread too, the outcome comes as the first event
+    pub fn spawn(port: String) -> Self {
+        let (jobs, pending) = mpsc::channel();
+        let (events, receiver) = unbounded();
+        spawn(move || match open(&port) {
+            Ok(fakeldat) => {
+                _ = events.unbounded_send(DeviceEvent::Hello(fakeldat.hello_answer()));
+                if events.unbounded_send(DeviceEvent::Connected).is_ok() {
+                    serve_device(fakeldat, &pending, &events);
+                }
+            }
+            Err(why) => _ = events.unbounded_send(DeviceEvent::ConnectFailed(error_message(&why))),
+        });
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            jobs,
+            events: Arc::new(Mutex::new(Some(receiver))),
+        }
+    }
+
+    // Errors come back as events, like the ones of the polling
+    pub fn run(&self, job: impl FnOnce(&mut FakeLDAT) -> Result<(), Error> + Send + 'static) {
+        self.run_with_events(move |fakeldat, _| job(fakeldat));
+    }
+
+    // For jobs with results of their own, sent back like the reports
+    pub fn run_with_events(
+        &self,
+        job: impl FnOnce(&mut FakeLDAT, &UnboundedSender<DeviceEvent>) -> Result<(), Error>
+            + Send
+            + 'static,
+    ) {
+        // the thread only stops once the device couldn't be opened
+        _ = self.jobs.send(Box::new(job));
+    }
+
+    pub fn subscription(&self) -> Subscription<DeviceEvent> {
+        let events = Arc::clone(&self.events);
+        iced::subscription::channel(self.id, 100, move |mut output| async move {
+            let receiver = events.lock().unwrap_or_else(PoisonError::into_inner).take();
+            if let Some(mut receiver) = receiver {
+                while let Some(event) = receiver.next().await {
+                    if output.send(event).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            iced::futures::future::pending().await
+        })
+    }
+}
+
+fn open(port: &str) -> Result<FakeLDAT, Error> {
+    let mut fakeldat = FakeLDAT::builder()
+        .path(port)
+        .timeout(Duration::from_secs(100_000))
+        .auto_reconnect(Duration::from_secs(2))
+        .open()?;
+    // older firmware can't answer pings, the disconnect still gets noticed on its own
+    _ = fakeldat.set_heartbeat(Some(HeartbeatPolicy::default()));
+    fakeldat.set_gap_detection(true);
+    Ok(fakeldat)
+}
+
+// Until the worker is dropped, the device gets closed on the way out
+fn serve_device(
+    mut fakeldat: FakeLDAT,
+    jobs: &mpsc::Receiver<Job>,
+    events: &UnboundedSender<DeviceEvent>,
+) {
+    let mut state = None;
+    let mut reports = Vec::new();
+    let mut last_sent = Instant::now();
+    loop {
+        loop {
+            match jobs.try_recv() {
+                Ok(job) => {
+                    if let Err(why) = job(&mut fakeldat, events) {
+                        _ = events.unbounded_send(DeviceEvent::Error(error_message(&why)));
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+        if let Err(why) = fakeldat.poll_bulk_data() {
+            _ = events.unbounded_send(DeviceEvent::Error(error_message(&why)));
+            sleep(ERROR_DELAY);
+        }
+        for warning in fakeldat.take_warnings() {
+            _ = events.unbounded_send(DeviceEvent::Warning(warning));
+        }
+        let current = DeviceState::of(&fakeldat);
+        if state != Some(current) {
+            state = Some(current);
+            _ = events.unbounded_send(DeviceEvent::State(current));
+        }
+        match fakeldat.take_report_buffer() {
+            Some(buffer) => reports.extend(buffer),
+            None => sleep(Duration::from_millis(1)),
+        }
+        if !reports.is_empty() && last_sent.elapsed() >= REPORT_INTERVAL {
+            _ = events.unbounded_send(DeviceEvent::Reports(std::mem::take(&mut reports)));
+            last_sent = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,58 @@
+// Smallest time range the raw chart zooms into, in microseconds
+const MIN_WIDTH: f64 = 200.0;
+
+// Time range shown on the raw chart instead of the whole buffer
+#[derive(Debug, Clone, Copy)]
+pub struct Zoom {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Zoom {
+    // Keeps the timestamp under the cursor in place, None once it covers all the data
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn zoomed(
+        (start, end): (u64, u64),
+        (first, last): (u64, u64),
+        at: f32,
+        factor: f32,
+    ) -> Option<Self> {
+        if start >= end {
+            return None;
+        }
+        let at = f64::from(at.clamp(0.0, 1.0));
+        let width = (end - start) as f64;
+        let anchor = start as f64 + width * at;
+        let width = (width * f64::from(factor)).max(MIN_WIDTH);
+        if width >= last.saturating_sub(first) as f64 {
+            return None;
+        }
+        let start = (anchor - width * at).max(0.0);
+        Some(Self {
+            start: start as u64,
+            end: (start + width) as u64,
+        })
+    }
+
+    // Moves by a fraction of the width, dragging right goes back in time
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn panned((start, end): (u64, u64), delta: f32) -> Option<Self> {
+        if start >= end {
+            return None;
+        }
+        let shift = ((end - start) as f64 * f64::from(delta)).round() as i64;
+        // stops at the start of the device time instead of squeezing
+        let shift = shift.min(i64::try_from(start).unwrap_or(i64::MAX));
+        let start = start.saturating_add_signed(-shift);
+        let end = end.saturating_add_signed(-shift);
+        Some(Self { start, end })
+    }
+}
@@ -1,18 +1,40 @@
 #ifndef SRC_FAKELDAT_H_
 #define SRC_FAKELDAT_H_
 #include <Arduino.h>
+#include <Joystick.h>
 #include <Keyboard.h>
 #include <Mouse.h>
 
 enum ReportMode {
     RAW,
     SUMMARY,
-    COMBINED
+    COMBINED,
+    RAW_AUDIO,
+    AUDIO_VIDEO,
 };
 
 enum ActionMode {
     MOUSE,
     KEYBOARD,
+    GAMEPAD,
+};
+
+enum IntegrityMode {
+    SUM,
+    CRC8,
+    CRC16,
+};
+
+// prefixed, RISING and FALLING are taken by the core
+enum DetectionEdge {
+    EDGE_AUTO,
+    EDGE_RISING,
+    EDGE_FALLING,
+};
+
+enum DetectionBaseline {
+    BASELINE_RELATIVE,
+    BASELINE_ABSOLUTE,
 };
 
 enum TriggerOverride {
@@ -20,6 +42,7 @@ enum TriggerOverride {
     PRESS,
     OVERRIDE_IN_PROGRESS,
     NOOVERRIDE,
+    BETWEEN_PRESSES,
 };
 
 enum Command {
@@ -31,21 +54,57 @@ enum Command {
     GET_THRESHOLD   = 0x23,
     SET_ACTION      = 0x04,
     GET_ACTION      = 0x24,
+    SET_INTEGRITY   = 0x05,
+    GET_INTEGRITY   = 0x25,
+    SET_SEQUENCE    = 0x06,
+    GET_SEQUENCE    = 0x26,
+    SET_RAW_BATCH   = 0x07,
+    GET_RAW_BATCH   = 0x27,
+    SET_BAUD_RATE   = 0x08,
+    GET_BAUD_RATE   = 0x28,
+    GET_CLOCK       = 0x29,
+    SET_ACTION_TIMING = 0x0A,
+    GET_ACTION_TIMING = 0x2A,
+    SET_AUTO_TRIGGER = 0x0B,
+    GET_AUTO_TRIGGER = 0x2B,
+    SET_DETECTION_MODE = 0x0C,
+    GET_DETECTION_MODE = 0x2C,
+    SET_SENSOR_GAIN = 0x0D,
+    GET_SENSOR_GAIN = 0x2D,
+    SET_CHANNELS    = 0x0E,
+    GET_CHANNELS    = 0x2E,
+    SET_TELEMETRY_INTERVAL = 0x0F,
+    GET_TELEMETRY_INTERVAL = 0x2F,
+    HELLO           = 0x30,
+    PING            = 0x31,
+    ARM             = 0x33,
+    DISARM          = 0x34,
+    SET_SUMMARY_AGGREGATION = 0x15,
+    GET_SUMMARY_AGGREGATION = 0x35,
+    SELF_TEST       = 0x36,
+    AUTO_TRIGGER    = 0x1D,
     MACRO_TRIGGER   = 0x1E,
     MANUAL_TRIGGER  = 0x1F,
     REPORT_RAW      = 0x41,
     REPORT_SUMMARY  = 0x42,
+    REPORT_RAW_BATCH = 0x43,
+    REPORT_RAW_AUDIO = 0x44,
+    REPORT_AUDIO_VIDEO = 0x45,
+    REPORT_TELEMETRY = 0x46,
+    REPORT_SUMMARY_AGGREGATE = 0x47,
 };
 
 // commands that can be received
 constexpr uint8_t allowed_commands[]{
-    SET_POLL_RATE, GET_POLL_RATE, SET_REPORT_MODE, GET_REPORT_MODE, SET_THRESHOLD, GET_THRESHOLD, SET_ACTION, GET_ACTION, MACRO_TRIGGER, MANUAL_TRIGGER,
+    SET_POLL_RATE, GET_POLL_RATE, SET_REPORT_MODE, GET_REPORT_MODE, SET_THRESHOLD, GET_THRESHOLD, SET_ACTION, GET_ACTION, SET_INTEGRITY, GET_INTEGRITY, SET_SEQUENCE, GET_SEQUENCE, SET_RAW_BATCH, GET_RAW_BATCH, SET_BAUD_RATE, GET_BAUD_RATE, GET_CLOCK, SET_ACTION_TIMING, GET_ACTION_TIMING, SET_AUTO_TRIGGER, GET_AUTO_TRIGGER, SET_DETECTION_MODE, GET_DETECTION_MODE, SET_SENSOR_GAIN, GET_SENSOR_GAIN, SET_CHANNELS, GET_CHANNELS, SET_TELEMETRY_INTERVAL, GET_TELEMETRY_INTERVAL, REPORT_TELEMETRY, MACRO_TRIGGER, MANUAL_TRIGGER, HELLO, PING, ARM, DISARM, SET_SUMMARY_AGGREGATION, GET_SUMMARY_AGGREGATION, SELF_TEST,
 };
 constexpr uint8_t commands_count = sizeof(allowed_commands);
 
 class Sensor {
     pin_size_t pin;
     uint16_t   value;
+    // readings summed per measurement
+    uint8_t    gain = 1;
 
   public:
     Sensor(pin_size_t pin) : pin(pin) {
@@ -53,11 +112,20 @@ class Sensor {
     }
 
     void measure() {
-        value = analogRead(pin) ^ (1 << ADC_RESOLUTION) - 1;
+        uint32_t sum = 0;
+        for (uint8_t i = 0; i < gain; i++)
+            sum += analogRead(pin) ^ (1 << ADC_RESOLUTION) - 1;
+        value = min(sum, (uint32_t)(1 << ADC_RESOLUTION) - 1);
     }
     uint16_t get_value() {
         return value;
     }
+    void set_gain(uint8_t new_gain) {
+        gain = new_gain;
+    }
+    uint8_t get_gain() {
+        return gain;
+    }
 };
 
 class Button {
@@ -84,6 +152,10 @@ class Button {
     }
 };
 
+// not buttons, one wheel step on press
+#define MOUSE_SCROLL_UP 32
+#define MOUSE_SCROLL_DOWN 64
+
 struct Action {
     ActionMode mode;
     uint8_t    button;
@@ -93,39 +165,118 @@ struct Action {
             button = MOUSE_LEFT;
         else if (mode == KEYBOARD)
             button = 'x';
+        else if (mode == GAMEPAD)
+            button = 1; // A
     }
 
     void press() {
-        if (mode == MOUSE)
+        if (mode == MOUSE && button == MOUSE_SCROLL_UP)
+            Mouse.move(0, 0, 1);
+        else if (mode == MOUSE && button == MOUSE_SCROLL_DOWN)
+            Mouse.move(0, 0, -1);
+        else if (mode == MOUSE)
             Mouse.press(button);
         else if (mode == KEYBOARD)
             Keyboard.press(button);
+        else if (mode == GAMEPAD)
+            Joystick.button(button, true);
     }
     void release() {
-        if (mode == MOUSE)
+        if (mode == MOUSE && (button == MOUSE_SCROLL_UP || button == MOUSE_SCROLL_DOWN))
+            return;
+        else if (mode == MOUSE)
             Mouse.release(button);
         else if (mode == KEYBOARD)
             Keyboard.release(button);
+        else if (mode == GAMEPAD)
+            Joystick.button(button, false);
     }
 };
 
 #define HISTORY_SIZE 150
+#define BAUD_RATE_CONFIRM_US 1000000
+#define BATCH_SIZE 4
+// bumped whenever frames change in a way older hosts can't follow
+#define PROTOCOL_VERSION 1
+// bit 9 is the second light sensor, only set when one is connected, no 32 byte frames (bit 15)
+#define CAPABILITIES 0x77DFF
+// distance from the running average which counts as the click
+#define AUDIO_THRESHOLD 300
+// audio/video report is sent without the missing part after this
+#define AUDIO_VIDEO_TIMEOUT_US 1000000
+#define NO_DELAY 0xFFFFFFFF
+// synthetic waveform of the self-test, dark and bright brightness with a little ripple on top
+#define SELF_TEST_LOW 100
+#define SELF_TEST_HIGH 4000
+#define SELF_TEST_RIPPLE 8
+#define SELF_TEST_TIMEOUT_US 1000000
+// full raw report every so many batches so the host can't drift
+#define BATCH_ANCHOR_INTERVAL 64
+// VSYS is divided by 3 on the Pico, a diode drop below VBUS when powered over USB
+#define SUPPLY_PIN 29
+#define SUPPLY_DIVIDER 3
+#define ADC_REFERENCE_MV 3300
 
 class FakeLDAT {
     Button*         trigger;
     Button*         macro;
     Sensor*         light_sensor;
+    // second photodiode, channel 1, only raw reports use it
+    Sensor*         second_light_sensor    = nullptr;
     Sensor*         audio_sensor;
     uint64_t        timestamp;
     uint64_t        interval_us            = 0;
     uint64_t        trigger_high_timestamp = 0;
-    uint16_t        trigger_override_count = 0;
+    uint32_t        trigger_override_count = 0;
+    int32_t         audio_average          = 0;
+    uint16_t        pending_audio          = 0;
+    uint64_t        pending_audio_timestamp = 0;
+    bool            audio_pending          = false;
+    uint64_t        audio_video_timestamp  = 0;
+    uint32_t        video_delay            = NO_DELAY;
+    uint32_t        audio_delay            = NO_DELAY;
+    uint16_t        video_level            = 0;
+    uint16_t        press_ms               = 50;
+    bool            double_press           = false;
+    bool            second_press_pending   = false;
+    bool            second_press           = false;
+    uint16_t        auto_min_ms            = 0;
+    uint16_t        auto_max_ms            = 0;
+    uint16_t        auto_remaining         = 0;
+    uint64_t        next_auto_trigger      = 0;
     int16_t         threshold              = 150;
+    DetectionEdge   edge                   = EDGE_AUTO;
+    DetectionBaseline baseline             = BASELINE_RELATIVE;
     TriggerOverride trigger_override       = NOOVERRIDE;
+    IntegrityMode   integrity              = SUM;
+    bool            sequence               = false;
+    uint8_t         sequence_counter       = 0;
+    bool            raw_batching           = false;
+    uint8_t         batch[16]{};
+    uint8_t         batch_count            = 0;
+    uint8_t         batches_since_anchor   = BATCH_ANCHOR_INTERVAL;
+    uint64_t        last_raw_timestamp     = 0;
+    // bit n streams channel n
+    uint8_t         channels               = 1;
+    uint16_t        telemetry_interval_s   = 0;
+    uint64_t        next_telemetry         = 0;
+    uint32_t        baud_rate              = 115200;
+    uint32_t        previous_baud_rate     = 115200;
+    uint64_t        baud_confirm_deadline  = 0;
+    // once the host armed, triggers only start a summary while armed
+    bool            arming                 = false;
+    bool            armed                  = false;
+    uint16_t        arm_id                 = 0;
+    // summaries per aggregate report, 0 and 1 send each one
+    uint8_t         aggregation            = 0;
+    uint8_t         aggregate_count        = 0;
+    uint32_t        aggregate_min          = 0;
+    uint32_t        aggregate_max          = 0;
+    uint64_t        aggregate_sum          = 0;
 
     const bool      trigger_on_press = true; // as opposed to on release
 
-    uint16_t        calc_threshold(uint16_t current_value) {
+    uint16_t        calc_baseline(uint16_t current_value) {
         static uint16_t history[HISTORY_SIZE]{};
         static uint64_t count;
         uint32_t        sum = 0;
@@ -133,12 +284,19 @@ class FakeLDAT {
             sum += num;
         history[count % HISTORY_SIZE] = current_value;
         count++;
-        return sum / HISTORY_SIZE + threshold;
+        return sum / HISTORY_SIZE;
     }
     void update_trigger_override() {
         if (trigger_override == PRESS)
             trigger_override = OVERRIDE_IN_PROGRESS;
-        else if (trigger_override == RELEASE)
+        else if (trigger_override == RELEASE && second_press_pending) {
+            // pause as long as the press
+            trigger_override       = BETWEEN_PRESSES;
+            trigger_override_count = press_count();
+            second_press_pending   = false;
+            second_press           = true;
+            return;
+        } else if (trigger_override == RELEASE)
             trigger_override = NOOVERRIDE;
 
         if (trigger_override == NOOVERRIDE)
@@ -146,7 +304,10 @@ class FakeLDAT {
 
         if (trigger_override == OVERRIDE_IN_PROGRESS && trigger_override_count == 0)
             trigger_override = RELEASE;
-        else
+        else if (trigger_override == BETWEEN_PRESSES && trigger_override_count == 0) {
+            trigger_override       = PRESS;
+            trigger_override_count = press_count();
+        } else
             trigger_override_count--;
     }
     uint8_t calc_checksum(uint8_t buf[], uint8_t length) {
@@ -156,46 +317,83 @@ class FakeLDAT {
         }
         return checksum;
     }
-    bool valid_checksum(uint8_t buf[], uint8_t length) {
-        uint8_t last_element_index = length - 1;
-        uint8_t checksum           = calc_checksum(buf, last_element_index);
-        return buf[last_element_index] == checksum;
+    // CRC-8/SMBUS
+    uint8_t calc_crc8(uint8_t buf[], uint8_t length) {
+        uint8_t crc = 0;
+        for (int i = 0; i < length; i++) {
+            crc ^= buf[i];
+            for (int bit = 0; bit < 8; bit++)
+                crc = crc & 0x80 ? (crc << 1) ^ 0x07 : crc << 1;
+        }
+        return crc;
+    }
+    // CRC-16/CCITT-FALSE
+    uint16_t calc_crc16(uint8_t buf[], uint8_t length) {
+        uint16_t crc = 0xFFFF;
+        for (int i = 0; i < length; i++) {
+            crc ^= static_cast<uint16_t>(buf[i]) << 8;
+            for (int bit = 0; bit < 8; bit++)
+                crc = crc & 0x8000 ? (crc << 1) ^ 0x1021 : crc << 1;
+        }
+        return crc;
+    }
+    // 16 byte frame, CRC-16 takes the otherwise empty byte 14
+    void seal(uint8_t buf[]) {
+        if (integrity == CRC16) {
+            uint16_t crc = calc_crc16(buf, 14);
+            buf[14]      = crc & 0xFF;
+            buf[15]      = crc >> 8 & 0xFF;
+        } else if (integrity == CRC8) {
+            buf[15] = calc_crc8(buf, 15);
+        } else {
+            buf[15] = calc_checksum(buf, 15);
+        }
+    }
+    // byte 14 carries the counter when enabled
+    void send_frame(uint8_t buf[]) {
+        if (sequence)
+            buf[14] = sequence_counter++;
+        seal(buf);
+        Serial.write(buf, 16);
+    }
+    bool valid_checksum(uint8_t buf[]) {
+        uint8_t sealed[16];
+        memcpy(sealed, buf, sizeof(sealed));
+        seal(sealed);
+        return memcmp(sealed, buf, sizeof(sealed)) == 0;
     }
     void write_report(uint8_t command, uint64_t timestamp, uint16_t brightness, uint16_t audio, uint8_t trigger) {
-        uint8_t checksum = command;
         uint8_t bytes[16]{};
         bytes[0] = command;
         for (int i = 0; i < sizeof(timestamp); i++) {
             bytes[1 + i] = (timestamp >> (8 * i)) & 0xFF;
-            checksum += bytes[1 + i];
         }
         for (int i = 0; i < sizeof(brightness); i++) {
             bytes[9 + i] = (brightness >> (8 * i)) & 0xFF;
-            checksum += bytes[9 + i];
         }
         for (int i = 0; i < sizeof(audio); i++) {
             bytes[11 + i] = (audio >> (8 * i)) & 0xFF;
-            checksum += bytes[11 + i];
         }
         bytes[13] = trigger;
-        checksum += trigger;
-        // 14 is empty
-        bytes[15] = checksum;
-        Serial.write(bytes, sizeof(bytes));
+        send_frame(bytes);
     }
     void update() {
         light_sensor->measure();
+        if (second_light_sensor)
+            second_light_sensor->measure();
         audio_sensor->measure();
         timestamp = time_us_64();
         switch (trigger_override) {
             case RELEASE:
                 action->release();
-                if (!trigger_on_press)
+                // delay is measured from the first press or the last release
+                if (!trigger_on_press && !second_press_pending && (!arming || armed))
                     trigger_high_timestamp = timestamp;
                 break;
             case PRESS:
                 action->press();
-                if (trigger_on_press)
+                // outside an armed window it's pressed without being measured
+                if (trigger_on_press && !second_press && (!arming || armed))
                     trigger_high_timestamp = timestamp;
                 break;
             case NOOVERRIDE:
@@ -222,8 +420,13 @@ class FakeLDAT {
                     break;
                 }
             }
-            if (!valid_command || !valid_checksum(command, sizeof(command)))
+            if (!valid_command || !valid_checksum(command))
                 continue;
+            // any valid command confirms the new baud rate
+            baud_confirm_deadline = 0;
+            uint32_t next_baud_rate = baud_rate;
+            IntegrityMode next_integrity = integrity;
+            bool          next_sequence  = sequence;
             switch ((Command)command[0]) {
                 case SET_POLL_RATE: set_rate(static_cast<unsigned>(command[2]) << 8 | static_cast<unsigned>(command[1]));
                 case GET_POLL_RATE:
@@ -232,7 +435,7 @@ class FakeLDAT {
                     break;
 
                 case SET_REPORT_MODE:
-                    if (command[1] > ReportMode::COMBINED)
+                    if (command[1] > ReportMode::AUDIO_VIDEO)
                         break; // :D
                     mode = (ReportMode)command[1];
                 case GET_REPORT_MODE: command[1] = mode; break;
@@ -244,7 +447,7 @@ class FakeLDAT {
                     break;
 
                 case SET_ACTION:
-                    if (command[1] > ActionMode::KEYBOARD)
+                    if (command[1] > ActionMode::GAMEPAD)
                         break; // :D
                     action->mode   = (ActionMode)command[1];
                     action->button = command[2]; // check if key is valid for a given trigger
@@ -253,37 +456,407 @@ class FakeLDAT {
                     command[2] = action->button;
                     break;
 
+                case SET_INTEGRITY:
+                    if (command[1] > IntegrityMode::CRC16)
+                        break;
+                    next_integrity = (IntegrityMode)command[1];
+                    command[1]     = next_integrity;
+                    // CRC-16 takes the counter byte
+                    if (next_integrity == CRC16)
+                        next_sequence = false;
+                    break;
+                case GET_INTEGRITY: command[1] = integrity; break;
+
+                case SET_SEQUENCE:
+                    if (command[1] > 1)
+                        break;
+                    next_sequence = command[1] && integrity != CRC16;
+                    command[1]    = next_sequence;
+                    break;
+                case GET_SEQUENCE: command[1] = sequence; break;
+
+                case SET_RAW_BATCH:
+                    if (command[1] > 1)
+                        break;
+                    flush_batch();
+                    raw_batching         = command[1];
+                    batches_since_anchor = BATCH_ANCHOR_INTERVAL;
+                case GET_RAW_BATCH: command[1] = raw_batching; break;
+
+                case SET_BAUD_RATE:
+                    next_baud_rate = (static_cast<unsigned>(command[2]) << 8 | static_cast<unsigned>(command[1])) * 100;
+                    if (next_baud_rate == 0)
+                        next_baud_rate = baud_rate;
+                case GET_BAUD_RATE:
+                    command[1] = (next_baud_rate / 100) & 0xFF;
+                    command[2] = (next_baud_rate / 100) >> 8 & 0xFF;
+                    break;
+
+                case GET_CLOCK: {
+                    // sampled as late as possible, host assumes the middle of the round trip
+                    uint64_t now = time_us_64();
+                    memcpy(&command[1], &now, sizeof(now));
+                    break;
+                }
+
+                case SET_ACTION_TIMING: {
+                    uint16_t value = static_cast<unsigned>(command[2]) << 8 | static_cast<unsigned>(command[1]);
+                    if ((value & 0x7FFF) == 0)
+                        break;
+                    press_ms     = value & 0x7FFF;
+                    double_press = value >> 15;
+                }
+                case GET_ACTION_TIMING:
+                    command[1] = press_ms & 0xFF;
+                    command[2] = (press_ms >> 8 & 0x7F) | double_press << 7;
+                    break;
+
+                case SET_SENSOR_GAIN:
+                    // powers of two up to 16
+                    if (command[1] == 0 || command[1] > 16 || (command[1] & (command[1] - 1)))
+                        break;
+                    light_sensor->set_gain(command[1]);
+                    if (second_light_sensor)
+                        second_light_sensor->set_gain(command[1]);
+                case GET_SENSOR_GAIN:
+                    command[1] = light_sensor->get_gain();
+                    break;
+
+                case SET_CHANNELS:
+                    if (command[1] == 0 || command[1] & ~(second_light_sensor ? 0x03 : 0x01))
+                        break;
+                    channels = command[1];
+                    // the last sample a batch continues from might have been skipped
+                    flush_batch();
+                    batches_since_anchor = BATCH_ANCHOR_INTERVAL;
+                case GET_CHANNELS:
+                    command[1] = channels;
+                    break;
+
+                case SET_TELEMETRY_INTERVAL:
+                    telemetry_interval_s = static_cast<unsigned>(command[2]) << 8 | static_cast<unsigned>(command[1]);
+                    next_telemetry       = time_us_64() + static_cast<uint64_t>(telemetry_interval_s) * 1000000;
+                case GET_TELEMETRY_INTERVAL:
+                    command[1] = telemetry_interval_s & 0xFF;
+                    command[2] = telemetry_interval_s >> 8 & 0xFF;
+                    break;
+
+                // host version is ignored for now, it's there for future firmware
+                case HELLO: {
+                    uint16_t version      = PROTOCOL_VERSION;
+                    uint32_t capabilities = CAPABILITIES | (second_light_sensor ? 1 << 9 : 0);
+                    memcpy(&command[1], &version, sizeof(version));
+                    memcpy(&command[3], &capabilities, sizeof(capabilities));
+                    break;
+                }
+
+                // the echo is the pong
+                case PING: break;
+
+                case SELF_TEST: self_test(command); break;
+
+                case SET_SUMMARY_AGGREGATION:
+                    // what was collected so far goes out as a shorter aggregate
+                    flush_aggregate();
+                    aggregation = command[1];
+                case GET_SUMMARY_AGGREGATION:
+                    command[1] = aggregation;
+                    break;
+
+                case ARM:
+                    arming = true;
+                    armed  = true;
+                    arm_id = static_cast<unsigned>(command[2]) << 8 | static_cast<unsigned>(command[1]);
+                    break;
+
+                case DISARM:
+                    armed                  = false;
+                    trigger_high_timestamp = 0;
+                    if (command[1] == 1)
+                        arming = false;
+                    break;
+
+                // answered with the measurement in place of the request
+                case REPORT_TELEMETRY: fill_telemetry(command); break;
+
+                case SET_DETECTION_MODE:
+                    if (command[1] > EDGE_FALLING || command[2] > BASELINE_ABSOLUTE)
+                        break;
+                    edge     = (DetectionEdge)command[1];
+                    baseline = (DetectionBaseline)command[2];
+                case GET_DETECTION_MODE:
+                    command[1] = edge;
+                    command[2] = baseline;
+                    break;
+
+                case SET_AUTO_TRIGGER: {
+                    uint16_t min_ms = static_cast<unsigned>(command[2]) << 8 | static_cast<unsigned>(command[1]);
+                    uint16_t max_ms = static_cast<unsigned>(command[4]) << 8 | static_cast<unsigned>(command[3]);
+                    uint16_t count  = static_cast<unsigned>(command[6]) << 8 | static_cast<unsigned>(command[5]);
+                    // count of 0 stops
+                    if (count && (min_ms == 0 || min_ms > max_ms))
+                        break;
+                    auto_min_ms    = min_ms;
+                    auto_max_ms    = max_ms;
+                    auto_remaining = count;
+                    schedule_auto_trigger();
+                }
+                case GET_AUTO_TRIGGER:
+                    command[1] = auto_min_ms & 0xFF;
+                    command[2] = auto_min_ms >> 8 & 0xFF;
+                    command[3] = auto_max_ms & 0xFF;
+                    command[4] = auto_max_ms >> 8 & 0xFF;
+                    command[5] = auto_remaining & 0xFF;
+                    command[6] = auto_remaining >> 8 & 0xFF;
+                    break;
+
                 case MANUAL_TRIGGER: manual_trigger(); break;
 
                 default: break;
             }
 
-            command[sizeof(command) - 1] = calc_checksum(command, sizeof(command) - 1);
             // 3 - 14 are empty
-            Serial.write(command, sizeof(command));
+            command[14] = 0;
+            send_frame(command);
+            // response still uses the old mode so the host can verify it
+            integrity = next_integrity;
+            sequence  = next_sequence;
+            if (next_baud_rate != baud_rate)
+                switch_baud_rate(next_baud_rate);
         }
     }
+    // USB CDC ignores the line coding, this matters only for UART links
+    void switch_baud_rate(uint32_t new_baud_rate) {
+        Serial.flush();
+        previous_baud_rate    = baud_rate;
+        baud_rate             = new_baud_rate;
+        baud_confirm_deadline = time_us_64() + BAUD_RATE_CONFIRM_US;
+        Serial.begin(baud_rate);
+    }
+    // host couldn't reach the device after the switch
+    void check_baud_rate_confirmation() {
+        if (baud_confirm_deadline == 0 || time_us_64() < baud_confirm_deadline)
+            return;
+        Serial.flush();
+        baud_rate             = previous_baud_rate;
+        baud_confirm_deadline = 0;
+        Serial.begin(baud_rate);
+    }
+    uint32_t press_count() {
+        return static_cast<uint32_t>(press_ms) * 1000 / interval_us;
+    }
     void manual_trigger() {
         trigger_override       = PRESS;
-        trigger_override_count = 50 * 1000 / interval_us; // always 50ms, make configurable?
+        trigger_override_count = press_count();
+        second_press_pending   = double_press;
+        second_press           = false;
+    }
+    void schedule_auto_trigger() {
+        next_auto_trigger = time_us_64() + static_cast<uint64_t>(random(auto_min_ms, auto_max_ms + 1)) * 1000;
+    }
+    void check_auto_trigger() {
+        // waits for the previous press to finish
+        if (auto_remaining == 0 || trigger_override != NOOVERRIDE || timestamp < next_auto_trigger)
+            return;
+        manual_trigger();
+        auto_remaining--;
+        write_report(Command::AUTO_TRIGGER, timestamp, auto_remaining, 0, 1);
+        schedule_auto_trigger();
     }
     void set_rate(uint64_t rate) {
         interval_us = 1000000 / rate;
     }
+    void flush_batch() {
+        if (batch_count == 0)
+            return;
+        batch[0] = Command::REPORT_RAW_BATCH;
+        batch[1] |= batch_count;
+        send_frame(batch);
+        memset(batch, 0, sizeof(batch));
+        batch_count = 0;
+        batches_since_anchor++;
+    }
+    // 3 bytes per sample, 12 bits of brightness and 12 bits of time since the previous sample
+    bool add_to_batch(uint16_t brightness, bool trigger_state) {
+        uint64_t delta = timestamp - last_raw_timestamp;
+        if (delta > 0xFFF || batches_since_anchor >= BATCH_ANCHOR_INTERVAL)
+            return false;
+        uint8_t* sample = &batch[2 + batch_count * 3];
+        sample[0]       = brightness & 0xFF;
+        sample[1]       = (brightness >> 8 & 0x0F) | (delta & 0x0F) << 4;
+        sample[2]       = delta >> 4 & 0xFF;
+        batch[1] |= trigger_state << (4 + batch_count);
+        batch_count++;
+        if (batch_count == BATCH_SIZE)
+            flush_batch();
+        return true;
+    }
+    // channel goes into the high nibble of the trigger byte, batches only carry channel 0
     void report_raw() {
         auto trigger_state = trigger->get_state() || trigger_override == OVERRIDE_IN_PROGRESS || trigger_override == PRESS;
-        write_report(Command::REPORT_RAW, timestamp, light_sensor->get_value(), audio_sensor->get_value(), (uint8_t)trigger_state);
+        if (channels & 0x01) {
+            if (!raw_batching || !add_to_batch(light_sensor->get_value(), trigger_state)) {
+                flush_batch();
+                write_report(Command::REPORT_RAW, timestamp, light_sensor->get_value(), audio_sensor->get_value(), (uint8_t)trigger_state);
+                batches_since_anchor = 0;
+            }
+            last_raw_timestamp = timestamp;
+        }
+        if (second_light_sensor && channels & 0x02)
+            write_report(Command::REPORT_RAW, timestamp, second_light_sensor->get_value(), audio_sensor->get_value(), (uint8_t)trigger_state | 1 << 4);
+    }
+    bool trigger_started() {
+        return trigger_override == NOOVERRIDE && trigger->state_changed() && trigger->get_state() == trigger_on_press;
+    }
+    // Updates the baseline, level is the threshold the brightness got compared to
+    bool light_crossed(uint16_t& level) {
+        uint16_t value   = light_sensor->get_value();
+        // history is kept up to date in either mode
+        uint16_t average = calc_baseline(value);
+        return crosses(value, average, level);
+    }
+    bool crosses(uint16_t value, uint16_t average, uint16_t& level) {
+        // auto keeps the old behaviour, direction from the sign and none for 0
+        bool     rising  = edge == EDGE_RISING || (edge == EDGE_AUTO && threshold > 0);
+        bool     falling = edge == EDGE_FALLING || (edge == EDGE_AUTO && threshold < 0);
+        int32_t  target  = baseline == BASELINE_ABSOLUTE ? abs(threshold) : average + (rising ? abs(threshold) : -abs(threshold));
+        level            = constrain(target, 0, 4095);
+        return (rising && value > level) || (falling && value < level);
+    }
+    // Runs a synthetic step through the same comparison as the light sensor at the poll rate,
+    // faster than real time and without touching the baseline history of the real sensor
+    void self_test(uint8_t buf[]) {
+        uint16_t step_ms  = static_cast<unsigned>(buf[2]) << 8 | static_cast<unsigned>(buf[1]);
+        uint64_t step_us  = static_cast<uint64_t>(step_ms) * 1000;
+        bool     rising   = edge == EDGE_RISING || (edge == EDGE_AUTO && threshold > 0);
+        int32_t  dark     = rising ? SELF_TEST_LOW : SELF_TEST_HIGH;
+        int32_t  bright   = rising ? SELF_TEST_HIGH : SELF_TEST_LOW;
+        int32_t  average  = dark;
+        uint32_t detected = NO_DELAY;
+        uint32_t interval = interval_us;
+        for (uint64_t elapsed = 0; interval && elapsed <= step_us + SELF_TEST_TIMEOUT_US; elapsed += interval) {
+            int32_t  value = (elapsed >= step_us ? bright : dark) + (elapsed / interval % 2 ? SELF_TEST_RIPPLE : 0);
+            uint16_t level;
+            if (crosses(value, average, level)) {
+                detected = elapsed;
+                break;
+            }
+            average += (value - average) / 16;
+        }
+        memcpy(&buf[3], &detected, sizeof(detected));
+        memcpy(&buf[7], &interval, sizeof(interval));
+    }
+    bool audio_crossed() {
+        int32_t value = audio_sensor->get_value();
+        bool    click = abs(value - audio_average) > AUDIO_THRESHOLD;
+        audio_average += (value - audio_average) / 16;
+        return click;
     }
     void report_summary() {
-        uint16_t absolute_threshold = calc_threshold(light_sensor->get_value());
-        if (trigger_override == NOOVERRIDE && trigger->state_changed() && trigger->get_state() == trigger_on_press) {
-            trigger_high_timestamp = timestamp;
-        } else if (trigger_high_timestamp &&
-                   ((threshold > 0 && light_sensor->get_value() > absolute_threshold) || (threshold < 0 && light_sensor->get_value() < absolute_threshold))) {
-            write_report(Command::REPORT_SUMMARY, timestamp - trigger_high_timestamp, absolute_threshold, 0, 1);
+        uint16_t level;
+        bool     crossed = light_crossed(level);
+        if (trigger_started()) {
+            if (!arming || armed)
+                trigger_high_timestamp = timestamp;
+        } else if (trigger_high_timestamp && crossed) {
+            if (aggregation > 1)
+                aggregate(timestamp - trigger_high_timestamp);
+            else
+                // bit 1 tells the arm id apart from the unused audio field
+                write_report(Command::REPORT_SUMMARY, timestamp - trigger_high_timestamp, level, armed ? arm_id : 0, armed ? 3 : 1);
             trigger_high_timestamp = 0;
         }
     }
+    void aggregate(uint32_t delay) {
+        if (aggregate_count == 0 || delay < aggregate_min)
+            aggregate_min = delay;
+        if (aggregate_count == 0 || delay > aggregate_max)
+            aggregate_max = delay;
+        aggregate_sum += delay;
+        aggregate_count++;
+        if (aggregate_count >= aggregation)
+            flush_aggregate();
+    }
+    void flush_aggregate() {
+        if (aggregate_count == 0)
+            return;
+        uint32_t mean = aggregate_sum / aggregate_count;
+        uint8_t  bytes[16]{};
+        bytes[0] = Command::REPORT_SUMMARY_AGGREGATE;
+        bytes[1] = aggregate_count;
+        memcpy(&bytes[2], &aggregate_min, sizeof(aggregate_min));
+        memcpy(&bytes[6], &mean, sizeof(mean));
+        memcpy(&bytes[10], &aggregate_max, sizeof(aggregate_max));
+        send_frame(bytes);
+        aggregate_count = 0;
+        aggregate_sum   = 0;
+    }
+    // Two samples per frame, a poll interval apart
+    void report_raw_audio() {
+        uint16_t value = audio_sensor->get_value();
+        if (!audio_pending) {
+            pending_audio           = value;
+            pending_audio_timestamp = timestamp;
+            audio_pending           = true;
+            return;
+        }
+        write_report(Command::REPORT_RAW_AUDIO, pending_audio_timestamp, pending_audio, value, 2);
+        audio_pending = false;
+    }
+    void write_audio_video_report() {
+        uint8_t bytes[16]{};
+        bytes[0] = Command::REPORT_AUDIO_VIDEO;
+        for (int i = 0; i < sizeof(video_delay); i++) {
+            bytes[1 + i] = (video_delay >> (8 * i)) & 0xFF;
+            bytes[5 + i] = (audio_delay >> (8 * i)) & 0xFF;
+        }
+        bytes[9]  = video_level & 0xFF;
+        bytes[10] = video_level >> 8 & 0xFF;
+        send_frame(bytes);
+    }
+    // Waits for both the screen and the click, whichever is missing gets reported as such after the timeout
+    void report_audio_video() {
+        uint16_t level;
+        bool     light = light_crossed(level);
+        bool     audio = audio_crossed();
+        if (trigger_started()) {
+            audio_video_timestamp = timestamp;
+            video_delay           = NO_DELAY;
+            audio_delay           = NO_DELAY;
+            return;
+        }
+        if (!audio_video_timestamp)
+            return;
+        uint64_t elapsed = timestamp - audio_video_timestamp;
+        if (light && video_delay == NO_DELAY) {
+            video_delay = elapsed;
+            video_level = level;
+        }
+        if (audio && audio_delay == NO_DELAY)
+            audio_delay = elapsed;
+        if ((video_delay != NO_DELAY && audio_delay != NO_DELAY) || elapsed > AUDIO_VIDEO_TIMEOUT_US) {
+            write_audio_video_report();
+            audio_video_timestamp = 0;
+        }
+    }
+    // hundredths of a degree and millivolts, both are slow so they stay out of the sampling loop
+    void fill_telemetry(uint8_t buf[]) {
+        uint64_t now         = time_us_64();
+        int16_t  temperature = analogReadTemp() * 100;
+        uint16_t supply      = static_cast<uint32_t>(analogRead(SUPPLY_PIN)) * ADC_REFERENCE_MV * SUPPLY_DIVIDER / ((1 << ADC_RESOLUTION) - 1);
+        memcpy(&buf[1], &now, sizeof(now));
+        memcpy(&buf[9], &temperature, sizeof(temperature));
+        memcpy(&buf[11], &supply, sizeof(supply));
+    }
+    void check_telemetry() {
+        if (telemetry_interval_s == 0 || timestamp < next_telemetry)
+            return;
+        uint8_t bytes[16]{};
+        bytes[0] = Command::REPORT_TELEMETRY;
+        fill_telemetry(bytes);
+        send_frame(bytes);
+        next_telemetry = timestamp + static_cast<uint64_t>(telemetry_interval_s) * 1000000;
+    }
     void report_macro_status() {
         macro->measure();
         if (macro->state_changed() && macro->get_state()) {
@@ -309,21 +882,37 @@ class FakeLDAT {
         delete (trigger);
         delete (macro);
         delete (light_sensor);
+        delete (second_light_sensor);
         delete (audio_sensor);
         delete (action);
     }
 
     void tick() {
         check_for_commands();
+        check_baud_rate_confirmation();
         update();
+        check_auto_trigger();
+        check_telemetry();
         if (mode == RAW || mode == COMBINED) {
             report_raw();
         }
         if (mode == SUMMARY || mode == COMBINED) {
             report_summary();
         }
+        if (mode == RAW_AUDIO) {
+            report_raw_audio();
+        }
+        if (mode == AUDIO_VIDEO) {
+            report_audio_video();
+        }
         report_macro_status();
     }
+    // Variants with a second photodiode, streamed as channel 1 once enabled
+    void add_light_sensor(pin_size_t lightsensor_pin) {
+        delete (second_light_sensor);
+        second_light_sensor = new Sensor(lightsensor_pin);
+        second_light_sensor->set_gain(light_sensor->get_gain());
+    }
     const uint64_t get_interval() {
         return interval_us;
     }
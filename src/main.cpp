@@ -12,6 +12,8 @@ void setup() {
     pin_size_t light_sensor_pin = 26;
     pin_size_t audio_sensor_pin = 28;
     m_device                    = new FakeLDAT(light_sensor_pin, audio_sensor_pin, button_pin, macro_pin, 2000, RAW, MOUSE);
+    // boards with a second photodiode, on pin 27
+    // m_device->add_light_sensor(27);
 }
 
 void loop() {